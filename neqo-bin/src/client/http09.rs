@@ -20,7 +20,7 @@ use std::{
 };
 
 use http::Uri as Url;
-use neqo_common::{Datagram, event::Provider, qdebug, qinfo, qwarn};
+use neqo_common::{Datagram, SharedBytesMut, event::Provider, qdebug, qinfo, qwarn};
 use neqo_crypto::{AuthenticationStatus, ResumptionToken};
 use neqo_transport::{
     CloseReason, Connection, ConnectionEvent, ConnectionIdGenerator, EmptyConnectionIdGenerator,
@@ -40,6 +40,7 @@ pub struct Handler<'a> {
     token: Option<ResumptionToken>,
     needs_key_update: bool,
     read_buffer: Vec<u8>,
+    bytes_transferred: usize,
 }
 
 impl Handler<'_> {
@@ -123,6 +124,10 @@ impl super::Handler for Handler<'_> {
     fn take_token(&mut self) -> Option<ResumptionToken> {
         self.token.take()
     }
+
+    fn bytes_transferred(&self) -> usize {
+        self.bytes_transferred
+    }
 }
 
 pub fn create_client(
@@ -153,6 +158,10 @@ pub fn create_client(
         Instant::now(),
     )?;
 
+    if let Some(ech) = &args.ech {
+        client.client_enable_ech(ech)?;
+    }
+
     if let Some(tok) = resumption_token {
         client.enable_resumption(Instant::now(), tok)?;
     }
@@ -161,6 +170,10 @@ pub fn create_client(
     if !ciphers.is_empty() {
         client.set_ciphers(&ciphers)?;
     }
+    let groups = args.shared.get_groups();
+    if !groups.is_empty() {
+        client.set_groups(&groups)?;
+    }
 
     client.set_qlog(qlog_new(
         args,
@@ -198,9 +211,9 @@ impl super::Client for Connection {
         self.process_multiple_output(now, max_datagrams)
     }
 
-    fn process_multiple_input<'a>(
+    fn process_multiple_input(
         &mut self,
-        dgrams: impl IntoIterator<Item = Datagram<&'a mut [u8]>>,
+        dgrams: impl IntoIterator<Item = Datagram<SharedBytesMut>>,
         now: Instant,
     ) {
         self.process_multiple_input(dgrams, now);
@@ -239,6 +252,7 @@ impl<'b> Handler<'b> {
             token: None,
             needs_key_update: args.key_update,
             read_buffer: vec![0; STREAM_IO_BUFFER_SIZE],
+            bytes_transferred: 0,
         }
     }
 
@@ -302,12 +316,14 @@ impl<'b> Handler<'b> {
         read_buffer: &mut [u8],
         output_read_data: bool,
         maybe_out_file: &mut Option<BufWriter<File>>,
+        bytes_transferred: &mut usize,
     ) -> Res<bool> {
         loop {
             let (sz, fin) = client.stream_recv(stream_id, read_buffer)?;
             if sz == 0 {
                 return Ok(fin);
             }
+            *bytes_transferred += sz;
             let read_buffer = &read_buffer[0..sz];
 
             if let Some(out_file) = maybe_out_file {
@@ -339,6 +355,7 @@ impl<'b> Handler<'b> {
                     &mut self.read_buffer,
                     self.args.output_read_data,
                     maybe_out_file,
+                    &mut self.bytes_transferred,
                 )?;
 
                 if fin_recvd {
@@ -20,7 +20,7 @@ use std::{
 };
 
 use http::Uri as Url;
-use neqo_common::{Datagram, event::Provider, hex, qdebug, qerror, qinfo, qwarn};
+use neqo_common::{Datagram, SharedBytesMut, event::Provider, hex, qdebug, qerror, qinfo, qwarn};
 use neqo_crypto::{AuthenticationStatus, ResumptionToken};
 use neqo_http3::{Error, Http3Client, Http3ClientEvent, Http3Parameters, Http3State, Priority};
 use neqo_transport::{
@@ -41,6 +41,7 @@ pub struct Handler {
     token: Option<ResumptionToken>,
     output_read_data: bool,
     read_buffer: Vec<u8>,
+    bytes_transferred: usize,
 }
 
 impl Handler {
@@ -59,6 +60,7 @@ impl Handler {
             token: None,
             output_read_data,
             read_buffer: vec![0; STREAM_IO_BUFFER_SIZE],
+            bytes_transferred: 0,
         }
     }
 }
@@ -91,6 +93,10 @@ pub fn create_client(
     if !ciphers.is_empty() {
         transport.set_ciphers(&ciphers)?;
     }
+    let groups = args.shared.get_groups();
+    if !groups.is_empty() {
+        transport.set_groups(&groups)?;
+    }
     let mut client = Http3Client::new_with_conn(
         transport,
         Http3Parameters::default()
@@ -143,9 +149,9 @@ impl super::Client for Http3Client {
         self.process_multiple_output(now, max_datagrams)
     }
 
-    fn process_multiple_input<'a>(
+    fn process_multiple_input(
         &mut self,
-        dgrams: impl IntoIterator<Item = Datagram<&'a mut [u8]>>,
+        dgrams: impl IntoIterator<Item = Datagram<SharedBytesMut>>,
         now: Instant,
     ) {
         self.process_multiple_input(dgrams, now);
@@ -203,6 +209,7 @@ impl super::Handler for Handler {
                                 stream_id,
                                 &mut self.read_buffer,
                             )?;
+                            self.bytes_transferred += sz;
 
                             handler.process_data_readable(
                                 stream_id,
@@ -232,7 +239,8 @@ impl super::Handler for Handler {
                             qwarn!("Data on unexpected stream: {stream_id}");
                         }
                         Some(handler) => {
-                            handler.process_data_writable(client, stream_id, Instant::now());
+                            self.bytes_transferred +=
+                                handler.process_data_writable(client, stream_id, Instant::now());
                         }
                     }
                 }
@@ -260,6 +268,10 @@ impl super::Handler for Handler {
     fn take_token(&mut self) -> Option<ResumptionToken> {
         self.token.take()
     }
+
+    fn bytes_transferred(&self) -> usize {
+        self.bytes_transferred
+    }
 }
 
 trait StreamHandler {
@@ -270,12 +282,13 @@ trait StreamHandler {
         data: &[u8],
         output_read_data: bool,
     ) -> Res<()>;
+    /// Returns the number of bytes sent.
     fn process_data_writable(
         &mut self,
         client: &mut Http3Client,
         stream_id: StreamId,
         now: Instant,
-    );
+    ) -> usize;
 }
 
 struct DownloadStreamHandler {
@@ -323,7 +336,8 @@ impl StreamHandler for DownloadStreamHandler {
         _client: &mut Http3Client,
         _stream_id: StreamId,
         _now: Instant,
-    ) {
+    ) -> usize {
+        0
     }
 }
 
@@ -343,7 +357,7 @@ impl StreamHandler for UploadStreamHandler {
         if let Ok(txt) = std::str::from_utf8(data) {
             let trimmed_txt = txt.trim_end_matches(char::from(0));
             let parsed: usize = trimmed_txt.parse().map_err(|_| Error::InvalidInput)?;
-            if parsed == self.data.len() {
+            if Some(parsed) == self.data.len() {
                 qinfo!(
                     "Stream ID: {stream_id:?}, Upload time: {:?}",
                     Instant::now().duration_since(self.start)
@@ -361,16 +375,19 @@ impl StreamHandler for UploadStreamHandler {
         client: &mut Http3Client,
         stream_id: StreamId,
         now: Instant,
-    ) {
-        match self
-            .data
-            .send(|chunk| client.send_data(stream_id, chunk, now))
-        {
+    ) -> usize {
+        let mut sent = 0;
+        match self.data.send(now, |chunk| {
+            client
+                .send_data(stream_id, chunk, now)
+                .inspect(|n| sent += n)
+        }) {
             SendResult::StreamClosed => qwarn!("Stream {stream_id} is closed"),
             // Stream may be closed; ignore errors.
             SendResult::Done => _ = client.stream_close_send(stream_id, now),
-            SendResult::MoreData => {}
+            SendResult::MoreData | SendResult::Throttled(_) => {}
         }
+        sent
     }
 }
 
@@ -428,7 +445,11 @@ impl UrlHandler {
                         Box::new(DownloadStreamHandler { out_file })
                     }
                     "POST" => Box::new(UploadStreamHandler {
-                        data: SendData::zeroes(self.args.upload_size),
+                        data: if self.args.duration.is_some() {
+                            SendData::infinite()
+                        } else {
+                            SendData::zeroes(self.args.upload_size)
+                        },
                         start: now,
                     }),
                     _ => unimplemented!(),
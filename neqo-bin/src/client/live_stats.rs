@@ -0,0 +1,104 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Periodic throughput reports, in the style of `iperf`.
+
+use std::time::{Duration, Instant};
+
+use neqo_common::qinfo;
+use neqo_transport::Stats;
+
+/// The counters at the start of an interval.
+#[derive(Clone, Copy)]
+struct Sample {
+    time: Instant,
+    bytes: usize,
+    packets_tx: usize,
+    lost: usize,
+}
+
+impl Sample {
+    const fn new(time: Instant, stats: &Stats, bytes: usize) -> Self {
+        Self {
+            time,
+            bytes,
+            packets_tx: stats.packets_tx,
+            lost: stats.lost,
+        }
+    }
+}
+
+/// Reports goodput, RTT, congestion window and losses once per
+/// [`LiveStats::INTERVAL`].
+///
+/// Goodput counts the bytes of request and response bodies.  The other values
+/// are those of the client, so the congestion window and losses describe
+/// uploads.
+pub struct LiveStats {
+    start: Sample,
+    last: Sample,
+}
+
+impl LiveStats {
+    pub const INTERVAL: Duration = Duration::from_secs(1);
+
+    pub const fn new(now: Instant) -> Self {
+        let start = Sample {
+            time: now,
+            bytes: 0,
+            packets_tx: 0,
+            lost: 0,
+        };
+        Self { start, last: start }
+    }
+
+    /// When the next report is due.
+    pub fn next(&self) -> Instant {
+        self.last.time + Self::INTERVAL
+    }
+
+    /// Report on the last interval, if it has ended.  `bytes` is the total
+    /// number of body bytes sent and received so far.
+    pub fn report(&mut self, now: Instant, stats: &Stats, bytes: usize) {
+        if now < self.next() {
+            return;
+        }
+        let sample = Sample::new(now, stats, bytes);
+        Self::print(&self.last, &sample, self.start.time, stats);
+        self.last = sample;
+    }
+
+    /// Report on the whole run.
+    pub fn summary(&self, now: Instant, stats: &Stats, bytes: usize) {
+        qinfo!("Total:");
+        Self::print(
+            &self.start,
+            &Sample::new(now, stats, bytes),
+            self.start.time,
+            stats,
+        );
+    }
+
+    fn print(from: &Sample, to: &Sample, start: Instant, stats: &Stats) {
+        let elapsed = to.time.saturating_duration_since(from.time).as_secs_f64();
+        let bytes = to.bytes.saturating_sub(from.bytes);
+        #[expect(clippy::cast_precision_loss, reason = "This is only for display.")]
+        let goodput = if elapsed > 0.0 {
+            bytes as f64 * 8.0 / elapsed / 1_000_000.0
+        } else {
+            0.0
+        };
+        let packets = to.packets_tx.saturating_sub(from.packets_tx);
+        let lost = to.lost.saturating_sub(from.lost);
+        qinfo!(
+            "{:6.2}-{:6.2}s {bytes:>12} bytes {goodput:>9.2} Mbit/s rtt {:>9.3?} cwnd {:>9} lost {lost}/{packets} packets",
+            from.time.saturating_duration_since(start).as_secs_f64(),
+            to.time.saturating_duration_since(start).as_secs_f64(),
+            stats.rtt,
+            stats.cc.cwnd.unwrap_or_default(),
+        );
+    }
+}
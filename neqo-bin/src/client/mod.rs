@@ -16,7 +16,7 @@ use std::{
     path::PathBuf,
     pin::Pin,
     process::exit,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use clap::Parser;
@@ -25,7 +25,7 @@ use futures::{
     future::{Either, select},
 };
 use http::Uri as Url;
-use neqo_common::{Datagram, Role, qdebug, qerror, qinfo, qlog::Qlog};
+use neqo_common::{Datagram, Role, SharedBytesMut, qdebug, qerror, qinfo, qlog::Qlog};
 use neqo_crypto::{
     Cipher, ResumptionToken,
     constants::{TLS_AES_128_GCM_SHA256, TLS_AES_256_GCM_SHA384, TLS_CHACHA20_POLY1305_SHA256},
@@ -38,10 +38,12 @@ use rustc_hash::FxHashMap as HashMap;
 use thiserror::Error;
 use tokio::time::Sleep;
 
+use self::live_stats::LiveStats;
 use crate::SharedArgs;
 
 mod http09;
 mod http3;
+mod live_stats;
 
 const BUFWRITER_BUFFER_SIZE: usize = 64 * 1024;
 
@@ -146,6 +148,16 @@ pub struct Args {
     #[arg(name = "stats", long)]
     stats: bool,
 
+    /// Print goodput, RTT, congestion window and losses every second.
+    #[arg(name = "live-stats", long)]
+    live_stats: bool,
+
+    /// Close the connection after this many seconds, even if requests are
+    /// outstanding.  Uploads send zeroes that do not end, and downloads can use
+    /// a server that sends them, such as `neqo-server --infinite`.
+    #[arg(name = "duration", long)]
+    duration: Option<u64>,
+
     /// The length of the local connection ID.
     #[arg(name = "cid-length", short = 'l', long, default_value = "0",
           value_parser = clap::value_parser!(u8).range(..=20))]
@@ -192,6 +204,8 @@ impl Args {
             test: None,
             upload_size,
             stats: false,
+            live_stats: false,
+            duration: None,
             cid_len: 0,
         }
     }
@@ -358,6 +372,8 @@ trait Handler {
 
     fn handle(&mut self, client: &mut Self::Client) -> Res<bool>;
     fn take_token(&mut self) -> Option<ResumptionToken>;
+    /// The number of bytes of request and response bodies sent and received.
+    fn bytes_transferred(&self) -> usize;
 }
 
 enum CloseState {
@@ -370,9 +386,9 @@ enum CloseState {
 trait Client {
     fn process_multiple_output(&mut self, now: Instant, max_datagrams: NonZeroUsize)
     -> OutputBatch;
-    fn process_multiple_input<'a>(
+    fn process_multiple_input(
         &mut self,
-        dgrams: impl IntoIterator<Item = Datagram<&'a mut [u8]>>,
+        dgrams: impl IntoIterator<Item = Datagram<SharedBytesMut>>,
         now: Instant,
     );
     fn has_events(&self) -> bool;
@@ -391,8 +407,15 @@ struct Runner<'a, H: Handler> {
     timeout: Option<Pin<Box<Sleep>>>,
     args: &'a Args,
     recv_buf: RecvBuf,
+    live_stats: Option<LiveStats>,
+    /// When to close the connection, see [`Args::duration`].
+    deadline: Option<Instant>,
 }
 
+#[expect(
+    clippy::future_not_send,
+    reason = "The receive buffers are not `Send`, which is OK for this example code."
+)]
 impl<'a, H: Handler> Runner<'a, H> {
     fn new(
         local_addr: SocketAddr,
@@ -401,6 +424,7 @@ impl<'a, H: Handler> Runner<'a, H> {
         handler: H,
         args: &'a Args,
     ) -> Self {
+        let now = Instant::now();
         Self {
             local_addr,
             socket,
@@ -408,17 +432,47 @@ impl<'a, H: Handler> Runner<'a, H> {
             handler,
             args,
             timeout: None,
-            recv_buf: RecvBuf::default(),
+            recv_buf: args
+                .shared
+                .io_batch_size
+                .map_or_else(RecvBuf::default, RecvBuf::new),
+            live_stats: args.live_stats.then(|| LiveStats::new(now)),
+            deadline: args.duration.map(|secs| now + Duration::from_secs(secs)),
+        }
+    }
+
+    /// Wake up at `time` at the latest.
+    fn wake_at(&mut self, time: Instant) {
+        let time = tokio::time::Instant::from_std(time);
+        if self.timeout.as_ref().is_none_or(|t| t.deadline() > time) {
+            self.timeout = Some(Box::pin(tokio::time::sleep_until(time)));
+        }
+    }
+
+    fn report_stats(&mut self) {
+        if let Some(live_stats) = &mut self.live_stats {
+            live_stats.report(
+                Instant::now(),
+                &self.client.stats(),
+                self.handler.bytes_transferred(),
+            );
+            let next = live_stats.next();
+            self.wake_at(next);
         }
     }
 
     async fn run(mut self) -> Res<Option<ResumptionToken>> {
         loop {
-            let handler_done = self.handler.handle(&mut self.client)?;
+            let expired = self.deadline.is_some_and(|d| Instant::now() >= d);
+            let handler_done = self.handler.handle(&mut self.client)? || expired;
             self.process_output().await?;
+            self.report_stats();
             if self.client.has_events() {
                 continue;
             }
+            if let Some(deadline) = self.deadline.filter(|_| !expired) {
+                self.wake_at(deadline);
+            }
 
             match (handler_done, self.client.is_closed()?) {
                 // more work; or no more work, already closing connection
@@ -440,14 +494,24 @@ impl<'a, H: Handler> Runner<'a, H> {
             }
         }
 
+        if let Some(live_stats) = &self.live_stats {
+            live_stats.summary(
+                Instant::now(),
+                &self.client.stats(),
+                self.handler.bytes_transferred(),
+            );
+        }
         if self.args.stats {
             qinfo!("{:?}", self.client.stats());
+            qinfo!("{:?}", self.socket.stats());
         }
 
         Ok(self.handler.take_token())
     }
 
     async fn process_output(&mut self) -> Result<(), io::Error> {
+        let io_batch_size = self.args.shared.io_batch_size.map_or(1, NonZeroUsize::get);
+        let mut pending = Vec::with_capacity(io_batch_size);
         loop {
             let max_datagrams = self
                 .socket
@@ -460,28 +524,12 @@ impl<'a, H: Handler> Runner<'a, H> {
                 .client
                 .process_multiple_output(Instant::now(), max_datagrams)
             {
-                OutputBatch::DatagramBatch(dgram) => loop {
-                    // Optimistically attempt sending datagram. In case the OS
-                    // buffer is full, wait till socket is writable then try
-                    // again.
-                    match self.socket.send(&dgram) {
-                        Ok(()) => break,
-                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                            self.socket.writable().await?;
-                            // Now try again.
-                        }
-                        Err(e)
-                            if e.raw_os_error() == Some(libc::EIO) && dgram.num_datagrams() > 1 =>
-                        {
-                            qinfo!(
-                                "`libc::sendmsg` failed with {e}; quinn-udp will halt segmentation offload"
-                            );
-                            // Drop the packets and let QUIC handle retransmission.
-                            break;
-                        }
-                        e @ Err(_) => return e,
+                OutputBatch::DatagramBatch(dgram) => {
+                    pending.push(dgram);
+                    if pending.len() >= io_batch_size {
+                        self.socket.send_all(&mut pending).await?;
                     }
-                },
+                }
                 OutputBatch::Callback(new_timeout) => {
                     qdebug!("Setting timeout of {new_timeout:?}");
                     self.timeout = Some(Box::pin(tokio::time::sleep(new_timeout)));
@@ -494,7 +542,7 @@ impl<'a, H: Handler> Runner<'a, H> {
             }
         }
 
-        Ok(())
+        self.socket.send_all(&mut pending).await
     }
 
     async fn process_multiple_input(&mut self) -> Res<()> {
@@ -593,6 +641,8 @@ pub async fn client(mut args: Args) -> Res<()> {
             qinfo!("Datagrams may be fragmented by the IP layer. Disabling PMTUD.");
             args.shared.quic_parameters.no_pmtud = true;
         }
+        args.shared.quic_parameters.enable_txtime(&socket);
+        args.shared.size_socket_buffers(&socket)?;
         let real_local = socket.local_addr().unwrap();
         qinfo!(
             "{} Client connecting: {real_local:?} -> {remote_addr:?}",
@@ -610,24 +660,41 @@ pub async fn client(mut args: Args) -> Res<()> {
 
             first = false;
 
-            token = if args.shared.alpn == "h3" {
-                let client = http3::create_client(&args, real_local, remote_addr, &host, token)
-                    .expect("failed to create client");
-
-                let handler = http3::Handler::new(to_request, args.clone());
-
-                Runner::new(real_local, &mut socket, client, handler, &args)
-                    .run()
-                    .await?
-            } else {
-                let client = http09::create_client(&args, real_local, remote_addr, &host, token)
-                    .expect("failed to create client");
-
-                let handler = http09::Handler::new(to_request, &args);
-
-                Runner::new(real_local, &mut socket, client, handler, &args)
-                    .run()
-                    .await?
+            let mut ech_retried = false;
+            token = loop {
+                let res = if args.shared.alpn == "h3" {
+                    let client =
+                        http3::create_client(&args, real_local, remote_addr, &host, token.clone())
+                            .expect("failed to create client");
+
+                    let handler = http3::Handler::new(to_request.clone(), args.clone());
+
+                    Runner::new(real_local, &mut socket, client, handler, &args)
+                        .run()
+                        .await
+                } else {
+                    let client =
+                        http09::create_client(&args, real_local, remote_addr, &host, token.clone())
+                            .expect("failed to create client");
+
+                    let handler = http09::Handler::new(to_request.clone(), &args);
+
+                    Runner::new(real_local, &mut socket, client, handler, &args)
+                        .run()
+                        .await
+                };
+                match res {
+                    // The server rejected ECH and provided retry configs, which we have
+                    // authenticated against the public name. Try again once with those.
+                    Err(Error::Transport(neqo_transport::Error::EchRetry(config)))
+                        if !ech_retried =>
+                    {
+                        qinfo!("Server rejected ECH, retrying with updated ECHConfigList");
+                        args.ech = Some(config);
+                        ech_retried = true;
+                    }
+                    res => break res?,
+                }
             };
         }
     }
@@ -7,16 +7,27 @@
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
 use std::{
+    io,
     net::{SocketAddr, ToSocketAddrs as _},
+    num::NonZeroUsize,
     path::PathBuf,
     time::Duration,
 };
 
 use clap::{Parser, builder::TypedValueParser as _};
+use neqo_common::{Dscp, qinfo, qwarn};
+use neqo_crypto::{
+    Group,
+    constants::{
+        TLS_GRP_EC_SECP256R1, TLS_GRP_EC_SECP384R1, TLS_GRP_EC_SECP521R1, TLS_GRP_EC_X25519,
+        TLS_GRP_KEM_MLKEM768X25519,
+    },
+};
 use neqo_transport::{
-    CongestionControl, ConnectionParameters, DEFAULT_INITIAL_RTT, SlowStart, StreamType, Version,
-    tparams::PreferredAddress,
+    CongestionControl, ConnectionParameters, DEFAULT_INITIAL_RTT, IdleRestart, SlowStart,
+    StreamType, Version, tparams::PreferredAddress,
 };
+use neqo_udp::TxTimeClock;
 use strum::VariantNames as _;
 use thiserror::Error;
 
@@ -59,10 +70,25 @@ pub struct SharedArgs {
     /// From: `TLS_AES_128_GCM_SHA256`, `TLS_AES_256_GCM_SHA384`, `TLS_CHACHA20_POLY1305_SHA256`.
     ciphers: Vec<String>,
 
+    #[arg(short = 'g', long, number_of_values = 1)]
+    /// The set of TLS key exchange groups to enable, in order of preference.
+    /// From: `X25519MLKEM768`, `X25519`, `P256`, `P384`, `P521`.
+    groups: Vec<String>,
+
     #[arg(name = "qns-test", long)]
     /// Enable special behavior for use with QUIC Network Simulator
     qns_test: Option<String>,
 
+    #[arg(name = "io-batch-size", long)]
+    /// The number of messages to send and receive with one syscall, using
+    /// `sendmmsg` and `recvmmsg` where available.
+    io_batch_size: Option<NonZeroUsize>,
+
+    #[arg(name = "max-bdp", long)]
+    /// The largest bandwidth-delay product to expect, in bytes. Socket buffers
+    /// are grown to hold this much, so that the OS does not drop datagrams.
+    max_bdp: Option<usize>,
+
     #[command(flatten)]
     quic_parameters: QuicParameters,
 }
@@ -78,13 +104,43 @@ impl Default for SharedArgs {
             max_table_size_decoder: 16384,
             max_blocked_streams: 10,
             ciphers: vec![],
+            groups: vec![],
             qns_test: None,
+            io_batch_size: None,
+            max_bdp: None,
             quic_parameters: QuicParameters::default(),
         }
     }
 }
 
 impl SharedArgs {
+    /// Size the buffers of `socket` for the configured maximum BDP, if any.
+    pub(crate) fn size_socket_buffers(&self, socket: &udp::Socket) -> io::Result<()> {
+        if let Some(bdp) = self.max_bdp {
+            let sizes = socket.size_buffers_for_bdp(bdp)?;
+            qinfo!(
+                "Socket buffer sizes: send {}, receive {}",
+                sizes.send,
+                sizes.recv
+            );
+        }
+        Ok(())
+    }
+
+    fn get_groups(&self) -> Vec<Group> {
+        self.groups
+            .iter()
+            .filter_map(|g| match g.as_str() {
+                "X25519MLKEM768" => Some(TLS_GRP_KEM_MLKEM768X25519),
+                "X25519" => Some(TLS_GRP_EC_X25519),
+                "P256" => Some(TLS_GRP_EC_SECP256R1),
+                "P384" => Some(TLS_GRP_EC_SECP384R1),
+                "P521" => Some(TLS_GRP_EC_SECP521R1),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    }
+
     #[must_use]
     pub fn get_alpn(&self) -> &str {
         &self.alpn
@@ -92,6 +148,10 @@ impl SharedArgs {
 }
 
 #[derive(Clone, Debug, Parser)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Not a good use of that lint."
+)]
 pub struct QuicParameters {
     #[arg(
         short = 'Q',
@@ -135,10 +195,58 @@ pub struct QuicParameters {
     /// The slow start algorithm to use.
     pub slow_start: SlowStart,
 
+    #[arg(long)]
+    /// Whether to use Proportional Rate Reduction during loss recovery.
+    pub prr: bool,
+
+    #[arg(long)]
+    /// Whether to reduce the congestion window after long periods where it is
+    /// not used.
+    pub cwv: bool,
+
+    #[arg(long)]
+    /// Whether to reduce the congestion window in proportion to the fraction of
+    /// packets marked CE, rather than as for a loss.
+    pub dctcp: bool,
+
+    #[arg(long = "init_cwnd", default_value = "10",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..))]
+    /// The initial congestion window, in packets.
+    pub initial_cwnd: usize,
+
+    #[arg(long = "idle_restart", default_value = "off",
+        value_parser = clap::builder::PossibleValuesParser::new(IdleRestart::VARIANTS)
+            .map(|s| s.parse::<IdleRestart>().unwrap()))]
+    /// What to do with the congestion window when sending resumes after an idle period.
+    pub idle_restart: IdleRestart,
+
     #[arg(long = "no-pacing")]
     /// Whether to disable pacing.
     pub no_pacing: bool,
 
+    #[arg(long = "pacing_burst", default_value = "2",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    /// The number of packets that the pacer can send in a burst.
+    pub pacing_burst: usize,
+
+    #[arg(name = "txtime-horizon", long)]
+    /// Offload pacing to the kernel with `SO_TXTIME`, releasing packets up to
+    /// this many milliseconds ahead of their departure time. Falls back to
+    /// pacing in the stack where `SO_TXTIME` is not available.
+    pub txtime_horizon_ms: Option<u64>,
+
+    #[arg(name = "txtime-clock", long, default_value = "monotonic",
+        value_parser = txtime_clock_from_str)]
+    /// The clock for `SO_TXTIME`: "monotonic" for the `fq` qdisc, or "tai" for
+    /// the ETF qdisc.
+    pub txtime_clock: TxTimeClock,
+
+    #[arg(long, value_parser = dscp_from_str)]
+    /// The DSCP codepoint to mark packets with, in decimal, e.g. 1 for
+    /// lower-effort or 46 for expedited forwarding.  Only Linux and Android
+    /// set it; elsewhere, packets are sent without it.
+    pub dscp: Option<Dscp>,
+
     #[arg(long)]
     /// Whether to disable path MTU discovery.
     pub no_pmtud: bool,
@@ -147,6 +255,14 @@ pub struct QuicParameters {
     /// Whether to slice the SNI.
     pub no_sni_slicing: bool,
 
+    #[arg(long)]
+    /// Whether to disable the hybrid post-quantum key exchange group.
+    pub no_mlkem: bool,
+
+    #[arg(long)]
+    /// Whether to advertise the hybrid post-quantum key exchange group after the classical ones.
+    pub mlkem_last: bool,
+
     #[arg(name = "preferred-address-v4", long)]
     /// An IPv4 address for the server preferred address.
     pub preferred_address_v4: Option<String>,
@@ -168,11 +284,22 @@ impl Default for QuicParameters {
                 .expect("this value will always be less than u64::MAX"),
             congestion_control: CongestionControl::Cubic,
             slow_start: SlowStart::Classic,
+            prr: false,
+            cwv: false,
+            dctcp: false,
+            initial_cwnd: 10,
+            idle_restart: IdleRestart::Off,
             no_pacing: false,
+            pacing_burst: 2,
+            txtime_horizon_ms: None,
+            txtime_clock: TxTimeClock::default(),
+            dscp: None,
             no_pmtud: false,
             preferred_address_v4: None,
             preferred_address_v6: None,
             no_sni_slicing: false,
+            no_mlkem: false,
+            mlkem_last: false,
         }
     }
 }
@@ -237,6 +364,17 @@ impl QuicParameters {
         }
     }
 
+    /// Enable `SO_TXTIME` on `socket` if pacing is to be offloaded, and
+    /// otherwise keep pacing in the stack.
+    pub(crate) fn enable_txtime(&mut self, socket: &udp::Socket) {
+        if self.txtime_horizon_ms.is_some()
+            && let Err(e) = socket.enable_txtime(self.txtime_clock)
+        {
+            qwarn!("Cannot enable SO_TXTIME ({e}), pacing in the stack");
+            self.txtime_horizon_ms = None;
+        }
+    }
+
     #[must_use]
     pub fn get(&self, alpn: &str) -> ConnectionParameters {
         let mut params = ConnectionParameters::default()
@@ -246,9 +384,22 @@ impl QuicParameters {
             .initial_rtt(Duration::from_millis(self.initial_rtt_ms))
             .congestion_control(self.congestion_control)
             .slow_start(self.slow_start)
+            .prr(self.prr)
+            .cwv(self.cwv)
+            .dctcp(self.dctcp)
+            .initial_cwnd(self.initial_cwnd)
+            .idle_restart(self.idle_restart)
             .pacing(!self.no_pacing)
+            .pacing_burst(self.pacing_burst)
+            .pacing_horizon(
+                self.txtime_horizon_ms
+                    .map_or(Duration::ZERO, Duration::from_millis),
+            )
+            .dscp(self.dscp.unwrap_or_default())
             .pmtud(!self.no_pmtud)
-            .sni_slicing(!self.no_sni_slicing);
+            .sni_slicing(!self.no_sni_slicing)
+            .mlkem(!self.no_mlkem)
+            .mlkem_preferred(!self.mlkem_last);
         params = if let Some(pa) = self.preferred_address() {
             params.preferred_address(pa)
         } else {
@@ -279,6 +430,24 @@ fn from_str(s: &str) -> Result<Version, Error> {
     Version::try_from(v).map_err(|_| Error::Argument("unknown version"))
 }
 
+fn dscp_from_str(s: &str) -> Result<Dscp, Error> {
+    let v = s
+        .parse::<u8>()
+        .map_err(|_| Error::Argument("DSCP codepoints need to be specified in decimal"))?;
+    (v < 64)
+        .then_some(v << 2)
+        .and_then(Dscp::from_repr)
+        .ok_or(Error::Argument("unknown DSCP codepoint"))
+}
+
+fn txtime_clock_from_str(s: &str) -> Result<TxTimeClock, Error> {
+    match s {
+        "monotonic" => Ok(TxTimeClock::Monotonic),
+        "tai" => Ok(TxTimeClock::Tai),
+        _ => Err(Error::Argument("unknown txtime clock")),
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Error: {0}")]
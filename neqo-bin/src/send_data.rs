@@ -4,7 +4,12 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{borrow::Cow, cmp::min};
+use std::{
+    borrow::Cow,
+    cmp::min,
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
 
 use crate::STREAM_IO_BUFFER_SIZE;
 
@@ -13,7 +18,33 @@ pub struct SendData {
     data: Cow<'static, [u8]>,
     offset: usize,
     remaining: usize,
-    total: usize,
+    /// The total length, or `None` if the data does not end.
+    total: Option<usize>,
+    throttle: Option<Throttle>,
+}
+
+/// Limits the rate at which [`SendData`] is sent.
+#[derive(Debug)]
+struct Throttle {
+    /// The rate, in bytes per second.
+    rate: NonZeroU64,
+    /// When sending started.
+    start: Option<Instant>,
+    sent: usize,
+}
+
+impl Throttle {
+    /// How often sending resumes, and how much data it can send at once.
+    const INTERVAL: Duration = Duration::from_millis(10);
+
+    /// The number of bytes that can be sent at `now`.
+    fn allowance(&mut self, now: Instant) -> usize {
+        let elapsed = now - *self.start.get_or_insert(now) + Self::INTERVAL;
+        let budget = u128::from(self.rate.get()) * elapsed.as_nanos() / 1_000_000_000;
+        usize::try_from(budget)
+            .unwrap_or(usize::MAX)
+            .saturating_sub(self.sent)
+    }
 }
 
 impl From<&[u8]> for SendData {
@@ -26,10 +57,11 @@ impl From<Vec<u8>> for SendData {
     fn from(data: Vec<u8>) -> Self {
         let remaining = data.len();
         Self {
-            total: data.len(),
+            total: Some(data.len()),
             data: Cow::Owned(data),
             offset: 0,
             remaining,
+            throttle: None,
         }
     }
 }
@@ -47,10 +79,29 @@ impl SendData {
             data: Cow::Borrowed(MESSAGE),
             offset: 0,
             remaining: total,
-            total,
+            total: Some(total),
+            throttle: None,
         }
     }
 
+    /// Zeroes that do not end.
+    pub const fn infinite() -> Self {
+        let mut data = Self::zeroes(usize::MAX);
+        data.total = None;
+        data
+    }
+
+    /// Send no faster than `rate` bytes per second.
+    #[must_use]
+    pub const fn throttled(mut self, rate: NonZeroU64) -> Self {
+        self.throttle = Some(Throttle {
+            rate,
+            start: None,
+            sent: 0,
+        });
+        self
+    }
+
     fn slice(&self) -> &[u8] {
         let end = min(self.data.len(), self.offset + self.remaining);
         &self.data[self.offset..end]
@@ -58,26 +109,39 @@ impl SendData {
 
     /// Send data using a fallible send function, handling stream closure gracefully.
     /// Returns `SendResult::Done` if all data was sent, `SendResult::MoreData` if
-    /// more data remains, or `SendResult::StreamClosed` if the stream was closed
-    /// (e.g., by `STOP_SENDING`).
-    pub fn send<F, E>(&mut self, mut f: F) -> SendResult
+    /// more data remains, `SendResult::Throttled` if the rate limit was reached,
+    /// or `SendResult::StreamClosed` if the stream was closed (e.g., by
+    /// `STOP_SENDING`).
+    pub fn send<F, E>(&mut self, now: Instant, mut f: F) -> SendResult
     where
         F: FnMut(&[u8]) -> Result<usize, E>,
     {
         while self.remaining > 0 {
-            match f(self.slice()) {
+            let allowance = match &mut self.throttle {
+                Some(throttle) => match throttle.allowance(now) {
+                    0 => return SendResult::Throttled(Throttle::INTERVAL),
+                    allowance => allowance,
+                },
+                None => usize::MAX,
+            };
+            let slice = self.slice();
+            match f(&slice[..min(slice.len(), allowance)]) {
                 Err(_) => return SendResult::StreamClosed,
                 Ok(0) => return SendResult::MoreData,
                 Ok(sent) => {
                     self.remaining -= sent;
                     self.offset = (self.offset + sent) % self.data.len();
+                    if let Some(throttle) = &mut self.throttle {
+                        throttle.sent += sent;
+                    }
                 }
             }
         }
         SendResult::Done
     }
 
-    pub const fn len(&self) -> usize {
+    /// The total length, or `None` if the data does not end.
+    pub const fn len(&self) -> Option<usize> {
         self.total
     }
 }
@@ -89,6 +153,65 @@ pub enum SendResult {
     Done,
     /// More data remains to be sent (stream buffer full).
     MoreData,
+    /// More data remains to be sent, but not before the given delay.
+    Throttled(Duration),
     /// Stream was closed by peer (e.g., `STOP_SENDING` received).
     StreamClosed,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        num::NonZeroU64,
+        time::{Duration, Instant},
+    };
+
+    use super::{SendData, SendResult};
+
+    fn send_all(data: &mut SendData, now: Instant) -> (SendResult, usize) {
+        let mut sent = 0;
+        let res = data.send(now, |chunk| {
+            sent += chunk.len();
+            Ok::<_, Infallible>(chunk.len())
+        });
+        (res, sent)
+    }
+
+    #[test]
+    fn throttled() {
+        let now = Instant::now();
+        let mut data = SendData::zeroes(10_000).throttled(NonZeroU64::new(100_000).unwrap());
+        assert_eq!(data.len(), Some(10_000));
+
+        // One interval worth of data can be sent immediately.
+        let (res, sent) = send_all(&mut data, now);
+        assert_eq!(res, SendResult::Throttled(Duration::from_millis(10)));
+        assert_eq!(sent, 1_000);
+        assert_eq!(send_all(&mut data, now), (res, 0));
+
+        let (res, sent) = send_all(&mut data, now + Duration::from_millis(50));
+        assert_eq!(res, SendResult::Throttled(Duration::from_millis(10)));
+        assert_eq!(sent, 5_000);
+
+        let (res, sent) = send_all(&mut data, now + Duration::from_secs(1));
+        assert_eq!(res, SendResult::Done);
+        assert_eq!(sent, 4_000);
+    }
+
+    #[test]
+    fn infinite() {
+        let mut data = SendData::infinite();
+        assert_eq!(data.len(), None);
+        let mut sent = 0;
+        let res = data.send(Instant::now(), |chunk| {
+            if sent > 1_000_000 {
+                return Ok::<_, Infallible>(0);
+            }
+            assert!(chunk.iter().all(|&b| b == 0));
+            sent += chunk.len();
+            Ok(chunk.len())
+        });
+        assert_eq!(res, SendResult::MoreData);
+    }
+}
@@ -8,22 +8,28 @@ use std::{
     borrow::Cow,
     cell::RefCell,
     fmt::{self, Display, Formatter},
+    mem,
     num::NonZeroUsize,
+    pin::Pin,
     rc::Rc,
     slice, str,
+    task::{Context, Poll},
     time::Instant,
 };
 
-use neqo_common::{Datagram, event::Provider as _, hex, qdebug, qerror, qinfo, qwarn};
-use neqo_crypto::{AllowZeroRtt, AntiReplay, generate_ech_keys, random};
+use neqo_common::{Datagram, datagram::Payload, event::Provider as _, hex, qdebug, qinfo, qwarn};
+use neqo_crypto::{AllowZeroRtt, generate_ech_keys, random};
 use neqo_http3::Error;
 use neqo_transport::{
     ConnectionEvent, ConnectionIdGenerator, OutputBatch, State, StreamId,
-    server::{ConnectionRef, Server, ValidateAddress},
+    server::{AdmissionStats, ConnectionRef, Server, ValidateAddress},
 };
 use rustc_hash::FxHashMap as HashMap;
 
-use super::{Args, qns_read_response};
+use super::{
+    Args, SharedState,
+    response::{ResponseProvider, Wakeup},
+};
 use crate::{
     STREAM_IO_BUFFER_SIZE,
     send_data::{SendData, SendResult},
@@ -39,31 +45,37 @@ pub struct HttpServer {
     server: Server,
     write_state: HashMap<StreamId, HttpStreamState>,
     read_state: HashMap<StreamId, Vec<u8>>,
-    is_qns_test: bool,
+    /// Streams waiting for their rate limit, see [`SendResult::Throttled`].
+    throttled: Vec<(StreamId, ConnectionRef)>,
+    wakeup: Wakeup,
+    responses: Box<dyn ResponseProvider>,
     read_buffer: Vec<u8>,
 }
 
 impl HttpServer {
     pub fn new(
         args: &Args,
-        anti_replay: AntiReplay,
+        state: &SharedState,
         cid_manager: Rc<RefCell<dyn ConnectionIdGenerator>>,
     ) -> Result<Self, Error> {
         let mut server = Server::new(
             args.now(),
             slice::from_ref(&args.key),
             slice::from_ref(&args.shared.alpn),
-            anti_replay,
+            state.anti_replay.clone(),
             Box::new(AllowZeroRtt {}),
             cid_manager,
             args.shared.quic_parameters.get(&args.shared.alpn),
         )?;
 
         server.set_ciphers(args.get_ciphers());
+        server.set_groups(args.shared.get_groups());
         server.set_qlog_dir(args.shared.qlog_dir.clone());
+        server.set_token_key(&state.token_key, state.epoch)?;
         if args.retry {
             server.set_validation(ValidateAddress::Always);
         }
+        server.set_admission_limits(&args.admission_limits());
         if args.ech {
             let (sk, pk) = generate_ech_keys().map_err(|_| Error::Internal)?;
             server
@@ -76,11 +88,20 @@ impl HttpServer {
             server,
             write_state: HashMap::default(),
             read_state: HashMap::default(),
-            is_qns_test: args.shared.qns_test.is_some(),
+            throttled: Vec::new(),
+            wakeup: Wakeup::default(),
+            responses: args.response_provider(),
             read_buffer: vec![0; STREAM_IO_BUFFER_SIZE],
         })
     }
 
+    /// Use `responses` to respond to requests.
+    #[must_use]
+    pub fn with_response_provider(mut self, responses: Box<dyn ResponseProvider>) -> Self {
+        self.responses = responses;
+        self
+    }
+
     fn save_partial(&mut self, stream_id: StreamId, partial: Vec<u8>, conn: &ConnectionRef) {
         if partial.len() < 4096 {
             qdebug!(
@@ -99,7 +120,7 @@ impl HttpServer {
         }
     }
 
-    fn stream_readable(&mut self, stream_id: StreamId, conn: &ConnectionRef) {
+    fn stream_readable(&mut self, stream_id: StreamId, conn: &ConnectionRef, now: Instant) {
         if !stream_id.is_client_initiated() || !stream_id.is_bidi() {
             qdebug!("Stream {stream_id} not client-initiated bidi, ignoring");
             return;
@@ -134,33 +155,15 @@ impl HttpServer {
         let Some(path) = msg
             .strip_prefix("GET /")
             .and_then(|s| s.lines().next())
-            .filter(|p| {
-                if self.is_qns_test {
-                    !p.chars().any(char::is_whitespace)
-                } else {
-                    p.chars().all(|c| c.is_ascii_digit())
-                }
-            })
+            .filter(|p| !p.chars().any(char::is_whitespace))
         else {
             self.save_partial(stream_id, buf.to_vec(), conn);
             return;
         };
 
-        let resp: SendData = {
-            qdebug!("Path = '{path}'");
-            if self.is_qns_test {
-                match qns_read_response(path) {
-                    Ok(data) => data.into(),
-                    Err(e) => {
-                        qerror!("Failed to read {path}: {e}");
-                        b"404".to_vec().into()
-                    }
-                }
-            } else {
-                let count = path.parse().unwrap();
-                SendData::zeroes(count)
-            }
-        };
+        qdebug!("Path = '{path}'");
+        // HTTP/0.9 has no status or headers, so only send the body.
+        let resp = self.responses.respond(path).body;
 
         if let Some(stream_state) = self.write_state.get_mut(&stream_id) {
             match stream_state.data_to_send {
@@ -170,7 +173,7 @@ impl HttpServer {
                 }
             }
             if stream_state.writable {
-                self.stream_writable(stream_id, conn);
+                self.stream_writable(stream_id, conn, now);
             }
         } else {
             self.write_state.insert(
@@ -183,7 +186,7 @@ impl HttpServer {
         }
     }
 
-    fn stream_writable(&mut self, stream_id: StreamId, conn: &ConnectionRef) {
+    fn stream_writable(&mut self, stream_id: StreamId, conn: &ConnectionRef, now: Instant) {
         let Some(stream_state) = self.write_state.get_mut(&stream_id) else {
             qwarn!("Unknown stream {stream_id}, ignoring event");
             return;
@@ -191,7 +194,7 @@ impl HttpServer {
 
         stream_state.writable = true;
         if let Some(resp) = &mut stream_state.data_to_send {
-            match resp.send(|chunk| conn.borrow_mut().stream_send(stream_id, chunk)) {
+            match resp.send(now, |chunk| conn.borrow_mut().stream_send(stream_id, chunk)) {
                 SendResult::StreamClosed => {
                     qwarn!("Stream {stream_id} closed by peer, stopping send");
                     self.write_state.remove(&stream_id);
@@ -203,13 +206,17 @@ impl HttpServer {
                 SendResult::MoreData => {
                     stream_state.writable = false;
                 }
+                SendResult::Throttled(delay) => {
+                    self.wakeup.after(delay);
+                    self.throttled.push((stream_id, conn.clone()));
+                }
             }
         }
     }
 }
 
 impl super::HttpServer for HttpServer {
-    fn process_multiple<'a, D: IntoIterator<Item = Datagram<&'a mut [u8]>>>(
+    fn process_multiple<P: Payload, D: IntoIterator<Item = Datagram<P>>>(
         &mut self,
         dgrams: D,
         now: Instant,
@@ -219,6 +226,11 @@ impl super::HttpServer for HttpServer {
     }
 
     fn process_events(&mut self, now: Instant) {
+        if self.wakeup.fired() {
+            for (stream_id, conn) in mem::take(&mut self.throttled) {
+                self.stream_writable(stream_id, &conn, now);
+            }
+        }
         #[expect(
             clippy::mutable_key_type,
             reason = "ActiveConnectionRef::Hash doesn't access any of the interior mutable types"
@@ -239,10 +251,10 @@ impl super::HttpServer for HttpServer {
                             .insert(stream_id, HttpStreamState::default());
                     }
                     ConnectionEvent::RecvStreamReadable { stream_id } => {
-                        self.stream_readable(stream_id, &acr);
+                        self.stream_readable(stream_id, &acr, now);
                     }
                     ConnectionEvent::SendStreamWritable { stream_id } => {
-                        self.stream_writable(stream_id, &acr);
+                        self.stream_writable(stream_id, &acr, now);
                     }
                     ConnectionEvent::StateChange(State::Connected) => {
                         acr.connection()
@@ -262,6 +274,14 @@ impl super::HttpServer for HttpServer {
     fn has_events(&self) -> bool {
         self.server.has_active_connections()
     }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.get_mut().wakeup.poll(cx)
+    }
+
+    fn admission_stats(&self) -> AdmissionStats {
+        *self.server.admission_stats()
+    }
 }
 
 impl Display for HttpServer {
@@ -7,30 +7,44 @@
 use std::{
     cell::RefCell,
     fmt::{self, Display},
+    mem,
     num::NonZeroUsize,
+    pin::Pin,
     rc::Rc,
     slice,
+    task::{Context, Poll},
     time::Instant,
 };
 
-use neqo_common::{Datagram, Header, header::HeadersExt as _, hex, qdebug, qerror, qinfo};
-use neqo_crypto::{AntiReplay, generate_ech_keys, random};
+use neqo_common::{
+    Datagram, Header, datagram::Payload, header::HeadersExt as _, hex, qdebug, qerror, qinfo,
+};
+use neqo_crypto::{generate_ech_keys, random};
 use neqo_http3::{
     Http3OrWebTransportStream, Http3Parameters, Http3Server, Http3ServerEvent, StreamId,
 };
-use neqo_transport::{ConnectionIdGenerator, OutputBatch, server::ValidateAddress};
+use neqo_transport::{
+    ConnectionIdGenerator, OutputBatch,
+    server::{AdmissionStats, ValidateAddress},
+};
 use rustc_hash::FxHashMap as HashMap;
 
-use super::{Args, qns_read_response};
+use super::{
+    Args, SharedState,
+    response::{Response, ResponseProvider, Wakeup},
+};
 use crate::send_data::{SendData, SendResult};
 
 pub struct HttpServer {
     server: Http3Server,
     /// Progress writing to each stream.
     remaining_data: HashMap<StreamId, SendData>,
+    /// Streams waiting for their rate limit, see [`SendResult::Throttled`].
+    throttled: Vec<(Http3OrWebTransportStream, SendData)>,
+    wakeup: Wakeup,
     /// Tracks POST requests: (bytes received, optional response size from path)
     posts: HashMap<Http3OrWebTransportStream, (usize, Option<usize>)>,
-    is_qns_test: bool,
+    responses: Box<dyn ResponseProvider>,
 }
 
 impl HttpServer {
@@ -39,56 +53,61 @@ impl HttpServer {
     fn send_response(
         &mut self,
         stream: &Http3OrWebTransportStream,
-        mut response: SendData,
+        response: Response,
         now: Instant,
     ) {
-        if stream
-            .send_headers(&[
-                Header::new(":status", "200"),
-                Header::new("content-length", response.len().to_string()),
-            ])
-            .is_err()
-        {
+        let mut headers = vec![Header::new(":status", response.status.to_string())];
+        headers.extend(response.headers);
+        if stream.send_headers(&headers).is_err() {
             qerror!("Stream {stream} closed by peer, not sending response");
             _ = stream.stream_reset_send(neqo_http3::Error::HttpNone.code());
             return;
         }
-        if let Some(remaining) = Self::send_response_body(stream, &mut response, now) {
-            self.remaining_data.insert(stream.stream_id(), remaining);
-        }
+        self.send_response_body(stream, response.body, now);
     }
 
-    /// Send response body data. Returns `None` if done or stream closed,
-    /// `Some(data)` if more data remains to send.
+    /// Send response body data, and keep track of any data that remains.
     fn send_response_body(
+        &mut self,
         stream: &Http3OrWebTransportStream,
-        response: &mut SendData,
+        mut response: SendData,
         now: Instant,
-    ) -> Option<SendData> {
-        match response.send(|chunk| stream.send_data(chunk, now)) {
+    ) {
+        match response.send(now, |chunk| stream.send_data(chunk, now)) {
             SendResult::StreamClosed => {
                 qerror!("Stream {stream} closed");
                 _ = stream.stream_reset_send(neqo_http3::Error::HttpNone.code());
-                None
             }
             SendResult::Done => {
                 _ = stream.stream_close_send(now); // Stream may be closed; ignore errors.
-                None
             }
-            SendResult::MoreData => Some(std::mem::take(response)),
+            SendResult::MoreData => {
+                self.remaining_data.insert(stream.stream_id(), response);
+            }
+            SendResult::Throttled(delay) => {
+                self.wakeup.after(delay);
+                self.throttled.push((stream.clone(), response));
+            }
         }
     }
 
+    /// Use `responses` to respond to GET requests.
+    #[must_use]
+    pub fn with_response_provider(mut self, responses: Box<dyn ResponseProvider>) -> Self {
+        self.responses = responses;
+        self
+    }
+
     pub fn new(
         args: &Args,
-        anti_replay: AntiReplay,
+        state: &SharedState,
         cid_mgr: Rc<RefCell<dyn ConnectionIdGenerator>>,
     ) -> Self {
         let mut server = Http3Server::new(
             args.now(),
             slice::from_ref(&args.key),
             slice::from_ref(&args.shared.alpn),
-            anti_replay,
+            state.anti_replay.clone(),
             cid_mgr,
             Http3Parameters::default()
                 .connection_parameters(args.shared.quic_parameters.get(&args.shared.alpn))
@@ -100,10 +119,15 @@ impl HttpServer {
         .expect("We cannot make a server!");
 
         server.set_ciphers(args.get_ciphers());
+        server.set_groups(args.shared.get_groups());
         server.set_qlog_dir(args.shared.qlog_dir.clone());
+        server
+            .set_token_key(&state.token_key, state.epoch)
+            .expect("We cannot set the token key!");
         if args.retry {
             server.set_validation(ValidateAddress::Always);
         }
+        server.set_admission_limits(&args.admission_limits());
         if args.ech {
             let (sk, pk) = generate_ech_keys().expect("should create ECH keys");
             server
@@ -114,8 +138,10 @@ impl HttpServer {
         Self {
             server,
             remaining_data: HashMap::default(),
+            throttled: Vec::new(),
+            wakeup: Wakeup::default(),
             posts: HashMap::default(),
-            is_qns_test: args.shared.qns_test.is_some(),
+            responses: args.response_provider(),
         }
     }
 }
@@ -127,7 +153,7 @@ impl Display for HttpServer {
 }
 
 impl super::HttpServer for HttpServer {
-    fn process_multiple<'a, D: IntoIterator<Item = Datagram<&'a mut [u8]>>>(
+    fn process_multiple<P: Payload, D: IntoIterator<Item = Datagram<P>>>(
         &mut self,
         dgrams: D,
         now: Instant,
@@ -138,6 +164,11 @@ impl super::HttpServer for HttpServer {
 
     fn process_events(&mut self, _now: Instant) {
         let now = Instant::now();
+        if self.wakeup.fired() {
+            for (stream, remaining) in mem::take(&mut self.throttled) {
+                self.send_response_body(&stream, remaining, now);
+            }
+        }
         while let Some(event) = self.server.next_event() {
             match event {
                 Http3ServerEvent::Headers {
@@ -164,42 +195,17 @@ impl super::HttpServer for HttpServer {
                         continue;
                     };
 
-                    let response = if self.is_qns_test {
-                        let path_str = path.value_utf8().unwrap_or("/");
-                        match qns_read_response(path_str) {
-                            Ok(data) => SendData::from(data),
-                            Err(e) => {
-                                qerror!("Failed to read {path_str}: {e}");
-                                // Stream may be closed; ignore errors.
-                                if stream
-                                    .send_headers(&[Header::new(":status", "404")])
-                                    .is_ok()
-                                {
-                                    _ = stream.stream_close_send(now);
-                                } else {
-                                    _ = stream
-                                        .stream_reset_send(neqo_http3::Error::HttpNone.code());
-                                }
-                                continue;
-                            }
-                        }
-                    } else if let Ok(path_str) = path.value_utf8() {
-                        path_str
-                            .trim_matches(|p| p == '/')
-                            .parse::<usize>()
-                            .map_or_else(|_| SendData::from(path.value()), SendData::zeroes)
-                    } else {
-                        SendData::from(path.value())
-                    };
-
+                    let response = path.value_utf8().map_or_else(
+                        |_| Response::ok(SendData::from(path.value())),
+                        |path| self.responses.respond(path),
+                    );
                     self.send_response(&stream, response, now);
                 }
                 Http3ServerEvent::DataWritable { stream } => {
                     if self.posts.get_mut(&stream).is_none()
-                        && let Some(mut remaining) = self.remaining_data.remove(&stream.stream_id())
-                        && let Some(data) = Self::send_response_body(&stream, &mut remaining, now)
+                        && let Some(remaining) = self.remaining_data.remove(&stream.stream_id())
                     {
-                        self.remaining_data.insert(stream.stream_id(), data);
+                        self.send_response_body(&stream, remaining, now);
                     }
                 }
 
@@ -212,7 +218,7 @@ impl super::HttpServer for HttpServer {
                             || SendData::from(received.to_string().into_bytes()),
                             SendData::zeroes,
                         );
-                        self.send_response(&stream, response, now);
+                        self.send_response(&stream, Response::ok(response), now);
                     }
                 }
                 _ => {}
@@ -223,4 +229,12 @@ impl super::HttpServer for HttpServer {
     fn has_events(&self) -> bool {
         self.server.has_events()
     }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.get_mut().wakeup.poll(cx)
+    }
+
+    fn admission_stats(&self) -> AdmissionStats {
+        *self.server.admission_stats()
+    }
 }
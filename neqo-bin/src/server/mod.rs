@@ -15,16 +15,17 @@
 use std::{
     cell::RefCell,
     fmt::Display,
-    fs,
     future::poll_fn,
     io::{self},
+    iter,
     net::{SocketAddr, ToSocketAddrs as _},
-    num::NonZeroUsize,
+    num::{NonZeroU8, NonZeroU64, NonZeroUsize},
     path::PathBuf,
     pin::Pin,
     process::exit,
     rc::Rc,
     task::{Context, Poll},
+    thread,
     time::{Duration, Instant},
 };
 
@@ -33,16 +34,27 @@ use futures::{
     FutureExt as _,
     future::{Either, select, select_all},
 };
-use neqo_common::{Datagram, qdebug, qerror, qinfo, qwarn};
+use neqo_common::{
+    Datagram,
+    datagram::{self, Payload},
+    qdebug, qerror, qinfo, qwarn,
+};
 use neqo_crypto::{
     AntiReplay, Cipher,
     constants::{TLS_AES_128_GCM_SHA256, TLS_AES_256_GCM_SHA384, TLS_CHACHA20_POLY1305_SHA256},
-    init_db,
+    init_db, random,
+};
+use neqo_transport::{
+    ConnectionIdGenerator, OutputBatch, RandomConnectionIdGenerator, ShardedConnectionIdGenerator,
+    Version,
+    server::{AdmissionLimits, AdmissionStats},
 };
-use neqo_transport::{OutputBatch, RandomConnectionIdGenerator, Version};
 use neqo_udp::{DatagramIter, RecvBuf};
 use thiserror::Error;
-use tokio::time::Sleep;
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    time::Sleep,
+};
 
 use crate::SharedArgs;
 
@@ -50,6 +62,7 @@ const ANTI_REPLAY_WINDOW: Duration = Duration::from_secs(10);
 
 pub mod http09;
 pub mod http3;
+pub mod response;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -69,7 +82,7 @@ pub enum Error {
 
 pub type Res<T> = Result<T, Error>;
 
-#[derive(Debug, Parser)]
+#[derive(Clone, Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
     #[command(flatten)]
@@ -96,6 +109,45 @@ pub struct Args {
     /// This generates a new set of ECH keys when it is invoked.
     /// The resulting configuration is printed to stdout in hexadecimal format.
     ech: bool,
+
+    #[arg(name = "anti-replay-window", long, default_value_t = ANTI_REPLAY_WINDOW.as_secs())]
+    /// The window over which 0-RTT replays are detected, in seconds.
+    anti_replay_window: u64,
+
+    #[arg(name = "anti-replay-k", long, default_value = "7")]
+    /// The number of hash functions used by the anti-replay bloom filter.
+    anti_replay_k: usize,
+
+    #[arg(name = "anti-replay-bits", long, default_value = "14")]
+    /// The log2 of the number of bits in the anti-replay bloom filter.
+    anti_replay_bits: usize,
+
+    #[arg(long, default_value = "1")]
+    /// The number of worker threads, each with its own sockets. Needs
+    /// `SO_REUSEPORT`. Connections are routed to workers by connection ID.
+    /// Each worker keeps its own 0-RTT anti-replay state.
+    workers: NonZeroU8,
+
+    #[arg(long)]
+    /// Respond to any request with zeroes that do not end.
+    infinite: bool,
+
+    #[arg(long)]
+    /// Send response bodies no faster than this many bytes per second.
+    throttle: Option<NonZeroU64>,
+
+    #[arg(name = "max-connections", long)]
+    /// Refuse connections beyond this many, per worker.
+    max_connections: Option<usize>,
+
+    #[arg(name = "max-handshakes", long)]
+    /// Send a Retry to clients when this many handshakes are in progress, per
+    /// worker.  Clients that already completed a Retry are refused.
+    max_handshakes: Option<usize>,
+
+    #[arg(name = "max-connections-per-ip", long)]
+    /// Refuse connections beyond this many from one IP address, per worker.
+    max_connections_per_ip: Option<usize>,
 }
 
 #[cfg(any(test, feature = "bench"))]
@@ -109,6 +161,15 @@ impl Default for Args {
             key: "key".to_string(),
             retry: false,
             ech: false,
+            anti_replay_window: ANTI_REPLAY_WINDOW.as_secs(),
+            anti_replay_k: 7,
+            anti_replay_bits: 14,
+            workers: NonZeroU8::MIN,
+            infinite: false,
+            throttle: None,
+            max_connections: None,
+            max_handshakes: None,
+            max_connections_per_ip: None,
         }
     }
 }
@@ -132,6 +193,29 @@ impl Args {
             .collect::<Vec<_>>()
     }
 
+    /// The [`response::ResponseProvider`] for the configured responses.
+    fn response_provider(&self) -> Box<dyn response::ResponseProvider> {
+        let provider: Box<dyn response::ResponseProvider> = if self.shared.qns_test.is_some() {
+            Box::new(response::Files::new(PathBuf::from("/www")))
+        } else if self.infinite {
+            Box::new(response::Infinite)
+        } else {
+            Box::new(response::Zeroes)
+        };
+        match self.throttle {
+            Some(rate) => Box::new(response::Throttled::new(provider, rate)),
+            None => provider,
+        }
+    }
+
+    const fn admission_limits(&self) -> AdmissionLimits {
+        AdmissionLimits {
+            connections: self.max_connections,
+            handshakes: self.max_handshakes,
+            connections_per_ip: self.max_connections_per_ip,
+        }
+    }
+
     fn listen_addresses(&self) -> Vec<SocketAddr> {
         self.hosts
             .iter()
@@ -142,6 +226,10 @@ impl Args {
             .collect()
     }
 
+    const fn anti_replay_window(&self) -> Duration {
+        Duration::from_secs(self.anti_replay_window)
+    }
+
     fn now(&self) -> Instant {
         if self.shared.qns_test.is_some() {
             // When NSS starts its anti-replay it blocks any acceptance of 0-RTT for a
@@ -155,7 +243,7 @@ impl Args {
             // in the future.
             //
             // This is NOT SAFE.  Don't do this.
-            Instant::now() + ANTI_REPLAY_WINDOW
+            Instant::now() + self.anti_replay_window()
         } else {
             Instant::now()
         }
@@ -211,16 +299,9 @@ impl Args {
     }
 }
 
-fn qns_read_response(filename: &str) -> Result<Vec<u8>, io::Error> {
-    let path: PathBuf = ["/www", filename.trim_matches(|p| p == '/')]
-        .iter()
-        .collect();
-    fs::read(path)
-}
-
 #[expect(clippy::module_name_repetitions, reason = "This is OK.")]
 pub trait HttpServer: Display {
-    fn process_multiple<'a, D: IntoIterator<Item = Datagram<&'a mut [u8]>>>(
+    fn process_multiple<P: Payload, D: IntoIterator<Item = Datagram<P>>>(
         &mut self,
         dgrams: D,
         now: Instant,
@@ -237,6 +318,64 @@ pub trait HttpServer: Display {
     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
         Poll::Pending
     }
+    /// The connection attempts that the server did not admit.
+    fn admission_stats(&self) -> AdmissionStats {
+        AdmissionStats::default()
+    }
+}
+
+/// Routing of datagrams between the workers of a server with multiple
+/// threads.
+///
+/// Each worker encodes its index in the connection IDs it hands out, see
+/// [`ShardedConnectionIdGenerator`].  The OS delivers datagrams to workers by
+/// their 4-tuple, so a datagram for a migrated connection can arrive at the
+/// wrong worker, which then forwards it.
+pub struct Shard {
+    index: u8,
+    /// Queues of forwarded datagrams, one for each worker.
+    peers: Vec<UnboundedSender<Datagram>>,
+    /// Datagrams that other workers forwarded to this one.
+    inbox: UnboundedReceiver<Datagram>,
+}
+
+impl Shard {
+    /// Create the shards for `workers` workers.
+    #[must_use]
+    pub fn all(workers: NonZeroU8) -> Vec<Self> {
+        let (peers, inboxes): (Vec<_>, Vec<_>) = iter::repeat_with(unbounded_channel)
+            .take(NonZeroUsize::from(workers).get())
+            .unzip();
+        (0..workers.get())
+            .zip(inboxes)
+            .map(|(index, inbox)| Self {
+                index,
+                peers: peers.clone(),
+                inbox,
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub const fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Forward `d` to the worker that owns its connection, unless that is
+    /// this one.  Returns whether `d` was forwarded.
+    fn forward(&self, d: &Datagram<impl AsRef<[u8]>>) -> bool {
+        let Some((shard, peer)) = ShardedConnectionIdGenerator::shard_of(d)
+            .filter(|&shard| shard != self.index)
+            .and_then(|shard| Some((shard, self.peers.get(usize::from(shard))?)))
+        else {
+            return false;
+        };
+        qdebug!(
+            "Worker {} forwarding datagram to worker {shard}",
+            self.index
+        );
+        peer.send(d.to_owned()).is_ok()
+    }
 }
 
 pub struct Runner<S> {
@@ -245,6 +384,10 @@ pub struct Runner<S> {
     timeout: Option<Pin<Box<Sleep>>>,
     sockets: Vec<(SocketAddr, crate::udp::Socket)>,
     recv_buf: RecvBuf,
+    io_batch_size: NonZeroUsize,
+    shard: Option<Shard>,
+    /// The admission counters that were last logged.
+    admission_stats: AdmissionStats,
 }
 
 impl<S: HttpServer + Unpin> Runner<S> {
@@ -259,10 +402,30 @@ impl<S: HttpServer + Unpin> Runner<S> {
             server,
             timeout: None,
             sockets,
-            recv_buf: RecvBuf::default(),
+            recv_buf: RecvBuf::default().with_packet_info(),
+            io_batch_size: NonZeroUsize::MIN,
+            shard: None,
+            admission_stats: AdmissionStats::default(),
         }
     }
 
+    /// Run as one of several workers, exchanging datagrams with the others
+    /// through `shard`.  The server needs to use a
+    /// [`ShardedConnectionIdGenerator`] for the same shard.
+    #[must_use]
+    pub fn with_shard(mut self, shard: Shard) -> Self {
+        self.shard = Some(shard);
+        self
+    }
+
+    /// Send and receive up to `io_batch_size` messages with one syscall.
+    #[must_use]
+    pub fn with_io_batch_size(mut self, io_batch_size: NonZeroUsize) -> Self {
+        self.io_batch_size = io_batch_size;
+        self.recv_buf = RecvBuf::new(io_batch_size).with_packet_info();
+        self
+    }
+
     #[must_use]
     pub fn local_addresses(&self) -> Vec<SocketAddr> {
         self.sockets
@@ -272,6 +435,10 @@ impl<S: HttpServer + Unpin> Runner<S> {
     }
 
     /// Tries to find a socket, but then just falls back to sending from the first.
+    ///
+    /// A socket bound to an unspecified address matches any address of the
+    /// same family, as datagrams received on it carry the address they were
+    /// sent to.
     fn find_socket(
         sockets: &mut [(SocketAddr, crate::udp::Socket)],
         addr: SocketAddr,
@@ -279,19 +446,27 @@ impl<S: HttpServer + Unpin> Runner<S> {
         let ((_host, first_socket), rest) = sockets.split_first_mut().unwrap();
         rest.iter_mut()
             .map(|(_host, socket)| socket)
-            .find(|socket| socket.local_addr().is_ok_and(|a| a == addr))
+            .find(|socket| {
+                socket.local_addr().is_ok_and(|a| {
+                    a == addr
+                        || (a.ip().is_unspecified()
+                            && a.is_ipv4() == addr.is_ipv4()
+                            && a.port() == addr.port())
+                })
+            })
             .unwrap_or(first_socket)
     }
 
     // Free function (i.e. not taking `&mut self: ServerRunner`) to be callable by
     // `ServerRunner::read_and_process` while holding a reference to
     // `ServerRunner::recv_buf`.
-    async fn process_inner(
+    async fn process_inner<P: Payload>(
         server: &mut S,
         timeout: &mut Option<Pin<Box<Sleep>>>,
         sockets: &mut [(SocketAddr, crate::udp::Socket)],
         now: &dyn Fn() -> Instant,
-        mut input_dgrams: Option<DatagramIter<'_>>,
+        io_batch_size: NonZeroUsize,
+        mut input_dgrams: Option<impl Iterator<Item = Datagram<P>>>,
     ) -> Result<(), io::Error> {
         // Each socket has a maximum number of GSO segments it can handle. When
         // calling `server.process_multiple` we don't know which socket will be
@@ -310,6 +485,8 @@ impl<S: HttpServer + Unpin> Runner<S> {
             .inspect_err(|_| qerror!("Socket return GSO size of 0"))
             .map_err(|_| io::Error::from(io::ErrorKind::Unsupported))?;
 
+        // Datagram batches waiting to be sent, all from the same socket.
+        let mut pending: Vec<datagram::Batch> = Vec::with_capacity(io_batch_size.get());
         loop {
             match server.process_multiple(
                 input_dgrams.take().into_iter().flatten(),
@@ -317,29 +494,19 @@ impl<S: HttpServer + Unpin> Runner<S> {
                 smallest_max_gso_segments,
             ) {
                 OutputBatch::DatagramBatch(dgram) => {
-                    let socket = Self::find_socket(sockets, dgram.source());
-                    loop {
-                        // Optimistically attempt sending datagram. In case the
-                        // OS buffer is full, wait till socket is writable then
-                        // try again.
-                        match socket.send(&dgram) {
-                            Ok(()) => break,
-                            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                                socket.writable().await?;
-                                // Now try again.
-                            }
-                            Err(e)
-                                if e.raw_os_error() == Some(libc::EIO)
-                                    && dgram.num_datagrams() > 1 =>
-                            {
-                                qinfo!(
-                                    "`libc::sendmsg` failed with {e}; quinn-udp will halt segmentation offload"
-                                );
-                                // Drop the packets and let QUIC handle retransmission.
-                                break;
-                            }
-                            e @ Err(_) => return e,
-                        }
+                    if pending
+                        .first()
+                        .is_some_and(|p| p.source() != dgram.source())
+                    {
+                        Self::find_socket(sockets, pending[0].source())
+                            .send_all(&mut pending)
+                            .await?;
+                    }
+                    pending.push(dgram);
+                    if pending.len() >= io_batch_size.get() {
+                        Self::find_socket(sockets, pending[0].source())
+                            .send_all(&mut pending)
+                            .await?;
                     }
                 }
                 OutputBatch::Callback(new_timeout) => {
@@ -350,6 +517,11 @@ impl<S: HttpServer + Unpin> Runner<S> {
                 OutputBatch::None => break,
             }
         }
+        if let Some(first) = pending.first() {
+            Self::find_socket(sockets, first.source())
+                .send_all(&mut pending)
+                .await?;
+        }
         Ok(())
     }
 
@@ -359,12 +531,15 @@ impl<S: HttpServer + Unpin> Runner<S> {
             let Some(input_dgrams) = socket.recv(*host, &mut self.recv_buf)? else {
                 break;
             };
+            let shard = self.shard.as_ref();
+            let input_dgrams = input_dgrams.filter(|d| !shard.is_some_and(|s| s.forward(d)));
 
             Self::process_inner(
                 &mut self.server,
                 &mut self.timeout,
                 &mut self.sockets,
                 &self.now,
+                self.io_batch_size,
                 Some(input_dgrams),
             )
             .await?;
@@ -379,7 +554,27 @@ impl<S: HttpServer + Unpin> Runner<S> {
             &mut self.timeout,
             &mut self.sockets,
             &self.now,
-            None,
+            self.io_batch_size,
+            None::<DatagramIter<'_>>,
+        )
+        .await
+    }
+
+    /// Process `first` and any other datagrams that other workers forwarded.
+    async fn process_forwarded(&mut self, first: Datagram) -> Result<(), io::Error> {
+        let mut forwarded = vec![first];
+        if let Some(shard) = &mut self.shard {
+            while let Ok(d) = shard.inbox.try_recv() {
+                forwarded.push(d);
+            }
+        }
+        Self::process_inner(
+            &mut self.server,
+            &mut self.timeout,
+            &mut self.sockets,
+            &self.now,
+            self.io_batch_size,
+            Some(forwarded.into_iter()),
         )
         .await
     }
@@ -405,18 +600,42 @@ impl<S: HttpServer + Unpin> Runner<S> {
         let server_ready = poll_fn(|cx| HttpServer::poll(Pin::new(&mut self.server), cx))
             .map(|()| Ok(Ready::Server));
 
+        // The queue cannot close, as each worker holds a sender for itself.
+        let forwarded_ready = self
+            .shard
+            .as_mut()
+            .map_or_else(
+                || Either::Right(futures::future::pending()),
+                |shard| Either::Left(Box::pin(shard.inbox.recv())),
+            )
+            .map(|d| Ok(Ready::Forwarded(d.expect("queue is open"))));
+
         select(
-            select(sockets_ready, timeout_ready).map(|either| either.factor_first().0),
-            server_ready,
+            select(
+                select(sockets_ready, timeout_ready).map(|either| either.factor_first().0),
+                server_ready,
+            )
+            .map(|either| either.factor_first().0),
+            forwarded_ready,
         )
         .map(|either| either.factor_first().0)
         .await
     }
 
+    /// Log the admission counters of the server when they change.
+    fn log_admission_stats(&mut self) {
+        let stats = self.server.admission_stats();
+        if stats != self.admission_stats {
+            qinfo!("Admission: {stats:?}");
+            self.admission_stats = stats;
+        }
+    }
+
     pub async fn run(mut self) -> Res<()> {
         loop {
             self.server.process_events((self.now)());
             self.process().await?;
+            self.log_admission_stats();
 
             if self.server.has_events() {
                 continue;
@@ -433,6 +652,9 @@ impl<S: HttpServer + Unpin> Runner<S> {
                 Ready::Server => {
                     // Processing server at top of the loop.
                 }
+                Ready::Forwarded(d) => {
+                    self.process_forwarded(d).await?;
+                }
             }
         }
     }
@@ -442,6 +664,37 @@ enum Ready {
     Socket(usize),
     Timeout,
     Server,
+    Forwarded(Datagram),
+}
+
+/// State that the workers of a server share, so that a client can move
+/// between them.
+#[derive(Clone)]
+pub struct SharedState {
+    /// Detects 0-RTT replays across all workers.
+    anti_replay: AntiReplay,
+    /// Protects Retry and `NEW_TOKEN` tokens, so that any worker accepts them.
+    token_key: [u8; 32],
+    /// The time that token expiry is relative to.
+    epoch: Instant,
+}
+
+impl SharedState {
+    /// Create a new anti-replay context and a random token key.
+    pub fn new(args: &Args) -> Res<Self> {
+        // Note: this is the exception to the case where we use `Args::now`.
+        let anti_replay = AntiReplay::new(
+            Instant::now(),
+            args.anti_replay_window(),
+            args.anti_replay_k,
+            args.anti_replay_bits,
+        )?;
+        Ok(Self {
+            anti_replay,
+            token_key: random::<32>(),
+            epoch: args.now(),
+        })
+    }
 }
 
 #[expect(clippy::type_complexity, reason = "pinned and boxed future")]
@@ -467,38 +720,170 @@ pub fn run(
         qerror!("No valid hosts defined");
         return Err(io::Error::new(io::ErrorKind::InvalidInput, "No hosts").into());
     }
+
+    let state = SharedState::new(&args)?;
+    if args.workers == NonZeroU8::MIN {
+        return worker(args, &state, hosts, None);
+    }
+    if cfg!(not(unix)) {
+        return Err(Error::Argument("multiple workers need SO_REUSEPORT"));
+    }
+    if args.ech {
+        // Each worker would generate its own ECH configuration.
+        return Err(Error::Argument(
+            "ECH is not supported with multiple workers",
+        ));
+    }
+
+    let mut shards = Shard::all(args.workers).into_iter();
+    let first = shards.next().expect("at least one worker");
+    let (run, local_addrs) = worker(args.clone(), &state, hosts, Some(first))?;
+    // The other workers bind to the addresses that the first one got, in case
+    // it was asked for any port.  If one of them fails, so does the server.
+    let (failed_tx, mut failed_rx) = unbounded_channel();
+    for shard in shards {
+        let args = args.clone();
+        let state = state.clone();
+        let hosts = local_addrs.clone();
+        let failed_tx = failed_tx.clone();
+        let index = shard.index();
+        thread::Builder::new()
+            .name(format!("neqo-server-{index}"))
+            .spawn(move || {
+                let res = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(Error::from)
+                    .and_then(|rt| {
+                        rt.block_on(async { worker(args, &state, hosts, Some(shard))?.0.await })
+                    });
+                if let Err(e) = res {
+                    qerror!("Worker {index} failed: {e}");
+                    _ = failed_tx.send(e);
+                }
+            })?;
+    }
+    drop(failed_tx);
+    let failed = async move {
+        match failed_rx.recv().await {
+            Some(e) => Err(e),
+            // The other workers all stopped without an error.
+            None => std::future::pending().await,
+        }
+    };
+    let run = select(run, Box::pin(failed)).map(|either| either.factor_first().0);
+    Ok((Box::pin(run), local_addrs))
+}
+
+/// Set up one worker of the server: bind `hosts` and create a [`Runner`].
+#[expect(clippy::type_complexity, reason = "pinned and boxed future")]
+fn worker(
+    mut args: Args,
+    state: &SharedState,
+    hosts: Vec<SocketAddr>,
+    shard: Option<Shard>,
+) -> Res<(
+    Pin<Box<dyn Future<Output = Res<()>> + 'static>>,
+    Vec<SocketAddr>,
+)> {
     let sockets: Vec<(SocketAddr, crate::udp::Socket)> = hosts
         .into_iter()
         .map(|host| {
+            #[cfg(unix)]
+            let socket = if shard.is_some() {
+                crate::udp::Socket::bind_reuse_port(host)?
+            } else {
+                crate::udp::Socket::bind(host)?
+            };
+            #[cfg(not(unix))]
             let socket = crate::udp::Socket::bind(host)?;
             qinfo!(
                 "Server waiting for connection on: {:?}",
                 socket.local_addr()
             );
 
-            Ok((host, socket))
+            Ok((host, socket.with_packet_info()))
         })
         .collect::<Result<_, io::Error>>()?;
+    for (_, socket) in &sockets {
+        args.shared.quic_parameters.enable_txtime(socket);
+        args.shared.size_socket_buffers(socket)?;
+    }
 
-    // Note: this is the exception to the case where we use `Args::now`.
-    let anti_replay = AntiReplay::new(Instant::now(), ANTI_REPLAY_WINDOW, 7, 14)?;
-    let cid_mgr = Rc::new(RefCell::new(RandomConnectionIdGenerator::new(10)));
+    let cid_mgr: Rc<RefCell<dyn ConnectionIdGenerator>> = match &shard {
+        Some(shard) => Rc::new(RefCell::new(ShardedConnectionIdGenerator::new(
+            shard.index(),
+            10,
+        ))),
+        None => Rc::new(RefCell::new(RandomConnectionIdGenerator::new(10))),
+    };
+    let io_batch_size = args.shared.io_batch_size;
 
     if args.shared.alpn == "h3" {
         let runner = Runner::new(
-            http3::HttpServer::new(&args, anti_replay, cid_mgr),
+            http3::HttpServer::new(&args, state, cid_mgr),
             Box::new(move || args.now()),
             sockets,
         );
-        let local_addrs = runner.local_addresses();
-        Ok((Box::pin(runner.run()), local_addrs))
+        Ok(start(runner, io_batch_size, shard))
     } else {
         let runner = Runner::new(
-            http09::HttpServer::new(&args, anti_replay, cid_mgr)?,
+            http09::HttpServer::new(&args, state, cid_mgr)?,
             Box::new(move || args.now()),
             sockets,
         );
-        let local_addrs = runner.local_addresses();
-        Ok((Box::pin(runner.run()), local_addrs))
+        Ok(start(runner, io_batch_size, shard))
+    }
+}
+
+#[expect(clippy::type_complexity, reason = "pinned and boxed future")]
+fn start<S: HttpServer + Unpin + 'static>(
+    mut runner: Runner<S>,
+    io_batch_size: Option<NonZeroUsize>,
+    shard: Option<Shard>,
+) -> (
+    Pin<Box<dyn Future<Output = Res<()>> + 'static>>,
+    Vec<SocketAddr>,
+) {
+    if let Some(io_batch_size) = io_batch_size {
+        runner = runner.with_io_batch_size(io_batch_size);
+    }
+    if let Some(shard) = shard {
+        runner = runner.with_shard(shard);
+    }
+    let local_addrs = runner.local_addresses();
+    (Box::pin(runner.run()), local_addrs)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::{net::SocketAddr, num::NonZeroU8};
+
+    use neqo_common::{Datagram, Tos};
+
+    use super::Shard;
+
+    #[test]
+    fn shard_forward() {
+        let addr: SocketAddr = "[::1]:4433".parse().unwrap();
+        let mut shards = Shard::all(NonZeroU8::new(2).unwrap());
+
+        // A short header packet for the second worker.
+        let mut short = [0x40, 1, 2, 3];
+        let d = Datagram::from_slice(addr, addr, Tos::default(), &mut short);
+        assert!(shards[0].forward(&d));
+        assert!(!shards[1].forward(&d));
+        assert_eq!(
+            shards[1].inbox.try_recv().unwrap().as_ref(),
+            &[0x40, 1, 2, 3]
+        );
+
+        // Packets with a long header and unknown shards are not forwarded.
+        for mut data in [[0xc0, 1, 2, 3], [0x40, 7, 2, 3]] {
+            let d = Datagram::from_slice(addr, addr, Tos::default(), &mut data);
+            assert!(!shards[0].forward(&d));
+        }
+        assert!(shards[1].inbox.try_recv().is_err());
     }
 }
@@ -0,0 +1,181 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Responses of the demo servers.
+//!
+//! A [`ResponseProvider`] maps the path of a request to a [`Response`].  The
+//! providers here can be combined, e.g. [`Throttled`] around [`Infinite`] for
+//! an endless body at a fixed rate.
+
+use std::{
+    fs,
+    future::Future as _,
+    mem,
+    num::NonZeroU64,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use neqo_common::{Header, qerror};
+use tokio::time::Sleep;
+
+use crate::send_data::SendData;
+
+/// A response: a status, headers and a body.
+///
+/// The HTTP/0.9 server only sends the body.
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<Header>,
+    pub body: SendData,
+}
+
+impl Response {
+    /// A 200 response with `body`, and a `content-length` header if the body
+    /// ends.
+    #[must_use]
+    pub fn ok(body: SendData) -> Self {
+        let headers = body
+            .len()
+            .map(|len| vec![Header::new("content-length", len.to_string())])
+            .unwrap_or_default();
+        Self {
+            status: 200,
+            headers,
+            body,
+        }
+    }
+
+    #[must_use]
+    pub fn not_found() -> Self {
+        Self {
+            status: 404,
+            ..Self::ok(SendData::from("404"))
+        }
+    }
+}
+
+/// Produces the response to a request, from its path.
+#[expect(clippy::module_name_repetitions, reason = "This is OK.")]
+pub trait ResponseProvider {
+    /// The response to a GET request for `path`.
+    fn respond(&self, path: &str) -> Response;
+}
+
+impl<F: Fn(&str) -> Response> ResponseProvider for F {
+    fn respond(&self, path: &str) -> Response {
+        self(path)
+    }
+}
+
+/// Responds to `/<n>` with `n` zero bytes, and echoes other paths.
+///
+/// This is the default.
+pub struct Zeroes;
+
+impl ResponseProvider for Zeroes {
+    fn respond(&self, path: &str) -> Response {
+        Response::ok(
+            path.trim_matches('/')
+                .parse::<usize>()
+                .map_or_else(|_| SendData::from(path), SendData::zeroes),
+        )
+    }
+}
+
+/// Responds to any path with zeroes that do not end.
+pub struct Infinite;
+
+impl ResponseProvider for Infinite {
+    fn respond(&self, _path: &str) -> Response {
+        Response::ok(SendData::infinite())
+    }
+}
+
+/// Responds with the files in a directory, as the QUIC Interop Runner expects.
+pub struct Files {
+    root: PathBuf,
+}
+
+impl Files {
+    #[must_use]
+    pub const fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+impl ResponseProvider for Files {
+    fn respond(&self, path: &str) -> Response {
+        match fs::read(self.root.join(path.trim_matches('/'))) {
+            Ok(data) => Response::ok(SendData::from(data)),
+            Err(e) => {
+                qerror!("Failed to read {path}: {e}");
+                Response::not_found()
+            }
+        }
+    }
+}
+
+/// Sends the bodies of another provider no faster than a given rate.
+pub struct Throttled {
+    inner: Box<dyn ResponseProvider>,
+    /// The rate, in bytes per second.
+    rate: NonZeroU64,
+}
+
+impl Throttled {
+    #[must_use]
+    pub fn new(inner: Box<dyn ResponseProvider>, rate: NonZeroU64) -> Self {
+        Self { inner, rate }
+    }
+}
+
+impl ResponseProvider for Throttled {
+    fn respond(&self, path: &str) -> Response {
+        let response = self.inner.respond(path);
+        Response {
+            body: response.body.throttled(self.rate),
+            ..response
+        }
+    }
+}
+
+/// Wakes a server up to continue sending throttled bodies.
+#[derive(Default)]
+pub struct Wakeup {
+    sleep: Option<Pin<Box<Sleep>>>,
+    fired: bool,
+}
+
+impl Wakeup {
+    /// Wake up after `delay`, unless a wakeup is due earlier.
+    pub fn after(&mut self, delay: Duration) {
+        let deadline = tokio::time::Instant::now() + delay;
+        if self.sleep.as_ref().is_none_or(|s| s.deadline() > deadline) {
+            self.sleep = Some(Box::pin(tokio::time::sleep_until(deadline)));
+        }
+    }
+
+    /// Whether the wakeup fired since the last call.
+    ///
+    /// Throttled bodies should only be resumed then, as resuming them more
+    /// often sends tiny amounts of data in a busy loop.
+    pub const fn fired(&mut self) -> bool {
+        mem::replace(&mut self.fired, false)
+    }
+
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(sleep) = &mut self.sleep else {
+            return Poll::Pending;
+        };
+        ready!(sleep.as_mut().poll(cx));
+        self.sleep = None;
+        self.fired = true;
+        Poll::Ready(())
+    }
+}
@@ -9,7 +9,7 @@
 use std::{io, net::SocketAddr};
 
 use neqo_common::{datagram, qdebug};
-use neqo_udp::{DatagramIter, RecvBuf};
+use neqo_udp::{BufferSizes, DatagramIter, RecvBuf, SendState, Stats, StatsCounters, TxTimeClock};
 
 /// Ideally this would live in [`neqo_udp`]. [`neqo_udp`] is used in Firefox.
 ///
@@ -20,14 +20,35 @@ use neqo_udp::{DatagramIter, RecvBuf};
 /// See <https://github.com/mozilla/cargo-vet/issues/626>.
 pub struct Socket {
     state: quinn_udp::UdpSocketState,
+    send_state: SendState,
     inner: tokio::net::UdpSocket,
+    stats: StatsCounters,
 }
 
 impl Socket {
     /// Create a new [`Socket`] bound to the provided address, not managed externally.
     pub fn bind<A: std::net::ToSocketAddrs>(addr: A) -> Result<Self, io::Error> {
+        Self::new(std::net::UdpSocket::bind(addr)?)
+    }
+
+    /// Create a new [`Socket`] bound to the provided address with
+    /// `SO_REUSEPORT`, so that other sockets can bind to the same address.
+    /// On Linux, the kernel spreads incoming datagrams over these sockets by
+    /// their 4-tuple.
+    #[cfg(unix)]
+    pub fn bind_reuse_port(addr: SocketAddr) -> Result<Self, io::Error> {
+        let socket = socket2::Socket::new(
+            socket2::Domain::for_address(addr),
+            socket2::Type::DGRAM,
+            None,
+        )?;
+        socket.set_reuse_port(true)?;
+        socket.bind(&addr.into())?;
+        Self::new(socket.into())
+    }
+
+    fn new(socket: std::net::UdpSocket) -> Result<Self, io::Error> {
         const ONE_MB: usize = 1 << 20;
-        let socket = std::net::UdpSocket::bind(addr)?;
         let state = quinn_udp::UdpSocketState::new((&socket).into())?;
 
         // FIXME: We need to experiment if increasing this actually improves performance.
@@ -60,10 +81,21 @@ impl Socket {
 
         Ok(Self {
             state,
+            send_state: SendState::new(&socket)?,
             inner: tokio::net::UdpSocket::from_std(socket)?,
+            stats: StatsCounters::default(),
         })
     }
 
+    /// See [`neqo_udp::SendState::with_packet_info`].
+    #[must_use]
+    pub fn with_packet_info(self) -> Self {
+        Self {
+            send_state: self.send_state.with_packet_info(),
+            ..self
+        }
+    }
+
     /// See [`tokio::net::UdpSocket::local_addr`].
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.inner.local_addr()
@@ -81,11 +113,46 @@ impl Socket {
 
     /// Send a [`datagram::Batch`] on the given [`Socket`].
     pub fn send(&self, d: &datagram::Batch) -> io::Result<()> {
+        let mode = self.inner.try_io(tokio::io::Interest::WRITABLE, || {
+            neqo_udp::send_inner(&self.state, &self.send_state, &self.inner, d)
+        })?;
+        self.stats.record(mode);
+        Ok(())
+    }
+
+    /// Send multiple [`datagram::Batch`]es on the given [`Socket`], returning
+    /// the number of batches sent.
+    ///
+    /// See [`neqo_udp::send_multiple_inner`].
+    pub fn send_multiple(&self, batches: &[datagram::Batch]) -> io::Result<usize> {
         self.inner.try_io(tokio::io::Interest::WRITABLE, || {
-            neqo_udp::send_inner(&self.state, (&self.inner).into(), d)
+            neqo_udp::send_multiple_inner(
+                &self.state,
+                &self.send_state,
+                &self.inner,
+                batches,
+                &self.stats,
+            )
         })
     }
 
+    /// Send all of `batches`, waiting for the socket to become writable as
+    /// needed.  Clears `batches` on success.
+    pub async fn send_all(&self, batches: &mut Vec<datagram::Batch>) -> io::Result<()> {
+        let mut sent = 0;
+        while sent < batches.len() {
+            // Optimistically attempt sending datagrams. In case the OS buffer
+            // is full, wait till socket is writable then try again.
+            match self.send_multiple(&batches[sent..]) {
+                Ok(n) => sent += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.writable().await?,
+                Err(e) => return Err(e),
+            }
+        }
+        batches.clear();
+        Ok(())
+    }
+
     /// Receive a batch of [`neqo_common::Datagram`]s on the given [`Socket`], each set with
     /// the provided local address.
     pub fn recv<'a>(
@@ -107,10 +174,25 @@ impl Socket {
             })
     }
 
+    /// See [`neqo_udp::enable_txtime`].
+    pub fn enable_txtime(&self, clock: TxTimeClock) -> io::Result<()> {
+        neqo_udp::enable_txtime(&self.send_state, &self.inner, clock)
+    }
+
+    /// See [`neqo_udp::size_buffers_for_bdp`].
+    pub fn size_buffers_for_bdp(&self, bdp: usize) -> io::Result<BufferSizes> {
+        neqo_udp::size_buffers_for_bdp(&self.state, &self.inner, bdp)
+    }
+
     pub fn max_gso_segments(&self) -> usize {
         self.state.max_gso_segments()
     }
 
+    /// Returns statistics on how datagrams were sent on this socket.
+    pub fn stats(&self) -> Stats {
+        self.stats.get(&self.state)
+    }
+
     /// Whether transmitted datagrams might get fragmented by the IP layer
     ///
     /// Returns `false` on targets which employ e.g. the `IPV6_DONTFRAG` socket option.
@@ -39,6 +39,18 @@ impl Bytes {
         self.len() == 0
     }
 
+    /// The number of bytes skipped at the start of the data.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the data, including the bytes that were skipped.
+    #[must_use]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+
     /// Skips the first `n` bytes, consuming and returning `self`.
     ///
     /// # Panics
@@ -9,9 +9,47 @@ use std::{
     net::SocketAddr,
     num::NonZeroUsize,
     ops::{Deref, DerefMut},
+    time::Instant,
 };
 
-use crate::{Bytes, Tos, hex_with_len};
+use crate::{Bytes, SharedBytes, SharedBytesMut, Tos, hex_with_len};
+
+/// The data of a received [`Datagram`].
+///
+/// Data that can be converted into [`SharedBytes`] without copying lets the
+/// receiver keep parts of it, e.g. the contents of `STREAM` frames.
+pub trait Payload: AsRef<[u8]> + AsMut<[u8]> + Sized {
+    /// Converts into [`SharedBytes`], or returns `self` if that would need a
+    /// copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns `self` if it cannot be converted without copying.
+    fn into_shared(self) -> Result<SharedBytes, Self> {
+        Err(self)
+    }
+}
+
+impl Payload for &mut [u8] {}
+
+impl Payload for Vec<u8> {
+    fn into_shared(self) -> Result<SharedBytes, Self> {
+        Ok(SharedBytes::from(self))
+    }
+}
+
+impl Payload for Bytes {
+    fn into_shared(self) -> Result<SharedBytes, Self> {
+        let offset = self.offset();
+        Ok(SharedBytes::from(self.into_vec()).slice(offset..))
+    }
+}
+
+impl Payload for SharedBytesMut {
+    fn into_shared(self) -> Result<SharedBytes, Self> {
+        Ok(self.freeze())
+    }
+}
 
 /// A UDP datagram.
 ///
@@ -83,6 +121,18 @@ impl<D: AsRef<[u8]>> Datagram<D> {
     }
 }
 
+impl<D: Payload> Datagram<D> {
+    /// Converts the data into [`SharedBytes`], see [`Payload::into_shared`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `self` if the data cannot be converted without copying.
+    pub fn into_shared(self) -> Result<SharedBytes, Self> {
+        let Self { src, dst, tos, d } = self;
+        d.into_shared().map_err(|d| Self { src, dst, tos, d })
+    }
+}
+
 impl<D: AsMut<[u8]> + AsRef<[u8]>> AsMut<[u8]> for Datagram<D> {
     fn as_mut(&mut self) -> &mut [u8] {
         self.d.as_mut()
@@ -149,6 +199,17 @@ impl Datagram<Bytes> {
     }
 }
 
+impl Datagram<SharedBytesMut> {
+    /// # Panics
+    ///
+    /// Panics if the data is empty.
+    #[must_use]
+    pub fn from_shared(src: SocketAddr, dst: SocketAddr, tos: Tos, d: SharedBytesMut) -> Self {
+        assert!(!d.is_empty(), "Datagram data cannot be empty");
+        Self { src, dst, tos, d }
+    }
+}
+
 impl<D: AsRef<[u8]>> AsRef<[u8]> for Datagram<D> {
     fn as_ref(&self) -> &[u8] {
         self.d.as_ref()
@@ -166,6 +227,8 @@ pub struct Batch {
     tos: Tos,
     datagram_size: NonZeroUsize,
     d: Vec<u8>,
+    /// The earliest time at which the datagrams should leave the host.
+    txtime: Option<Instant>,
 }
 
 impl Debug for Batch {
@@ -191,6 +254,7 @@ impl From<Datagram<Vec<u8>>> for Batch {
             datagram_size: NonZeroUsize::new(d.d.len())
                 .expect("Datagram is guaranteed to be non-empty"),
             d: d.d,
+            txtime: None,
         }
     }
 }
@@ -210,6 +274,7 @@ impl Batch {
             tos,
             datagram_size,
             d,
+            txtime: None,
         }
     }
 
@@ -232,6 +297,17 @@ impl Batch {
         self.tos = tos;
     }
 
+    /// The time at which the datagrams should be transmitted, if the sender
+    /// relies on the OS to pace them (e.g., with `SO_TXTIME` on Linux).
+    #[must_use]
+    pub const fn txtime(&self) -> Option<Instant> {
+        self.txtime
+    }
+
+    pub const fn set_txtime(&mut self, txtime: Option<Instant>) {
+        self.txtime = txtime;
+    }
+
     #[must_use]
     pub const fn datagram_size(&self) -> NonZeroUsize {
         self.datagram_size
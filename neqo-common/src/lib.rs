@@ -17,6 +17,7 @@ pub mod hrtime;
 mod incrdecoder;
 pub mod log;
 pub mod qlog;
+pub mod shared_bytes;
 pub mod tos;
 
 use std::fmt::Write as _;
@@ -32,6 +33,7 @@ pub use self::{
     datagram::Datagram,
     header::Header,
     incrdecoder::{IncrementalDecoderBuffer, IncrementalDecoderIgnore, IncrementalDecoderUint},
+    shared_bytes::{SharedBytes, SharedBytesMut},
     tos::{Dscp, Ecn, Tos},
 };
 
@@ -6,29 +6,127 @@
 
 use std::{
     io::Write as _,
-    sync::{Once, OnceLock},
+    sync::{Once, OnceLock, PoisonError, RwLock},
     time::{Duration, Instant},
 };
 
-use env_logger::Builder;
+use env_logger::{Builder, filter::Filter};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Log level overrides for individual modules, see [`set_module_level`].
+static MODULE_LEVELS: RwLock<Vec<(String, LevelFilter)>> = RwLock::new(Vec::new());
+
+/// The maximum level of the filter configured by [`init`], without any overrides.
+static BASE_LEVEL: OnceLock<LevelFilter> = OnceLock::new();
 
 fn since_start() -> Duration {
     static START_TIME: OnceLock<Instant> = OnceLock::new();
     START_TIME.get_or_init(Instant::now).elapsed()
 }
 
-pub fn init(level_filter: Option<log::LevelFilter>) {
+/// A logger that applies the per-module overrides from [`set_module_level`] before
+/// falling back to the filter configured through `RUST_LOG`.
+struct Logger {
+    filter: Filter,
+    output: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        module_level(metadata.target()).map_or_else(
+            || self.filter.enabled(metadata),
+            |level| metadata.level() <= level,
+        )
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.output.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.output.flush();
+    }
+}
+
+/// Returns the override for the most specific module that `target` belongs to, if any.
+fn module_level(target: &str) -> Option<LevelFilter> {
+    MODULE_LEVELS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|(module, _)| {
+            target
+                .strip_prefix(module.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map(|&(_, level)| level)
+}
+
+/// Raises the global maximum level so that records enabled by an override reach the logger.
+fn update_max_level() {
+    let Some(&base) = BASE_LEVEL.get() else {
+        return;
+    };
+    let max_level = MODULE_LEVELS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .fold(base, |max_level, &(_, level)| max_level.max(level));
+    ::log::set_max_level(max_level);
+}
+
+/// Sets the log level for `module` and its submodules at runtime.
+///
+/// This overrides the filter configured through `RUST_LOG` or [`init`].  `module` is a
+/// module path, such as `neqo_transport::pace` or `neqo_transport::recovery`.  Where
+/// overrides are nested, the most specific one applies.
+///
+/// This only affects the logger installed by [`init`].
+pub fn set_module_level(module: &str, level: LevelFilter) {
+    {
+        let mut levels = MODULE_LEVELS
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = levels.iter_mut().find(|(m, _)| m == module) {
+            entry.1 = level;
+        } else {
+            levels.push((module.to_owned(), level));
+        }
+    }
+    update_max_level();
+}
+
+/// Removes an override set with [`set_module_level`], so that `module` is logged
+/// according to the filter configured through `RUST_LOG` or [`init`] again.
+pub fn clear_module_level(module: &str) {
+    MODULE_LEVELS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|(m, _)| m != module);
+    update_max_level();
+}
+
+pub fn init(level_filter: Option<LevelFilter>) {
     static INIT_ONCE: Once = Once::new();
 
-    if ::log::STATIC_MAX_LEVEL == ::log::LevelFilter::Off {
+    if ::log::STATIC_MAX_LEVEL == LevelFilter::Off {
         return;
     }
 
     INIT_ONCE.call_once(|| {
-        let mut builder = Builder::from_env("RUST_LOG");
-        if let Some(filter) = level_filter {
-            builder.filter_level(filter);
+        let mut filter = env_logger::filter::Builder::from_env("RUST_LOG");
+        if let Some(level_filter) = level_filter {
+            filter.filter_level(level_filter);
         }
+        let filter = filter.build();
+        let base_level = filter.filter();
+
+        // Filtering happens in `Logger`, so the output logger accepts everything.
+        let mut builder = Builder::new();
+        builder.filter_level(LevelFilter::Trace);
         builder.format(|buf, record| {
             let elapsed = since_start();
             writeln!(
@@ -40,9 +138,15 @@ pub fn init(level_filter: Option<log::LevelFilter>) {
                 record.args()
             )
         });
-        if let Err(e) = builder.try_init() {
+        let logger = Logger {
+            filter,
+            output: builder.build(),
+        };
+        if let Err(e) = ::log::set_boxed_logger(Box::new(logger)) {
             eprintln!("Logging initialization error {e:?}");
         } else {
+            _ = BASE_LEVEL.set(base_level);
+            update_max_level();
             ::log::debug!("Logging initialized");
         }
     });
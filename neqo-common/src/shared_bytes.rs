@@ -0,0 +1,364 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reference-counted byte buffers that can be split without copying.
+//!
+//! A [`SharedBytesMut`] has exclusive access to its part of an allocation, so
+//! that it can be written to, e.g. to receive a datagram and decrypt it in
+//! place.  Freezing it gives a [`SharedBytes`], which can be cloned and sliced
+//! cheaply.  The allocation is freed when the last handle to any part of it is
+//! dropped.
+//!
+//! Inspired by `bytes` crate's `BytesMut` and `Bytes`.
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    mem::ManuallyDrop,
+    ops::{Bound, Deref, Range, RangeBounds},
+    ptr::NonNull,
+    rc::Rc,
+    slice,
+};
+
+use crate::hex_with_len;
+
+/// The memory behind [`SharedBytes`] and [`SharedBytesMut`].
+///
+/// The handles only access the memory through `ptr`, each within its own
+/// range.  A [`SharedBytesMut`] range never overlaps the range of any other
+/// handle, which makes it safe to write to.
+struct Allocation {
+    ptr: NonNull<u8>,
+    len: usize,
+    capacity: usize,
+}
+
+impl From<Vec<u8>> for Allocation {
+    fn from(v: Vec<u8>) -> Self {
+        let mut v = ManuallyDrop::new(v);
+        Self {
+            ptr: NonNull::from(v.as_mut_slice()).cast(),
+            len: v.len(),
+            capacity: v.capacity(),
+        }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        // SAFETY: The fields came from a `Vec`, which no handle refers to any
+        // more.
+        drop(unsafe { Vec::from_raw_parts(self.ptr.as_ptr(), self.len, self.capacity) });
+    }
+}
+
+impl Allocation {
+    /// # Safety
+    ///
+    /// `range` must be within the allocation, and no handle may write to it
+    /// while the slice exists.
+    unsafe fn slice(&self, range: &Range<usize>) -> &[u8] {
+        debug_assert!(range.end <= self.len);
+        #[expect(clippy::disallowed_methods, reason = "This is non-null.")]
+        unsafe {
+            slice::from_raw_parts(self.ptr.as_ptr().add(range.start), range.len())
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `range` must be within the allocation, and no other handle may access
+    /// it while the slice exists.
+    #[expect(clippy::mut_from_ref, reason = "Handles have disjoint ranges.")]
+    unsafe fn slice_mut(&self, range: &Range<usize>) -> &mut [u8] {
+        debug_assert!(range.end <= self.len);
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr().add(range.start), range.len()) }
+    }
+}
+
+/// Exclusive, writable access to part of a shared allocation.
+#[expect(clippy::module_name_repetitions, reason = "This is OK.")]
+pub struct SharedBytesMut {
+    alloc: Rc<Allocation>,
+    range: Range<usize>,
+}
+
+impl SharedBytesMut {
+    /// A buffer of `len` zero bytes in a new allocation.
+    #[must_use]
+    pub fn zeroed(len: usize) -> Self {
+        Self::from(vec![0; len])
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.range.end - self.range.start
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits off the first `at` bytes, which are returned, leaving the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > self.len()`.
+    #[must_use]
+    pub fn split_to(&mut self, at: usize) -> Self {
+        assert!(
+            at <= self.len(),
+            "cannot split at {at} when only {} bytes remain",
+            self.len()
+        );
+        let mid = self.range.start + at;
+        let front = Self {
+            alloc: Rc::clone(&self.alloc),
+            range: self.range.start..mid,
+        };
+        self.range.start = mid;
+        front
+    }
+
+    /// Shortens the buffer to `len` bytes.  Does nothing if it is shorter.
+    pub const fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.range.end = self.range.start + len;
+        }
+    }
+
+    /// Converts into a read-only buffer that can be cloned.
+    #[must_use]
+    pub fn freeze(self) -> SharedBytes {
+        SharedBytes {
+            alloc: self.alloc,
+            range: self.range,
+        }
+    }
+
+    /// Extends the buffer to the whole allocation, if no other handle refers
+    /// to it.  Returns whether that was possible.
+    ///
+    /// This allows an allocation to be used again once all data in it has
+    /// been consumed.
+    pub fn try_reclaim(&mut self) -> bool {
+        let unique = Rc::strong_count(&self.alloc) == 1;
+        if unique {
+            self.range = 0..self.alloc.len;
+        }
+        unique
+    }
+}
+
+impl Default for SharedBytesMut {
+    fn default() -> Self {
+        Self::from(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for SharedBytesMut {
+    fn from(v: Vec<u8>) -> Self {
+        let range = 0..v.len();
+        Self {
+            alloc: Rc::new(Allocation::from(v)),
+            range,
+        }
+    }
+}
+
+impl AsRef<[u8]> for SharedBytesMut {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: No other handle accesses this range.
+        unsafe { self.alloc.slice(&self.range) }
+    }
+}
+
+impl AsMut<[u8]> for SharedBytesMut {
+    fn as_mut(&mut self) -> &mut [u8] {
+        // SAFETY: No other handle accesses this range, and `&mut self` ensures
+        // that this is the only slice of it.
+        unsafe { self.alloc.slice_mut(&self.range) }
+    }
+}
+
+impl PartialEq for SharedBytesMut {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ref() == other.as_ref()
+    }
+}
+
+impl Eq for SharedBytesMut {}
+
+impl Debug for SharedBytesMut {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "SharedBytesMut {}", hex_with_len(self))
+    }
+}
+
+/// Read-only access to part of a shared allocation.
+#[derive(Clone)]
+pub struct SharedBytes {
+    alloc: Rc<Allocation>,
+    range: Range<usize>,
+}
+
+impl SharedBytes {
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.range.end - self.range.start
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `self` and `other` are part of the same allocation, which stays
+    /// allocated as long as either exists.
+    #[must_use]
+    pub fn same_allocation(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.alloc, &other.alloc)
+    }
+
+    /// A handle to `range` of this buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is not within the buffer.
+    #[must_use]
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e + 1,
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            start <= end && end <= self.len(),
+            "range {start}..{end} is out of bounds for length {}",
+            self.len()
+        );
+        Self {
+            alloc: Rc::clone(&self.alloc),
+            range: self.range.start + start..self.range.start + end,
+        }
+    }
+
+    /// A handle to `subset`, if it is part of this buffer.
+    #[must_use]
+    pub fn slice_ref(&self, subset: &[u8]) -> Option<Self> {
+        let start = subset.as_ptr().addr().checked_sub(self.as_ptr().addr())?;
+        let end = start.checked_add(subset.len())?;
+        (end <= self.len()).then(|| self.slice(start..end))
+    }
+}
+
+impl From<Vec<u8>> for SharedBytes {
+    fn from(v: Vec<u8>) -> Self {
+        SharedBytesMut::from(v).freeze()
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: No handle writes to this range, as it was frozen.
+        unsafe { self.alloc.slice(&self.range) }
+    }
+}
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for SharedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for SharedBytes {}
+
+impl Debug for SharedBytes {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "SharedBytes {}", hex_with_len(self))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use crate::{SharedBytes, SharedBytesMut};
+
+    #[test]
+    fn split_and_freeze() {
+        let mut b = SharedBytesMut::from(vec![1, 2, 3, 4, 5]);
+        let mut front = b.split_to(2);
+        front.as_mut()[0] = 9;
+        b.as_mut()[0] = 8;
+        assert_eq!(front.freeze().as_ref(), &[9, 2]);
+        assert_eq!(b.freeze().as_ref(), &[8, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot split at 4 when only 3 bytes remain")]
+    fn illegal_split() {
+        _ = SharedBytesMut::from(vec![1, 2, 3]).split_to(4);
+    }
+
+    #[test]
+    fn truncate() {
+        let mut b = SharedBytesMut::zeroed(4);
+        b.truncate(6);
+        assert_eq!(b.len(), 4);
+        b.truncate(1);
+        assert_eq!(b.as_ref(), &[0]);
+    }
+
+    #[test]
+    fn reclaim() {
+        let mut b = SharedBytesMut::zeroed(4);
+        let front = b.split_to(3).freeze();
+        assert!(!b.try_reclaim());
+        assert_eq!(b.len(), 1);
+        drop(front);
+        assert!(b.try_reclaim());
+        assert_eq!(b.len(), 4);
+    }
+
+    #[test]
+    fn slice() {
+        let b = SharedBytes::from(vec![1, 2, 3, 4]);
+        assert_eq!(b.slice(1..).as_ref(), &[2, 3, 4]);
+        assert_eq!(b.slice(1..3).slice(1..).as_ref(), &[3]);
+        assert!(b.slice(4..).is_empty());
+    }
+
+    #[test]
+    fn slice_ref() {
+        let b = SharedBytes::from(vec![1, 2, 3, 4]);
+        let part = b.slice(1..3);
+        assert_eq!(b.slice_ref(&part), Some(part.clone()));
+        assert_eq!(part.slice_ref(&b[1..2]).as_deref(), Some(&[2][..]));
+        assert_eq!(part.slice_ref(&b), None);
+        assert_eq!(part.slice_ref(&[2, 3]), None);
+    }
+
+    #[test]
+    fn same_allocation() {
+        let b = SharedBytes::from(vec![1, 2, 3, 4]);
+        assert!(b.slice(1..2).same_allocation(&b.slice(3..)));
+        assert!(!b.same_allocation(&SharedBytes::from(vec![1, 2, 3, 4])));
+    }
+}
@@ -4,7 +4,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use neqo_common::{qdebug, qerror, qinfo, qtrace, qwarn};
+use log::{Level, LevelFilter, Metadata};
+use neqo_common::{
+    log::{clear_module_level, init, set_module_level},
+    qdebug, qerror, qinfo, qtrace, qwarn,
+};
 
 #[test]
 fn basic() {
@@ -25,3 +29,30 @@ fn args() {
     qdebug!("debug {num} {obj:?}");
     qtrace!("trace {num} {obj:?}");
 }
+
+#[test]
+fn module_level() {
+    init(None);
+    let enabled = |target: &str, level: Level| {
+        log::logger().enabled(&Metadata::builder().target(target).level(level).build())
+    };
+
+    set_module_level("neqo_test::a", LevelFilter::Trace);
+    set_module_level("neqo_test::a::b", LevelFilter::Off);
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+    assert!(enabled("neqo_test::a", Level::Trace));
+    assert!(enabled("neqo_test::a::c", Level::Trace));
+    assert!(!enabled("neqo_test::a::b", Level::Error));
+    assert!(!enabled("neqo_test::a::b::c", Level::Error));
+
+    // Overrides apply to whole path segments only.
+    set_module_level("neqo_test::ab", LevelFilter::Off);
+    assert!(!enabled("neqo_test::ab", Level::Error));
+    assert!(enabled("neqo_test::a", Level::Trace));
+
+    clear_module_level("neqo_test::a::b");
+    assert!(enabled("neqo_test::a::b", Level::Trace));
+
+    clear_module_level("neqo_test::a");
+    clear_module_level("neqo_test::ab");
+}
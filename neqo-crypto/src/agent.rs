@@ -11,6 +11,7 @@
 
 use std::{
     cell::RefCell,
+    collections::HashMap,
     ffi::{CStr, CString},
     fmt::{self, Debug, Display, Formatter},
     mem::MaybeUninit,
@@ -45,7 +46,7 @@ use crate::{
 };
 pub use crate::{
     agentio::{Record, RecordList, as_c_void},
-    cert::CertificateInfo,
+    cert::{CertificateInfo, CertificateVerifier},
 };
 
 /// Private trait for Certificate Compression implementation
@@ -802,6 +803,38 @@ impl SecretAgent {
         CertificateInfo::new(self.fd)
     }
 
+    /// Derive `len` bytes of keying material using the TLS exporter interface
+    /// (see RFC 8446, Section 7.5).  An absent `context` is distinct from an
+    /// empty one.
+    ///
+    /// # Errors
+    ///
+    /// When the handshake is not complete, or the lengths are too large.
+    pub fn export_keying_material(
+        &self,
+        label: &str,
+        context: Option<&[u8]>,
+        len: usize,
+    ) -> Res<Vec<u8>> {
+        let label_len = c_uint::try_from(label.len())?;
+        let context_len = c_uint::try_from(context.map_or(0, <[u8]>::len))?;
+        let out_len = c_uint::try_from(len)?;
+        let mut out = vec![0; len];
+        secstatus_to_res(unsafe {
+            ssl::SSL_ExportKeyingMaterial(
+                self.fd,
+                label.as_ptr().cast(),
+                label_len,
+                PRBool::from(context.is_some()),
+                context.map_or(null(), <[u8]>::as_ptr),
+                context_len,
+                out.as_mut_ptr(),
+                out_len,
+            )
+        })?;
+        Ok(out)
+    }
+
     /// Return any fatal alert that the TLS stack might have sent.
     #[must_use]
     pub fn alert(&self) -> Option<Alert> {
@@ -1256,6 +1289,9 @@ pub struct Server {
     agent: SecretAgent,
     /// This holds the HRR callback context.
     zero_rtt_check: Option<Pin<Box<ZeroRttCheckState>>>,
+    /// Signed certificate timestamps, by certificate name.  These are kept so that
+    /// they can be configured again when the stapled OCSP responses change.
+    scts: HashMap<String, Vec<u8>>,
 }
 
 fn load_cert_and_key(name: &str) -> Res<(p11::Certificate, PrivateKey)> {
@@ -1287,6 +1323,7 @@ impl Server {
         Ok(Self {
             agent,
             zero_rtt_check: None,
+            scts: HashMap::new(),
         })
     }
 
@@ -1305,39 +1342,74 @@ impl Server {
     ) -> Res<Self> {
         let mut agent = SecretAgent::new()?;
         for n in certificates {
-            let (cert, key) = load_cert_and_key(n.as_ref())?;
-            let ocsp_items: Vec<p11::SECItem> = ocsp_responses
-                .iter()
-                .map(|b| p11::Item::wrap(b))
-                .collect::<Res<_>>()?;
-            let ocsp_array = ssl::SECItemArrayStr {
-                items: ocsp_items.as_ptr().cast::<ssl::SECItem>().cast_mut(),
-                len: c_uint::try_from(ocsp_items.len())?,
-            };
-            let sct_item = p11::Item::wrap(scts)?;
-            let extra = ssl::SSLExtraServerCertDataStr {
-                // ssl_auth_null means "I don't care what sort of certificate this is".
-                authType: ssl::SSLAuthType::ssl_auth_null,
-                certChain: null(),
-                stapledOCSPResponses: &raw const ocsp_array,
-                signedCertTimestamps: std::ptr::from_ref(&sct_item).cast(),
-                delegCred: null(),
-                delegCredPrivKey: null(),
-            };
-            secstatus_to_res(unsafe {
-                ssl::SSL_ConfigServerCert(
-                    agent.fd,
-                    (*cert).cast(),
-                    (*key).cast(),
-                    &raw const extra,
-                    c_uint::try_from(size_of::<ssl::SSLExtraServerCertDataStr>())?,
-                )
-            })?;
+            Self::config_server_cert(agent.fd, n.as_ref(), ocsp_responses, Some(scts))?;
         }
         agent.ready(true, true)?;
         Ok(Self {
             agent,
             zero_rtt_check: None,
+            scts: certificates
+                .iter()
+                .map(|n| (n.as_ref().to_owned(), scts.to_vec()))
+                .collect(),
+        })
+    }
+
+    /// Staple the given OCSP responses when presenting `certificate`, which needs
+    /// to be one of the certificates that this server was created with.
+    /// Any signed certificate timestamps for `certificate` are retained.
+    ///
+    /// # Errors
+    ///
+    /// Errors returned when NSS fails.
+    pub fn set_stapled_ocsp_responses(
+        &self,
+        certificate: &str,
+        ocsp_responses: &[&[u8]],
+    ) -> Res<()> {
+        Self::config_server_cert(
+            self.agent.fd,
+            certificate,
+            ocsp_responses,
+            self.scts.get(certificate).map(Vec::as_slice),
+        )
+    }
+
+    fn config_server_cert(
+        fd: *mut ssl::PRFileDesc,
+        certificate: &str,
+        ocsp_responses: &[&[u8]],
+        scts: Option<&[u8]>,
+    ) -> Res<()> {
+        let (cert, key) = load_cert_and_key(certificate)?;
+        let ocsp_items: Vec<p11::SECItem> = ocsp_responses
+            .iter()
+            .map(|b| p11::Item::wrap(b))
+            .collect::<Res<_>>()?;
+        let ocsp_array = ssl::SECItemArrayStr {
+            items: ocsp_items.as_ptr().cast::<ssl::SECItem>().cast_mut(),
+            len: c_uint::try_from(ocsp_items.len())?,
+        };
+        let sct_item = scts.map(p11::Item::wrap).transpose()?;
+        let extra = ssl::SSLExtraServerCertDataStr {
+            // ssl_auth_null means "I don't care what sort of certificate this is".
+            authType: ssl::SSLAuthType::ssl_auth_null,
+            certChain: null(),
+            stapledOCSPResponses: &raw const ocsp_array,
+            signedCertTimestamps: sct_item
+                .as_ref()
+                .map_or(null(), |sct| std::ptr::from_ref(sct).cast()),
+            delegCred: null(),
+            delegCredPrivKey: null(),
+        };
+        secstatus_to_res(unsafe {
+            ssl::SSL_ConfigServerCert(
+                fd,
+                (*cert).cast(),
+                (*key).cast(),
+                &raw const extra,
+                c_uint::try_from(size_of::<ssl::SSLExtraServerCertDataStr>())?,
+            )
         })
     }
 
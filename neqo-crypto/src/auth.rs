@@ -33,6 +33,7 @@ pub enum AuthenticationStatus {
     IssuerUnknown = sec::SEC_ERROR_UNKNOWN_ISSUER,
     IssuerUntrusted = sec::SEC_ERROR_UNTRUSTED_ISSUER,
     PolicyRejection = mozpkix::MOZILLA_PKIX_ERROR_ADDITIONAL_POLICY_CONSTRAINT_FAILED,
+    RequiredTlsFeatureMissing = mozpkix::MOZILLA_PKIX_ERROR_REQUIRED_TLS_FEATURE_MISSING,
     Unknown = sec::SEC_ERROR_LIBRARY_FAILURE,
 }
 
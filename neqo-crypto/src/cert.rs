@@ -6,14 +6,27 @@
 
 use std::ptr::NonNull;
 
-use neqo_common::qerror;
+use neqo_common::{Decoder, qerror};
 
 use crate::{
-    experimental_api, null_safe_slice,
+    AuthenticationStatus, experimental_api, null_safe_slice,
     p11::{ItemArray, ItemArrayIterator, SECItem, SECItemArray},
     ssl::{PRFileDesc, SSL_PeerSignedCertTimestamps, SSL_PeerStapledOCSPResponses},
 };
 
+/// The DER-encoded OID of the TLS Feature extension (1.3.6.1.5.5.7.1.24), see RFC 7633.
+const TLS_FEATURE_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x18];
+/// The `status_request` TLS extension, which a TLS Feature extension uses to require stapling.
+const TLS_FEATURE_STATUS_REQUEST: &[u8] = &[0x05];
+
+const DER_BOOLEAN: u8 = 0x01;
+const DER_INTEGER: u8 = 0x02;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_OID: u8 = 0x06;
+const DER_SEQUENCE: u8 = 0x30;
+/// The explicit `[3]` tag that wraps the extensions in a `TBSCertificate`.
+const DER_EXTENSIONS: u8 = 0xa3;
+
 experimental_api!(SSL_PeerCertificateChainDER(
     fd: *mut PRFileDesc,
     out: *mut *mut SECItemArray,
@@ -69,6 +82,67 @@ fn signed_cert_timestamp(fd: *mut PRFileDesc) -> Option<Vec<u8>> {
     })
 }
 
+/// Read a single DER element, returning its tag and contents.
+fn der_element<'a>(dec: &mut Decoder<'a>) -> Option<(u8, &'a [u8])> {
+    let tag = dec.decode_uint::<u8>()?;
+    let first = dec.decode_uint::<u8>()?;
+    let len = if first < 0x80 {
+        usize::from(first)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || usize::from(n) > size_of::<u32>() {
+            return None;
+        }
+        let mut len = 0;
+        for _ in 0..n {
+            len = (len << 8) | usize::from(dec.decode_uint::<u8>()?);
+        }
+        len
+    };
+    Some((tag, dec.decode(len)?))
+}
+
+/// Read a DER element, checking that it has the expected tag.
+fn der_expect<'a>(dec: &mut Decoder<'a>, expected: u8) -> Option<&'a [u8]> {
+    let (tag, contents) = der_element(dec)?;
+    (tag == expected).then_some(contents)
+}
+
+/// Determine whether a DER-encoded certificate includes a TLS Feature extension
+/// that requires the `status_request` extension, that is, whether it is "must-staple".
+fn der_must_staple(cert: &[u8]) -> Option<bool> {
+    let mut cert = Decoder::from(der_expect(&mut Decoder::from(cert), DER_SEQUENCE)?);
+    let mut tbs = Decoder::from(der_expect(&mut cert, DER_SEQUENCE)?);
+    // Skip to the extensions, which come last in the `TBSCertificate`.
+    let extensions = loop {
+        let (tag, contents) = der_element(&mut tbs)?;
+        if tag == DER_EXTENSIONS {
+            break contents;
+        }
+    };
+    let mut extensions = Decoder::from(der_expect(&mut Decoder::from(extensions), DER_SEQUENCE)?);
+    while extensions.remaining() > 0 {
+        let mut ext = Decoder::from(der_expect(&mut extensions, DER_SEQUENCE)?);
+        if der_expect(&mut ext, DER_OID)? != TLS_FEATURE_OID {
+            continue;
+        }
+        let (mut tag, mut value) = der_element(&mut ext)?;
+        if tag == DER_BOOLEAN {
+            (tag, value) = der_element(&mut ext)?;
+        }
+        if tag != DER_OCTET_STRING {
+            return None;
+        }
+        let mut features = Decoder::from(der_expect(&mut Decoder::from(value), DER_SEQUENCE)?);
+        while features.remaining() > 0 {
+            if der_expect(&mut features, DER_INTEGER)? == TLS_FEATURE_STATUS_REQUEST {
+                return Some(true);
+            }
+        }
+    }
+    Some(false)
+}
+
 impl<'a> IntoIterator for &'a CertificateInfo {
     type IntoIter = ItemArrayIterator<'a>;
     type Item = &'a [u8];
@@ -100,4 +174,81 @@ impl CertificateInfo {
     pub const fn signed_cert_timestamp(&self) -> Option<&Vec<u8>> {
         self.signed_cert_timestamp.as_ref()
     }
+
+    /// Whether the end-entity certificate requires a stapled OCSP response
+    /// ("must-staple"), as indicated by a TLS Feature extension (RFC 7633).
+    /// A certificate that can't be parsed is treated as not requiring stapling.
+    #[must_use]
+    pub fn must_staple(&self) -> bool {
+        self.iter()
+            .next()
+            .and_then(der_must_staple)
+            .unwrap_or(false)
+    }
+
+    /// Whether the peer stapled a non-empty OCSP response for the end-entity certificate.
+    #[must_use]
+    pub fn has_stapled_ocsp_response(&self) -> bool {
+        self.stapled_ocsp_responses
+            .as_ref()
+            .is_some_and(|r| r.iter().any(|r| !r.is_empty()))
+    }
+}
+
+/// A hook for verifying the peer's certificate.
+///
+/// This can be used to implement certificate pinning, to accept a custom
+/// set of trust anchors, or to accept test certificates.
+pub trait CertificateVerifier {
+    /// Verify the certificate chain, along with any stapled OCSP responses, that the peer
+    /// presented for `server_name`.  Returning `None` defers the decision to the
+    /// application, which is then asked to authenticate the peer as usual.
+    fn verify(
+        &mut self,
+        server_name: &str,
+        certificate: &CertificateInfo,
+    ) -> Option<AuthenticationStatus>;
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::der_must_staple;
+
+    /// Build a DER element with a short-form length.
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut v = vec![tag, u8::try_from(contents.len()).unwrap()];
+        v.extend_from_slice(contents);
+        v
+    }
+
+    /// Build a skeleton certificate with the given extension value under the TLS Feature OID.
+    fn cert_with_tls_feature(features: &[u8]) -> Vec<u8> {
+        let ext = [
+            der(0x06, super::TLS_FEATURE_OID),
+            der(0x04, &der(0x30, features)),
+        ]
+        .concat();
+        let extensions = der(0xa3, &der(0x30, &der(0x30, &ext)));
+        let tbs = [der(0x02, &[1]), extensions].concat();
+        der(0x30, &der(0x30, &tbs))
+    }
+
+    #[test]
+    fn must_staple() {
+        let cert = cert_with_tls_feature(&der(0x02, &[5]));
+        assert_eq!(der_must_staple(&cert), Some(true));
+    }
+
+    #[test]
+    fn other_tls_feature() {
+        let cert = cert_with_tls_feature(&der(0x02, &[17]));
+        assert_eq!(der_must_staple(&cert), Some(false));
+    }
+
+    #[test]
+    fn truncated() {
+        let cert = cert_with_tls_feature(&der(0x02, &[5]));
+        assert_eq!(der_must_staple(&cert[..cert.len() - 1]), None);
+    }
 }
@@ -41,9 +41,9 @@ pub use self::aead_null::AeadNull as Aead;
 pub use self::{
     aead::Aead as AeadTrait,
     agent::{
-        Agent, AllowZeroRtt, Client, HandshakeState, Record, RecordList, ResumptionToken,
-        SecretAgent, SecretAgentInfo, SecretAgentPreInfo, Server, ZeroRttCheckResult,
-        ZeroRttChecker,
+        Agent, AllowZeroRtt, CertificateVerifier, Client, HandshakeState, Record, RecordList,
+        ResumptionToken, SecretAgent, SecretAgentInfo, SecretAgentPreInfo, Server,
+        ZeroRttCheckResult, ZeroRttChecker,
     },
     auth::AuthenticationStatus,
     constants::*,
@@ -8,6 +8,7 @@ use std::{
     ops::Deref,
     os::raw::c_uint,
     ptr::null_mut,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -39,13 +40,25 @@ scoped_ptr!(
     SSL_ReleaseAntiReplayContext
 );
 
+// SAFETY: NSS guards the state of an anti-replay context with a lock, and
+// counts references to it atomically.
+unsafe impl Send for AntiReplayContext {}
+// SAFETY: As above.
+unsafe impl Sync for AntiReplayContext {}
+
 /// `AntiReplay` is used by servers when processing 0-RTT handshakes.
 ///
 /// It limits the exposure of servers to replay attack by rejecting 0-RTT
 /// if it appears to be a replay.  There is a false-positive rate that can be
 /// managed by tuning the parameters used to create the context.
+///
+/// Cloning produces a handle to the same context, so that multiple servers
+/// can share the same anti-replay state, also across threads.  To rotate the state, replace the
+/// context with a new one; note that NSS rejects all 0-RTT for one window
+/// after a context is created.
+#[derive(Clone)]
 pub struct AntiReplay {
-    ctx: AntiReplayContext,
+    ctx: Arc<AntiReplayContext>,
 }
 
 impl AntiReplay {
@@ -69,13 +82,13 @@ impl AntiReplay {
         }?;
 
         Ok(Self {
-            ctx: AntiReplayContext::from_ptr(ctx)?,
+            ctx: Arc::new(AntiReplayContext::from_ptr(ctx)?),
         })
     }
 
     /// Configure the provided socket with this anti-replay context.
     pub(crate) fn config_socket(&self, fd: *mut PRFileDesc) -> Res<()> {
-        unsafe { SSL_SetAntiReplayContext(fd, *self.ctx) }
+        unsafe { SSL_SetAntiReplayContext(fd, **self.ctx) }
     }
 }
 
@@ -44,6 +44,23 @@ impl SelfEncrypt {
         })
     }
 
+    /// Create with `key` rather than a random key, so that multiple instances
+    /// can open what each other sealed.
+    ///
+    /// # Errors
+    ///
+    /// Failure to import the key into NSS results in an error.
+    pub fn with_key(version: Version, cipher: Cipher, key: &[u8]) -> Res<Self> {
+        let key = hkdf::import_key(version, key)?;
+        Ok(Self {
+            version,
+            cipher,
+            key_id: 0,
+            key,
+            old_key: None,
+        })
+    }
+
     fn make_aead(&self, k: &SymKey, salt: &[u8]) -> Res<Aead> {
         debug_assert_eq!(salt.len(), Self::SALT_LENGTH);
         let salt = hkdf::import_key(self.version, salt)?;
@@ -171,6 +171,28 @@ fn ocsp_stapling_and_signed_cert_timestamps() {
     assert_eq!(certs.signed_cert_timestamp().unwrap(), scts);
 }
 
+#[test]
+fn restaple_keeps_signed_cert_timestamps() {
+    fixture_init();
+    let mut client = Client::new("server.example", true).expect("should create client");
+    client
+        .set_option(neqo_crypto::Opt::SignedCertificateTimestamps, true)
+        .unwrap();
+    let scts = b"fake signed certificate timestamps";
+    let mut server = Server::new_with_ocsp_and_scts(&["key"], &[&b"old ocsp response"[..]], scts)
+        .expect("should create server");
+    let ocsp_response = b"new ocsp response";
+    server
+        .set_stapled_ocsp_responses("key", &[&ocsp_response[..]])
+        .unwrap();
+
+    connect(&mut client, &mut server);
+
+    let certs = client.peer_certificate().unwrap();
+    assert_eq!(certs.stapled_ocsp_responses().unwrap(), &[ocsp_response]);
+    assert_eq!(certs.signed_cert_timestamp().unwrap(), scts);
+}
+
 #[test]
 fn chacha_client() {
     fixture_init();
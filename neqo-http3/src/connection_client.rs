@@ -15,8 +15,8 @@ use std::{
 };
 
 use neqo_common::{
-    Datagram, Decoder, Encoder, Header, MessageType, Role, event::Provider as EventProvider, hex,
-    hex_with_len, qdebug, qinfo, qlog::Qlog, qtrace, qwarn,
+    Datagram, Decoder, Encoder, Header, MessageType, Role, datagram::Payload,
+    event::Provider as EventProvider, hex, hex_with_len, qdebug, qinfo, qlog::Qlog, qtrace, qwarn,
 };
 use neqo_crypto::{AuthenticationStatus, ResumptionToken, SecretAgentInfo, agent::CertificateInfo};
 use neqo_qpack::Stats as QpackStats;
@@ -930,11 +930,7 @@ impl Http3Client {
     }
 
     /// This function combines  `process_input` and `process_output` function.
-    pub fn process<A: AsRef<[u8]> + AsMut<[u8]>>(
-        &mut self,
-        dgram: Option<Datagram<A>>,
-        now: Instant,
-    ) -> Output {
+    pub fn process<A: Payload>(&mut self, dgram: Option<Datagram<A>>, now: Instant) -> Output {
         qtrace!("[{self}] Process");
         if let Some(d) = dgram {
             self.process_input(d, now);
@@ -952,18 +948,11 @@ impl Http3Client {
     /// packets need to be sent or if a timer needs to be updated.
     ///
     /// [1]: ../neqo_transport/enum.ConnectionEvent.html
-    pub fn process_input<A: AsRef<[u8]> + AsMut<[u8]>>(
-        &mut self,
-        dgram: Datagram<A>,
-        now: Instant,
-    ) {
+    pub fn process_input<A: Payload>(&mut self, dgram: Datagram<A>, now: Instant) {
         self.process_multiple_input(iter::once(dgram), now);
     }
 
-    pub fn process_multiple_input<
-        A: AsRef<[u8]> + AsMut<[u8]>,
-        I: IntoIterator<Item = Datagram<A>>,
-    >(
+    pub fn process_multiple_input<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -13,11 +13,11 @@ use std::{
     time::Instant,
 };
 
-use neqo_common::{Datagram, qtrace};
-use neqo_crypto::{AntiReplay, Cipher, PrivateKey, PublicKey, ZeroRttChecker};
+use neqo_common::{Datagram, datagram::Payload, qtrace};
+use neqo_crypto::{AntiReplay, Cipher, Group, PrivateKey, PublicKey, ZeroRttChecker};
 use neqo_transport::{
     ConnectionIdGenerator, Output, OutputBatch,
-    server::{ConnectionRef, Server, ValidateAddress},
+    server::{AdmissionLimits, AdmissionStats, ConnectionRef, Server, ValidateAddress},
 };
 use rustc_hash::FxHashMap as HashMap;
 
@@ -89,10 +89,36 @@ impl Http3Server {
         self.server.set_validation(v);
     }
 
+    /// See [`neqo_transport::server::Server::set_token_key`].
+    ///
+    /// # Errors
+    /// When the key cannot be imported.
+    pub fn set_token_key(&self, key: &[u8], epoch: Instant) -> Res<()> {
+        self.server.set_token_key(key, epoch)?;
+        Ok(())
+    }
+
+    pub const fn set_admission_limits(&mut self, limits: &AdmissionLimits) {
+        self.server.set_admission_limits(limits);
+    }
+
+    #[must_use]
+    pub const fn admission_stats(&self) -> &AdmissionStats {
+        self.server.admission_stats()
+    }
+
     pub fn set_ciphers<A: AsRef<[Cipher]>>(&mut self, ciphers: A) {
         self.server.set_ciphers(ciphers);
     }
 
+    pub fn set_anti_replay(&mut self, anti_replay: AntiReplay) {
+        self.server.set_anti_replay(anti_replay);
+    }
+
+    pub fn set_groups<A: AsRef<[Group]>>(&mut self, groups: A) {
+        self.server.set_groups(groups);
+    }
+
     /// Enable encrypted client hello (ECH).
     ///
     /// # Errors
@@ -122,7 +148,7 @@ impl Http3Server {
     /// Wrapper around [`Http3Server::process_multiple`] that processes a single
     /// output datagram only.
     #[expect(clippy::missing_panics_doc, reason = "see expect()")]
-    pub fn process<A: AsRef<[u8]> + AsMut<[u8]>, I: IntoIterator<Item = Datagram<A>>>(
+    pub fn process<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -132,7 +158,7 @@ impl Http3Server {
             .expect("max_datagrams is 1")
     }
 
-    pub fn process_multiple<A: AsRef<[u8]> + AsMut<[u8]>, I: IntoIterator<Item = Datagram<A>>>(
+    pub fn process_multiple<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -0,0 +1,164 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Congestion control scenarios, asserting minimum utilization and maximum loss.
+//!
+//! Each combination of congestion controller, slow start algorithm, and link
+//! profile runs one transfer in simulated time.  As with `min_bandwidth`, the
+//! simulation is deterministic for a given seed, so each scenario runs once.
+//! All scenarios run before any failure is reported, so that a regression
+//! shows up in every scenario that it affects.
+//!
+//! Set `SIMULATION_SEED` to run with a different seed.
+
+#![expect(
+    clippy::cast_precision_loss,
+    clippy::unwrap_used,
+    reason = "OK in a bench."
+)]
+
+use std::time::Duration;
+
+use neqo_common::{log::init as init_log, qinfo};
+use neqo_transport::{CongestionControl, ConnectionParameters, SlowStart, State};
+use test_fixture::{
+    boxed,
+    sim::{
+        Simulator,
+        connection::{Node, ReachState, ReceiveData, SendData},
+        network::{Drop, TailDrop},
+    },
+};
+
+const TRANSFER_AMOUNT: usize = 1 << 23; // 8M
+const FIXED_SEED: &str = "62df6933ba1f543cece01db8f27fb2025529b27f93df39e19f006e1db3b8c843";
+/// The position of the sending node in the simulator.
+const SENDER: usize = 2;
+
+/// The characteristics of the link toward the receiver.
+struct Profile {
+    name: &'static str,
+    /// The rate, in bytes per second.
+    rate: usize,
+    /// How long the queue is when full.
+    buffer: Duration,
+    /// The one-way delay.
+    delay: Duration,
+    /// The percentage of datagrams that are dropped at random.
+    loss: u8,
+    /// The share of the link rate that a transfer needs to achieve.
+    min_utilization: f64,
+    /// The largest share of packets that can be declared lost.
+    max_loss: f64,
+}
+
+impl Profile {
+    fn link(&self) -> TailDrop {
+        let capacity = self.rate * usize::try_from(self.buffer.as_millis()).unwrap() / 1000;
+        TailDrop::new(self.rate, capacity, false, self.delay)
+    }
+}
+
+const PROFILES: &[Profile] = &[
+    Profile {
+        name: "dsl",
+        rate: 1_000_000,
+        buffer: Duration::from_millis(30),
+        delay: Duration::from_millis(25),
+        loss: 0,
+        min_utilization: 0.6,
+        max_loss: 0.05,
+    },
+    Profile {
+        name: "long-fat",
+        rate: 10_000_000,
+        buffer: Duration::from_millis(100),
+        delay: Duration::from_millis(100),
+        loss: 0,
+        min_utilization: 0.2,
+        max_loss: 0.05,
+    },
+    Profile {
+        name: "shallow-buffer",
+        rate: 5_000_000,
+        buffer: Duration::from_millis(5),
+        delay: Duration::from_millis(20),
+        loss: 0,
+        min_utilization: 0.4,
+        max_loss: 0.1,
+    },
+    Profile {
+        name: "lossy",
+        rate: 1_000_000,
+        buffer: Duration::from_millis(30),
+        delay: Duration::from_millis(25),
+        loss: 1,
+        min_utilization: 0.2,
+        max_loss: 0.1,
+    },
+];
+
+/// Run one scenario, returning a description of any failure.
+fn scenario(cc: CongestionControl, ss: SlowStart, profile: &Profile) -> Option<String> {
+    let name = format!("{cc:?}-{ss:?}-{}", profile.name).to_lowercase();
+    let params = ConnectionParameters::default()
+        .congestion_control(cc)
+        .slow_start(ss)
+        .mlkem(false);
+    let mut sim = Simulator::new(
+        &name,
+        boxed![
+            Node::new_client(
+                params.clone(),
+                boxed![ReachState::new(State::Confirmed)],
+                boxed![ReceiveData::new(TRANSFER_AMOUNT)]
+            ),
+            TailDrop::dsl_uplink(),
+            Node::new_server(
+                params,
+                boxed![ReachState::new(State::Confirmed)],
+                boxed![SendData::new(TRANSFER_AMOUNT)]
+            ),
+            profile.link(),
+            Drop::percentage(profile.loss),
+        ],
+    );
+    sim.seed_str(std::env::var("SIMULATION_SEED").unwrap_or_else(|_| FIXED_SEED.to_string()));
+    let (time, metrics) = sim.setup().run_with_metrics(Duration::from_millis(100));
+
+    let sender = metrics.node(SENDER).unwrap().samples.last().unwrap().1;
+    let utilization = TRANSFER_AMOUNT as f64 / time.as_secs_f64() / profile.rate as f64;
+    let loss = sender.lost as f64 / sender.sent.max(1) as f64;
+    qinfo!("{name}: utilization {utilization:.3}, loss {loss:.4}, time {time:?}");
+
+    if utilization < profile.min_utilization {
+        Some(format!(
+            "{name}: utilization {utilization:.3} below {}",
+            profile.min_utilization
+        ))
+    } else if loss > profile.max_loss {
+        Some(format!("{name}: loss {loss:.4} above {}", profile.max_loss))
+    } else {
+        None
+    }
+}
+
+fn main() {
+    init_log(None);
+    let mut failures = Vec::new();
+    for cc in [
+        CongestionControl::NewReno,
+        CongestionControl::Cubic,
+        CongestionControl::Westwood,
+    ] {
+        for ss in [SlowStart::Classic, SlowStart::HyStart, SlowStart::Search] {
+            for profile in PROFILES {
+                failures.extend(scenario(cc, ss, profile));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -19,7 +19,7 @@ fn rx_stream_orderer() {
     let data: &[u8] = &[0; 1337];
 
     for i in 0..100_000 {
-        rx.inbound_frame(i * 1337, data);
+        rx.inbound_frame(i * 1337, data, None);
     }
 }
 
@@ -190,6 +190,12 @@ impl PeerAckDelay {
         }
     }
 
+    /// Whether the peer supports the ACK frequency extension, which
+    /// includes the IMMEDIATE_ACK frame.
+    pub const fn is_flexible(&self) -> bool {
+        matches!(self, Self::Flexible(_))
+    }
+
     pub fn max(&self) -> Duration {
         match self {
             Self::Flexible(rate) => rate.peer_ack_delay(),
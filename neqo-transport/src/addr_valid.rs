@@ -166,6 +166,14 @@ impl AddressValidation {
         self.generate_token(None, peer_address, now)
     }
 
+    /// Protect tokens with `key`, with their expiry relative to `epoch`.
+    /// Instances with the same key and epoch accept each other's tokens.
+    pub fn set_token_key(&mut self, key: &[u8], epoch: Instant) -> Res<()> {
+        self.self_encrypt = SelfEncrypt::with_key(TLS_VERSION_1_3, TLS_AES_128_GCM_SHA256, key)?;
+        self.start_time = epoch;
+        Ok(())
+    }
+
     pub fn set_validation(&mut self, validation: ValidateAddress) {
         qtrace!("AddressValidation {self:p}: set to {validation:?}");
         self.validation = validation;
@@ -0,0 +1,646 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// BBR congestion control, version 1.
+//
+// See <https://datatracker.ietf.org/doc/html/draft-cardwell-iccrg-bbr-congestion-control-00>.
+
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{qdebug, qinfo, qlog::Qlog, qtrace};
+
+use super::{CongestionController, CongestionEvent, CongestionPhase, classic_cc};
+use crate::{
+    Pmtud,
+    delivery_rate::RateSample,
+    packet, qlog,
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+/// Gains are expressed in units of `1 / GAIN_UNIT`.
+pub(super) const GAIN_UNIT: u64 = 1000;
+/// `2 / ln(2)`, the smallest gain that doubles the sending rate each round.
+const HIGH_GAIN: u64 = 2885;
+/// The inverse of [`HIGH_GAIN`], which drains the queue built during startup.
+const DRAIN_GAIN: u64 = 347;
+/// The congestion window gain used outside of startup and drain.
+const CWND_GAIN: u64 = 2000;
+/// The pacing gains that `ProbeBW` cycles through, one per minimum RTT.
+const PACING_GAIN_CYCLE: [u64; 8] = [1250, 750, 1000, 1000, 1000, 1000, 1000, 1000];
+/// The number of rounds that the bottleneck bandwidth filter covers.
+const BTL_BW_FILTER_ROUNDS: u64 = 10;
+/// How long a minimum RTT sample remains valid.
+pub(super) const RT_PROP_FILTER_LEN: Duration = Duration::from_secs(10);
+/// How long to hold the congestion window at its minimum in `ProbeRTT`.
+pub(super) const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
+/// The minimum congestion window, in packets.
+pub(super) const MIN_PIPE_CWND_PKTS: usize = 4;
+/// The bandwidth has stopped growing when it grows by less than 25%...
+pub(super) const FULL_BW_GROWTH: u64 = 1250;
+/// ... for this many rounds.
+pub(super) const FULL_BW_ROUNDS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Startup,
+    Drain,
+    ProbeBw,
+    ProbeRtt,
+}
+
+impl Mode {
+    pub const fn to_qlog(self) -> &'static str {
+        match self {
+            Self::Startup => "startup",
+            Self::Drain => "drain",
+            Self::ProbeBw => "probe_bw",
+            Self::ProbeRtt => "probe_rtt",
+        }
+    }
+}
+
+/// A windowed maximum of bandwidth samples, indexed by round.
+#[derive(Debug)]
+pub(super) struct MaxBwFilter {
+    /// The number of rounds that the filter covers.
+    len: u64,
+    /// Samples that might still be the maximum, with the largest first.
+    samples: VecDeque<(u64, u64)>,
+}
+
+impl MaxBwFilter {
+    pub const fn new(len: u64) -> Self {
+        Self {
+            len,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn update(&mut self, round: u64, bw: u64) {
+        while self.samples.back().is_some_and(|&(_, b)| b <= bw) {
+            self.samples.pop_back();
+        }
+        self.samples.push_back((round, bw));
+        // Expire old samples, but always keep the latest.
+        while self.samples.len() > 1
+            && self
+                .samples
+                .front()
+                .is_some_and(|&(r, _)| r + self.len <= round)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn get(&self) -> u64 {
+        self.samples.front().map_or(0, |&(_, bw)| bw)
+    }
+}
+
+#[derive(Debug)]
+pub struct Bbr {
+    pmtud: Pmtud,
+    qlog: Qlog,
+    mode: Mode,
+    congestion_window: usize,
+    /// The initial congestion window, in packets.
+    initial_cwnd: usize,
+    bytes_in_flight: usize,
+    /// The total amount delivered, from the latest rate sample.
+    delivered: usize,
+    /// The rate sample for the packets that are being acknowledged.
+    rate_sample: Option<RateSample>,
+    /// The bottleneck bandwidth estimate, in bytes per second.
+    btl_bw: MaxBwFilter,
+    /// The round trip propagation time estimate.
+    rt_prop: Option<Duration>,
+    rt_prop_stamp: Option<Instant>,
+    pacing_gain: u64,
+    cwnd_gain: u64,
+    /// The number of round trips so far.
+    round_count: u64,
+    next_round_delivered: usize,
+    round_start: bool,
+    /// Startup exit detection.
+    filled_pipe: bool,
+    full_bw: u64,
+    full_bw_count: usize,
+    /// The position in [`PACING_GAIN_CYCLE`] and when it was entered.
+    cycle_index: usize,
+    cycle_stamp: Option<Instant>,
+    /// `ProbeRTT` state.
+    probe_rtt_done_stamp: Option<Instant>,
+    probe_rtt_round_done: bool,
+    /// The congestion window to restore after recovery or `ProbeRTT`.
+    prior_cwnd: usize,
+    /// Packets sent before this one don't start a new recovery period.
+    recovery_start: Option<packet::Number>,
+    in_recovery: bool,
+    /// Whether the next packet may be sent without regard to the congestion
+    /// window, as the first packet of a recovery period.
+    recovery_packet: bool,
+    /// The packet number of the last packet sent.
+    last_sent: packet::Number,
+}
+
+impl Display for Bbr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BBR CongCtrl [bif: {}, cwnd: {}, {:?}, btl_bw: {}, rt_prop: {:?}]",
+            self.bytes_in_flight,
+            self.congestion_window,
+            self.mode,
+            self.btl_bw.get(),
+            self.rt_prop
+        )
+    }
+}
+
+impl Bbr {
+    #[must_use]
+    pub fn new(pmtud: Pmtud) -> Self {
+        let cwnd = classic_cc::cwnd_initial(classic_cc::CWND_INITIAL_PKTS, pmtud.plpmtu());
+        Self {
+            pmtud,
+            qlog: Qlog::disabled(),
+            mode: Mode::Startup,
+            congestion_window: cwnd,
+            initial_cwnd: classic_cc::CWND_INITIAL_PKTS,
+            bytes_in_flight: 0,
+            delivered: 0,
+            rate_sample: None,
+            btl_bw: MaxBwFilter::new(BTL_BW_FILTER_ROUNDS),
+            rt_prop: None,
+            rt_prop_stamp: None,
+            pacing_gain: HIGH_GAIN,
+            cwnd_gain: HIGH_GAIN,
+            round_count: 0,
+            next_round_delivered: 0,
+            round_start: false,
+            filled_pipe: false,
+            full_bw: 0,
+            full_bw_count: 0,
+            cycle_index: 0,
+            cycle_stamp: None,
+            probe_rtt_done_stamp: None,
+            probe_rtt_round_done: false,
+            prior_cwnd: 0,
+            recovery_start: None,
+            in_recovery: false,
+            recovery_packet: false,
+            last_sent: 0,
+        }
+    }
+
+    /// Start with a congestion window of `packets` packets.
+    #[must_use]
+    pub const fn with_initial_cwnd(mut self, packets: usize) -> Self {
+        self.initial_cwnd = packets;
+        self.congestion_window = classic_cc::cwnd_initial(packets, self.max_datagram_size());
+        self
+    }
+
+    const fn max_datagram_size(&self) -> usize {
+        self.pmtud.plpmtu()
+    }
+
+    /// The bandwidth-delay product, scaled by `gain`.
+    fn bdp(&self, gain: u64) -> Option<usize> {
+        let bw = self.btl_bw.get();
+        let rt_prop = self.rt_prop?;
+        if bw == 0 {
+            return None;
+        }
+        let bdp = u128::from(bw) * rt_prop.as_nanos() / 1_000_000_000;
+        let bdp = bdp * u128::from(gain) / u128::from(GAIN_UNIT);
+        Some(usize::try_from(bdp).unwrap_or(usize::MAX))
+    }
+
+    /// The target congestion window.
+    fn target_cwnd(&self, gain: u64) -> usize {
+        self.bdp(gain).map_or_else(
+            || classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size()),
+            |bdp| max(bdp, self.cwnd_min()),
+        )
+    }
+
+    fn set_mode(&mut self, mode: Mode, now: Instant) {
+        if self.mode == mode {
+            return;
+        }
+        qdebug!("[{self}] mode -> {mode:?}");
+        qlog::congestion_state_updated(
+            &mut self.qlog,
+            self.mode.to_qlog(),
+            mode.to_qlog(),
+            None,
+            now,
+        );
+        self.mode = mode;
+        let (pacing_gain, cwnd_gain) = match mode {
+            Mode::Startup => (HIGH_GAIN, HIGH_GAIN),
+            Mode::Drain => (DRAIN_GAIN, HIGH_GAIN),
+            Mode::ProbeBw => (PACING_GAIN_CYCLE[self.cycle_index], CWND_GAIN),
+            Mode::ProbeRtt => (GAIN_UNIT, GAIN_UNIT),
+        };
+        self.pacing_gain = pacing_gain;
+        self.cwnd_gain = cwnd_gain;
+    }
+
+    fn update_round(&mut self, sample: Option<&RateSample>) {
+        self.round_start = false;
+        if let Some(sample) = sample
+            && sample.prior_delivered >= self.next_round_delivered
+        {
+            self.next_round_delivered = self.delivered;
+            self.round_count += 1;
+            self.round_start = true;
+        }
+    }
+
+    fn update_btl_bw(&mut self, sample: Option<&RateSample>) {
+        if let Some(sample) = sample
+            && let Some(rate) = sample.rate
+            && (!sample.app_limited || rate >= self.btl_bw.get())
+        {
+            self.btl_bw.update(self.round_count, rate);
+        }
+    }
+
+    fn update_rt_prop(&mut self, rtt: Duration, now: Instant) -> bool {
+        let expired = self
+            .rt_prop_stamp
+            .is_some_and(|t| now > t + RT_PROP_FILTER_LEN);
+        if self.rt_prop.is_none_or(|r| rtt <= r) || expired {
+            self.rt_prop = Some(rtt);
+            self.rt_prop_stamp = Some(now);
+        }
+        expired
+    }
+
+    fn check_full_pipe(&mut self, sample: Option<&RateSample>) {
+        if self.filled_pipe || !self.round_start || sample.is_none_or(|s| s.app_limited) {
+            return;
+        }
+        let bw = self.btl_bw.get();
+        if u128::from(bw) * u128::from(GAIN_UNIT)
+            >= u128::from(self.full_bw) * u128::from(FULL_BW_GROWTH)
+        {
+            self.full_bw = bw;
+            self.full_bw_count = 0;
+            return;
+        }
+        self.full_bw_count += 1;
+        if self.full_bw_count >= FULL_BW_ROUNDS {
+            qinfo!("[{self}] filled pipe");
+            self.filled_pipe = true;
+        }
+    }
+
+    fn check_drain(&mut self, now: Instant, cc_stats: &mut CongestionControlStats) {
+        if self.mode == Mode::Startup && self.filled_pipe {
+            cc_stats.slow_start_exit_cwnd = Some(self.congestion_window);
+            cc_stats.slow_start_exit_reason = Some(SlowStartExitReason::Heuristic);
+            self.set_mode(Mode::Drain, now);
+        }
+        if self.mode == Mode::Drain && self.bytes_in_flight <= self.target_cwnd(GAIN_UNIT) {
+            self.enter_probe_bw(now);
+        }
+    }
+
+    fn enter_probe_bw(&mut self, now: Instant) {
+        // Start in one of the cruising phases, so that we don't probe for
+        // bandwidth straight after draining the queue.
+        self.cycle_index = 2;
+        self.cycle_stamp = Some(now);
+        self.set_mode(Mode::ProbeBw, now);
+    }
+
+    fn update_gain_cycle(&mut self, lost: bool, now: Instant) {
+        if self.mode != Mode::ProbeBw {
+            return;
+        }
+        let full_length = match (self.cycle_stamp, self.rt_prop) {
+            (Some(t), Some(rt_prop)) => now.saturating_duration_since(t) > rt_prop,
+            _ => true,
+        };
+        let next = match self.pacing_gain {
+            // Probe until enough is in flight to fill the pipe at the higher rate.
+            g if g > GAIN_UNIT => {
+                full_length && (lost || self.bytes_in_flight >= self.target_cwnd(g))
+            }
+            // Drain until the queue is gone, or for one round trip.
+            g if g < GAIN_UNIT => {
+                full_length || self.bytes_in_flight <= self.target_cwnd(GAIN_UNIT)
+            }
+            _ => full_length,
+        };
+        if next {
+            self.cycle_index = (self.cycle_index + 1) % PACING_GAIN_CYCLE.len();
+            self.cycle_stamp = Some(now);
+            self.pacing_gain = PACING_GAIN_CYCLE[self.cycle_index];
+            qtrace!("[{self}] gain cycle {}", self.cycle_index);
+        }
+    }
+
+    fn check_probe_rtt(&mut self, rt_prop_expired: bool, now: Instant) {
+        if self.mode != Mode::ProbeRtt && rt_prop_expired {
+            self.prior_cwnd = max(self.prior_cwnd, self.congestion_window);
+            self.probe_rtt_done_stamp = None;
+            self.set_mode(Mode::ProbeRtt, now);
+        }
+        if self.mode != Mode::ProbeRtt {
+            return;
+        }
+        let min_cwnd = self.cwnd_min();
+        match self.probe_rtt_done_stamp {
+            None if self.bytes_in_flight <= min_cwnd => {
+                self.probe_rtt_done_stamp = Some(now + PROBE_RTT_DURATION);
+                self.probe_rtt_round_done = false;
+                self.next_round_delivered = self.delivered;
+            }
+            Some(done) => {
+                if self.round_start {
+                    self.probe_rtt_round_done = true;
+                }
+                if self.probe_rtt_round_done && now >= done {
+                    self.rt_prop_stamp = Some(now);
+                    self.congestion_window = max(self.congestion_window, self.prior_cwnd);
+                    if self.filled_pipe {
+                        self.enter_probe_bw(now);
+                    } else {
+                        self.set_mode(Mode::Startup, now);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn update_cwnd(&mut self, newly_acked: usize) {
+        let target = self.target_cwnd(self.cwnd_gain);
+        if self.in_recovery {
+            // Packet conservation: send one packet for each one that is acknowledged.
+            self.congestion_window =
+                max(self.congestion_window, self.bytes_in_flight + newly_acked);
+        } else if self.filled_pipe {
+            self.congestion_window = min(self.congestion_window + newly_acked, target);
+        } else if self.congestion_window < target
+            || self.delivered
+                < classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size())
+        {
+            self.congestion_window += newly_acked;
+        }
+        self.congestion_window = max(self.congestion_window, self.cwnd_min());
+        if self.mode == Mode::ProbeRtt {
+            self.congestion_window = min(self.congestion_window, self.cwnd_min());
+        }
+    }
+}
+
+impl CongestionController for Bbr {
+    fn set_qlog(&mut self, qlog: Qlog) {
+        self.pmtud.set_qlog(qlog.clone());
+        self.qlog = qlog;
+    }
+
+    fn cwnd(&self) -> usize {
+        self.congestion_window
+    }
+
+    fn bytes_in_flight(&self) -> usize {
+        self.bytes_in_flight
+    }
+
+    fn cwnd_avail(&self) -> usize {
+        self.congestion_window.saturating_sub(self.bytes_in_flight)
+    }
+
+    fn cwnd_min(&self) -> usize {
+        self.max_datagram_size() * MIN_PIPE_CWND_PKTS
+    }
+
+    fn ssthresh(&self) -> usize {
+        usize::MAX
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.in_recovery {
+            return CongestionPhase::Recovery;
+        }
+        match self.mode {
+            Mode::Startup => CongestionPhase::SlowStart,
+            Mode::Drain | Mode::ProbeBw | Mode::ProbeRtt => CongestionPhase::CongestionAvoidance,
+        }
+    }
+
+    /// Whether the sender is not using the pipe, so that rate samples taken
+    /// from packets sent now might underestimate the bandwidth.  The connection
+    /// doesn't tell us whether it has more to send, so this uses the amount in flight.
+    fn app_limited(&self) -> bool {
+        self.bdp(GAIN_UNIT)
+            .is_some_and(|bdp| self.bytes_in_flight < bdp / 2)
+    }
+
+    fn pacing_rate(&self) -> Option<u64> {
+        let bw = self.btl_bw.get();
+        (bw > 0).then(|| {
+            let rate = u128::from(bw) * u128::from(self.pacing_gain) / u128::from(GAIN_UNIT);
+            u64::try_from(rate).unwrap_or(u64::MAX)
+        })
+    }
+
+    #[cfg(test)]
+    fn cwnd_initial(&self) -> usize {
+        classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size())
+    }
+
+    fn pmtud(&self) -> &Pmtud {
+        &self.pmtud
+    }
+
+    fn pmtud_mut(&mut self) -> &mut Pmtud {
+        &mut self.pmtud
+    }
+
+    fn on_rate_sample(&mut self, sample: &RateSample) {
+        self.delivered = sample.delivered;
+        self.rate_sample = Some(*sample);
+    }
+
+    fn on_packets_acked(
+        &mut self,
+        acked_pkts: &[sent::Packet],
+        _rtt_est: &RttEstimate,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        cc_stats.cwnd.get_or_insert(self.congestion_window);
+
+        let mut newly_acked = 0;
+        let mut rtt = None;
+        for pkt in acked_pkts.iter().filter(|pkt| pkt.cc_outstanding()) {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            newly_acked += pkt.len();
+            if self.in_recovery && self.recovery_start.is_none_or(|pn| pkt.pn() >= pn) {
+                qdebug!("[{self}] recovery done");
+                self.in_recovery = false;
+                self.congestion_window = max(self.congestion_window, self.prior_cwnd);
+            }
+            let sample = now.saturating_duration_since(pkt.time_sent());
+            rtt = Some(rtt.map_or(sample, |r: Duration| min(r, sample)));
+        }
+        let sample = self.rate_sample.take();
+
+        self.update_round(sample.as_ref());
+        self.update_btl_bw(sample.as_ref());
+        self.update_gain_cycle(false, now);
+        self.check_full_pipe(sample.as_ref());
+        self.check_drain(now, cc_stats);
+        let rt_prop_expired = rtt.is_some_and(|rtt| self.update_rt_prop(rtt, now));
+        self.check_probe_rtt(rt_prop_expired, now);
+        self.update_cwnd(newly_acked);
+
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.congestion_window),
+                qlog::Metric::BytesInFlight(self.bytes_in_flight),
+            ],
+            now,
+        );
+        qtrace!("[{self}] on_packets_acked, new_acked={newly_acked}");
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        first_rtt_sample_time: Option<Instant>,
+        prev_largest_acked_sent: Option<Instant>,
+        pto: Duration,
+        lost_packets: &[sent::Packet],
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        for pkt in lost_packets {
+            if pkt.cc_in_flight() {
+                self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            }
+        }
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+
+        // Lost PMTUD probes do not elicit a congestion control reaction.
+        let lost = lost_packets
+            .iter()
+            .filter(|pkt| !pkt.is_pmtud_probe())
+            .collect::<Vec<_>>();
+        let Some(last) = lost.last() else {
+            return false;
+        };
+        self.update_gain_cycle(true, now);
+
+        let mut reduced = false;
+        if self.recovery_start.is_none_or(|pn| last.pn() >= pn) {
+            qinfo!("[{self}] loss -> recovery");
+            if !self.in_recovery {
+                self.prior_cwnd = self.congestion_window;
+            }
+            self.in_recovery = true;
+            self.recovery_packet = true;
+            self.recovery_start = Some(self.last_sent + 1);
+            self.congestion_window = max(self.bytes_in_flight, self.cwnd_min());
+            cc_stats.congestion_events[CongestionEvent::Loss] += 1;
+            reduced = true;
+        }
+        if classic_cc::persistent_congestion(
+            first_rtt_sample_time,
+            prev_largest_acked_sent,
+            pto,
+            lost,
+        ) {
+            qinfo!("[{self}] persistent congestion");
+            cc_stats.persistent_congestion += 1;
+            self.prior_cwnd = self.cwnd_min();
+            self.congestion_window = self.cwnd_min();
+            qlog::congestion_state_updated(
+                &mut self.qlog,
+                self.mode.to_qlog(),
+                self.mode.to_qlog(),
+                Some(qlog::CongestionStateTrigger::PersistentCongestion),
+                now,
+            );
+            reduced = true;
+        }
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::CongestionWindow(self.congestion_window)],
+            now,
+        );
+        reduced
+    }
+
+    /// BBR version 1 does not respond to ECN.
+    fn on_ecn_ce_received(
+        &mut self,
+        _largest_acked_pkt: &sent::Packet,
+        _ce_marks: u64,
+        _now: Instant,
+        _cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        false
+    }
+
+    fn recovery_packet(&self) -> bool {
+        self.recovery_packet
+    }
+
+    fn discard(&mut self, pkt: &sent::Packet, now: Instant) {
+        if pkt.cc_outstanding() {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+                now,
+            );
+        }
+    }
+
+    fn on_packet_sent(&mut self, pkt: &sent::Packet, now: Instant) {
+        self.recovery_packet = false;
+        self.last_sent = pkt.pn();
+        if !pkt.cc_in_flight() {
+            return;
+        }
+        self.bytes_in_flight += pkt.len();
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+
+    fn discard_in_flight(&mut self, now: Instant) {
+        self.bytes_in_flight = 0;
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+}
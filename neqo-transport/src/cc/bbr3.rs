@@ -0,0 +1,828 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// BBR congestion control, version 3.
+//
+// See <https://datatracker.ietf.org/doc/html/draft-ietf-ccwg-bbr-02>.
+// This shares the bandwidth filter with version 1.
+
+use std::{
+    cmp::{max, min},
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{qdebug, qinfo, qlog::Qlog, qtrace};
+
+use super::{
+    CongestionController, CongestionEvent, CongestionPhase,
+    bbr::{
+        FULL_BW_GROWTH, FULL_BW_ROUNDS, GAIN_UNIT, MIN_PIPE_CWND_PKTS, MaxBwFilter,
+        PROBE_RTT_DURATION, RT_PROP_FILTER_LEN,
+    },
+    classic_cc,
+};
+use crate::{
+    Pmtud,
+    delivery_rate::RateSample,
+    packet, qlog,
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+const STARTUP_PACING_GAIN: u64 = 2770;
+const STARTUP_CWND_GAIN: u64 = 2000;
+const DRAIN_PACING_GAIN: u64 = 350;
+const CWND_GAIN: u64 = 2000;
+const PROBE_DOWN_PACING_GAIN: u64 = 900;
+const PROBE_UP_PACING_GAIN: u64 = 1250;
+const PROBE_UP_CWND_GAIN: u64 = 2250;
+const PROBE_RTT_CWND_GAIN: u64 = 500;
+/// Pace a little below the estimated bandwidth, in units of [`GAIN_UNIT`].
+const PACING_MARGIN: u64 = 10;
+/// The loss rate, in units of [`GAIN_UNIT`], above which the path is deemed to be overloaded.
+const LOSS_THRESH: u64 = 20;
+/// The multiplicative decrease applied to the lower bounds on congestion.
+const BETA: u64 = 700;
+/// The fraction of `inflight_hi` that is used while cruising, to leave room for other flows.
+const HEADROOM: u64 = 850;
+/// The number of `ProbeBW` cycles that the maximum bandwidth filter covers.
+const MAX_BW_FILTER_CYCLES: u64 = 2;
+/// How often to probe for a lower RTT.
+const PROBE_RTT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long to wait between bandwidth probes.  The draft picks a random time
+/// between 2 and 3 seconds; this uses the midpoint so that tests are repeatable.
+const BW_PROBE_WAIT: Duration = Duration::from_millis(2500);
+/// The most rounds between bandwidth probes, for coexistence with Reno.
+const MAX_BW_PROBE_ROUNDS: u64 = 63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeBw {
+    Down,
+    Cruise,
+    Refill,
+    Up,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Startup,
+    Drain,
+    ProbeBw(ProbeBw),
+    ProbeRtt,
+}
+
+impl Mode {
+    pub const fn to_qlog(self) -> &'static str {
+        match self {
+            Self::Startup => "startup",
+            Self::Drain => "drain",
+            Self::ProbeBw(ProbeBw::Down) => "probe_bw_down",
+            Self::ProbeBw(ProbeBw::Cruise) => "probe_bw_cruise",
+            Self::ProbeBw(ProbeBw::Refill) => "probe_bw_refill",
+            Self::ProbeBw(ProbeBw::Up) => "probe_bw_up",
+            Self::ProbeRtt => "probe_rtt",
+        }
+    }
+
+    /// Whether the sender is deliberately putting more data in flight to find
+    /// the limits of the path.
+    pub const fn probing(self) -> bool {
+        matches!(
+            self,
+            Self::Startup | Self::ProbeBw(ProbeBw::Refill | ProbeBw::Up)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Bbr3 {
+    pmtud: Pmtud,
+    qlog: Qlog,
+    mode: Mode,
+    congestion_window: usize,
+    /// The initial congestion window, in packets.
+    initial_cwnd: usize,
+    bytes_in_flight: usize,
+    /// The total amount delivered, from the latest rate sample.
+    delivered: usize,
+    /// The rate sample for the packets that are being acknowledged.
+    rate_sample: Option<RateSample>,
+    /// The maximum bandwidth, in bytes per second, over recent `ProbeBW` cycles.
+    max_bw: MaxBwFilter,
+    cycle_count: u64,
+    min_rtt: Option<Duration>,
+    min_rtt_stamp: Option<Instant>,
+    /// The minimum RTT over [`PROBE_RTT_INTERVAL`].
+    probe_rtt_min_delay: Option<Duration>,
+    probe_rtt_min_stamp: Option<Instant>,
+    pacing_gain: u64,
+    cwnd_gain: u64,
+
+    round_count: u64,
+    next_round_delivered: usize,
+    round_start: bool,
+    filled_pipe: bool,
+    full_bw: u64,
+    full_bw_count: usize,
+
+    cycle_stamp: Option<Instant>,
+    rounds_since_bw_probe: u64,
+
+    /// The upper bound on what is in flight, from loss and ECN when probing.
+    /// `usize::MAX` when not yet known.
+    inflight_hi: usize,
+    /// The lower bounds, from loss and ECN in the latest rounds.  These are
+    /// the maximum value of their type when not set.
+    inflight_lo: usize,
+    bw_lo: u64,
+    /// The largest delivery rate and amount delivered in the current round.
+    bw_latest: u64,
+    inflight_latest: usize,
+    /// Congestion signals in the current round.
+    delivered_at_round_start: usize,
+    lost_in_round: usize,
+    ce_in_round: bool,
+
+    probe_rtt_done_stamp: Option<Instant>,
+    probe_rtt_round_done: bool,
+    prior_cwnd: usize,
+    recovery_start: Option<packet::Number>,
+    in_recovery: bool,
+    recovery_packet: bool,
+    last_sent: packet::Number,
+}
+
+impl Display for Bbr3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BBRv3 CongCtrl [bif: {}, cwnd: {}, {:?}, bw: {}, min_rtt: {:?}]",
+            self.bytes_in_flight,
+            self.congestion_window,
+            self.mode,
+            self.bw(),
+            self.min_rtt
+        )
+    }
+}
+
+impl Bbr3 {
+    #[must_use]
+    pub fn new(pmtud: Pmtud) -> Self {
+        let cwnd = classic_cc::cwnd_initial(classic_cc::CWND_INITIAL_PKTS, pmtud.plpmtu());
+        Self {
+            pmtud,
+            qlog: Qlog::disabled(),
+            mode: Mode::Startup,
+            congestion_window: cwnd,
+            initial_cwnd: classic_cc::CWND_INITIAL_PKTS,
+            bytes_in_flight: 0,
+            delivered: 0,
+            rate_sample: None,
+            max_bw: MaxBwFilter::new(MAX_BW_FILTER_CYCLES),
+            cycle_count: 0,
+            min_rtt: None,
+            min_rtt_stamp: None,
+            probe_rtt_min_delay: None,
+            probe_rtt_min_stamp: None,
+            pacing_gain: STARTUP_PACING_GAIN,
+            cwnd_gain: STARTUP_CWND_GAIN,
+            round_count: 0,
+            next_round_delivered: 0,
+            round_start: false,
+            filled_pipe: false,
+            full_bw: 0,
+            full_bw_count: 0,
+            cycle_stamp: None,
+            rounds_since_bw_probe: 0,
+            inflight_hi: usize::MAX,
+            inflight_lo: usize::MAX,
+            bw_lo: u64::MAX,
+            bw_latest: 0,
+            inflight_latest: 0,
+            delivered_at_round_start: 0,
+            lost_in_round: 0,
+            ce_in_round: false,
+            probe_rtt_done_stamp: None,
+            probe_rtt_round_done: false,
+            prior_cwnd: 0,
+            recovery_start: None,
+            in_recovery: false,
+            recovery_packet: false,
+            last_sent: 0,
+        }
+    }
+
+    /// Start with a congestion window of `packets` packets.
+    #[must_use]
+    pub const fn with_initial_cwnd(mut self, packets: usize) -> Self {
+        self.initial_cwnd = packets;
+        self.congestion_window = classic_cc::cwnd_initial(packets, self.max_datagram_size());
+        self
+    }
+
+    const fn max_datagram_size(&self) -> usize {
+        self.pmtud.plpmtu()
+    }
+
+    /// The bandwidth estimate, which is the maximum bandwidth limited by the
+    /// lower bound from recent congestion.
+    fn bw(&self) -> u64 {
+        min(self.max_bw.get(), self.bw_lo)
+    }
+
+    /// The bandwidth-delay product, scaled by `gain`.
+    fn bdp(&self, gain: u64) -> Option<usize> {
+        let bw = self.bw();
+        let min_rtt = self.min_rtt?;
+        if bw == 0 {
+            return None;
+        }
+        let bdp = u128::from(bw) * min_rtt.as_nanos() / 1_000_000_000;
+        let bdp = bdp * u128::from(gain) / u128::from(GAIN_UNIT);
+        Some(usize::try_from(bdp).unwrap_or(usize::MAX))
+    }
+
+    fn target_inflight(&self, gain: u64) -> usize {
+        self.bdp(gain).map_or_else(
+            || classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size()),
+            |bdp| max(bdp, self.cwnd_min()),
+        )
+    }
+
+    fn inflight_with_headroom(&self) -> usize {
+        if self.inflight_hi == usize::MAX {
+            return usize::MAX;
+        }
+        let headroom = self
+            .inflight_hi
+            .saturating_mul(usize::try_from(HEADROOM).expect("fits"))
+            / usize::try_from(GAIN_UNIT).expect("fits");
+        max(headroom, self.cwnd_min())
+    }
+
+    fn set_mode(&mut self, mode: Mode, now: Instant) {
+        if self.mode == mode {
+            return;
+        }
+        qdebug!("[{self}] mode -> {mode:?}");
+        qlog::congestion_state_updated(
+            &mut self.qlog,
+            self.mode.to_qlog(),
+            mode.to_qlog(),
+            None,
+            now,
+        );
+        self.mode = mode;
+        (self.pacing_gain, self.cwnd_gain) = match mode {
+            Mode::Startup => (STARTUP_PACING_GAIN, STARTUP_CWND_GAIN),
+            Mode::Drain => (DRAIN_PACING_GAIN, STARTUP_CWND_GAIN),
+            Mode::ProbeBw(ProbeBw::Down) => (PROBE_DOWN_PACING_GAIN, CWND_GAIN),
+            Mode::ProbeBw(ProbeBw::Cruise | ProbeBw::Refill) => (GAIN_UNIT, CWND_GAIN),
+            Mode::ProbeBw(ProbeBw::Up) => (PROBE_UP_PACING_GAIN, PROBE_UP_CWND_GAIN),
+            Mode::ProbeRtt => (GAIN_UNIT, PROBE_RTT_CWND_GAIN),
+        };
+    }
+
+    const fn start_round(&mut self) {
+        self.next_round_delivered = self.delivered;
+    }
+
+    fn update_round(&mut self, sample: Option<&RateSample>) {
+        self.round_start = false;
+        if let Some(sample) = sample
+            && sample.prior_delivered >= self.next_round_delivered
+        {
+            self.start_round();
+            self.round_count += 1;
+            self.rounds_since_bw_probe += 1;
+            self.round_start = true;
+        }
+    }
+
+    fn update_max_bw(&mut self, sample: Option<&RateSample>) {
+        let Some(sample) = sample else {
+            return;
+        };
+        let Some(rate) = sample.rate else {
+            return;
+        };
+        self.bw_latest = max(self.bw_latest, rate);
+        if !sample.app_limited || rate >= self.max_bw.get() {
+            self.max_bw.update(self.cycle_count, rate);
+        }
+    }
+
+    /// At the end of each round, check for loss or ECN marks and adapt the
+    /// bounds on what is in flight.
+    fn adapt_to_congestion(&mut self, now: Instant, cc_stats: &mut CongestionControlStats) {
+        self.inflight_latest = self.delivered - self.delivered_at_round_start;
+        let total = self.lost_in_round + self.inflight_latest;
+        let too_much_loss = total > 0
+            && u128::try_from(self.lost_in_round).expect("usize fits in u128")
+                * u128::from(GAIN_UNIT)
+                > u128::try_from(total).expect("usize fits in u128") * u128::from(LOSS_THRESH);
+        // The number of CE marks isn't known here, so any mark counts.
+        let congested = too_much_loss || self.ce_in_round;
+
+        if congested {
+            if self.mode.probing() {
+                // Probing found the limit of the path.
+                let beta = self
+                    .target_inflight(GAIN_UNIT)
+                    .saturating_mul(usize::try_from(BETA).expect("fits"))
+                    / usize::try_from(GAIN_UNIT).expect("fits");
+                self.inflight_hi = max(self.inflight_latest, beta);
+                qinfo!("[{self}] inflight_hi = {}", self.inflight_hi);
+                if self.mode == Mode::Startup {
+                    self.filled_pipe = true;
+                    cc_stats.slow_start_exit_cwnd = Some(self.congestion_window);
+                    cc_stats.slow_start_exit_reason = Some(SlowStartExitReason::CongestionEvent);
+                    self.set_mode(Mode::Drain, now);
+                } else if self.mode == Mode::ProbeBw(ProbeBw::Up) {
+                    self.start_probe_down(now);
+                }
+            } else {
+                let bw_lo = if self.bw_lo == u64::MAX {
+                    self.max_bw.get()
+                } else {
+                    self.bw_lo
+                };
+                self.bw_lo = max(self.bw_latest, bw_lo.saturating_mul(BETA) / GAIN_UNIT);
+                let inflight_lo = min(self.inflight_lo, self.congestion_window);
+                self.inflight_lo = max(
+                    self.inflight_latest,
+                    inflight_lo.saturating_mul(usize::try_from(BETA).expect("fits"))
+                        / usize::try_from(GAIN_UNIT).expect("fits"),
+                );
+                qdebug!(
+                    "[{self}] lower bounds bw_lo={} inflight_lo={}",
+                    self.bw_lo,
+                    self.inflight_lo
+                );
+            }
+        }
+
+        self.delivered_at_round_start = self.delivered;
+        self.lost_in_round = 0;
+        self.ce_in_round = false;
+        self.bw_latest = 0;
+    }
+
+    const fn reset_lower_bounds(&mut self) {
+        self.bw_lo = u64::MAX;
+        self.inflight_lo = usize::MAX;
+    }
+
+    fn check_full_pipe(
+        &mut self,
+        sample: Option<&RateSample>,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        if self.filled_pipe || !self.round_start || sample.is_none_or(|s| s.app_limited) {
+            return;
+        }
+        let bw = self.max_bw.get();
+        if u128::from(bw) * u128::from(GAIN_UNIT)
+            >= u128::from(self.full_bw) * u128::from(FULL_BW_GROWTH)
+        {
+            self.full_bw = bw;
+            self.full_bw_count = 0;
+            return;
+        }
+        self.full_bw_count += 1;
+        if self.full_bw_count >= FULL_BW_ROUNDS {
+            qinfo!("[{self}] filled pipe");
+            self.filled_pipe = true;
+            cc_stats.slow_start_exit_cwnd = Some(self.congestion_window);
+            cc_stats.slow_start_exit_reason = Some(SlowStartExitReason::Heuristic);
+        }
+    }
+
+    fn check_drain(&mut self, now: Instant) {
+        if self.mode == Mode::Startup && self.filled_pipe {
+            self.set_mode(Mode::Drain, now);
+        }
+        if self.mode == Mode::Drain && self.bytes_in_flight <= self.target_inflight(GAIN_UNIT) {
+            self.start_probe_down(now);
+        }
+    }
+
+    fn start_probe_down(&mut self, now: Instant) {
+        self.cycle_count += 1;
+        self.cycle_stamp = Some(now);
+        self.rounds_since_bw_probe = 0;
+        self.start_round();
+        self.set_mode(Mode::ProbeBw(ProbeBw::Down), now);
+    }
+
+    /// Whether it is time to probe for more bandwidth, which happens after a
+    /// wall clock time or a number of rounds, whichever comes first.
+    fn time_to_probe(&self, now: Instant) -> bool {
+        if self
+            .cycle_stamp
+            .is_some_and(|t| now.saturating_duration_since(t) > BW_PROBE_WAIT)
+        {
+            return true;
+        }
+        let rounds = self.target_inflight(GAIN_UNIT) / self.max_datagram_size();
+        self.rounds_since_bw_probe
+            >= min(
+                u64::try_from(rounds).unwrap_or(u64::MAX),
+                MAX_BW_PROBE_ROUNDS,
+            )
+    }
+
+    fn update_probe_bw(&mut self, newly_acked: usize, now: Instant) {
+        let Mode::ProbeBw(phase) = self.mode else {
+            return;
+        };
+        match phase {
+            ProbeBw::Down => {
+                if self.time_to_probe(now) {
+                    self.start_refill(now);
+                } else if self.bytes_in_flight
+                    <= min(
+                        self.target_inflight(GAIN_UNIT),
+                        self.inflight_with_headroom(),
+                    )
+                {
+                    self.set_mode(Mode::ProbeBw(ProbeBw::Cruise), now);
+                }
+            }
+            ProbeBw::Cruise => {
+                if self.time_to_probe(now) {
+                    self.start_refill(now);
+                }
+            }
+            ProbeBw::Refill => {
+                // Refill the pipe for a round before probing.
+                if self.round_start {
+                    self.start_round();
+                    self.set_mode(Mode::ProbeBw(ProbeBw::Up), now);
+                }
+            }
+            ProbeBw::Up => {
+                // Raise the upper bound while it is what limits the sender.
+                if self.inflight_hi != usize::MAX
+                    && self.bytes_in_flight + newly_acked >= self.inflight_hi
+                {
+                    self.inflight_hi += newly_acked;
+                }
+                let min_rtt_passed = match (self.cycle_stamp, self.min_rtt) {
+                    (Some(t), Some(min_rtt)) => now.saturating_duration_since(t) > min_rtt,
+                    _ => true,
+                };
+                if min_rtt_passed
+                    && self.bytes_in_flight >= self.target_inflight(PROBE_UP_PACING_GAIN)
+                {
+                    self.start_probe_down(now);
+                }
+            }
+        }
+    }
+
+    fn start_refill(&mut self, now: Instant) {
+        qtrace!("[{self}] refill");
+        self.reset_lower_bounds();
+        self.rounds_since_bw_probe = 0;
+        self.start_round();
+        self.set_mode(Mode::ProbeBw(ProbeBw::Refill), now);
+    }
+
+    /// Update the minimum RTT.  Returns true if it is time to probe for a lower RTT.
+    fn update_min_rtt(&mut self, rtt: Option<Duration>, now: Instant) -> bool {
+        let expired = self
+            .probe_rtt_min_stamp
+            .is_some_and(|t| now > t + PROBE_RTT_INTERVAL);
+        if let Some(rtt) = rtt
+            && (self.probe_rtt_min_delay.is_none_or(|d| rtt < d) || expired)
+        {
+            self.probe_rtt_min_delay = Some(rtt);
+            self.probe_rtt_min_stamp = Some(now);
+        }
+        let min_rtt_expired = self
+            .min_rtt_stamp
+            .is_some_and(|t| now > t + RT_PROP_FILTER_LEN);
+        if let Some(delay) = self.probe_rtt_min_delay
+            && (self.min_rtt.is_none_or(|m| delay < m) || min_rtt_expired)
+        {
+            self.min_rtt = Some(delay);
+            self.min_rtt_stamp = self.probe_rtt_min_stamp;
+        }
+        expired
+    }
+
+    fn check_probe_rtt(&mut self, expired: bool, now: Instant) {
+        if self.mode != Mode::ProbeRtt && expired {
+            self.prior_cwnd = max(self.prior_cwnd, self.congestion_window);
+            self.probe_rtt_done_stamp = None;
+            self.set_mode(Mode::ProbeRtt, now);
+        }
+        if self.mode != Mode::ProbeRtt {
+            return;
+        }
+        match self.probe_rtt_done_stamp {
+            None if self.bytes_in_flight <= self.probe_rtt_cwnd() => {
+                self.probe_rtt_done_stamp = Some(now + PROBE_RTT_DURATION);
+                self.probe_rtt_round_done = false;
+                self.start_round();
+            }
+            Some(done) => {
+                if self.round_start {
+                    self.probe_rtt_round_done = true;
+                }
+                if self.probe_rtt_round_done && now >= done {
+                    self.probe_rtt_min_stamp = Some(now);
+                    self.reset_lower_bounds();
+                    self.congestion_window = max(self.congestion_window, self.prior_cwnd);
+                    if self.filled_pipe {
+                        self.start_probe_down(now);
+                        self.set_mode(Mode::ProbeBw(ProbeBw::Cruise), now);
+                    } else {
+                        self.set_mode(Mode::Startup, now);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn probe_rtt_cwnd(&self) -> usize {
+        self.target_inflight(PROBE_RTT_CWND_GAIN)
+    }
+
+    fn update_cwnd(&mut self, newly_acked: usize) {
+        let target = self.target_inflight(self.cwnd_gain);
+        if self.in_recovery {
+            // Packet conservation: send one packet for each one that is acknowledged.
+            self.congestion_window =
+                max(self.congestion_window, self.bytes_in_flight + newly_acked);
+        } else if self.filled_pipe {
+            self.congestion_window = min(self.congestion_window + newly_acked, target);
+        } else if self.congestion_window < target
+            || self.delivered
+                < classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size())
+        {
+            self.congestion_window += newly_acked;
+        }
+
+        // Apply the bounds from the model of the path.
+        let cap = match self.mode {
+            Mode::ProbeBw(ProbeBw::Cruise) | Mode::ProbeRtt => self.inflight_with_headroom(),
+            Mode::ProbeBw(_) => self.inflight_hi,
+            Mode::Startup | Mode::Drain => usize::MAX,
+        };
+        let cap = max(min(cap, self.inflight_lo), self.cwnd_min());
+        self.congestion_window = max(min(self.congestion_window, cap), self.cwnd_min());
+        if self.mode == Mode::ProbeRtt {
+            self.congestion_window = min(self.congestion_window, self.probe_rtt_cwnd());
+        }
+    }
+}
+
+impl CongestionController for Bbr3 {
+    fn set_qlog(&mut self, qlog: Qlog) {
+        self.pmtud.set_qlog(qlog.clone());
+        self.qlog = qlog;
+    }
+
+    fn cwnd(&self) -> usize {
+        self.congestion_window
+    }
+
+    fn bytes_in_flight(&self) -> usize {
+        self.bytes_in_flight
+    }
+
+    fn cwnd_avail(&self) -> usize {
+        self.congestion_window.saturating_sub(self.bytes_in_flight)
+    }
+
+    fn cwnd_min(&self) -> usize {
+        self.max_datagram_size() * MIN_PIPE_CWND_PKTS
+    }
+
+    fn ssthresh(&self) -> usize {
+        usize::MAX
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.in_recovery {
+            return CongestionPhase::Recovery;
+        }
+        match self.mode {
+            Mode::Startup => CongestionPhase::SlowStart,
+            Mode::Drain | Mode::ProbeBw(_) | Mode::ProbeRtt => CongestionPhase::CongestionAvoidance,
+        }
+    }
+
+    /// Whether the sender is not using the pipe.  See [`super::Bbr`].
+    fn app_limited(&self) -> bool {
+        self.bdp(GAIN_UNIT)
+            .is_some_and(|bdp| self.bytes_in_flight < bdp / 2)
+    }
+
+    fn pacing_rate(&self) -> Option<u64> {
+        let bw = self.bw();
+        (bw > 0).then(|| {
+            let gain = self.pacing_gain * (GAIN_UNIT - PACING_MARGIN) / GAIN_UNIT;
+            let rate = u128::from(bw) * u128::from(gain) / u128::from(GAIN_UNIT);
+            u64::try_from(rate).unwrap_or(u64::MAX)
+        })
+    }
+
+    #[cfg(test)]
+    fn cwnd_initial(&self) -> usize {
+        classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size())
+    }
+
+    fn pmtud(&self) -> &Pmtud {
+        &self.pmtud
+    }
+
+    fn pmtud_mut(&mut self) -> &mut Pmtud {
+        &mut self.pmtud
+    }
+
+    fn on_rate_sample(&mut self, sample: &RateSample) {
+        self.delivered = sample.delivered;
+        self.rate_sample = Some(*sample);
+    }
+
+    fn on_packets_acked(
+        &mut self,
+        acked_pkts: &[sent::Packet],
+        _rtt_est: &RttEstimate,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        cc_stats.cwnd.get_or_insert(self.congestion_window);
+
+        let mut newly_acked = 0;
+        let mut rtt = None;
+        for pkt in acked_pkts.iter().filter(|pkt| pkt.cc_outstanding()) {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            newly_acked += pkt.len();
+            if self.in_recovery && self.recovery_start.is_none_or(|pn| pkt.pn() >= pn) {
+                qdebug!("[{self}] recovery done");
+                self.in_recovery = false;
+                self.congestion_window = max(self.congestion_window, self.prior_cwnd);
+            }
+            let sample = now.saturating_duration_since(pkt.time_sent());
+            rtt = Some(rtt.map_or(sample, |r: Duration| min(r, sample)));
+        }
+        let sample = self.rate_sample.take();
+
+        self.update_round(sample.as_ref());
+        self.update_max_bw(sample.as_ref());
+        if self.round_start {
+            self.adapt_to_congestion(now, cc_stats);
+        }
+        self.check_full_pipe(sample.as_ref(), cc_stats);
+        self.check_drain(now);
+        self.update_probe_bw(newly_acked, now);
+        let probe_rtt = self.update_min_rtt(rtt, now);
+        self.check_probe_rtt(probe_rtt, now);
+        self.update_cwnd(newly_acked);
+
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.congestion_window),
+                qlog::Metric::BytesInFlight(self.bytes_in_flight),
+            ],
+            now,
+        );
+        qtrace!("[{self}] on_packets_acked, new_acked={newly_acked}");
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        first_rtt_sample_time: Option<Instant>,
+        prev_largest_acked_sent: Option<Instant>,
+        pto: Duration,
+        lost_packets: &[sent::Packet],
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        for pkt in lost_packets {
+            if pkt.cc_in_flight() {
+                self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            }
+        }
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+
+        // Lost PMTUD probes do not elicit a congestion control reaction.
+        let lost = lost_packets
+            .iter()
+            .filter(|pkt| !pkt.is_pmtud_probe())
+            .collect::<Vec<_>>();
+        let Some(last) = lost.last() else {
+            return false;
+        };
+        self.lost_in_round += lost.iter().map(|pkt| pkt.len()).sum::<usize>();
+
+        let mut reduced = false;
+        if self.recovery_start.is_none_or(|pn| last.pn() >= pn) {
+            qinfo!("[{self}] loss -> recovery");
+            if !self.in_recovery {
+                self.prior_cwnd = self.congestion_window;
+            }
+            self.in_recovery = true;
+            self.recovery_packet = true;
+            self.recovery_start = Some(self.last_sent + 1);
+            self.congestion_window = max(self.bytes_in_flight, self.cwnd_min());
+            cc_stats.congestion_events[CongestionEvent::Loss] += 1;
+            reduced = true;
+        }
+        if classic_cc::persistent_congestion(
+            first_rtt_sample_time,
+            prev_largest_acked_sent,
+            pto,
+            lost,
+        ) {
+            qinfo!("[{self}] persistent congestion");
+            cc_stats.persistent_congestion += 1;
+            self.prior_cwnd = self.cwnd_min();
+            self.congestion_window = self.cwnd_min();
+            qlog::congestion_state_updated(
+                &mut self.qlog,
+                self.mode.to_qlog(),
+                self.mode.to_qlog(),
+                Some(qlog::CongestionStateTrigger::PersistentCongestion),
+                now,
+            );
+            reduced = true;
+        }
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::CongestionWindow(self.congestion_window)],
+            now,
+        );
+        reduced
+    }
+
+    /// ECN marks don't reduce the congestion window directly.  Instead, they
+    /// bound what is in flight at the end of the round, like loss does.
+    fn on_ecn_ce_received(
+        &mut self,
+        _largest_acked_pkt: &sent::Packet,
+        _ce_marks: u64,
+        _now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        self.ce_in_round = true;
+        cc_stats.congestion_events[CongestionEvent::Ecn] += 1;
+        false
+    }
+
+    fn recovery_packet(&self) -> bool {
+        self.recovery_packet
+    }
+
+    fn discard(&mut self, pkt: &sent::Packet, now: Instant) {
+        if pkt.cc_outstanding() {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+                now,
+            );
+        }
+    }
+
+    fn on_packet_sent(&mut self, pkt: &sent::Packet, now: Instant) {
+        self.recovery_packet = false;
+        self.last_sent = pkt.pn();
+        if !pkt.cc_in_flight() {
+            return;
+        }
+        self.bytes_in_flight += pkt.len();
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+
+    fn discard_in_flight(&mut self, now: Instant) {
+        self.bytes_in_flight = 0;
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+}
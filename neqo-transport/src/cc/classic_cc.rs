@@ -15,10 +15,11 @@ use std::{
 use neqo_common::{const_max, const_min, qdebug, qinfo, qlog::Qlog, qtrace};
 use rustc_hash::FxHashMap as HashMap;
 
-use super::CongestionController;
+use super::{CongestionController, cwv::Cwv, dctcp::Dctcp, prr::Prr};
 use crate::{
     Pmtud,
-    cc::CongestionEvent,
+    cc::{CongestionEvent, CongestionPhase, IdleRestart},
+    delivery_rate::RateSample,
     packet, qlog,
     recovery::sent,
     rtt::RttEstimate,
@@ -103,6 +104,12 @@ pub trait WindowAdjustment: Display + Debug {
     ) -> (usize, usize);
     /// Cubic needs this signal to reset its epoch.
     fn on_app_limited(&mut self);
+    /// Called with a delivery rate sample for newly acknowledged packets.
+    fn on_rate_sample(&mut self, _sample: &RateSample) {}
+    /// The congestion window just before the last reduction, if this tracks it.
+    fn window_max(&self) -> Option<usize> {
+        None
+    }
     /// Store the current congestion controller state, to be recovered in the case of a spurious
     /// congestion event.
     fn save_undo_state(&mut self);
@@ -121,6 +128,9 @@ pub trait SlowStart: Display + Debug {
     /// Enables a trait implementor to track RTT rounds via the next packet numer that is to be sent
     /// out.
     fn on_packet_sent(&mut self, sent_pn: packet::Number);
+    /// Note that `delivered` bytes were acknowledged at `now`, in any phase.  The default
+    /// implementation does nothing.
+    fn on_delivered(&mut self, _delivered: usize, _now: Instant) {}
     /// Handle packets being acknowledged during slow start. Returns the congestion window in bytes
     /// that slow start should be exited with. If slow start isn't exited returns `None`.
     fn on_packets_acked(
@@ -140,6 +150,12 @@ pub trait SlowStart: Display + Debug {
     /// Resets slow start state. Is used after persistent congestion so slow start algorithms
     /// perform cleanly in non-initial slow starts.
     fn reset(&mut self) {}
+
+    /// Whether slow start is growing the congestion window more slowly because it might soon
+    /// exit.  The default implementation never does.
+    fn conservative(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
@@ -169,10 +185,10 @@ impl Display for State {
 }
 
 impl State {
-    pub const fn new(mtu: usize) -> Self {
+    pub const fn new(cwnd: usize) -> Self {
         Self {
             phase: Phase::SlowStart,
-            congestion_window: cwnd_initial(mtu),
+            congestion_window: cwnd,
             acked_bytes: 0,
             ssthresh: usize::MAX,
             recovery_start: None,
@@ -211,6 +227,17 @@ pub struct ClassicCongestionController<S, T> {
     /// - [`Self::bytes_in_flight`] is not stored because if it was to be restored it might get
     ///   out-of-sync with the actual number of bytes-in-flight on the path.
     stored: Option<State>,
+    /// The initial congestion window, in packets.
+    initial_cwnd: usize,
+    /// Proportional Rate Reduction, if enabled.  This spreads the reduction of the congestion
+    /// window over the recovery period.
+    prr: Option<Prr>,
+    /// Congestion window validation, if enabled.  This reduces a congestion window that the
+    /// sender has not been using.
+    cwv: Option<Cwv>,
+    /// The DCTCP response to ECN, if enabled.  This reduces the congestion window in proportion
+    /// to the fraction of packets that are marked CE, rather than as for a loss.
+    dctcp: Option<Dctcp>,
 }
 
 impl<S: Display, T: Display> Display for ClassicCongestionController<S, T> {
@@ -259,9 +286,44 @@ where
         self.max_datagram_size() * 2
     }
 
+    fn ssthresh(&self) -> usize {
+        self.current.ssthresh
+    }
+
+    fn window_max(&self) -> Option<usize> {
+        self.congestion_control.window_max()
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        match self.current.phase {
+            Phase::SlowStart | Phase::PersistentCongestion if self.slow_start.conservative() => {
+                CongestionPhase::ConservativeSlowStart
+            }
+            Phase::SlowStart | Phase::PersistentCongestion => CongestionPhase::SlowStart,
+            Phase::CongestionAvoidance => CongestionPhase::CongestionAvoidance,
+            Phase::RecoveryStart | Phase::Recovery => CongestionPhase::Recovery,
+        }
+    }
+
+    fn app_limited(&self) -> bool {
+        if self.bytes_in_flight >= self.current.congestion_window {
+            false
+        } else if self.current.phase.in_slow_start() {
+            // Allow for potential doubling of the congestion window during slow start.
+            // That is, the application might not have been able to send enough to respond
+            // to increases to the congestion window.
+            self.bytes_in_flight < self.current.congestion_window / 2
+        } else {
+            // We're not limited if the in-flight data is within a single burst of the
+            // congestion window.
+            (self.bytes_in_flight + self.max_datagram_size() * PACING_BURST_SIZE)
+                < self.current.congestion_window
+        }
+    }
+
     #[cfg(test)]
     fn cwnd_initial(&self) -> usize {
-        cwnd_initial(self.pmtud.plpmtu())
+        cwnd_initial(self.initial_cwnd, self.pmtud.plpmtu())
     }
 
     fn pmtud(&self) -> &Pmtud {
@@ -272,6 +334,10 @@ where
         &mut self.pmtud
     }
 
+    fn on_rate_sample(&mut self, sample: &RateSample) {
+        self.congestion_control.on_rate_sample(sample);
+    }
+
     #[expect(
         clippy::too_many_lines,
         reason = "The main congestion control function contains a lot of logic."
@@ -285,6 +351,7 @@ where
     ) {
         let mut is_app_limited = true;
         let mut new_acked = 0;
+        let mut recovery_acked = 0;
         let largest_packet_acked = acked_pkts
             .first()
             .expect("`acked_pkts.first().is_some()` is checked in `Loss::on_ack_received`");
@@ -299,6 +366,9 @@ where
         self.cleanup_maybe_lost_packets(now, rtt_est.pto(true));
 
         self.detect_spurious_congestion_event(acked_pkts, cc_stats);
+        if let Some(dctcp) = &mut self.dctcp {
+            dctcp.on_packets_acked(acked_pkts);
+        }
 
         for pkt in acked_pkts {
             qtrace!(
@@ -321,15 +391,47 @@ where
             if !self.after_recovery_start(pkt) {
                 // Do not increase congestion window for packets sent before
                 // recovery last started.
+                recovery_acked += pkt.len();
                 continue;
             }
 
             if self.current.phase.in_recovery() {
                 self.set_phase(Phase::CongestionAvoidance, None, now);
+                if self.prr.is_some() {
+                    // The congestion window reaches the slow start threshold at the end of
+                    // recovery.
+                    self.current.congestion_window = self.current.ssthresh;
+                }
             }
 
             new_acked += pkt.len();
         }
+        self.slow_start
+            .on_delivered(new_acked + recovery_acked, now);
+
+        if self.current.phase.in_recovery()
+            && let Some(prr) = &mut self.prr
+        {
+            self.current.congestion_window = prr.on_delivered(
+                recovery_acked,
+                self.bytes_in_flight,
+                self.current.ssthresh,
+                self.pmtud.plpmtu(),
+            );
+            qtrace!(
+                "[{self}] PRR delivered={recovery_acked}, cwnd={}",
+                self.current.congestion_window
+            );
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[
+                    qlog::Metric::CongestionWindow(self.current.congestion_window),
+                    qlog::Metric::BytesInFlight(self.bytes_in_flight),
+                ],
+                now,
+            );
+            return;
+        }
 
         if is_app_limited {
             self.congestion_control.on_app_limited();
@@ -345,6 +447,7 @@ where
         // Slow start: grow up to ssthresh.
         if self.current.congestion_window < self.current.ssthresh {
             // Check if the slow start algorithm wants to exit.
+            let was_conservative = self.slow_start.conservative();
             if let Some(exit_cwnd) = self.slow_start.on_packets_acked(
                 rtt_est,
                 largest_packet_acked.pn(),
@@ -358,6 +461,7 @@ where
                 cc_stats.slow_start_exit_reason = Some(SlowStartExitReason::Heuristic);
                 self.set_phase(Phase::CongestionAvoidance, None, now);
             } else {
+                self.log_conservative_change(was_conservative, now);
                 let cwnd_increase = self
                     .slow_start
                     .calc_cwnd_increase(new_acked, self.max_datagram_size());
@@ -512,9 +616,13 @@ where
     fn on_ecn_ce_received(
         &mut self,
         largest_acked_pkt: &sent::Packet,
+        ce_marks: u64,
         now: Instant,
         cc_stats: &mut CongestionControlStats,
     ) -> bool {
+        if let Some(dctcp) = &mut self.dctcp {
+            dctcp.on_ecn_ce_received(ce_marks);
+        }
         self.on_congestion_event(largest_acked_pkt, CongestionEvent::Ecn, now, cc_stats)
     }
 
@@ -553,9 +661,25 @@ where
             self.current.phase.update();
         }
 
+        if let Some(dctcp) = &mut self.dctcp {
+            dctcp.on_packet_sent(pkt.pn());
+        }
         if !pkt.cc_in_flight() {
             return;
         }
+        if self.current.phase.in_recovery()
+            && let Some(prr) = &mut self.prr
+        {
+            prr.on_packet_sent(pkt.len());
+        }
+        self.maybe_reduce_unvalidated(now);
+        if let Some(cwv) = &mut self.cwv {
+            cwv.on_packet_sent(
+                self.bytes_in_flight + pkt.len(),
+                self.current.congestion_window,
+                now,
+            );
+        }
         if !self.app_limited() {
             // Given the current non-app-limited condition, we're fully utilizing the congestion
             // window. Assume that all in-flight packets up to this one are NOT app-limited.
@@ -577,14 +701,121 @@ where
         );
     }
 
+    /// Restart after an idle period, as in RFC 2861.  The slow start threshold
+    /// keeps a record of the window that was in use before the reduction.
+    fn on_idle_restart(&mut self, restart: IdleRestart, periods: u32, now: Instant) {
+        if self.current.phase.in_recovery() {
+            return;
+        }
+        let restart_window = min(
+            cwnd_initial(self.initial_cwnd, self.max_datagram_size()),
+            self.current.congestion_window,
+        );
+        let cwnd = match restart {
+            IdleRestart::Off => return,
+            IdleRestart::Decay => max(
+                self.current
+                    .congestion_window
+                    .checked_shr(periods)
+                    .unwrap_or(0),
+                restart_window,
+            ),
+            IdleRestart::Reset => restart_window,
+        };
+        if cwnd == self.current.congestion_window {
+            return;
+        }
+        self.current.ssthresh = max(
+            self.current.ssthresh,
+            self.current.congestion_window * 3 / 4,
+        );
+        self.current.congestion_window = cwnd;
+        self.current.acked_bytes = 0;
+        if cwnd < self.current.ssthresh && !self.current.phase.in_slow_start() {
+            self.slow_start.reset();
+            self.set_phase(Phase::SlowStart, None, now);
+        }
+        qinfo!(
+            "[{self}] Restart after {periods} idle periods, cwnd {cwnd}, ssthresh {}",
+            self.current.ssthresh
+        );
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.current.congestion_window),
+                qlog::Metric::SsThresh(self.current.ssthresh),
+            ],
+            now,
+        );
+    }
+
     /// Whether a packet can be sent immediately as a result of entering recovery.
     fn recovery_packet(&self) -> bool {
         self.current.phase == Phase::RecoveryStart
     }
 }
 
-const fn cwnd_initial(mtu: usize) -> usize {
-    const_min(CWND_INITIAL_PKTS * mtu, const_max(2 * mtu, 14_720))
+/// The initial congestion window for `packets` packets of `mtu` bytes.
+///
+/// For the default of [`CWND_INITIAL_PKTS`], this is the window from RFC 9002,
+/// Section 7.2.  Other values scale the limit of 14720 bytes to match.
+pub(super) const fn cwnd_initial(packets: usize, mtu: usize) -> usize {
+    const_min(
+        packets * mtu,
+        const_max(2 * mtu, 14_720 / CWND_INITIAL_PKTS * packets),
+    )
+}
+
+/// Whether `lost_packets`, which are in order, include a contiguous run of lost packets
+/// that were sent over a period longer than the persistent congestion duration.
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc9002#section-7.6>.
+pub(super) fn persistent_congestion<'a>(
+    first_rtt_sample_time: Option<Instant>,
+    prev_largest_acked_sent: Option<Instant>,
+    pto: Duration,
+    lost_packets: impl IntoIterator<Item = &'a sent::Packet>,
+) -> bool {
+    if first_rtt_sample_time.is_none() {
+        return false;
+    }
+
+    let pc_period = pto * PERSISTENT_CONG_THRESH;
+
+    let mut last_pn = 1 << 62; // Impossibly large, but not enough to overflow.
+    let mut start = None;
+
+    // Look for the first lost packet after the previous largest acknowledged.
+    // Ignore packets that weren't ack-eliciting for the start of this range.
+    // Also, make sure to ignore any packets sent before we got an RTT estimate
+    // as we might not have sent PTO packets soon enough after those.
+    let cutoff = max(first_rtt_sample_time, prev_largest_acked_sent);
+    for p in lost_packets
+        .into_iter()
+        .skip_while(|p| Some(p.time_sent()) < cutoff)
+    {
+        if p.pn() != last_pn + 1 {
+            // Not a contiguous range of lost packets, start over.
+            start = None;
+        }
+        last_pn = p.pn();
+        if !p.cc_in_flight() {
+            // Not interesting, keep looking.
+            continue;
+        }
+        if let Some(t) = start {
+            let elapsed = p
+                .time_sent()
+                .checked_duration_since(t)
+                .expect("time is monotonic");
+            if elapsed > pc_period {
+                return true;
+            }
+        } else {
+            start = Some(p.time_sent());
+        }
+    }
+    false
 }
 
 impl<S, T> ClassicCongestionController<S, T>
@@ -593,7 +824,7 @@ where
     T: WindowAdjustment,
 {
     pub fn new(slow_start: S, congestion_control: T, pmtud: Pmtud) -> Self {
-        let mtu = pmtud.plpmtu();
+        let cwnd = cwnd_initial(CWND_INITIAL_PKTS, pmtud.plpmtu());
         Self {
             slow_start,
             congestion_control,
@@ -602,15 +833,71 @@ where
             qlog: Qlog::disabled(),
             first_app_limited: 0,
             pmtud,
-            current: State::new(mtu),
+            current: State::new(cwnd),
             stored: None,
+            initial_cwnd: CWND_INITIAL_PKTS,
+            prr: None,
+            cwv: None,
+            dctcp: None,
         }
     }
 
-    #[cfg(test)]
+    /// Start with a congestion window of `packets` packets.
     #[must_use]
-    pub const fn ssthresh(&self) -> usize {
-        self.current.ssthresh
+    pub const fn with_initial_cwnd(mut self, packets: usize) -> Self {
+        self.initial_cwnd = packets;
+        self.current.congestion_window = cwnd_initial(packets, self.pmtud.plpmtu());
+        self
+    }
+
+    /// Enable or disable Proportional Rate Reduction during recovery.
+    #[must_use]
+    pub fn with_prr(mut self, prr: bool) -> Self {
+        self.prr = prr.then(Prr::default);
+        self
+    }
+
+    /// Enable or disable congestion window validation.
+    #[must_use]
+    pub fn with_cwv(mut self, cwv: bool) -> Self {
+        self.cwv = cwv.then(Cwv::default);
+        self
+    }
+
+    /// Enable or disable the DCTCP response to ECN.
+    #[must_use]
+    pub fn with_dctcp(mut self, dctcp: bool) -> Self {
+        self.dctcp = dctcp.then(Dctcp::default);
+        self
+    }
+
+    /// Reduce a congestion window that has not been validated for a while, as in
+    /// RFC 7661, Section 4.4.
+    fn maybe_reduce_unvalidated(&mut self, now: Instant) {
+        if self.current.phase.in_recovery()
+            || !self.cwv.as_mut().is_some_and(|cwv| cwv.expired(now))
+        {
+            return;
+        }
+        let cwnd_initial = cwnd_initial(self.initial_cwnd, self.max_datagram_size());
+        self.current.ssthresh = max(
+            self.current.ssthresh,
+            self.current.congestion_window * 3 / 4,
+        );
+        self.current.congestion_window = max(self.current.congestion_window / 2, cwnd_initial);
+        self.current.acked_bytes = 0;
+        qinfo!(
+            "[{self}] Congestion window not validated, reduced to {}",
+            self.current.congestion_window
+        );
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.current.congestion_window),
+                qlog::Metric::SsThresh(self.current.ssthresh),
+            ],
+            now,
+        );
     }
 
     #[cfg(test)]
@@ -647,12 +934,12 @@ where
             return;
         }
         qdebug!("[{self}] phase -> {phase:?}");
-        let old_state = self.current.phase;
+        let old_state = self.qlog_state(self.current.phase);
         // Only emit a qlog event when a transition changes the qlog state.
-        if old_state.to_qlog() != phase.to_qlog() {
+        if old_state != phase.to_qlog() {
             qlog::congestion_state_updated(
                 &mut self.qlog,
-                old_state.to_qlog(),
+                old_state,
                 phase.to_qlog(),
                 trigger,
                 now,
@@ -661,6 +948,31 @@ where
         self.current.phase = phase;
     }
 
+    /// The qlog state for `phase`, which distinguishes the conservative part of
+    /// slow start that HyStart++ has.
+    fn qlog_state(&self, phase: Phase) -> &'static str {
+        if phase.in_slow_start() && self.slow_start.conservative() {
+            "conservative_slow_start"
+        } else {
+            phase.to_qlog()
+        }
+    }
+
+    /// Log a change between slow start and its conservative part, which happens
+    /// without a change in phase.
+    fn log_conservative_change(&mut self, was_conservative: bool, now: Instant) {
+        let conservative = self.slow_start.conservative();
+        if conservative == was_conservative || !self.current.phase.in_slow_start() {
+            return;
+        }
+        let (old_state, new_state) = if conservative {
+            ("slow_start", "conservative_slow_start")
+        } else {
+            ("conservative_slow_start", "slow_start")
+        };
+        qlog::congestion_state_updated(&mut self.qlog, old_state, new_state, None, now);
+    }
+
     // NOTE: Maybe do tracking of lost packets per congestion epoch. Right now if we get a spurious
     // event and then before the first was recovered get another (or even a real congestion event
     // because of random loss, path change, ...), it will only be detected as spurious once the old
@@ -759,67 +1071,36 @@ where
         now: Instant,
         cc_stats: &mut CongestionControlStats,
     ) -> bool {
-        if first_rtt_sample_time.is_none() {
+        if !persistent_congestion(
+            first_rtt_sample_time,
+            prev_largest_acked_sent,
+            pto,
+            lost_packets,
+        ) {
             return false;
         }
+        qinfo!("[{self}] persistent congestion");
+        cc_stats.persistent_congestion += 1;
+        self.current.congestion_window = self.cwnd_min();
+        self.current.acked_bytes = 0;
+        self.set_phase(
+            Phase::PersistentCongestion,
+            Some(qlog::CongestionStateTrigger::PersistentCongestion),
+            now,
+        );
+        // We re-enter slow start after persistent congestion, so we need to reset any
+        // state leftover from initial slow start to have it perform correctly.
+        self.slow_start.reset();
 
-        let pc_period = pto * PERSISTENT_CONG_THRESH;
-
-        let mut last_pn = 1 << 62; // Impossibly large, but not enough to overflow.
-        let mut start = None;
-
-        // Look for the first lost packet after the previous largest acknowledged.
-        // Ignore packets that weren't ack-eliciting for the start of this range.
-        // Also, make sure to ignore any packets sent before we got an RTT estimate
-        // as we might not have sent PTO packets soon enough after those.
-        let cutoff = max(first_rtt_sample_time, prev_largest_acked_sent);
-        for p in lost_packets
-            .into_iter()
-            .skip_while(|p| Some(p.time_sent()) < cutoff)
-        {
-            if p.pn() != last_pn + 1 {
-                // Not a contiguous range of lost packets, start over.
-                start = None;
-            }
-            last_pn = p.pn();
-            if !p.cc_in_flight() {
-                // Not interesting, keep looking.
-                continue;
-            }
-            if let Some(t) = start {
-                let elapsed = p
-                    .time_sent()
-                    .checked_duration_since(t)
-                    .expect("time is monotonic");
-                if elapsed > pc_period {
-                    qinfo!("[{self}] persistent congestion");
-                    self.current.congestion_window = self.cwnd_min();
-                    self.current.acked_bytes = 0;
-                    self.set_phase(
-                        Phase::PersistentCongestion,
-                        Some(qlog::CongestionStateTrigger::PersistentCongestion),
-                        now,
-                    );
-                    // We re-enter slow start after persistent congestion, so we need to reset any
-                    // state leftover from initial slow start to have it perform correctly.
-                    self.slow_start.reset();
-
-                    cc_stats.cwnd = Some(self.current.congestion_window);
-                    qlog::metrics_updated(
-                        &mut self.qlog,
-                        &[qlog::Metric::CongestionWindow(
-                            self.current.congestion_window,
-                        )],
-                        now,
-                    );
-
-                    return true;
-                }
-            } else {
-                start = Some(p.time_sent());
-            }
-        }
-        false
+        cc_stats.cwnd = Some(self.current.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::CongestionWindow(
+                self.current.congestion_window,
+            )],
+            now,
+        );
+        true
     }
 
     #[must_use]
@@ -862,13 +1143,18 @@ where
             self.congestion_control.save_undo_state();
         }
 
-        let (cwnd, acked_bytes) = self.congestion_control.reduce_cwnd(
+        let (mut cwnd, acked_bytes) = self.congestion_control.reduce_cwnd(
             self.current.congestion_window,
             self.current.acked_bytes,
             self.max_datagram_size(),
             congestion_event,
             cc_stats,
         );
+        if congestion_event == CongestionEvent::Ecn
+            && let Some(dctcp) = &self.dctcp
+        {
+            cwnd = self.current.congestion_window - dctcp.reduction(self.current.congestion_window);
+        }
         self.current.congestion_window = max(cwnd, self.cwnd_min());
         self.current.acked_bytes = acked_bytes;
         self.current.ssthresh = self.current.congestion_window;
@@ -895,27 +1181,22 @@ where
             ],
             now,
         );
+        if let Some(prr) = &mut self.prr {
+            // Rather than reducing the congestion window to the slow start threshold now, let
+            // packets out as others are delivered.
+            prr.start(self.bytes_in_flight);
+            self.current.congestion_window = prr.on_delivered(
+                0,
+                self.bytes_in_flight,
+                self.current.ssthresh,
+                self.pmtud.plpmtu(),
+            );
+        }
         let trigger =
             (congestion_event == CongestionEvent::Ecn).then_some(qlog::CongestionStateTrigger::Ecn);
         self.set_phase(Phase::RecoveryStart, trigger, now);
         true
     }
-
-    fn app_limited(&self) -> bool {
-        if self.bytes_in_flight >= self.current.congestion_window {
-            false
-        } else if self.current.phase.in_slow_start() {
-            // Allow for potential doubling of the congestion window during slow start.
-            // That is, the application might not have been able to send enough to respond
-            // to increases to the congestion window.
-            self.bytes_in_flight < self.current.congestion_window / 2
-        } else {
-            // We're not limited if the in-flight data is within a single burst of the
-            // congestion window.
-            (self.bytes_in_flight + self.max_datagram_size() * PACING_BURST_SIZE)
-                < self.current.congestion_window
-        }
-    }
 }
 
 #[cfg(test)]
@@ -932,6 +1213,7 @@ mod tests {
             CWND_INITIAL_PKTS, ClassicSlowStart, CongestionController, CongestionEvent,
             classic_cc::Phase,
             cubic::Cubic,
+            hystart::HyStart,
             new_reno::NewReno,
             tests::{RTT, make_cc_cubic, make_cc_hystart, make_cc_newreno},
         },
@@ -1592,7 +1874,7 @@ mod tests {
         assert_eq!(cc_stats.congestion_events[CongestionEvent::Ecn], 0);
 
         // Signal congestion (ECN CE) and thus change phase to recovery start.
-        cc.on_ecn_ce_received(&p_ce, now, &mut cc_stats);
+        cc.on_ecn_ce_received(&p_ce, 1, now, &mut cc_stats);
         assert_eq!(cc.cwnd(), cc.cwnd_initial() * 85 / 100);
         assert_eq!(cc.ssthresh(), cc.cwnd_initial() * 85 / 100);
         assert_eq!(cc.current.phase, Phase::RecoveryStart);
@@ -1907,7 +2189,7 @@ mod tests {
 
         match congestion_event {
             CongestionEvent::Ecn => {
-                cc.on_ecn_ce_received(&pkt1, now, &mut cc_stats);
+                cc.on_ecn_ce_received(&pkt1, 1, now, &mut cc_stats);
             }
             CongestionEvent::Loss => {
                 cc.on_packets_lost(
@@ -2087,7 +2369,7 @@ mod tests {
                 cc.max_datagram_size(),
             );
             cc.on_packet_sent(&p_ce, now);
-            cc.on_ecn_ce_received(&p_ce, now, stats);
+            cc.on_ecn_ce_received(&p_ce, 1, now, stats);
         });
     }
 
@@ -2110,4 +2392,57 @@ mod tests {
             );
         });
     }
+
+    /// Entering the conservative part of HyStart++ is logged, even though the
+    /// phase doesn't change.
+    #[test]
+    fn congestion_state_updated_conservative_slow_start() {
+        let (log, contents) = new_neqo_qlog();
+        let mut cc = make_cc_hystart(true);
+        cc.set_qlog(log);
+        let mut cc_stats = CongestionControlStats::default();
+        let n = HyStart::N_RTT_SAMPLE as u64;
+
+        // A first round at `RTT` and most of a second at twice that.
+        cc.slow_start.on_packet_sent(n);
+        for pn in 0..=n {
+            cc.slow_start
+                .on_packets_acked(&RttEstimate::new(RTT), pn, cc.cwnd(), &mut cc_stats);
+        }
+        cc.slow_start.on_packet_sent(2 * n);
+        for pn in n + 1..2 * n {
+            cc.slow_start.on_packets_acked(
+                &RttEstimate::new(RTT * 2),
+                pn,
+                cc.cwnd(),
+                &mut cc_stats,
+            );
+        }
+        assert!(!cc.slow_start.in_css());
+
+        // Acknowledging a full congestion window provides the last sample.
+        let mut pn = 2 * n;
+        let mut pkts = Vec::new();
+        while cc.bytes_in_flight() < cc.cwnd() {
+            let pkt = sent::make_packet(pn, now(), cc.max_datagram_size());
+            cc.on_packet_sent(&pkt, now());
+            pkts.push(pkt);
+            pn += 1;
+        }
+        cc.on_packets_acked(
+            &pkts,
+            &RttEstimate::new(RTT * 2),
+            now() + RTT * 2,
+            &mut cc_stats,
+        );
+        assert!(cc.slow_start.in_css());
+        drop(cc);
+
+        assert!(
+            contents
+                .to_string()
+                .contains(r#""old":"slow_start","new":"conservative_slow_start""#),
+            "Expected a transition to conservative slow start in qlog"
+        );
+    }
 }
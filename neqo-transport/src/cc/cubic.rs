@@ -466,6 +466,15 @@ impl WindowAdjustment for Cubic {
         )
     }
 
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "Cast from f64 to usize."
+    )]
+    fn window_max(&self) -> Option<usize> {
+        self.current.w_max.map(|w_max| w_max as usize)
+    }
+
     fn on_app_limited(&mut self) {
         // Reset t_epoch. Let it start again when the congestion controller
         // exits the app-limited period.
@@ -0,0 +1,46 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Congestion window validation, see <https://datatracker.ietf.org/doc/html/rfc7661>.
+
+use std::time::{Duration, Instant};
+
+/// How long the congestion window can go without being validated before it is
+/// reduced (`NVP`, the non-validated period).
+pub const NVP_DURATION: Duration = Duration::from_secs(300);
+
+/// Tracks whether the congestion window is being used.
+///
+/// The window is validated when at least half of it is in flight.  RFC 7661 uses
+/// the amount acknowledged in each round trip (`pipeACK`) for this, but the amount
+/// in flight when a packet is sent is simpler to track and serves the same purpose.
+#[derive(Debug, Default, Clone)]
+pub struct Cwv {
+    /// When the congestion window was last validated.
+    validated: Option<Instant>,
+}
+
+impl Cwv {
+    /// Note that a packet was sent, leaving `bytes_in_flight` in flight with a
+    /// congestion window of `cwnd`.
+    pub fn on_packet_sent(&mut self, bytes_in_flight: usize, cwnd: usize, now: Instant) {
+        if self.validated.is_none() || bytes_in_flight * 2 >= cwnd {
+            self.validated = Some(now);
+        }
+    }
+
+    /// Whether the congestion window has not been validated for [`NVP_DURATION`].
+    /// This starts a new period, so that the window is reduced once per period.
+    pub fn expired(&mut self, now: Instant) -> bool {
+        match self.validated {
+            Some(t) if now.saturating_duration_since(t) >= NVP_DURATION => {
+                self.validated = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The DCTCP response to ECN, see <https://datatracker.ietf.org/doc/html/rfc8257>.
+
+use neqo_common::qtrace;
+
+use crate::{packet, recovery::sent};
+
+/// The fraction of marked packets is expressed in units of `1 / ALPHA_UNIT`.
+pub const ALPHA_UNIT: u64 = 1 << 16;
+/// The gain of the moving average of the marked fraction is `2^-ALPHA_SHIFT`.
+const ALPHA_SHIFT: u32 = 4;
+
+/// Tracks the fraction of packets that are marked CE each round, so that the
+/// congestion window can be reduced in proportion to it, rather than by the
+/// same amount as for a loss.
+#[derive(Debug, Clone)]
+pub struct Dctcp {
+    /// A moving average of the fraction of packets that are marked CE each round.
+    /// This starts at [`ALPHA_UNIT`], so that the first reaction is to halve the window.
+    alpha: u64,
+    /// The round ends when a packet with this number or higher is acknowledged.
+    round_end: Option<packet::Number>,
+    acked_in_round: u64,
+    ce_in_round: u64,
+    last_sent: packet::Number,
+}
+
+impl Default for Dctcp {
+    fn default() -> Self {
+        Self {
+            alpha: ALPHA_UNIT,
+            round_end: None,
+            acked_in_round: 0,
+            ce_in_round: 0,
+            last_sent: 0,
+        }
+    }
+}
+
+impl Dctcp {
+    #[must_use]
+    pub const fn alpha(&self) -> u64 {
+        self.alpha
+    }
+
+    pub const fn on_packet_sent(&mut self, pn: packet::Number) {
+        self.last_sent = pn;
+    }
+
+    /// Count the packets that are newly acknowledged, ending the round if the
+    /// largest of them was sent after it started.
+    pub fn on_packets_acked(&mut self, acked_pkts: &[sent::Packet]) {
+        let acked = acked_pkts.iter().filter(|pkt| pkt.cc_outstanding()).count();
+        self.acked_in_round += u64::try_from(acked).unwrap_or(u64::MAX);
+        if let Some(largest) = acked_pkts.first()
+            && self.round_end.is_none_or(|pn| largest.pn() >= pn)
+        {
+            self.end_round();
+        }
+    }
+
+    pub const fn on_ecn_ce_received(&mut self, ce_marks: u64) {
+        self.ce_in_round += ce_marks;
+    }
+
+    /// How much to reduce `cwnd` by, which is `alpha / 2` of it.
+    #[must_use]
+    pub fn reduction(&self, cwnd: usize) -> usize {
+        let cwnd = u64::try_from(cwnd).unwrap_or(u64::MAX);
+        usize::try_from(cwnd * self.alpha / ALPHA_UNIT / 2).unwrap_or(0)
+    }
+
+    /// Fold the fraction of packets marked CE in the round that just ended into `alpha`.
+    fn end_round(&mut self) {
+        if self.acked_in_round > 0 {
+            let marked =
+                self.ce_in_round.min(self.acked_in_round) * ALPHA_UNIT / self.acked_in_round;
+            self.alpha = self.alpha - (self.alpha >> ALPHA_SHIFT) + (marked >> ALPHA_SHIFT);
+            qtrace!(
+                "DCTCP round marked {marked}/{ALPHA_UNIT}, alpha {}",
+                self.alpha
+            );
+        }
+        self.acked_in_round = 0;
+        self.ce_in_round = 0;
+        self.round_end = Some(self.last_sent + 1);
+    }
+}
@@ -14,8 +14,46 @@ use neqo_common::{qdebug, qtrace};
 
 use crate::{cc::classic_cc::SlowStart, packet, rtt::RttEstimate, stats::CongestionControlStats};
 
+/// The HyStart++ constants, which RFC 9406 leaves for deployments to tune.
+/// The defaults are the values that it recommends.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc9406#section-4.3>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HyStartConfig {
+    /// The smallest increase in the minimum RTT that ends slow start.
+    pub min_rtt_thresh: Duration,
+    /// The largest increase in the minimum RTT that is needed to end slow start.
+    pub max_rtt_thresh: Duration,
+    /// Between these, the increase needs to be the minimum RTT divided by this.
+    pub min_rtt_divisor: u32,
+    /// The number of RTT samples needed in a round to compare its minimum RTT.
+    pub n_rtt_sample: usize,
+    /// How much slower the congestion window grows in Conservative Slow Start.
+    pub css_growth_divisor: usize,
+    /// The number of rounds of Conservative Slow Start before congestion avoidance.
+    pub css_rounds: usize,
+}
+
+impl HyStartConfig {
+    const RECOMMENDED: Self = Self {
+        min_rtt_thresh: HyStart::MIN_RTT_THRESH,
+        max_rtt_thresh: HyStart::MAX_RTT_THRESH,
+        min_rtt_divisor: HyStart::MIN_RTT_DIVISOR,
+        n_rtt_sample: HyStart::N_RTT_SAMPLE,
+        css_growth_divisor: HyStart::CSS_GROWTH_DIVISOR,
+        css_rounds: HyStart::CSS_ROUNDS,
+    };
+}
+
+impl Default for HyStartConfig {
+    fn default() -> Self {
+        Self::RECOMMENDED
+    }
+}
+
 #[derive(Debug)]
 pub struct HyStart {
+    config: HyStartConfig,
     /// > While an arriving ACK may newly acknowledge an arbitrary number of bytes, the HyStart++
     /// > algorithm limits the number of those bytes applied to increase the cwnd to `L*SMSS`
     /// > bytes.
@@ -63,6 +101,7 @@ impl HyStart {
             Self::NON_PACED_L
         };
         Self {
+            config: HyStartConfig::RECOMMENDED,
             limit,
             last_round_min_rtt: None,
             current_round_min_rtt: None,
@@ -73,6 +112,18 @@ impl HyStart {
         }
     }
 
+    /// Use `config` in place of the constants that RFC 9406 recommends.
+    ///
+    /// # Panics
+    ///
+    /// When either of the divisors is zero.
+    #[must_use]
+    pub const fn with_config(mut self, config: HyStartConfig) -> Self {
+        assert!(config.min_rtt_divisor > 0 && config.css_growth_divisor > 0);
+        self.config = config;
+        self
+    }
+
     /// > For each arriving ACK in slow start \[...\] keep track of the minimum observed RTT:
     /// >
     /// > ```pseudo
@@ -124,7 +175,7 @@ impl HyStart {
     }
 
     const fn enough_samples(&self) -> bool {
-        self.rtt_sample_count >= Self::N_RTT_SAMPLE
+        self.rtt_sample_count >= self.config.n_rtt_sample
     }
 
     #[cfg(test)]
@@ -153,6 +204,10 @@ impl SlowStart for HyStart {
         self.maybe_start_new_round(sent_pn);
     }
 
+    fn conservative(&self) -> bool {
+        self.in_css()
+    }
+
     fn reset(&mut self) {
         self.last_round_min_rtt = None;
         self.current_round_min_rtt = None;
@@ -215,8 +270,11 @@ impl SlowStart for HyStart {
             && let Some(last) = self.last_round_min_rtt
         {
             let rtt_thresh = max(
-                Self::MIN_RTT_THRESH,
-                min(last / Self::MIN_RTT_DIVISOR, Self::MAX_RTT_THRESH),
+                self.config.min_rtt_thresh,
+                min(
+                    last / self.config.min_rtt_divisor,
+                    self.config.max_rtt_thresh,
+                ),
             );
             if current >= last + rtt_thresh {
                 self.css_baseline_min_rtt = Some(current);
@@ -250,11 +308,14 @@ impl SlowStart for HyStart {
 
             self.css_baseline_min_rtt = None;
             self.css_round_count = 0;
+            cc_stats.hystart_css_spurious_exits += 1;
         }
 
-        // Check for end of round. If `window_end` is acked it is set to `None` to indicate end of a
-        // round. [`SlowStart::on_packet_sent`] will then set it to the next packet number we send
-        // out to start a new round.
+        // Check for end of round. If `window_end` or any later packet is acked it is set to `None`
+        // to indicate end of a round. Comparing against the largest acked packet number means
+        // that a lost or reordered `window_end` doesn't stall round tracking.
+        // [`SlowStart::on_packet_sent`] will then set it to the next packet number we send out to
+        // start a new round.
         if self
             .window_end
             .is_none_or(|window_end| largest_acked < window_end)
@@ -276,11 +337,11 @@ impl SlowStart for HyStart {
         // to exit to congestion avoidance have been completed.
         self.css_round_count += 1;
         cc_stats.hystart_css_rounds_finished += 1;
-        let exit_slow_start = self.css_round_count >= Self::CSS_ROUNDS;
+        let exit_slow_start = self.css_round_count >= self.config.css_rounds;
         qdebug!(
             "HyStart: on_packets_acked -> exit={exit_slow_start} because css_rounds={} >= {}",
             self.css_round_count,
-            Self::CSS_ROUNDS
+            self.config.css_rounds
         );
         if !exit_slow_start {
             return None;
@@ -290,6 +351,7 @@ impl SlowStart for HyStart {
         // > the current cwnd.
         //
         // <https://datatracker.ietf.org/doc/html/rfc9406#section-4.2-23>
+        cc_stats.hystart_delay_exits += 1;
         Some(curr_cwnd)
     }
 
@@ -313,7 +375,7 @@ impl SlowStart for HyStart {
         //
         // <https://datatracker.ietf.org/doc/html/rfc9406#section-4.2-15>
         if self.in_css() {
-            cwnd_increase /= Self::CSS_GROWTH_DIVISOR;
+            cwnd_increase /= self.config.css_growth_divisor;
         }
         cwnd_increase
     }
@@ -0,0 +1,475 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// LEDBAT++ congestion control, for background transfers.
+//
+// See <https://datatracker.ietf.org/doc/html/draft-irtf-iccrg-ledbat-plus-plus>.
+// This yields to other traffic by keeping the queuing delay that it adds below
+// a target.  The queuing delay is the RTT less the minimum RTT, which stands in
+// for the base delay of the path.  To keep the minimum RTT accurate when other
+// LEDBAT++ flows share the bottleneck, it periodically slows down to let the
+// queue drain.
+
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{qdebug, qinfo, qlog::Qlog, qtrace};
+
+use super::{CongestionController, CongestionEvent, CongestionPhase, classic_cc};
+use crate::{
+    Pmtud, packet, qlog,
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+/// The most queuing delay that the sender aims to add.
+const TARGET: Duration = Duration::from_millis(60);
+/// The window grows by at most `1 / GAIN_DIVISOR_MAX` of what Reno would.
+const GAIN_DIVISOR_MAX: u32 = 16;
+/// The number of RTT samples that the current delay is the minimum of.
+const CURRENT_FILTER_LEN: usize = 4;
+/// The number of round trips that the window is held at its minimum for during a slowdown.
+const SLOWDOWN_RTTS: u32 = 2;
+/// The time between slowdowns, as a multiple of how long the last one took.
+const SLOWDOWN_INTERVAL: u32 = 9;
+/// The minimum congestion window, in packets.
+const MIN_CWND_PKTS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slowdown {
+    /// Slow down at this time.
+    Scheduled(Instant),
+    /// Hold the window at its minimum until `until`.
+    Holding { started: Instant, until: Instant },
+    /// Grow the window back to where it was before the slowdown that started at this time.
+    Recovering(Instant),
+}
+
+#[derive(Debug)]
+pub struct Ledbat {
+    pmtud: Pmtud,
+    qlog: Qlog,
+    congestion_window: usize,
+    /// The initial congestion window, in packets.
+    initial_cwnd: usize,
+    bytes_in_flight: usize,
+    ssthresh: usize,
+    /// Bytes acknowledged that have not yet increased the window.
+    acked_bytes: usize,
+    /// Recent RTT samples, most recent last.
+    recent_rtt: VecDeque<Duration>,
+    /// The round ends when a packet with this number or higher is acknowledged.
+    round_end: Option<packet::Number>,
+    /// `None` until slow start first exits.
+    slowdown: Option<Slowdown>,
+    /// Loss recovery ends when a packet with this number or higher is acknowledged.
+    recovery_start: Option<packet::Number>,
+    in_recovery: bool,
+    recovery_packet: bool,
+    last_sent: packet::Number,
+}
+
+impl Display for Ledbat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LEDBAT++ CongCtrl [bif: {}, cwnd: {}, ssthresh: {}, {:?}]",
+            self.bytes_in_flight, self.congestion_window, self.ssthresh, self.slowdown
+        )
+    }
+}
+
+impl Ledbat {
+    #[must_use]
+    pub fn new(pmtud: Pmtud) -> Self {
+        let cwnd = classic_cc::cwnd_initial(classic_cc::CWND_INITIAL_PKTS, pmtud.plpmtu());
+        Self {
+            pmtud,
+            qlog: Qlog::disabled(),
+            congestion_window: cwnd,
+            initial_cwnd: classic_cc::CWND_INITIAL_PKTS,
+            bytes_in_flight: 0,
+            ssthresh: usize::MAX,
+            acked_bytes: 0,
+            recent_rtt: VecDeque::with_capacity(CURRENT_FILTER_LEN),
+            round_end: None,
+            slowdown: None,
+            recovery_start: None,
+            in_recovery: false,
+            recovery_packet: false,
+            last_sent: 0,
+        }
+    }
+
+    /// Start with a congestion window of `packets` packets.
+    #[must_use]
+    pub const fn with_initial_cwnd(mut self, packets: usize) -> Self {
+        self.initial_cwnd = packets;
+        self.congestion_window = classic_cc::cwnd_initial(packets, self.max_datagram_size());
+        self
+    }
+
+    const fn max_datagram_size(&self) -> usize {
+        self.pmtud.plpmtu()
+    }
+
+    /// The queuing delay, from the most recent RTT samples.
+    fn queuing_delay(&self, rtt_est: &RttEstimate) -> Duration {
+        self.recent_rtt
+            .iter()
+            .min()
+            .map_or(Duration::ZERO, |rtt| rtt.saturating_sub(rtt_est.minimum()))
+    }
+
+    /// The window grows more slowly when the base delay is short, so that
+    /// flows with short paths don't overshoot the target.
+    fn gain_divisor(rtt_est: &RttEstimate) -> usize {
+        let base = rtt_est.minimum().as_nanos().max(1);
+        let divisor = (2 * TARGET.as_nanos()).div_ceil(base);
+        usize::try_from(divisor.clamp(1, u128::from(GAIN_DIVISOR_MAX))).expect("fits")
+    }
+
+    /// Whether the sender is still in its initial slow start.
+    const fn initial_slow_start(&self) -> bool {
+        self.ssthresh == usize::MAX
+    }
+
+    fn exit_slow_start(
+        &mut self,
+        reason: SlowStartExitReason,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        if self.initial_slow_start() {
+            cc_stats.slow_start_exit_cwnd = Some(self.congestion_window);
+            cc_stats.slow_start_exit_reason = Some(reason);
+        }
+        self.ssthresh = self.congestion_window;
+    }
+
+    /// Halve the window in response to loss or ECN, as Reno does.
+    fn on_congestion_event(
+        &mut self,
+        event: CongestionEvent,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        self.recovery_start = Some(self.last_sent + 1);
+        self.congestion_window = max(self.congestion_window / 2, self.cwnd_min());
+        self.acked_bytes = 0;
+        self.exit_slow_start(SlowStartExitReason::CongestionEvent, cc_stats);
+        cc_stats.congestion_events[event] += 1;
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.congestion_window),
+                qlog::Metric::SsThresh(self.ssthresh),
+            ],
+            now,
+        );
+    }
+
+    /// Move through the periodic slowdown, if one is due.  Returns true while
+    /// the window is being held at its minimum.
+    fn update_slowdown(&mut self, now: Instant, rtt_est: &RttEstimate) -> bool {
+        match self.slowdown {
+            // The first slowdown comes soon after slow start.
+            None if !self.initial_slow_start() => {
+                self.slowdown = Some(Slowdown::Scheduled(
+                    now + rtt_est.estimate() * SLOWDOWN_RTTS,
+                ));
+                false
+            }
+            Some(Slowdown::Scheduled(at)) if now >= at => {
+                qdebug!("[{self}] slowdown");
+                self.ssthresh = self.congestion_window;
+                self.congestion_window = self.cwnd_min();
+                self.acked_bytes = 0;
+                self.slowdown = Some(Slowdown::Holding {
+                    started: now,
+                    until: now + rtt_est.estimate() * SLOWDOWN_RTTS,
+                });
+                true
+            }
+            Some(Slowdown::Holding { started, until }) => {
+                if now < until {
+                    return true;
+                }
+                self.slowdown = Some(Slowdown::Recovering(started));
+                false
+            }
+            Some(Slowdown::Recovering(started)) if self.congestion_window >= self.ssthresh => {
+                let interval = now.saturating_duration_since(started) * SLOWDOWN_INTERVAL;
+                qtrace!("[{self}] next slowdown in {interval:?}");
+                self.slowdown = Some(Slowdown::Scheduled(now + interval));
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Reduce the window in proportion to how far the queuing delay is over the target.
+    fn on_round_end(&mut self, rtt_est: &RttEstimate, now: Instant) {
+        self.round_end = Some(self.last_sent + 1);
+        let delay = self.queuing_delay(rtt_est);
+        if delay <= TARGET || self.initial_slow_start() {
+            return;
+        }
+        let over = (delay - TARGET).as_nanos();
+        let cwnd = self.congestion_window;
+        let reduction =
+            u128::try_from(cwnd).expect("usize fits in u128") * over / TARGET.as_nanos();
+        let reduction = min(usize::try_from(reduction).unwrap_or(usize::MAX), cwnd / 2);
+        self.congestion_window = max(cwnd - reduction, self.cwnd_min());
+        self.acked_bytes = 0;
+        qdebug!("[{self}] queuing delay {delay:?} -> reduce by {reduction}");
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::CongestionWindow(self.congestion_window)],
+            now,
+        );
+    }
+}
+
+impl CongestionController for Ledbat {
+    fn set_qlog(&mut self, qlog: Qlog) {
+        self.pmtud.set_qlog(qlog.clone());
+        self.qlog = qlog;
+    }
+
+    fn cwnd(&self) -> usize {
+        self.congestion_window
+    }
+
+    fn bytes_in_flight(&self) -> usize {
+        self.bytes_in_flight
+    }
+
+    fn cwnd_avail(&self) -> usize {
+        self.congestion_window.saturating_sub(self.bytes_in_flight)
+    }
+
+    fn cwnd_min(&self) -> usize {
+        self.max_datagram_size() * MIN_CWND_PKTS
+    }
+
+    fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.in_recovery {
+            CongestionPhase::Recovery
+        } else if self.congestion_window < self.ssthresh
+            && !matches!(self.slowdown, Some(Slowdown::Holding { .. }))
+        {
+            CongestionPhase::SlowStart
+        } else {
+            CongestionPhase::CongestionAvoidance
+        }
+    }
+
+    #[cfg(test)]
+    fn cwnd_initial(&self) -> usize {
+        classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size())
+    }
+
+    fn pmtud(&self) -> &Pmtud {
+        &self.pmtud
+    }
+
+    fn pmtud_mut(&mut self) -> &mut Pmtud {
+        &mut self.pmtud
+    }
+
+    fn on_packets_acked(
+        &mut self,
+        acked_pkts: &[sent::Packet],
+        rtt_est: &RttEstimate,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        cc_stats.cwnd.get_or_insert(self.congestion_window);
+
+        let mut newly_acked = 0;
+        for pkt in acked_pkts.iter().filter(|pkt| pkt.cc_outstanding()) {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            newly_acked += pkt.len();
+            if self.in_recovery && self.recovery_start.is_none_or(|pn| pkt.pn() >= pn) {
+                qdebug!("[{self}] recovery done");
+                self.in_recovery = false;
+            }
+        }
+        if newly_acked == 0 {
+            return;
+        }
+
+        if self.recent_rtt.len() == CURRENT_FILTER_LEN {
+            self.recent_rtt.pop_front();
+        }
+        self.recent_rtt.push_back(rtt_est.latest_rtt());
+        if let Some(largest) = acked_pkts.first()
+            && self.round_end.is_none_or(|pn| largest.pn() >= pn)
+        {
+            self.on_round_end(rtt_est, now);
+        }
+
+        let holding = self.update_slowdown(now, rtt_est);
+        if !self.in_recovery && !holding {
+            let divisor = Self::gain_divisor(rtt_est);
+            if self.congestion_window < self.ssthresh {
+                if self.initial_slow_start() && self.queuing_delay(rtt_est) > TARGET * 3 / 4 {
+                    qinfo!("[{self}] queuing delay -> exit slow start");
+                    self.exit_slow_start(SlowStartExitReason::Heuristic, cc_stats);
+                } else {
+                    self.acked_bytes += newly_acked;
+                    let increase = self.acked_bytes / divisor;
+                    self.acked_bytes -= increase * divisor;
+                    self.congestion_window += increase;
+                }
+            } else if self.queuing_delay(rtt_est) <= TARGET {
+                // Additive increase of one packet per `divisor` rounds.
+                self.acked_bytes += newly_acked;
+                let threshold = self.congestion_window * divisor;
+                if self.acked_bytes >= threshold {
+                    self.acked_bytes -= threshold;
+                    self.congestion_window += self.max_datagram_size();
+                }
+            }
+        }
+
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.congestion_window),
+                qlog::Metric::BytesInFlight(self.bytes_in_flight),
+            ],
+            now,
+        );
+        qtrace!("[{self}] on_packets_acked, new_acked={newly_acked}");
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        first_rtt_sample_time: Option<Instant>,
+        prev_largest_acked_sent: Option<Instant>,
+        pto: Duration,
+        lost_packets: &[sent::Packet],
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        for pkt in lost_packets.iter().filter(|pkt| pkt.cc_in_flight()) {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+        }
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+
+        // Lost PMTUD probes do not elicit a congestion control reaction.
+        let lost = lost_packets
+            .iter()
+            .filter(|pkt| !pkt.is_pmtud_probe())
+            .collect::<Vec<_>>();
+        let Some(last) = lost.last() else {
+            return false;
+        };
+
+        let mut reduced = false;
+        if self.recovery_start.is_none_or(|pn| last.pn() >= pn) {
+            // Loss gets the same response as it does in Reno.
+            qinfo!("[{self}] loss -> recovery");
+            self.in_recovery = true;
+            self.recovery_packet = true;
+            self.on_congestion_event(CongestionEvent::Loss, now, cc_stats);
+            reduced = true;
+        }
+        if classic_cc::persistent_congestion(
+            first_rtt_sample_time,
+            prev_largest_acked_sent,
+            pto,
+            lost,
+        ) {
+            qinfo!("[{self}] persistent congestion");
+            cc_stats.persistent_congestion += 1;
+            self.congestion_window = self.cwnd_min();
+            cc_stats.cwnd = Some(self.congestion_window);
+            qlog::congestion_state_updated(
+                &mut self.qlog,
+                "recovery",
+                "recovery",
+                Some(qlog::CongestionStateTrigger::PersistentCongestion),
+                now,
+            );
+            reduced = true;
+        }
+        reduced
+    }
+
+    /// ECN marks are treated like loss, but without retransmission.
+    fn on_ecn_ce_received(
+        &mut self,
+        largest_acked_pkt: &sent::Packet,
+        _ce_marks: u64,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        if self
+            .recovery_start
+            .is_some_and(|pn| largest_acked_pkt.pn() < pn)
+        {
+            return false;
+        }
+        qinfo!("[{self}] ECN CE -> reduce");
+        self.on_congestion_event(CongestionEvent::Ecn, now, cc_stats);
+        true
+    }
+
+    fn recovery_packet(&self) -> bool {
+        self.recovery_packet
+    }
+
+    fn discard(&mut self, pkt: &sent::Packet, now: Instant) {
+        if pkt.cc_outstanding() {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+                now,
+            );
+        }
+    }
+
+    fn on_packet_sent(&mut self, pkt: &sent::Packet, now: Instant) {
+        self.recovery_packet = false;
+        self.last_sent = pkt.pn();
+        if !pkt.cc_in_flight() {
+            return;
+        }
+        self.bytes_in_flight += pkt.len();
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+
+    fn discard_in_flight(&mut self, now: Instant) {
+        self.bytes_in_flight = 0;
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+}
@@ -7,26 +7,45 @@
 // Congestion control
 
 use std::{
-    fmt::{Debug, Display},
+    fmt::{self, Debug, Display, Formatter},
+    rc::Rc,
     time::{Duration, Instant},
 };
 
 use enum_map::Enum;
-use neqo_common::qlog::Qlog;
+use neqo_common::{Buffer, Decoder, Encoder, qlog::Qlog};
 
-use crate::{Pmtud, recovery::sent, rtt::RttEstimate, stats::CongestionControlStats};
+use crate::{
+    Pmtud, delivery_rate::RateSample, recovery::sent, rtt::RttEstimate,
+    stats::CongestionControlStats,
+};
 
+mod bbr;
+mod bbr3;
 mod classic_cc;
 mod classic_slow_start;
 mod cubic;
+mod cwv;
+mod dctcp;
 mod hystart;
+mod ledbat;
 mod new_reno;
+mod prague;
+mod prr;
+mod search;
+mod westwood;
 
+pub use bbr::Bbr;
+pub use bbr3::Bbr3;
 pub use classic_cc::{CWND_INITIAL_PKTS, ClassicCongestionController, PERSISTENT_CONG_THRESH};
 pub use classic_slow_start::ClassicSlowStart;
 pub use cubic::Cubic;
-pub use hystart::HyStart;
+pub use hystart::{HyStart, HyStartConfig};
+pub use ledbat::Ledbat;
 pub use new_reno::NewReno;
+pub use prague::Prague;
+pub use search::Search;
+pub use westwood::Westwood;
 
 #[derive(Clone, Copy, PartialEq, Eq, Enum, Debug)]
 pub enum CongestionEvent {
@@ -35,6 +54,10 @@ pub enum CongestionEvent {
     Spurious,
 }
 
+/// A congestion controller, which decides how much a path can have in flight.
+///
+/// Applications can provide their own, using
+/// [`crate::ConnectionParameters::congestion_controller`].
 pub trait CongestionController: Display + Debug {
     fn set_qlog(&mut self, qlog: Qlog);
 
@@ -50,9 +73,48 @@ pub trait CongestionController: Display + Debug {
     #[must_use]
     fn cwnd_min(&self) -> usize;
 
+    /// The slow start threshold, which is `usize::MAX` until slow start first exits.
+    #[must_use]
+    fn ssthresh(&self) -> usize;
+
+    #[must_use]
+    fn phase(&self) -> CongestionPhase;
+
+    /// The congestion window just before the last reduction (`W_max`), for
+    /// controllers that track it, such as Cubic.
+    #[must_use]
+    fn window_max(&self) -> Option<usize> {
+        None
+    }
+
+    /// The rate, in bytes per second, at which the pacer should send, if the
+    /// controller sets one.  Otherwise, the pacer uses the congestion window.
+    #[must_use]
+    fn pacing_rate(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether the controller is built for L4S, in which case packets are
+    /// marked ECT(1) rather than ECT(0).
+    #[must_use]
+    fn l4s(&self) -> bool {
+        false
+    }
+
+    /// Whether the sender is not using the congestion window fully, so that
+    /// delivery rate samples for packets sent now might underestimate the path.
+    #[must_use]
+    fn app_limited(&self) -> bool {
+        false
+    }
+
+    /// The initial congestion window, for tests.  Controllers that start with
+    /// a different window override this.
     #[cfg(test)]
     #[must_use]
-    fn cwnd_initial(&self) -> usize;
+    fn cwnd_initial(&self) -> usize {
+        classic_cc::cwnd_initial(CWND_INITIAL_PKTS, self.pmtud().plpmtu())
+    }
 
     #[must_use]
     fn pmtud(&self) -> &Pmtud;
@@ -60,6 +122,10 @@ pub trait CongestionController: Display + Debug {
     #[must_use]
     fn pmtud_mut(&mut self) -> &mut Pmtud;
 
+    /// Called with a delivery rate sample for newly acknowledged packets,
+    /// before [`Self::on_packets_acked`] is called for the same packets.
+    fn on_rate_sample(&mut self, _sample: &RateSample) {}
+
     fn on_packets_acked(
         &mut self,
         acked_pkts: &[sent::Packet],
@@ -79,10 +145,12 @@ pub trait CongestionController: Display + Debug {
         cc_stats: &mut CongestionControlStats,
     ) -> bool;
 
+    /// Called when an ACK reports `ce_marks` new CE marks.
     /// Returns true if the congestion window was reduced.
     fn on_ecn_ce_received(
         &mut self,
         largest_acked_pkt: &sent::Packet,
+        ce_marks: u64,
         now: Instant,
         cc_stats: &mut CongestionControlStats,
     ) -> bool;
@@ -94,9 +162,145 @@ pub trait CongestionController: Display + Debug {
 
     fn on_packet_sent(&mut self, pkt: &sent::Packet, now: Instant);
 
+    /// Called before a packet is sent after nothing was sent for `periods`
+    /// retransmission timeouts, with nothing in flight.  Controllers that use the
+    /// congestion window can reduce it as `restart` says.
+    fn on_idle_restart(&mut self, _restart: IdleRestart, _periods: u32, _now: Instant) {}
+
     fn discard_in_flight(&mut self, now: Instant);
 }
 
+/// Makes a congestion controller for each path that a connection uses.
+/// The controller takes ownership of the path MTU discovery state.
+#[derive(Clone)]
+pub struct CongestionControllerFactory(Rc<dyn Fn(Pmtud) -> Box<dyn CongestionController>>);
+
+impl CongestionControllerFactory {
+    #[must_use]
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Pmtud) -> Box<dyn CongestionController> + 'static,
+    {
+        Self(Rc::new(f))
+    }
+
+    pub(crate) fn make(&self, pmtud: Pmtud) -> Box<dyn CongestionController> {
+        (self.0)(pmtud)
+    }
+}
+
+impl Debug for CongestionControllerFactory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("CongestionControllerFactory")
+    }
+}
+
+/// The phase that a congestion controller is in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CongestionPhase {
+    SlowStart,
+    /// The conservative slow start phase of HyStart++, which follows a possible
+    /// slow start exit.
+    ConservativeSlowStart,
+    CongestionAvoidance,
+    Recovery,
+}
+
+/// What is stopping a connection from sending more right now.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LimitingFactor {
+    /// The server is waiting for the client address to be validated.
+    Amplification,
+    /// The congestion window is full.
+    Cwnd,
+    /// The pacer is holding packets back.
+    Pacing,
+    /// The peer has not provided enough flow control credit.
+    FlowControl,
+    /// The application has not provided enough data.
+    AppLimited,
+}
+
+/// The congestion control state of the primary path, taken all at once.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CongestionSnapshot {
+    pub cwnd: usize,
+    /// The slow start threshold, which is `usize::MAX` until slow start first exits.
+    pub ssthresh: usize,
+    pub bytes_in_flight: usize,
+    pub phase: CongestionPhase,
+    /// Whether pacing is enabled.
+    pub pacing: bool,
+    /// When the pacer next allows a packet to be sent, if there is anything in flight.
+    pub next_paced: Option<Instant>,
+    pub min_rtt: Duration,
+    pub smoothed_rtt: Duration,
+    pub limit: LimitingFactor,
+}
+
+/// The congestion control state of the primary path, in a form that can be
+/// saved and restored, for use in a later connection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CongestionState {
+    pub cwnd: usize,
+    /// The slow start threshold, which is `usize::MAX` until slow start first exits.
+    pub ssthresh: usize,
+    pub bytes_in_flight: usize,
+    pub phase: CongestionPhase,
+    /// The congestion window just before the last reduction (Cubic's `W_max`),
+    /// if the congestion controller tracks it.
+    pub window_max: Option<usize>,
+}
+
+impl CongestionState {
+    /// Encode the state.  A zero value stands in for an unset slow start threshold
+    /// or a missing `W_max`, neither of which can otherwise be zero.
+    pub fn encode<B: Buffer>(&self, enc: &mut Encoder<B>) {
+        let varint = |v: usize| u64::try_from(v).expect("usize fits in u64");
+        enc.encode_varint(varint(self.cwnd));
+        enc.encode_varint(if self.ssthresh == usize::MAX {
+            0
+        } else {
+            varint(self.ssthresh)
+        });
+        enc.encode_varint(varint(self.bytes_in_flight));
+        enc.encode_byte(match self.phase {
+            CongestionPhase::SlowStart => 0,
+            CongestionPhase::ConservativeSlowStart => 1,
+            CongestionPhase::CongestionAvoidance => 2,
+            CongestionPhase::Recovery => 3,
+        });
+        enc.encode_varint(self.window_max.map_or(0, varint));
+    }
+
+    /// Decode state that was produced by [`Self::encode`].
+    /// Returns `None` if the encoding is not valid.
+    #[must_use]
+    pub fn decode(dec: &mut Decoder) -> Option<Self> {
+        fn varint(dec: &mut Decoder) -> Option<usize> {
+            dec.decode_varint().and_then(|v| usize::try_from(v).ok())
+        }
+        let cwnd = varint(dec)?;
+        let ssthresh = varint(dec)?;
+        let bytes_in_flight = varint(dec)?;
+        let phase = match dec.decode_uint::<u8>()? {
+            0 => CongestionPhase::SlowStart,
+            1 => CongestionPhase::ConservativeSlowStart,
+            2 => CongestionPhase::CongestionAvoidance,
+            3 => CongestionPhase::Recovery,
+            _ => return None,
+        };
+        let window_max = varint(dec)?;
+        Some(Self {
+            cwnd,
+            ssthresh: if ssthresh == 0 { usize::MAX } else { ssthresh },
+            bytes_in_flight,
+            phase,
+            window_max: (window_max != 0).then_some(window_max),
+        })
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, strum::EnumString, strum::VariantNames)]
 #[strum(ascii_case_insensitive)]
 pub enum CongestionControl {
@@ -105,6 +309,16 @@ pub enum CongestionControl {
     #[strum(serialize = "cubic")]
     #[default]
     Cubic,
+    #[strum(serialize = "bbr")]
+    Bbr,
+    #[strum(serialize = "bbr3")]
+    Bbr3,
+    #[strum(serialize = "prague")]
+    Prague,
+    #[strum(serialize = "ledbat")]
+    Ledbat,
+    #[strum(serialize = "westwood")]
+    Westwood,
 }
 
 #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, strum::EnumString, strum::VariantNames)]
@@ -115,6 +329,25 @@ pub enum SlowStart {
     Classic,
     #[strum(serialize = "hystart")]
     HyStart,
+    #[strum(serialize = "search")]
+    Search,
+}
+
+/// What to do with the congestion window when sending resumes after an idle period.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, strum::EnumString, strum::VariantNames)]
+#[strum(ascii_case_insensitive)]
+pub enum IdleRestart {
+    /// Keep the congestion window.
+    #[strum(serialize = "off")]
+    #[default]
+    Off,
+    /// Halve the congestion window for each retransmission timeout that passed,
+    /// but not below the initial window, as in RFC 2861.
+    #[strum(serialize = "decay")]
+    Decay,
+    /// Reduce the congestion window to the initial window, as in RFC 5681, Section 4.1.
+    #[strum(serialize = "reset")]
+    Reset,
 }
 
 #[cfg(test)]
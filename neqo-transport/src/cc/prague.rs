@@ -0,0 +1,362 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Prague congestion control, for L4S.
+//
+// See <https://datatracker.ietf.org/doc/html/draft-briscoe-iccrg-prague-congestion-control>.
+// This reduces the congestion window in proportion to the fraction of packets
+// that are marked CE, rather than halving it, so it needs a network that marks
+// early, as L4S queues do.  Packets are marked ECT(1) to identify the flow as L4S.
+
+use std::{
+    cmp::max,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{qdebug, qinfo, qlog::Qlog, qtrace};
+
+use super::{
+    CongestionController, CongestionEvent, CongestionPhase, classic_cc,
+    dctcp::{ALPHA_UNIT, Dctcp},
+};
+use crate::{
+    Pmtud, packet, qlog,
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+/// The minimum congestion window, in packets.
+const MIN_CWND_PKTS: usize = 2;
+
+#[derive(Debug)]
+pub struct Prague {
+    pmtud: Pmtud,
+    qlog: Qlog,
+    congestion_window: usize,
+    /// The initial congestion window, in packets.
+    initial_cwnd: usize,
+    bytes_in_flight: usize,
+    ssthresh: usize,
+    /// Bytes acknowledged in congestion avoidance that have not yet increased the window.
+    acked_bytes: usize,
+    /// The fraction of packets that are marked CE.
+    dctcp: Dctcp,
+    /// CE marks only reduce the window once per round; marks for packets sent
+    /// before this were already responded to.
+    cwr_end: Option<packet::Number>,
+    /// Loss recovery ends when a packet with this number or higher is acknowledged.
+    recovery_start: Option<packet::Number>,
+    in_recovery: bool,
+    recovery_packet: bool,
+    last_sent: packet::Number,
+}
+
+impl Display for Prague {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Prague CongCtrl [bif: {}, cwnd: {}, ssthresh: {}, alpha: {}/{ALPHA_UNIT}]",
+            self.bytes_in_flight,
+            self.congestion_window,
+            self.ssthresh,
+            self.dctcp.alpha()
+        )
+    }
+}
+
+impl Prague {
+    #[must_use]
+    pub fn new(pmtud: Pmtud) -> Self {
+        let cwnd = classic_cc::cwnd_initial(classic_cc::CWND_INITIAL_PKTS, pmtud.plpmtu());
+        Self {
+            pmtud,
+            qlog: Qlog::disabled(),
+            congestion_window: cwnd,
+            initial_cwnd: classic_cc::CWND_INITIAL_PKTS,
+            bytes_in_flight: 0,
+            ssthresh: usize::MAX,
+            acked_bytes: 0,
+            dctcp: Dctcp::default(),
+            cwr_end: None,
+            recovery_start: None,
+            in_recovery: false,
+            recovery_packet: false,
+            last_sent: 0,
+        }
+    }
+
+    /// Start with a congestion window of `packets` packets.
+    #[must_use]
+    pub const fn with_initial_cwnd(mut self, packets: usize) -> Self {
+        self.initial_cwnd = packets;
+        self.congestion_window = classic_cc::cwnd_initial(packets, self.max_datagram_size());
+        self
+    }
+
+    const fn max_datagram_size(&self) -> usize {
+        self.pmtud.plpmtu()
+    }
+
+    /// Reduce the window to `cwnd`, leaving slow start if necessary.
+    fn reduce(
+        &mut self,
+        cwnd: usize,
+        event: CongestionEvent,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        if self.ssthresh == usize::MAX {
+            cc_stats.slow_start_exit_cwnd = Some(max(cwnd, self.cwnd_min()));
+            cc_stats.slow_start_exit_reason = Some(SlowStartExitReason::CongestionEvent);
+        }
+        self.congestion_window = max(cwnd, self.cwnd_min());
+        self.ssthresh = self.congestion_window;
+        self.acked_bytes = 0;
+        cc_stats.congestion_events[event] += 1;
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.congestion_window),
+                qlog::Metric::SsThresh(self.ssthresh),
+            ],
+            now,
+        );
+    }
+}
+
+impl CongestionController for Prague {
+    fn set_qlog(&mut self, qlog: Qlog) {
+        self.pmtud.set_qlog(qlog.clone());
+        self.qlog = qlog;
+    }
+
+    fn cwnd(&self) -> usize {
+        self.congestion_window
+    }
+
+    fn bytes_in_flight(&self) -> usize {
+        self.bytes_in_flight
+    }
+
+    fn cwnd_avail(&self) -> usize {
+        self.congestion_window.saturating_sub(self.bytes_in_flight)
+    }
+
+    fn cwnd_min(&self) -> usize {
+        self.max_datagram_size() * MIN_CWND_PKTS
+    }
+
+    fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+
+    fn phase(&self) -> CongestionPhase {
+        if self.in_recovery {
+            CongestionPhase::Recovery
+        } else if self.congestion_window < self.ssthresh {
+            CongestionPhase::SlowStart
+        } else {
+            CongestionPhase::CongestionAvoidance
+        }
+    }
+
+    fn l4s(&self) -> bool {
+        true
+    }
+
+    #[cfg(test)]
+    fn cwnd_initial(&self) -> usize {
+        classic_cc::cwnd_initial(self.initial_cwnd, self.max_datagram_size())
+    }
+
+    fn pmtud(&self) -> &Pmtud {
+        &self.pmtud
+    }
+
+    fn pmtud_mut(&mut self) -> &mut Pmtud {
+        &mut self.pmtud
+    }
+
+    fn on_packets_acked(
+        &mut self,
+        acked_pkts: &[sent::Packet],
+        _rtt_est: &RttEstimate,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) {
+        cc_stats.cwnd.get_or_insert(self.congestion_window);
+
+        self.dctcp.on_packets_acked(acked_pkts);
+        let mut newly_acked = 0;
+        for pkt in acked_pkts.iter().filter(|pkt| pkt.cc_outstanding()) {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            newly_acked += pkt.len();
+            if self.in_recovery && self.recovery_start.is_none_or(|pn| pkt.pn() >= pn) {
+                qdebug!("[{self}] recovery done");
+                self.in_recovery = false;
+            }
+        }
+        if !self.in_recovery && newly_acked > 0 {
+            if self.congestion_window < self.ssthresh {
+                self.congestion_window += newly_acked;
+            } else {
+                // Additive increase of one packet per round.
+                self.acked_bytes += newly_acked;
+                if self.acked_bytes >= self.congestion_window {
+                    self.acked_bytes -= self.congestion_window;
+                    self.congestion_window += self.max_datagram_size();
+                }
+            }
+        }
+
+        cc_stats.cwnd = Some(self.congestion_window);
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[
+                qlog::Metric::CongestionWindow(self.congestion_window),
+                qlog::Metric::BytesInFlight(self.bytes_in_flight),
+            ],
+            now,
+        );
+        qtrace!("[{self}] on_packets_acked, new_acked={newly_acked}");
+    }
+
+    fn on_packets_lost(
+        &mut self,
+        first_rtt_sample_time: Option<Instant>,
+        prev_largest_acked_sent: Option<Instant>,
+        pto: Duration,
+        lost_packets: &[sent::Packet],
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        for pkt in lost_packets.iter().filter(|pkt| pkt.cc_in_flight()) {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+        }
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+
+        // Lost PMTUD probes do not elicit a congestion control reaction.
+        let lost = lost_packets
+            .iter()
+            .filter(|pkt| !pkt.is_pmtud_probe())
+            .collect::<Vec<_>>();
+        let Some(last) = lost.last() else {
+            return false;
+        };
+
+        let mut reduced = false;
+        if self.recovery_start.is_none_or(|pn| last.pn() >= pn) {
+            // Loss gets the same response as it does in Reno.
+            qinfo!("[{self}] loss -> recovery");
+            self.in_recovery = true;
+            self.recovery_packet = true;
+            self.recovery_start = Some(self.last_sent + 1);
+            self.cwr_end = self.recovery_start;
+            self.reduce(
+                self.congestion_window / 2,
+                CongestionEvent::Loss,
+                now,
+                cc_stats,
+            );
+            reduced = true;
+        }
+        if classic_cc::persistent_congestion(
+            first_rtt_sample_time,
+            prev_largest_acked_sent,
+            pto,
+            lost,
+        ) {
+            qinfo!("[{self}] persistent congestion");
+            cc_stats.persistent_congestion += 1;
+            self.congestion_window = self.cwnd_min();
+            cc_stats.cwnd = Some(self.congestion_window);
+            qlog::congestion_state_updated(
+                &mut self.qlog,
+                "recovery",
+                "recovery",
+                Some(qlog::CongestionStateTrigger::PersistentCongestion),
+                now,
+            );
+            reduced = true;
+        }
+        reduced
+    }
+
+    /// Reduce the window by `alpha / 2` at most once per round, where `alpha`
+    /// tracks the fraction of packets that are marked.
+    fn on_ecn_ce_received(
+        &mut self,
+        largest_acked_pkt: &sent::Packet,
+        ce_marks: u64,
+        now: Instant,
+        cc_stats: &mut CongestionControlStats,
+    ) -> bool {
+        self.dctcp.on_ecn_ce_received(ce_marks);
+        if self.cwr_end.is_some_and(|pn| largest_acked_pkt.pn() < pn) {
+            return false;
+        }
+        let reduction = self.dctcp.reduction(self.congestion_window);
+        if reduction == 0 {
+            // Marks are too rare to make a difference to the window.
+            return false;
+        }
+        self.cwr_end = Some(self.last_sent + 1);
+        qdebug!("[{self}] CE -> reduce by {reduction}");
+        self.reduce(
+            self.congestion_window - reduction,
+            CongestionEvent::Ecn,
+            now,
+            cc_stats,
+        );
+        true
+    }
+
+    fn recovery_packet(&self) -> bool {
+        self.recovery_packet
+    }
+
+    fn discard(&mut self, pkt: &sent::Packet, now: Instant) {
+        if pkt.cc_outstanding() {
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(pkt.len());
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+                now,
+            );
+        }
+    }
+
+    fn on_packet_sent(&mut self, pkt: &sent::Packet, now: Instant) {
+        self.recovery_packet = false;
+        self.last_sent = pkt.pn();
+        self.dctcp.on_packet_sent(pkt.pn());
+        if !pkt.cc_in_flight() {
+            return;
+        }
+        self.bytes_in_flight += pkt.len();
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+
+    fn discard_in_flight(&mut self, now: Instant) {
+        self.bytes_in_flight = 0;
+        qlog::metrics_updated(
+            &mut self.qlog,
+            &[qlog::Metric::BytesInFlight(self.bytes_in_flight)],
+            now,
+        );
+    }
+}
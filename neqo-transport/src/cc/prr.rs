@@ -0,0 +1,61 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Proportional Rate Reduction, see <https://datatracker.ietf.org/doc/html/rfc6937>.
+
+use std::cmp::{max, min};
+
+/// The state of Proportional Rate Reduction during a recovery period.
+///
+/// Rather than reducing the congestion window all at once on a congestion event,
+/// PRR lets the sender send in proportion to what is delivered, so that the amount
+/// in flight reaches the slow start threshold at the end of recovery.
+#[derive(Debug, Default, Clone)]
+pub struct Prr {
+    /// The bytes in flight at the start of recovery (`RecoverFS`).
+    recover_fs: usize,
+    /// The bytes delivered since the start of recovery (`prr_delivered`).
+    delivered: usize,
+    /// The bytes sent since the start of recovery (`prr_out`).
+    out: usize,
+}
+
+impl Prr {
+    /// Start a recovery period with `flight_size` bytes in flight.
+    pub fn start(&mut self, flight_size: usize) {
+        self.recover_fs = flight_size;
+        self.delivered = 0;
+        self.out = 0;
+    }
+
+    pub fn on_packet_sent(&mut self, len: usize) {
+        self.out += len;
+    }
+
+    /// Account for `delivered` bytes being newly delivered, when `pipe` bytes remain
+    /// in flight.  Returns the congestion window to use, which allows for sending
+    /// as much as PRR permits.
+    pub fn on_delivered(
+        &mut self,
+        delivered: usize,
+        pipe: usize,
+        ssthresh: usize,
+        max_datagram_size: usize,
+    ) -> usize {
+        self.delivered += delivered;
+        let sndcnt = if pipe > ssthresh {
+            // Proportional rate reduction.
+            (self.delivered * ssthresh)
+                .div_ceil(max(self.recover_fs, 1))
+                .saturating_sub(self.out)
+        } else {
+            // Slow start reduction bound.
+            let limit = max(self.delivered.saturating_sub(self.out), delivered) + max_datagram_size;
+            min(ssthresh - pipe, limit)
+        };
+        pipe + sndcnt
+    }
+}
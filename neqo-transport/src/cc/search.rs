@@ -0,0 +1,155 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{qdebug, qtrace};
+
+use crate::{cc::classic_cc::SlowStart, packet, rtt::RttEstimate, stats::CongestionControlStats};
+
+/// Slow start that exits when the bytes delivered stop growing as fast as slow
+/// start sends them (SEARCH).
+///
+/// During slow start, the amount delivered in any period should be twice what was
+/// delivered one round trip earlier, which is what was sent in that period.  When
+/// the bottleneck is full, it falls short, so SEARCH exits slow start when the
+/// shortfall exceeds a threshold.  Unlike HyStart++, this doesn't depend on the
+/// RTT increasing, which it might not do on paths with a large BDP.
+///
+/// <https://datatracker.ietf.org/doc/html/draft-chung-ccwg-search-07>
+#[derive(Debug, Default)]
+pub struct Search {
+    /// The total number of bytes delivered.
+    delivered: usize,
+    /// The duration of each bin, which is set from the first RTT sample.
+    bin_duration: Option<Duration>,
+    /// When the current bin ends.
+    bin_end: Option<Instant>,
+    /// The number of bins since the current sequence of bins started.
+    bin_idx: usize,
+    /// A ring of the total delivered at the end of each bin.
+    bins: [usize; Self::NUM_BINS],
+}
+
+impl Display for Search {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SEARCH")
+    }
+}
+
+impl Search {
+    /// The number of bins in the window that delivery is measured over.
+    pub const W: usize = 10;
+
+    /// The number of bins kept beyond those in the window, which limits how many
+    /// bins a round trip can span.
+    pub const EXTRA_BINS: usize = 15;
+
+    const NUM_BINS: usize = Self::W + Self::EXTRA_BINS + 1;
+
+    /// The window is this many tenths of the initial RTT.
+    pub const WINDOW_RTT_TENTHS: u32 = 35;
+
+    /// The shortfall, in percent of what is expected, at which slow start exits.
+    pub const THRESH_PERCENT: usize = 35;
+
+    const fn bin(&self, idx: usize) -> usize {
+        self.bins[idx % Self::NUM_BINS]
+    }
+
+    /// Delivered in the `W` bins up to `idx`.
+    const fn window(&self, idx: usize) -> usize {
+        self.bin(idx) - self.bin(idx - Self::W)
+    }
+
+    /// Move on to the bin that contains `now`, filling in any that were skipped.
+    fn update_bins(&mut self, now: Instant) {
+        let Some(bin_duration) = self.bin_duration else {
+            return;
+        };
+        let passed = self.bin_end.map(|end| {
+            if now <= end {
+                0
+            } else {
+                let passed = (now - end).as_nanos() / bin_duration.as_nanos().max(1) + 1;
+                u32::try_from(passed).unwrap_or(u32::MAX)
+            }
+        });
+        match passed {
+            Some(0) => {}
+            Some(passed) if passed as usize <= Self::NUM_BINS => {
+                let previous = self.bin(self.bin_idx);
+                for i in 1..passed as usize {
+                    self.bins[(self.bin_idx + i) % Self::NUM_BINS] = previous;
+                }
+                self.bin_idx += passed as usize;
+                self.bins[self.bin_idx % Self::NUM_BINS] = self.delivered;
+                self.bin_end = self.bin_end.map(|end| end + bin_duration * passed);
+            }
+            _ => {
+                // Start over, either at the start or after a gap longer than
+                // the bins cover.
+                self.bin_end = Some(now + bin_duration);
+                self.bin_idx = 0;
+                self.bins[0] = self.delivered;
+            }
+        }
+    }
+}
+
+impl SlowStart for Search {
+    fn on_packet_sent(&mut self, _sent_pn: packet::Number) {}
+
+    fn on_delivered(&mut self, delivered: usize, now: Instant) {
+        self.delivered += delivered;
+        self.update_bins(now);
+    }
+
+    fn reset(&mut self) {
+        self.bin_end = None;
+    }
+
+    fn on_packets_acked(
+        &mut self,
+        rtt_est: &RttEstimate,
+        _largest_acked: packet::Number,
+        curr_cwnd: usize,
+        _cc_stats: &mut CongestionControlStats,
+    ) -> Option<usize> {
+        let Some(bin_duration) = self.bin_duration else {
+            self.bin_duration = Some(rtt_est.minimum() * Self::WINDOW_RTT_TENTHS / 10 / 10);
+            return None;
+        };
+        let rtt_bins = usize::try_from(
+            rtt_est
+                .estimate()
+                .as_nanos()
+                .div_ceil(bin_duration.as_nanos().max(1)),
+        )
+        .unwrap_or(usize::MAX);
+        if self.bin_end.is_none()
+            || rtt_bins > Self::EXTRA_BINS
+            || self.bin_idx < Self::W + rtt_bins
+        {
+            return None;
+        }
+
+        // What was sent in the window one RTT ago is what should be delivered
+        // in the current window, and slow start sent twice what was delivered.
+        let delivered = self.window(self.bin_idx);
+        let expected = 2 * self.window(self.bin_idx - rtt_bins);
+        let shortfall = expected.saturating_sub(delivered);
+        qtrace!("[{self}] delivered={delivered} expected={expected}");
+        if expected == 0 || shortfall * 100 < expected * Self::THRESH_PERCENT {
+            return None;
+        }
+        qdebug!("[{self}] Exit slow start, delivered={delivered} expected={expected}");
+        Some(curr_cwnd)
+    }
+}
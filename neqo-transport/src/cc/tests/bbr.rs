@@ -0,0 +1,242 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use neqo_common::qlog::Qlog;
+use test_fixture::now;
+
+use super::{IP_ADDR, MTU, RTT};
+use crate::{
+    Pmtud,
+    cc::{Bbr, CongestionController, CongestionEvent, CongestionPhase},
+    delivery_rate::DeliveryRate,
+    packet,
+    recovery::{self, sent},
+    rtt::{RttEstimate, RttSource},
+    stats::CongestionControlStats,
+};
+
+const PTO: Duration = RTT;
+
+fn make_cc_bbr() -> Bbr {
+    Bbr::new(Pmtud::new(IP_ADDR, MTU))
+}
+
+pub fn packet(pn: packet::Number, len: usize, t: Instant) -> sent::Packet {
+    sent::Packet::new(
+        packet::Type::Short,
+        pn,
+        t,
+        true,
+        recovery::Tokens::new(),
+        len,
+    )
+}
+
+/// How long it takes to send `len` bytes at `rate` bytes per second.
+fn tx_time(len: usize, rate: u64) -> Duration {
+    Duration::from_nanos(u64::try_from(len).unwrap() * 1_000_000_000 / rate)
+}
+
+/// Send a packet, recording its delivery state as `PacketSender` does.
+pub fn send(
+    cc: &mut dyn CongestionController,
+    delivery: &mut DeliveryRate,
+    mut p: sent::Packet,
+    t: Instant,
+) -> sent::Packet {
+    delivery.on_packet_sent(&mut p, cc.bytes_in_flight(), cc.app_limited());
+    cc.on_packet_sent(&p, t);
+    p
+}
+
+/// Acknowledge packets, passing the rate sample to the congestion controller first.
+pub fn ack(
+    cc: &mut dyn CongestionController,
+    delivery: &mut DeliveryRate,
+    acked: &[sent::Packet],
+    rtt_est: &RttEstimate,
+    t: Instant,
+    cc_stats: &mut CongestionControlStats,
+) {
+    if let Some(sample) = delivery.on_packets_acked(acked, rtt_est.minimum(), t) {
+        cc.on_rate_sample(&sample);
+    }
+    cc.on_packets_acked(acked, rtt_est, t, cc_stats);
+}
+
+/// Run a sender over a path with a bottleneck of `rate` bytes per second and a
+/// minimum RTT of [`RTT`], for `duration`.  Each packet is acknowledged as it arrives.
+pub fn run(cc: &mut dyn CongestionController, rate: u64, duration: Duration) {
+    let mss = cc.pmtud().plpmtu();
+    let mut delivery = DeliveryRate::default();
+    let mut rtt_est = RttEstimate::new(RTT);
+    let mut cc_stats = CongestionControlStats::default();
+    let start = now();
+    let mut t = start;
+    let mut pn = 0;
+    let mut link_free = start;
+    let mut next_send = start;
+    let mut in_flight = VecDeque::<(Instant, sent::Packet)>::new();
+    while t < start + duration {
+        if t >= next_send && cc.cwnd_avail() >= mss {
+            let p = send(cc, &mut delivery, packet(pn, mss, t), t);
+            pn += 1;
+            link_free = max(link_free, t) + tx_time(mss, rate);
+            in_flight.push_back((link_free + RTT, p));
+            next_send = cc.pacing_rate().map_or(t, |r| t + tx_time(mss, r));
+            continue;
+        }
+
+        let ack_at = in_flight.front().map(|(a, _)| *a);
+        let send_at = (cc.cwnd_avail() >= mss).then_some(next_send);
+        t = match (ack_at, send_at) {
+            (Some(a), Some(s)) => min(a, s),
+            (Some(a), None) => a,
+            (None, Some(s)) => s,
+            (None, None) => panic!("nothing to do"),
+        };
+
+        let mut acked = Vec::new();
+        while in_flight.front().is_some_and(|(a, _)| *a <= t) {
+            acked.push(in_flight.pop_front().unwrap().1);
+        }
+        if let Some(last) = acked.last() {
+            let sample = t.duration_since(last.time_sent());
+            rtt_est.update(
+                &mut Qlog::disabled(),
+                sample,
+                Duration::ZERO,
+                RttSource::AckConfirmed,
+                t,
+            );
+            // Largest first.
+            acked.reverse();
+            ack(cc, &mut delivery, &acked, &rtt_est, t, &mut cc_stats);
+        }
+    }
+}
+
+#[test]
+fn startup() {
+    let mut cc = make_cc_bbr();
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    assert_eq!(cc.cwnd(), cc.cwnd_initial());
+    assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+    assert_eq!(cc.ssthresh(), usize::MAX);
+    assert_eq!(cc.pacing_rate(), None);
+
+    let mut delivery = DeliveryRate::default();
+    let pkts = (0..4)
+        .map(|pn| send(&mut cc, &mut delivery, packet(pn, mss, now()), now()))
+        .collect::<Vec<_>>();
+    let rtt_est = RttEstimate::new(RTT);
+    ack(
+        &mut cc,
+        &mut delivery,
+        &pkts,
+        &rtt_est,
+        now() + RTT,
+        &mut cc_stats,
+    );
+    // Startup grows the window by the amount acknowledged.
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() + 4 * mss);
+    assert_eq!(cc.bytes_in_flight(), 0);
+    // A whole round trip was measured, so there is a bandwidth estimate.
+    assert!(cc.pacing_rate().is_some());
+}
+
+/// BBR finds the bottleneck bandwidth, leaves startup, and settles on a
+/// congestion window of twice the bandwidth-delay product.
+#[test]
+fn steady_state() {
+    const RATE: u64 = 1_000_000;
+    let mut cc = make_cc_bbr();
+    run(&mut cc, RATE, Duration::from_secs(5));
+
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+    let pacing_rate = cc.pacing_rate().unwrap();
+    assert!(
+        (RATE * 7 / 10..=RATE * 14 / 10).contains(&pacing_rate),
+        "pacing rate {pacing_rate}"
+    );
+    let bdp = usize::try_from(RATE).unwrap() * usize::try_from(RTT.as_millis()).unwrap() / 1000;
+    assert!(
+        (bdp * 3 / 2..=bdp * 5 / 2).contains(&cc.cwnd()),
+        "cwnd {} bdp {bdp}",
+        cc.cwnd()
+    );
+}
+
+#[test]
+fn loss_recovery() {
+    let mut cc = make_cc_bbr();
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    let mut pkts = (0..10).map(|pn| packet(pn, mss, now())).collect::<Vec<_>>();
+    for p in &pkts {
+        cc.on_packet_sent(p, now());
+    }
+
+    let t = now() + RTT;
+    let mut lost = pkts.remove(0);
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, PTO, &[lost], t, &mut cc_stats));
+    assert_eq!(cc.phase(), CongestionPhase::Recovery);
+    // Packet conservation: the window is what is still in flight.
+    assert_eq!(cc.cwnd(), 9 * mss);
+    assert!(cc.recovery_packet());
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Loss], 1);
+
+    let p = packet(10, mss, t);
+    cc.on_packet_sent(&p, t);
+    assert!(!cc.recovery_packet());
+
+    // Acknowledging a packet that was sent before recovery started doesn't end it.
+    let rtt_est = RttEstimate::new(RTT);
+    cc.on_packets_acked(&pkts[..1], &rtt_est, t, &mut cc_stats);
+    assert_eq!(cc.phase(), CongestionPhase::Recovery);
+
+    // A packet sent during recovery does, and the window is restored.
+    cc.on_packets_acked(&[p], &rtt_est, t + RTT, &mut cc_stats);
+    assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+    assert!(cc.cwnd() >= cc.cwnd_initial());
+}
+
+#[test]
+fn persistent_congestion() {
+    let mut cc = make_cc_bbr();
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    let lost = (0..5)
+        .map(|pn| {
+            let t = now() + PTO * u32::try_from(pn).unwrap();
+            let mut p = packet(pn, mss, t);
+            cc.on_packet_sent(&p, t);
+            p.declare_lost(t, sent::LossTrigger::TimeThreshold);
+            p
+        })
+        .collect::<Vec<_>>();
+    let t = now() + PTO * 5;
+    assert!(cc.on_packets_lost(Some(now()), None, PTO, &lost, t, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_min());
+}
+
+#[test]
+fn ecn_ce_ignored() {
+    let mut cc = make_cc_bbr();
+    let mut cc_stats = CongestionControlStats::default();
+    let p = packet(0, cc.pmtud().plpmtu(), now());
+    cc.on_packet_sent(&p, now());
+    assert!(!cc.on_ecn_ce_received(&p, 1, now(), &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_initial());
+}
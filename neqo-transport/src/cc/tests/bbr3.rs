@@ -0,0 +1,86 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Duration;
+
+use test_fixture::now;
+
+use super::{
+    IP_ADDR, MTU, RTT,
+    bbr::{ack, packet, run, send},
+};
+use crate::{
+    Pmtud,
+    cc::{Bbr3, CongestionController as _, CongestionEvent, CongestionPhase},
+    delivery_rate::DeliveryRate,
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+fn make_cc_bbr3() -> Bbr3 {
+    Bbr3::new(Pmtud::new(IP_ADDR, MTU))
+}
+
+/// BBRv3 finds the bottleneck bandwidth and leaves startup.
+#[test]
+fn steady_state() {
+    const RATE: u64 = 1_000_000;
+    let mut cc = make_cc_bbr3();
+    run(&mut cc, RATE, Duration::from_secs(4));
+
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+    let pacing_rate = cc.pacing_rate().unwrap();
+    assert!(
+        (RATE * 7 / 10..=RATE * 13 / 10).contains(&pacing_rate),
+        "pacing rate {pacing_rate}"
+    );
+    let bdp = usize::try_from(RATE).unwrap() * usize::try_from(RTT.as_millis()).unwrap() / 1000;
+    assert!(
+        (bdp..=bdp * 5 / 2).contains(&cc.cwnd()),
+        "cwnd {} bdp {bdp}",
+        cc.cwnd()
+    );
+}
+
+/// Loss above the threshold in a round ends startup.
+#[test]
+fn loss_ends_startup() {
+    let mut cc = make_cc_bbr3();
+    let mut cc_stats = CongestionControlStats::default();
+    let mut delivery = DeliveryRate::default();
+    let mss = cc.pmtud().plpmtu();
+    let mut pkts = (0..10)
+        .map(|pn| send(&mut cc, &mut delivery, packet(pn, mss, now()), now()))
+        .collect::<Vec<_>>();
+
+    let t = now() + RTT;
+    let mut lost = pkts.remove(0);
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, RTT, &[lost], t, &mut cc_stats));
+    assert_eq!(cc_stats.slow_start_exit_reason, None);
+
+    pkts.reverse();
+    let rtt_est = RttEstimate::new(RTT);
+    ack(&mut cc, &mut delivery, &pkts, &rtt_est, t, &mut cc_stats);
+    assert_eq!(
+        cc_stats.slow_start_exit_reason,
+        Some(SlowStartExitReason::CongestionEvent)
+    );
+    assert_eq!(cc.phase(), CongestionPhase::Recovery);
+}
+
+/// ECN marks are counted, but don't reduce the congestion window immediately.
+#[test]
+fn ecn_ce() {
+    let mut cc = make_cc_bbr3();
+    let mut cc_stats = CongestionControlStats::default();
+    let p = packet(0, cc.pmtud().plpmtu(), now());
+    cc.on_packet_sent(&p, now());
+    assert!(!cc.on_ecn_ce_received(&p, 1, now(), &mut cc_stats));
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Ecn], 1);
+    assert_eq!(cc.cwnd(), cc.cwnd_initial());
+}
@@ -106,7 +106,7 @@ fn ecn_ce(
     cc_stats: &mut CongestionControlStats,
 ) {
     let pkt = sent::make_packet(pn, now, cc.max_datagram_size());
-    cc.on_ecn_ce_received(&pkt, now, cc_stats);
+    cc.on_ecn_ce_received(&pkt, 1, now, cc_stats);
 }
 
 fn expected_tcp_acks(cwnd_rtt_start: usize, mtu: usize) -> u64 {
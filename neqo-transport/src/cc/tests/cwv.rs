@@ -0,0 +1,64 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Instant;
+
+use test_fixture::now;
+
+use super::{RTT, bbr::packet, make_cc_newreno};
+use crate::{
+    cc::{
+        ClassicSlowStart, CongestionController as _, classic_cc::ClassicCongestionController,
+        cwv::NVP_DURATION, new_reno::NewReno,
+    },
+    rtt::RttEstimate,
+    stats::CongestionControlStats,
+};
+
+/// Fill the initial congestion window and have it all acknowledged, which
+/// doubles it in slow start.  Returns the time of the acknowledgment.
+fn grow(cc: &mut ClassicCongestionController<ClassicSlowStart, NewReno>) -> Instant {
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.max_datagram_size();
+    let pkts = (0..10).map(|pn| packet(pn, mss, now())).collect::<Vec<_>>();
+    for p in &pkts {
+        cc.on_packet_sent(p, now());
+    }
+    let t = now() + RTT;
+    cc.on_packets_acked(&pkts, &RttEstimate::new(RTT), t, &mut cc_stats);
+    assert_eq!(cc.cwnd(), 2 * cc.cwnd_initial());
+    t
+}
+
+/// A congestion window that isn't used is halved after the non-validated period.
+#[test]
+fn reduced() {
+    let mut cc = make_cc_newreno().with_cwv(true);
+    let t = grow(&mut cc);
+    let mss = cc.max_datagram_size();
+    let cwnd = cc.cwnd();
+
+    // Sending a little doesn't validate the window.
+    cc.on_packet_sent(&packet(10, mss, t), t);
+    assert_eq!(cc.cwnd(), cwnd);
+
+    cc.on_packet_sent(&packet(11, mss, now() + NVP_DURATION), now() + NVP_DURATION);
+    assert_eq!(cc.cwnd(), cwnd / 2);
+    // The slow start threshold is only ever raised, so it stays unset.
+    assert_eq!(cc.ssthresh(), usize::MAX);
+}
+
+/// Without congestion window validation, the window is kept.
+#[test]
+fn disabled() {
+    let mut cc = make_cc_newreno().with_cwv(false);
+    grow(&mut cc);
+    let mss = cc.max_datagram_size();
+    let cwnd = cc.cwnd();
+
+    cc.on_packet_sent(&packet(10, mss, now() + NVP_DURATION), now() + NVP_DURATION);
+    assert_eq!(cc.cwnd(), cwnd);
+}
@@ -0,0 +1,77 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Instant;
+
+use test_fixture::now;
+
+use super::{RTT, bbr::packet, make_cc_cubic, make_cc_newreno};
+use crate::{cc::CongestionController, rtt::RttEstimate, stats::CongestionControlStats};
+
+/// Have `rounds` packets acknowledged, one each round trip, without any marks.
+/// Returns the time of the last acknowledgment.
+fn unmarked_rounds<C: CongestionController>(cc: &mut C, rounds: u64) -> Instant {
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    let rtt_est = RttEstimate::new(RTT);
+    let mut t = now();
+    for pn in 0..rounds {
+        let p = packet(pn, mss, t);
+        cc.on_packet_sent(&p, t);
+        t += RTT;
+        cc.on_packets_acked(&[p], &rtt_est, t, &mut cc_stats);
+    }
+    t
+}
+
+/// Send a packet at `t` and have it marked CE.
+fn mark<C: CongestionController>(cc: &mut C, pn: u64, t: Instant) {
+    let p = packet(pn, cc.pmtud().plpmtu(), t);
+    cc.on_packet_sent(&p, t);
+    assert!(cc.on_ecn_ce_received(&p, 1, t + RTT, &mut CongestionControlStats::default()));
+}
+
+/// Until there is a history of marks, the first CE mark halves the window.
+#[test]
+fn first_ce_halves() {
+    let mut cc = make_cc_newreno().with_dctcp(true);
+    mark(&mut cc, 0, now());
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() / 2);
+}
+
+/// Rounds without marks make the response to the next mark smaller.
+#[test]
+fn response_proportional_to_marks() {
+    let mut cc = make_cc_newreno().with_dctcp(true);
+    let t = unmarked_rounds(&mut cc, 16);
+    let before = cc.cwnd();
+    mark(&mut cc, 16, t);
+    assert!(
+        (before * 3 / 4..before).contains(&cc.cwnd()),
+        "cwnd {} before {before}",
+        cc.cwnd()
+    );
+}
+
+/// Cubic reduces by the same proportion.
+#[test]
+fn cubic() {
+    let mut cc = make_cc_cubic().with_dctcp(true);
+    let t = unmarked_rounds(&mut cc, 16);
+    let before = cc.cwnd();
+    mark(&mut cc, 16, t);
+    assert!((before * 3 / 4..before).contains(&cc.cwnd()));
+}
+
+/// Without the DCTCP response, a mark halves the window regardless of history.
+#[test]
+fn disabled() {
+    let mut cc = make_cc_newreno();
+    let t = unmarked_rounds(&mut cc, 16);
+    let before = cc.cwnd();
+    mark(&mut cc, 16, t);
+    assert_eq!(cc.cwnd(), before / 2);
+}
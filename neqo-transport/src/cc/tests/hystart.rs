@@ -14,7 +14,8 @@ use test_fixture::now;
 use super::make_cc_hystart;
 use crate::{
     cc::{
-        CWND_INITIAL_PKTS, CongestionController as _, classic_cc::SlowStart as _, hystart::HyStart,
+        CWND_INITIAL_PKTS, CongestionController as _, HyStartConfig, classic_cc::SlowStart as _,
+        hystart::HyStart,
     },
     packet::MIN_INITIAL_PACKET_SIZE,
     recovery::sent,
@@ -135,6 +136,42 @@ fn round_tracking_lifecycle() {
     );
 }
 
+/// Tests that a round ends when a packet sent after `window_end` is acked, even if `window_end`
+/// itself is lost or its ACK arrives out of order.
+#[test]
+fn round_tracking_lost_or_reordered_window_end() {
+    let mut hystart = make_hystart_paced();
+    let mut cc_stats = CongestionControlStats::default();
+
+    let window_end = 10;
+    hystart.on_packet_sent(window_end);
+    hystart.on_packet_sent(11);
+    hystart.on_packet_sent(12);
+
+    // `window_end` is lost, but a later packet is acked. The round ends.
+    hystart.on_packets_acked(&RttEstimate::new(BASE_RTT), 12, INITIAL_CWND, &mut cc_stats);
+    assert!(
+        hystart.window_end().is_none(),
+        "Round should end when a packet after window_end is acked"
+    );
+
+    let window_end2 = 20;
+    hystart.on_packet_sent(window_end2);
+
+    // Late ACKs for packets from the previous round don't end the new round.
+    for pn in [9, window_end, 11] {
+        hystart.on_packets_acked(&RttEstimate::new(BASE_RTT), pn, INITIAL_CWND, &mut cc_stats);
+        assert_eq!(
+            hystart.window_end(),
+            Some(window_end2),
+            "Reordered ACKs from the previous round should not end the round"
+        );
+    }
+
+    hystart.on_packets_acked(&RttEstimate::new(BASE_RTT), 21, INITIAL_CWND, &mut cc_stats);
+    assert!(hystart.window_end().is_none());
+}
+
 /// Tests that `current_round_min_rtt` is tracked correctly when packets are acked.
 #[test]
 fn rtt_sample_collection_tracks_minimum() {
@@ -347,6 +384,48 @@ fn css_growth_rate_is_one_quarter() {
     );
 }
 
+#[test]
+fn config_rtt_thresh() {
+    let mut hystart = make_hystart_paced().with_config(HyStartConfig {
+        min_rtt_thresh: Duration::from_millis(30),
+        max_rtt_thresh: Duration::from_millis(50),
+        ..HyStartConfig::default()
+    });
+    let mut cc_stats = CongestionControlStats::default();
+
+    // rtt_thresh = max(30ms, min(100ms / 8, 50ms)) = 30ms
+    // Since 120ms < 100ms + 30ms, CSS should not be entered
+    maybe_enter_css(&mut hystart, BASE_RTT, HIGH_RTT, &mut cc_stats);
+
+    assert!(
+        !hystart.in_css(),
+        "CSS should not be entered below the configured threshold"
+    );
+    assert_eq!(cc_stats.hystart_css_entries, 0);
+}
+
+#[test]
+fn config_css_growth_divisor() {
+    const NEW_ACKED: usize = 4 * MIN_INITIAL_PACKET_SIZE;
+    let mut hystart = make_hystart_paced().with_config(HyStartConfig {
+        css_growth_divisor: 2,
+        ..HyStartConfig::default()
+    });
+
+    maybe_enter_css(
+        &mut hystart,
+        BASE_RTT,
+        HIGH_RTT,
+        &mut CongestionControlStats::default(),
+    );
+    assert!(hystart.in_css(), "Should have entered CSS");
+
+    assert_eq!(
+        hystart.calc_cwnd_increase(NEW_ACKED, MIN_INITIAL_PACKET_SIZE),
+        NEW_ACKED / 2
+    );
+}
+
 #[test]
 fn css_exit_after_n_rounds() {
     let mut hystart = make_hystart_paced();
@@ -395,6 +474,8 @@ fn css_exit_after_n_rounds() {
                 HyStart::CSS_ROUNDS
             );
             assert_eq!(cc_stats.hystart_css_rounds_finished, HyStart::CSS_ROUNDS);
+            assert_eq!(cc_stats.hystart_delay_exits, 1);
+            assert_eq!(cc_stats.hystart_css_spurious_exits, 0);
         }
     }
 }
@@ -432,6 +513,8 @@ fn css_back_to_slow_start_on_rtt_decrease() {
         0,
         "CSS round count should be reset"
     );
+    assert_eq!(cc_stats.hystart_css_spurious_exits, 1);
+    assert_eq!(cc_stats.hystart_delay_exits, 0);
 }
 
 #[test]
@@ -597,6 +680,7 @@ fn integration_full_slow_start_to_css_to_ca() {
                 Some(SlowStartExitReason::Heuristic)
             );
             assert_eq!(stats.hystart_css_rounds_finished, HyStart::CSS_ROUNDS);
+            assert_eq!(stats.hystart_delay_exits, 1);
             break;
         }
 
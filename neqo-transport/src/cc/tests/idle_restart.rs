@@ -0,0 +1,97 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::Instant;
+
+use test_fixture::now;
+
+use super::{RTT, bbr::packet, make_cc_newreno};
+use crate::{
+    cc::{
+        ClassicSlowStart, CongestionController as _, CongestionPhase, IdleRestart,
+        classic_cc::ClassicCongestionController, new_reno::NewReno,
+    },
+    rtt::RttEstimate,
+    stats::CongestionControlStats,
+};
+
+/// Grow the congestion window to four times the initial window, where slow start
+/// exits.  Returns the time of the last acknowledgment, after which nothing is in flight.
+fn grow(cc: &mut ClassicCongestionController<ClassicSlowStart, NewReno>) -> Instant {
+    let mut cc_stats = CongestionControlStats::default();
+    cc.set_ssthresh(4 * cc.cwnd_initial());
+    let mss = cc.max_datagram_size();
+    let mut pn = 0;
+    let mut t = now();
+    while cc.cwnd() < 4 * cc.cwnd_initial() {
+        let count = u64::try_from(cc.cwnd() / mss).unwrap();
+        let pkts = (pn..pn + count)
+            .map(|pn| packet(pn, mss, t))
+            .collect::<Vec<_>>();
+        for p in &pkts {
+            cc.on_packet_sent(p, t);
+        }
+        pn += count;
+        t += RTT;
+        cc.on_packets_acked(&pkts, &RttEstimate::new(RTT), t, &mut cc_stats);
+    }
+    assert_eq!(cc.cwnd(), 4 * cc.cwnd_initial());
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+    assert_eq!(cc.bytes_in_flight(), 0);
+    t
+}
+
+/// The window is halved for each period that passed.
+#[test]
+fn decay() {
+    let mut cc = make_cc_newreno();
+    let t = grow(&mut cc);
+    let cwnd = cc.cwnd();
+
+    cc.on_idle_restart(IdleRestart::Decay, 1, t);
+    assert_eq!(cc.cwnd(), cwnd / 2);
+    // The slow start threshold is only ever raised, so slow start can get back
+    // to where the window was.
+    assert_eq!(cc.ssthresh(), cwnd);
+    assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+}
+
+/// Decay doesn't reduce the window below the initial window.
+#[test]
+fn decay_to_initial() {
+    let mut cc = make_cc_newreno();
+    let t = grow(&mut cc);
+
+    cc.on_idle_restart(IdleRestart::Decay, 40, t);
+    assert_eq!(cc.cwnd(), cc.cwnd_initial());
+}
+
+/// Reset goes straight to the initial window.
+#[test]
+fn reset() {
+    let mut cc = make_cc_newreno();
+    let t = grow(&mut cc);
+    let cwnd = cc.cwnd();
+
+    cc.on_idle_restart(IdleRestart::Reset, 1, t);
+    assert_eq!(cc.cwnd(), cc.cwnd_initial());
+    assert_eq!(cc.ssthresh(), cwnd);
+    assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+}
+
+/// Without a restart policy, the window is kept.
+#[test]
+fn off() {
+    let mut cc = make_cc_newreno();
+    let t = grow(&mut cc);
+    let cwnd = cc.cwnd();
+    let ssthresh = cc.ssthresh();
+
+    cc.on_idle_restart(IdleRestart::Off, 10, t);
+    assert_eq!(cc.cwnd(), cwnd);
+    assert_eq!(cc.ssthresh(), ssthresh);
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+}
@@ -0,0 +1,150 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::{Duration, Instant};
+
+use neqo_common::qlog::Qlog;
+use test_fixture::now;
+
+use super::{
+    IP_ADDR, MTU, RTT,
+    bbr::{packet, run},
+};
+use crate::{
+    Pmtud,
+    cc::{CongestionController as _, CongestionEvent, CongestionPhase, Ledbat},
+    packet,
+    recovery::sent,
+    rtt::{RttEstimate, RttSource},
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+fn make_cc_ledbat() -> Ledbat {
+    Ledbat::new(Pmtud::new(IP_ADDR, MTU))
+}
+
+/// Send a packet at `t` and acknowledge it `rtt` later.  Returns when it was acknowledged.
+fn round_trip(
+    cc: &mut Ledbat,
+    rtt_est: &mut RttEstimate,
+    pn: packet::Number,
+    t: Instant,
+    rtt: Duration,
+    cc_stats: &mut CongestionControlStats,
+) -> Instant {
+    let p = packet(pn, cc.pmtud().plpmtu(), t);
+    cc.on_packet_sent(&p, t);
+    let t = t + rtt;
+    rtt_est.update(
+        &mut Qlog::disabled(),
+        rtt,
+        Duration::ZERO,
+        RttSource::AckConfirmed,
+        t,
+    );
+    cc.on_packets_acked(&[p], rtt_est, t, cc_stats);
+    t
+}
+
+/// Slow start ends when the queuing delay gets close to the target.
+#[test]
+fn slow_start_exits_on_delay() {
+    let mut cc = make_cc_ledbat();
+    let mut cc_stats = CongestionControlStats::default();
+    let mut rtt_est = RttEstimate::new(RTT);
+    let mut t = now();
+    for pn in 0..4 {
+        t = round_trip(&mut cc, &mut rtt_est, pn, t, RTT, &mut cc_stats);
+    }
+    assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+    assert!(cc.cwnd() > cc.cwnd_initial());
+
+    // Delay doesn't count until it has persisted for a few samples.
+    let delayed = RTT + Duration::from_millis(50);
+    for pn in 4..7 {
+        t = round_trip(&mut cc, &mut rtt_est, pn, t, delayed, &mut cc_stats);
+        assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+    }
+    round_trip(&mut cc, &mut rtt_est, 7, t, delayed, &mut cc_stats);
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+    assert_eq!(cc.ssthresh(), cc.cwnd());
+    assert_eq!(
+        cc_stats.slow_start_exit_reason,
+        Some(SlowStartExitReason::Heuristic)
+    );
+}
+
+/// After slow start, the sender periodically drops to the minimum window to
+/// let the queue drain, then grows back.
+#[test]
+fn slowdown() {
+    let mut cc = make_cc_ledbat();
+    let mut cc_stats = CongestionControlStats::default();
+    let mut rtt_est = RttEstimate::new(RTT);
+    let mut t = round_trip(&mut cc, &mut rtt_est, 0, now(), RTT, &mut cc_stats);
+    let mut pn = 1;
+    while cc.phase() == CongestionPhase::SlowStart {
+        t = round_trip(&mut cc, &mut rtt_est, pn, t, RTT * 3 / 2, &mut cc_stats);
+        pn += 1;
+    }
+
+    let rounds = (0..10)
+        .position(|_| {
+            t = round_trip(&mut cc, &mut rtt_est, pn, t, RTT, &mut cc_stats);
+            pn += 1;
+            cc.cwnd() == cc.cwnd_min()
+        })
+        .unwrap();
+    assert!(rounds > 0);
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+
+    (0..10)
+        .position(|_| {
+            t = round_trip(&mut cc, &mut rtt_est, pn, t, RTT, &mut cc_stats);
+            pn += 1;
+            cc.cwnd() > cc.cwnd_min()
+        })
+        .unwrap();
+    assert_eq!(cc.phase(), CongestionPhase::SlowStart);
+}
+
+/// On a path with a deep queue, the window stays close to the bandwidth-delay
+/// product, rather than filling the queue.
+#[test]
+fn yields() {
+    const RATE: u64 = 1_000_000;
+    let mut cc = make_cc_ledbat();
+    run(&mut cc, RATE, Duration::from_secs(10));
+
+    assert_ne!(cc.ssthresh(), usize::MAX);
+    let bdp = usize::try_from(RATE).unwrap() * usize::try_from(RTT.as_millis()).unwrap() / 1000;
+    assert!(cc.cwnd() <= bdp * 2, "cwnd {} bdp {bdp}", cc.cwnd());
+}
+
+/// Loss halves the window, like Reno.
+#[test]
+fn loss() {
+    let mut cc = make_cc_ledbat();
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    let mut pkts = (0..4).map(|pn| packet(pn, mss, now())).collect::<Vec<_>>();
+    for p in &pkts {
+        cc.on_packet_sent(p, now());
+    }
+
+    let t = now() + RTT;
+    let mut lost = pkts.remove(0);
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, RTT, &[lost], t, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() / 2);
+    assert_eq!(cc.ssthresh(), cc.cwnd());
+    assert_eq!(cc.phase(), CongestionPhase::Recovery);
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Loss], 1);
+    assert_eq!(
+        cc_stats.slow_start_exit_reason,
+        Some(SlowStartExitReason::CongestionEvent)
+    );
+}
@@ -17,9 +17,20 @@ use crate::{
     },
 };
 
+mod bbr;
+mod bbr3;
 mod cubic;
+mod cwv;
+mod dctcp;
 mod hystart;
+mod idle_restart;
+mod ledbat;
 mod new_reno;
+mod prague;
+mod prr;
+mod replay;
+mod search;
+mod westwood;
 
 pub const IP_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
 pub const MTU: Option<usize> = Some(1_500);
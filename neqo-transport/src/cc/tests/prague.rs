@@ -0,0 +1,135 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use test_fixture::now;
+
+use super::{IP_ADDR, MTU, RTT, bbr::packet};
+use crate::{
+    Pmtud,
+    cc::{CongestionController as _, CongestionEvent, CongestionPhase, Prague},
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::{CongestionControlStats, SlowStartExitReason},
+};
+
+fn make_cc_prague() -> Prague {
+    Prague::new(Pmtud::new(IP_ADDR, MTU))
+}
+
+#[test]
+fn l4s() {
+    assert!(make_cc_prague().l4s());
+}
+
+/// Until there is a history of marks, the first CE mark halves the window.
+/// Further marks in the same round don't reduce it again.
+#[test]
+fn first_ce_halves() {
+    let mut cc = make_cc_prague();
+    let mut cc_stats = CongestionControlStats::default();
+    let p = packet(0, cc.pmtud().plpmtu(), now());
+    cc.on_packet_sent(&p, now());
+
+    assert!(cc.on_ecn_ce_received(&p, 1, now() + RTT, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() / 2);
+    assert_eq!(cc.ssthresh(), cc.cwnd());
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Ecn], 1);
+    assert_eq!(
+        cc_stats.slow_start_exit_reason,
+        Some(SlowStartExitReason::CongestionEvent)
+    );
+    assert_eq!(cc_stats.slow_start_exit_cwnd, Some(cc.cwnd()));
+
+    assert!(!cc.on_ecn_ce_received(&p, 1, now() + RTT, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() / 2);
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Ecn], 1);
+}
+
+/// Rounds without marks make the response to the next mark smaller.
+#[test]
+fn response_proportional_to_marks() {
+    let mut cc = make_cc_prague();
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    let rtt_est = RttEstimate::new(RTT);
+    let mut t = now();
+    for pn in 0..16 {
+        let p = packet(pn, mss, t);
+        cc.on_packet_sent(&p, t);
+        t += RTT;
+        cc.on_packets_acked(&[p], &rtt_est, t, &mut cc_stats);
+    }
+
+    let before = cc.cwnd();
+    let p = packet(16, mss, t);
+    cc.on_packet_sent(&p, t);
+    assert!(cc.on_ecn_ce_received(&p, 1, t + RTT, &mut cc_stats));
+    assert!(
+        (before * 3 / 4..before).contains(&cc.cwnd()),
+        "cwnd {} before {before}",
+        cc.cwnd()
+    );
+}
+
+/// A mark that is too rare to reduce the window has no other effect either.
+#[test]
+fn ce_without_reduction() {
+    let mut cc = make_cc_prague();
+    let mut cc_stats = CongestionControlStats::default();
+    let rtt_est = RttEstimate::new(RTT);
+    let mut t = now();
+
+    // Halve the window with a loss, so that it is small.
+    let mut lost = packet(0, 1, t);
+    cc.on_packet_sent(&lost, t);
+    t += RTT;
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, RTT, &[lost], t, &mut cc_stats));
+
+    // Many rounds without marks make alpha as small as it gets.  Tiny
+    // packets keep the window from growing.
+    for pn in 1..200 {
+        let p = packet(pn, 1, t);
+        cc.on_packet_sent(&p, t);
+        t += RTT;
+        cc.on_packets_acked(&[p], &rtt_est, t, &mut cc_stats);
+    }
+
+    let cwnd = cc.cwnd();
+    let ssthresh = cc.ssthresh();
+    let p = packet(200, 1, t);
+    cc.on_packet_sent(&p, t);
+    assert!(!cc.on_ecn_ce_received(&p, 1, t + RTT, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cwnd);
+    assert_eq!(cc.ssthresh(), ssthresh);
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Ecn], 0);
+}
+
+/// Loss halves the window, like Reno.
+#[test]
+fn loss() {
+    let mut cc = make_cc_prague();
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.pmtud().plpmtu();
+    let mut pkts = (0..4).map(|pn| packet(pn, mss, now())).collect::<Vec<_>>();
+    for p in &pkts {
+        cc.on_packet_sent(p, now());
+    }
+
+    let t = now() + RTT;
+    let mut lost = pkts.remove(0);
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, RTT, &[lost], t, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() / 2);
+    assert_eq!(cc.phase(), CongestionPhase::Recovery);
+    assert!(cc.recovery_packet());
+    assert_eq!(cc_stats.congestion_events[CongestionEvent::Loss], 1);
+
+    // A CE mark for a packet sent before the loss doesn't reduce the window further.
+    assert!(!cc.on_ecn_ce_received(&pkts[0], 1, t, &mut cc_stats));
+    assert_eq!(cc.cwnd(), cc.cwnd_initial() / 2);
+}
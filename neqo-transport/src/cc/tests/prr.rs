@@ -0,0 +1,81 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use test_fixture::now;
+
+use super::{RTT, bbr::packet, make_cc_newreno};
+use crate::{
+    cc::{CongestionController as _, CongestionPhase},
+    recovery::sent,
+    rtt::RttEstimate,
+    stats::CongestionControlStats,
+};
+
+/// With PRR, packets are sent in proportion to those delivered during recovery,
+/// rather than stopping until enough have been delivered to get below the slow
+/// start threshold.
+#[test]
+fn proportional() {
+    let mut cc = make_cc_newreno().with_prr(true);
+    let mut cc_stats = CongestionControlStats::default();
+    let rtt_est = RttEstimate::new(RTT);
+    let mss = cc.max_datagram_size();
+    let mut pkts = (0..10).map(|pn| packet(pn, mss, now())).collect::<Vec<_>>();
+    for p in &pkts {
+        cc.on_packet_sent(p, now());
+    }
+
+    let t = now() + RTT;
+    let mut lost = pkts.remove(0);
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, RTT, &[lost], t, &mut cc_stats));
+    assert_eq!(cc.ssthresh(), 5 * mss);
+    // Nothing can be sent until something is delivered.
+    assert_eq!(cc.cwnd(), 9 * mss);
+    assert_eq!(cc.cwnd_avail(), 0);
+
+    let mut pn = 10;
+    let mut sent = Vec::new();
+    for (i, p) in pkts.iter().enumerate() {
+        cc.on_packets_acked(std::slice::from_ref(p), &rtt_est, t, &mut cc_stats);
+        assert_eq!(cc.phase(), CongestionPhase::Recovery);
+        while cc.cwnd_avail() >= mss {
+            let p = packet(pn, mss, t);
+            pn += 1;
+            cc.on_packet_sent(&p, t);
+            sent.push(p);
+        }
+        // The first packet isn't sent until two have been delivered.
+        assert_eq!(sent.is_empty(), i == 0);
+    }
+    // Over the recovery period, the amount in flight reaches the slow start threshold.
+    assert_eq!(sent.len(), 5);
+    assert_eq!(cc.bytes_in_flight(), cc.ssthresh());
+
+    // Recovery ends with the congestion window at the slow start threshold.
+    cc.on_packets_acked(&sent[..1], &rtt_est, t + RTT, &mut cc_stats);
+    assert_eq!(cc.phase(), CongestionPhase::CongestionAvoidance);
+    assert_eq!(cc.cwnd(), cc.ssthresh());
+}
+
+/// Without PRR, the congestion window is reduced immediately.
+#[test]
+fn disabled() {
+    let mut cc = make_cc_newreno().with_prr(false);
+    let mut cc_stats = CongestionControlStats::default();
+    let mss = cc.max_datagram_size();
+    let mut pkts = (0..10).map(|pn| packet(pn, mss, now())).collect::<Vec<_>>();
+    for p in &pkts {
+        cc.on_packet_sent(p, now());
+    }
+
+    let t = now() + RTT;
+    let mut lost = pkts.remove(0);
+    lost.declare_lost(t, sent::LossTrigger::TimeThreshold);
+    assert!(cc.on_packets_lost(Some(now()), None, RTT, &[lost], t, &mut cc_stats));
+    assert_eq!(cc.cwnd(), 5 * mss);
+    assert_eq!(cc.cwnd_avail(), 0);
+}
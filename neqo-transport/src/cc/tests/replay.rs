@@ -0,0 +1,300 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Replay the sent, acknowledged, and lost packets from a qlog trace against
+//! a congestion controller, so that problems seen in a recorded trace can be
+//! reproduced in a test without the rest of the connection.
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use neqo_common::qlog::Qlog;
+use qlog::{
+    events::{
+        EventData,
+        quic::{PacketLostTrigger, PacketNumberSpace, PacketType},
+    },
+    reader::{Event, QlogSeqReader},
+};
+use test_fixture::{EXPECTED_LOG_HEADER, now};
+
+use super::{RTT, make_cc_newreno};
+use crate::{
+    cc::CongestionController,
+    packet,
+    recovery::{self, sent},
+    rtt::{RttEstimate, RttSource},
+    stats::CongestionControlStats,
+};
+
+/// One event from a trace that matters to congestion control.
+#[derive(Debug)]
+enum Step {
+    Sent {
+        pt: packet::Type,
+        pn: packet::Number,
+        len: usize,
+    },
+    Acked {
+        space: PacketNumberSpace,
+        pns: Vec<packet::Number>,
+    },
+    Lost {
+        pt: packet::Type,
+        pn: packet::Number,
+        trigger: sent::LossTrigger,
+    },
+    Rtt(Duration),
+    /// The congestion window that the trace recorded.
+    Cwnd(usize),
+}
+
+const fn packet_type(t: &PacketType) -> Option<packet::Type> {
+    match t {
+        PacketType::Initial => Some(packet::Type::Initial),
+        PacketType::Handshake => Some(packet::Type::Handshake),
+        PacketType::ZeroRtt => Some(packet::Type::ZeroRtt),
+        PacketType::OneRtt => Some(packet::Type::Short),
+        _ => None,
+    }
+}
+
+/// Identifies a sent packet, using the packet number space and packet number.
+type Key = (u8, packet::Number);
+
+const fn key(space: &PacketNumberSpace, pn: packet::Number) -> Key {
+    match space {
+        PacketNumberSpace::Initial => (0, pn),
+        PacketNumberSpace::Handshake => (1, pn),
+        PacketNumberSpace::ApplicationData => (2, pn),
+    }
+}
+
+const fn space(pt: packet::Type) -> PacketNumberSpace {
+    match pt {
+        packet::Type::Initial => PacketNumberSpace::Initial,
+        packet::Type::Handshake => PacketNumberSpace::Handshake,
+        _ => PacketNumberSpace::ApplicationData,
+    }
+}
+
+fn ms(v: f32) -> Duration {
+    Duration::from_secs_f64(f64::from(v) / 1000.0)
+}
+
+/// The congestion control events from a qlog trace, in order.
+#[derive(Debug, Default)]
+pub struct Trace {
+    steps: Vec<(Duration, Step)>,
+}
+
+impl Trace {
+    /// Read a JSON-SEQ qlog trace, as written by neqo.
+    pub fn parse(log: &str) -> Self {
+        let reader = QlogSeqReader::new(Box::new(log.as_bytes())).unwrap();
+        let mut trace = Self::default();
+        for event in reader {
+            let Event::Qlog(event) = event else {
+                continue;
+            };
+            let t = ms(event.time);
+            match event.data {
+                EventData::PacketSent(ps) => {
+                    if let (Some(pt), Some(pn), Some(len)) = (
+                        packet_type(&ps.header.packet_type),
+                        ps.header.packet_number,
+                        ps.raw.and_then(|raw| raw.length),
+                    ) {
+                        let len = usize::try_from(len).unwrap();
+                        trace.steps.push((t, Step::Sent { pt, pn, len }));
+                    }
+                }
+                EventData::PacketsAcked(pa) => {
+                    if let (Some(space), Some(pns)) = (pa.packet_number_space, pa.packet_numbers) {
+                        trace.steps.push((t, Step::Acked { space, pns }));
+                    }
+                }
+                EventData::PacketLost(pl) => {
+                    if let Some(header) = pl.header
+                        && let (Some(pt), Some(pn)) =
+                            (packet_type(&header.packet_type), header.packet_number)
+                    {
+                        let trigger = match pl.trigger {
+                            Some(PacketLostTrigger::ReorderingThreshold) => {
+                                sent::LossTrigger::ReorderingThreshold
+                            }
+                            _ => sent::LossTrigger::TimeThreshold,
+                        };
+                        trace.steps.push((t, Step::Lost { pt, pn, trigger }));
+                    }
+                }
+                EventData::MetricsUpdated(mu) => {
+                    if let Some(latest) = mu.latest_rtt {
+                        trace.steps.push((t, Step::Rtt(ms(latest))));
+                    }
+                    if let Some(cwnd) = mu.congestion_window {
+                        trace
+                            .steps
+                            .push((t, Step::Cwnd(usize::try_from(cwnd).unwrap())));
+                    }
+                }
+                _ => {}
+            }
+        }
+        trace
+    }
+
+    /// The congestion window values that the trace recorded, in order.
+    pub fn cwnd(&self) -> Vec<usize> {
+        self.steps
+            .iter()
+            .filter_map(|(_, s)| match s {
+                Step::Cwnd(cwnd) => Some(*cwnd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replay the trace against `cc`, starting at `start`.
+    /// This returns the congestion window each time it changes.
+    pub fn replay(&self, cc: &mut dyn CongestionController, start: Instant) -> Vec<usize> {
+        let mut rtt = RttEstimate::new(RTT);
+        let mut stats = CongestionControlStats::default();
+        let mut outstanding = BTreeMap::<Key, sent::Packet>::new();
+        let mut cwnd = vec![cc.cwnd()];
+        for (t, step) in &self.steps {
+            let now = start + *t;
+            match step {
+                Step::Sent { pt, pn, len } => {
+                    let p = sent::Packet::new(*pt, *pn, now, true, recovery::Tokens::new(), *len);
+                    cc.on_packet_sent(&p, now);
+                    outstanding.insert(key(&space(*pt), *pn), p);
+                }
+                Step::Acked { space, pns } => {
+                    let mut acked = pns
+                        .iter()
+                        .filter_map(|pn| outstanding.remove(&key(space, *pn)))
+                        .collect::<Vec<_>>();
+                    // The congestion controller expects the largest first.
+                    acked.sort_by_key(|p| Reverse(p.pn()));
+                    if !acked.is_empty() {
+                        cc.on_packets_acked(&acked, &rtt, now, &mut stats);
+                    }
+                }
+                Step::Lost { pt, pn, trigger } => {
+                    // Keep lost packets, in case they are acknowledged later.
+                    if let Some(p) = outstanding.get_mut(&key(&space(*pt), *pn))
+                        && p.declare_lost(now, *trigger)
+                    {
+                        let lost = [p.clone()];
+                        cc.on_packets_lost(
+                            rtt.first_sample_time(),
+                            None,
+                            rtt.pto(true),
+                            &lost,
+                            now,
+                            &mut stats,
+                        );
+                    }
+                }
+                Step::Rtt(sample) => {
+                    rtt.update(
+                        &mut Qlog::disabled(),
+                        *sample,
+                        Duration::ZERO,
+                        RttSource::AckConfirmed,
+                        now,
+                    );
+                }
+                Step::Cwnd(_) => {}
+            }
+            if cwnd.last() != Some(&cc.cwnd()) {
+                cwnd.push(cc.cwnd());
+            }
+        }
+        cwnd
+    }
+}
+
+/// Build a trace in the form that neqo writes.
+fn trace_log(events: &[(f32, &str, &str)]) -> String {
+    let mut log = String::from(EXPECTED_LOG_HEADER);
+    for (time, name, data) in events {
+        log.push_str(&format!(
+            "\u{1e}{{\"time\":{time},\"name\":\"{name}\",\"data\":{data}}}\n"
+        ));
+    }
+    log
+}
+
+fn sent_event(pn: u64) -> String {
+    format!(r#"{{"header":{{"packet_type":"1RTT","packet_number":{pn}}},"raw":{{"length":1000}}}}"#)
+}
+
+#[test]
+fn replay_slow_start_then_loss() {
+    let sent = (0..12).map(sent_event).collect::<Vec<_>>();
+    let mut events = sent
+        .iter()
+        .map(|data| (0.0, "transport:packet_sent", data.as_str()))
+        .collect::<Vec<_>>();
+    events.extend([
+        (100.0, "recovery:metrics_updated", r#"{"latest_rtt":100.0}"#),
+        (
+            100.0,
+            "transport:packets_acked",
+            r#"{"packet_number_space":"application_data","packet_numbers":[0,1,2,3,4,5,6,7]}"#,
+        ),
+        (
+            150.0,
+            "recovery:packet_lost",
+            r#"{"header":{"packet_type":"1RTT","packet_number":8},"trigger":"time_threshold"}"#,
+        ),
+        // An acknowledgment for a packet that was already acknowledged does nothing.
+        (
+            160.0,
+            "transport:packets_acked",
+            r#"{"packet_number_space":"application_data","packet_numbers":[7]}"#,
+        ),
+    ]);
+    let trace = Trace::parse(&trace_log(&events));
+    assert!(trace.cwnd().is_empty());
+
+    let mut cc = make_cc_newreno();
+    let initial = cc.cwnd();
+    let cwnd = trace.replay(&mut cc, now());
+    let grown = initial + 8 * 1000;
+    assert_eq!(cwnd, [initial, grown, grown / 2]);
+    assert_eq!(cc.bytes_in_flight(), 3 * 1000);
+}
+
+/// A trace that recorded the congestion window can be checked against a replay.
+#[test]
+fn replay_matches_recorded_cwnd() {
+    let mut cc = make_cc_newreno();
+    let initial = cc.cwnd();
+    let sent = (0..12).map(sent_event).collect::<Vec<_>>();
+    let cwnd = format!(r#"{{"congestion_window":{}}}"#, initial + 8 * 1000);
+    let mut events = sent
+        .iter()
+        .map(|data| (0.0, "transport:packet_sent", data.as_str()))
+        .collect::<Vec<_>>();
+    events.extend([
+        (100.0, "recovery:metrics_updated", r#"{"latest_rtt":100.0}"#),
+        (
+            100.0,
+            "transport:packets_acked",
+            r#"{"packet_number_space":"application_data","packet_numbers":[7,6,5,4,3,2,1,0]}"#,
+        ),
+        (100.0, "recovery:metrics_updated", cwnd.as_str()),
+    ]);
+    let trace = Trace::parse(&trace_log(&events));
+    let replayed = trace.replay(&mut cc, now());
+    assert_eq!(replayed[1..], trace.cwnd());
+}
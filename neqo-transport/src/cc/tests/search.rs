@@ -0,0 +1,88 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SEARCH slow start test suite
+
+use std::time::Duration;
+
+use test_fixture::now;
+
+use super::RTT;
+use crate::{
+    cc::{classic_cc::SlowStart as _, search::Search},
+    rtt::RttEstimate,
+    stats::CongestionControlStats,
+};
+
+const ACK_INTERVAL: Duration = Duration::from_millis(5);
+const INITIAL_RATE: usize = 1_000;
+
+/// Acknowledge at [`ACK_INTERVAL`] for `duration`, delivering what `rate` returns
+/// for the time since the start, if that isn't zero.  Returns the time since the start at which slow
+/// start exited, if it did.
+fn run<F: Fn(Duration) -> usize>(duration: Duration, rate: F) -> Option<Duration> {
+    let mut search = Search::default();
+    let rtt_est = RttEstimate::new(RTT);
+    let mut cc_stats = CongestionControlStats::default();
+    let mut elapsed = Duration::ZERO;
+    let mut pn = 0;
+    while elapsed < duration {
+        let delivered = rate(elapsed);
+        if delivered == 0 {
+            elapsed += ACK_INTERVAL;
+            continue;
+        }
+        search.on_delivered(delivered, now() + elapsed);
+        if search
+            .on_packets_acked(&rtt_est, pn, 10_000, &mut cc_stats)
+            .is_some()
+        {
+            return Some(elapsed);
+        }
+        pn += 1;
+        elapsed += ACK_INTERVAL;
+    }
+    None
+}
+
+/// Doubles every round trip.
+fn doubling(elapsed: Duration) -> usize {
+    INITIAL_RATE << (elapsed.as_millis() / RTT.as_millis())
+}
+
+/// While delivery keeps doubling every round trip, slow start continues.
+#[test]
+fn doubling_continues() {
+    assert_eq!(run(RTT * 20, doubling), None);
+}
+
+/// When delivery stops growing, slow start exits, even though the RTT doesn't change.
+#[test]
+fn plateau_exits() {
+    let plateau = RTT * 6;
+    let exit = run(RTT * 20, |elapsed| doubling(elapsed.min(plateau))).unwrap();
+    assert!(exit > plateau);
+    // The window has to fill with bins from after the plateau.
+    assert!(exit < plateau + RTT * 5);
+}
+
+/// Bins are started over after a gap in acknowledgments, so that delivery from
+/// before the gap isn't compared with delivery after it.
+#[test]
+fn gap_restarts() {
+    let gap_start = RTT * 6;
+    let gap_end = RTT * 16;
+    let exit = run(RTT * 30, |elapsed| {
+        if elapsed < gap_start {
+            doubling(elapsed)
+        } else if elapsed < gap_end {
+            0
+        } else {
+            doubling(elapsed - gap_end)
+        }
+    });
+    assert_eq!(exit, None);
+}
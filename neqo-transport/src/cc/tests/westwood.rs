@@ -0,0 +1,107 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use test_fixture::now;
+
+use super::{IP_ADDR, MTU, RTT, bbr::packet};
+use crate::{
+    Pmtud,
+    cc::{
+        ClassicSlowStart, CongestionController, Westwood, classic_cc::ClassicCongestionController,
+    },
+    delivery_rate::RateSample,
+    stats::CongestionControlStats,
+};
+
+fn make_cc_westwood() -> ClassicCongestionController<ClassicSlowStart, Westwood> {
+    ClassicCongestionController::new(
+        ClassicSlowStart::default(),
+        Westwood::default(),
+        Pmtud::new(IP_ADDR, MTU),
+    )
+}
+
+/// A sample of `rate` bytes per second, for a round trip that started when
+/// `prior_delivered` bytes had been delivered and ended at `delivered`.
+const fn sample(rate: u64, prior_delivered: usize, delivered: usize) -> RateSample {
+    RateSample {
+        rate: Some(rate),
+        delivered,
+        prior_delivered,
+        app_limited: false,
+        min_rtt: RTT,
+    }
+}
+
+/// Lose a packet and return the congestion window that results.
+fn lose<C: CongestionController>(cc: &mut C) -> usize {
+    let p = packet(0, cc.pmtud().plpmtu(), now());
+    cc.on_packet_sent(&p, now());
+    let t = now() + RTT;
+    assert!(cc.on_packets_lost(
+        Some(now()),
+        None,
+        RTT,
+        &[p],
+        t,
+        &mut CongestionControlStats::default()
+    ));
+    cc.cwnd()
+}
+
+/// Without a bandwidth estimate, the window is halved.
+#[test]
+fn no_estimate_halves() {
+    let mut cc = make_cc_westwood();
+    assert_eq!(lose(&mut cc), cc.cwnd_initial() / 2);
+}
+
+/// With an estimate, the window is reduced to the bandwidth-delay product.
+#[test]
+fn reduces_to_bdp() {
+    let mut cc = make_cc_westwood();
+    // At 100ms, this rate is three quarters of the window each round trip.
+    let rate = u64::try_from(cc.cwnd_initial() * 3 / 4 * 10).unwrap();
+    cc.on_rate_sample(&sample(rate, 0, 1));
+    assert_eq!(lose(&mut cc), cc.cwnd_initial() * 3 / 4);
+    assert_eq!(cc.ssthresh(), cc.cwnd_initial() * 3 / 4);
+}
+
+/// The window isn't increased when the estimate is larger than it.
+#[test]
+fn bdp_above_cwnd() {
+    let mut cc = make_cc_westwood();
+    let rate = u64::try_from(cc.cwnd_initial() * 100).unwrap();
+    cc.on_rate_sample(&sample(rate, 0, 1));
+    assert_eq!(lose(&mut cc), cc.cwnd_initial());
+}
+
+/// The estimate is only updated once each round trip, and it is smoothed.
+#[test]
+fn once_per_round() {
+    let mut cc = make_cc_westwood();
+    cc.on_rate_sample(&sample(8_000, 0, 1_000));
+    assert_eq!(cc.congestion_control().bwe(), Some(8_000));
+
+    // This sample is from the same round trip.
+    cc.on_rate_sample(&sample(16_000, 500, 1_500));
+    assert_eq!(cc.congestion_control().bwe(), Some(8_000));
+
+    cc.on_rate_sample(&sample(16_000, 1_000, 2_000));
+    assert_eq!(cc.congestion_control().bwe(), Some(9_000));
+}
+
+/// A low rate from when the sender was application limited doesn't lower the estimate.
+#[test]
+fn app_limited() {
+    let mut cc = make_cc_westwood();
+    cc.on_rate_sample(&sample(8_000, 0, 1_000));
+    cc.on_rate_sample(&RateSample {
+        app_limited: true,
+        ..sample(800, 1_000, 2_000)
+    });
+    assert_eq!(cc.congestion_control().bwe(), Some(8_000));
+}
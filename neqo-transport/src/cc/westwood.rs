@@ -0,0 +1,119 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Westwood+ congestion control
+
+use std::{
+    cmp::min,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::qtrace;
+
+use crate::{
+    cc::{CongestionEvent, classic_cc::WindowAdjustment},
+    delivery_rate::RateSample,
+    stats::CongestionControlStats,
+};
+
+/// Westwood+ grows the congestion window as `NewReno` does, but on a congestion
+/// event it reduces the window to the estimated bandwidth-delay product, rather
+/// than halving it.  This recovers faster on paths with random loss, such as
+/// wireless and satellite links, where loss doesn't mean that the path is full.
+///
+/// The bandwidth estimate is a moving average of the delivery rate, sampled
+/// once per round trip.  Until there is an estimate, the window is halved.
+///
+/// <https://doi.org/10.1002/sat.799>
+#[derive(Debug, Default)]
+pub struct Westwood {
+    /// The estimated bandwidth, in bytes per second.
+    bwe: Option<u64>,
+    /// The minimum RTT of the path.
+    min_rtt: Duration,
+    /// The amount delivered at which the current round trip ends.
+    round_end: usize,
+}
+
+impl Display for Westwood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Westwood+")
+    }
+}
+
+impl Westwood {
+    /// The gain of the moving average of the bandwidth is `1 / BWE_GAIN`.
+    pub const BWE_GAIN: u64 = 8;
+
+    /// The estimated bandwidth, in bytes per second, if there is an estimate.
+    #[must_use]
+    pub const fn bwe(&self) -> Option<u64> {
+        self.bwe
+    }
+
+    /// The estimated bandwidth-delay product, in bytes.
+    fn bdp(&self) -> Option<usize> {
+        let bwe = u128::from(self.bwe?);
+        (!self.min_rtt.is_zero()).then(|| {
+            usize::try_from(bwe * self.min_rtt.as_nanos() / 1_000_000_000).unwrap_or(usize::MAX)
+        })
+    }
+}
+
+impl WindowAdjustment for Westwood {
+    fn bytes_for_cwnd_increase(
+        &mut self,
+        curr_cwnd: usize,
+        _new_acked_bytes: usize,
+        _min_rtt: Duration,
+        _max_datagram_size: usize,
+        _now: Instant,
+    ) -> usize {
+        curr_cwnd
+    }
+
+    fn reduce_cwnd(
+        &mut self,
+        curr_cwnd: usize,
+        acked_bytes: usize,
+        _max_datagram_size: usize,
+        _congestion_event: CongestionEvent,
+        _cc_stats: &mut CongestionControlStats,
+    ) -> (usize, usize) {
+        let cwnd = self.bdp().map_or(curr_cwnd / 2, |bdp| min(curr_cwnd, bdp));
+        qtrace!(
+            "[{self}] reduce cwnd {curr_cwnd} to {cwnd}, bwe {:?}",
+            self.bwe
+        );
+        (cwnd, acked_bytes / 2)
+    }
+
+    fn on_app_limited(&mut self) {}
+
+    fn on_rate_sample(&mut self, sample: &RateSample) {
+        self.min_rtt = sample.min_rtt;
+        let Some(rate) = sample.rate else {
+            return;
+        };
+        if sample.prior_delivered < self.round_end {
+            return;
+        }
+        // A sample from when the sender was application limited only shows
+        // that the path can do at least that much.
+        if sample.app_limited && self.bwe.is_some_and(|bwe| rate <= bwe) {
+            return;
+        }
+        self.round_end = sample.delivered;
+        self.bwe = Some(self.bwe.map_or(rate, |bwe| {
+            bwe - bwe / Self::BWE_GAIN + rate / Self::BWE_GAIN
+        }));
+    }
+
+    fn save_undo_state(&mut self) {}
+
+    fn restore_undo_state(&mut self, _cc_stats: &mut CongestionControlStats) {}
+}
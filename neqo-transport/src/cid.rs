@@ -234,6 +234,71 @@ impl ConnectionIdGenerator for RandomConnectionIdGenerator {
     }
 }
 
+/// A `ShardedConnectionIdGenerator` produces connection IDs of a fixed length
+/// that start with a shard number, followed by random content.
+///
+/// A server that spreads connections over several workers, each with its own
+/// generator, can use [`ShardedConnectionIdGenerator::shard_of`] to route
+/// packets to the worker that owns the connection.  No effort is made to
+/// prevent collisions.
+pub struct ShardedConnectionIdGenerator {
+    shard: u8,
+    len: usize,
+}
+
+impl ShardedConnectionIdGenerator {
+    /// Create a generator for connection IDs of `len` bytes, the first of
+    /// which is `shard`.
+    ///
+    /// # Panics
+    ///
+    /// When `len` is zero, as there is no room for the shard.
+    #[must_use]
+    pub const fn new(shard: u8, len: usize) -> Self {
+        assert!(len > 0, "connection IDs need room for the shard");
+        Self { shard, len }
+    }
+
+    #[must_use]
+    pub const fn shard(&self) -> u8 {
+        self.shard
+    }
+
+    /// Get the shard from the connection ID of the first packet in `datagram`.
+    ///
+    /// This only looks at packets with a short header.  Packets with a long
+    /// header can carry a connection ID that the client chose, for Initial
+    /// and 0-RTT packets, and return `None`.  As clients do not migrate before
+    /// the handshake is confirmed, these arrive on the same 4-tuple as the
+    /// packet that created the connection.
+    #[must_use]
+    pub fn shard_of(datagram: &[u8]) -> Option<u8> {
+        match datagram {
+            [first, shard, ..] if first & packet::BIT_LONG == 0 => Some(*shard),
+            _ => None,
+        }
+    }
+}
+
+impl ConnectionIdDecoder for ShardedConnectionIdGenerator {
+    fn decode_cid<'a>(&self, dec: &mut Decoder<'a>) -> Option<ConnectionIdRef<'a>> {
+        dec.decode(self.len).map(ConnectionIdRef::from)
+    }
+}
+
+impl ConnectionIdGenerator for ShardedConnectionIdGenerator {
+    fn generate_cid(&mut self) -> Option<ConnectionId> {
+        let mut buf = smallvec![0; self.len];
+        buf[0] = self.shard;
+        randomize(&mut buf[1..]);
+        Some(ConnectionId::from(buf))
+    }
+
+    fn as_decoder(&self) -> &dyn ConnectionIdDecoder {
+        self
+    }
+}
+
 /// A single connection ID, as saved from `NEW_CONNECTION_ID`.
 /// This is templated so that the connection ID entries from a peer can be
 /// saved with a stateless reset token.  Local entries don't need that.
@@ -674,6 +739,25 @@ mod tests {
         assert_eq!(nonempty_cid.to_string().len(), 16); // 8 bytes = 16 hex chars
     }
 
+    #[test]
+    fn sharded_connection_id_generator() {
+        use crate::cid::{ConnectionIdGenerator as _, ShardedConnectionIdGenerator};
+        fixture_init();
+        let mut generator = ShardedConnectionIdGenerator::new(3, 8);
+        assert!(!generator.generates_empty_cids());
+        let cid = generator.generate_cid().unwrap();
+        assert_eq!(cid.len(), 8);
+        assert_eq!(cid[0], 3);
+
+        // A short header packet carries the shard right after the first byte.
+        let mut short = vec![0x40];
+        short.extend_from_slice(&cid);
+        assert_eq!(ShardedConnectionIdGenerator::shard_of(&short), Some(3));
+        // A long header packet is not routed.
+        assert_eq!(ShardedConnectionIdGenerator::shard_of(&[0xc0, 3]), None);
+        assert_eq!(ShardedConnectionIdGenerator::shard_of(&[0x40]), None);
+    }
+
     #[test]
     fn connection_id_ref_display() {
         use super::ConnectionIdRef;
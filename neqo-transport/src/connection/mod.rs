@@ -18,13 +18,17 @@ use std::{
 };
 
 use neqo_common::{
-    Buffer, Datagram, Decoder, Ecn, Encoder, Role, Tos, datagram, event::Provider as EventProvider,
-    hex, hex_snip_middle, hex_with_len, hrtime, qdebug, qerror, qinfo, qlog::Qlog, qtrace, qwarn,
+    Buffer, Datagram, Decoder, Dscp, Ecn, Encoder, Role, Tos,
+    datagram::{self, Payload},
+    event::Provider as EventProvider,
+    hex, hex_snip_middle, hex_with_len, hrtime, qdebug, qerror, qinfo,
+    qlog::Qlog,
+    qtrace, qwarn,
 };
 use neqo_crypto::{
     Agent, AntiReplay, AuthenticationStatus, Cipher, Client, Group, HandshakeState, PrivateKey,
     PublicKey, ResumptionToken, SecretAgentInfo, SecretAgentPreInfo, Server, ZeroRttChecker,
-    agent::{CertificateCompressor, CertificateInfo},
+    agent::{CertificateCompressor, CertificateInfo, CertificateVerifier},
 };
 use smallvec::SmallVec;
 use strum::IntoEnumIterator as _;
@@ -32,6 +36,7 @@ use strum::IntoEnumIterator as _;
 use crate::{
     AppError, CloseReason, Error, Res, StreamId,
     addr_valid::{AddressValidation, NewTokenState},
+    cc::{CongestionControl, CongestionSnapshot, CongestionState, LimitingFactor},
     cid::{
         ConnectionId, ConnectionIdEntry, ConnectionIdGenerator, ConnectionIdManager,
         ConnectionIdRef, ConnectionIdStore,
@@ -49,6 +54,7 @@ use crate::{
     rtt::{GRANULARITY, RttEstimate},
     saved::SavedDatagrams,
     send_stream::{self, SendStream},
+    session_store::SessionStore,
     stateless_reset::Token as Srt,
     stats::{Stats, StatsCell},
     stream_id::StreamType,
@@ -303,6 +309,8 @@ pub struct Connection {
     saved_datagrams: SavedDatagrams,
     /// Some packets were received, but not tracked.
     received_untracked: bool,
+    /// The server refuses this connection, see [`Connection::refuse`].
+    refused: bool,
 
     /// This is responsible for the `QuicDatagrams`' handling:
     /// <https://datatracker.ietf.org/doc/html/draft-ietf-quic-datagram>
@@ -321,6 +329,12 @@ pub struct Connection {
     /// A session ticket was received without `NEW_TOKEN`,
     /// this is when that turns into an event without `NEW_TOKEN`.
     release_resumption_token_timer: Option<Instant>,
+    /// Verifies the peer certificate before the application is asked to.
+    certificate_verifier: Option<Box<dyn CertificateVerifier>>,
+    /// The result from `certificate_verifier`, applied once input processing completes.
+    certificate_verified: Option<AuthenticationStatus>,
+    /// Where resumption tokens are saved, in addition to being released as events.
+    session_store: Option<Rc<RefCell<dyn SessionStore>>>,
     conn_params: ConnectionParameters,
     hrtime: hrtime::Handle,
 
@@ -457,18 +471,27 @@ impl Connection {
             original_destination_cid: None,
             saved_datagrams: SavedDatagrams::default(),
             received_untracked: false,
+            refused: false,
             crypto,
             acks: AckTracker::default(),
             idle_timeout: IdleTimeout::new(conn_params.get_idle_timeout()),
             streams: Streams::new(tphandler, role, events.clone()),
             cids: ConnectionIdStore::default(),
             state_signaling: StateSignaling::Idle,
-            loss_recovery: recovery::Loss::new(stats.clone(), conn_params.get_fast_pto()),
+            loss_recovery: recovery::Loss::new(
+                stats.clone(),
+                conn_params.get_fast_pto(),
+                conn_params.get_packet_threshold(),
+                conn_params.get_time_threshold(),
+            ),
             events,
             new_token: NewTokenState::new(role),
             stats,
             qlog: Qlog::disabled(),
             release_resumption_token_timer: None,
+            certificate_verifier: None,
+            certificate_verified: None,
+            session_store: None,
             conn_params,
             hrtime: hrtime::Time::get(Self::LOOSE_TIMER_RESOLUTION),
             quic_datagrams,
@@ -515,6 +538,19 @@ impl Connection {
         self.crypto.ech_config()
     }
 
+    /// Staple OCSP responses to the named certificate.
+    ///
+    /// # Errors
+    /// When the certificate can't be found or the operation fails.
+    pub fn server_set_stapled_ocsp_responses(
+        &self,
+        certificate: &str,
+        ocsp_responses: &[&[u8]],
+    ) -> Res<()> {
+        self.crypto
+            .server_set_stapled_ocsp_responses(certificate, ocsp_responses)
+    }
+
     /// # Errors
     /// When the operation fails.
     pub fn client_enable_ech<A: AsRef<[u8]>>(&mut self, ech_config_list: A) -> Res<()> {
@@ -528,6 +564,15 @@ impl Connection {
         self.qlog = qlog;
     }
 
+    /// Change the DSCP codepoint that outgoing packets are marked with, e.g. to
+    /// move the connection to a different traffic class.  This applies to
+    /// packets sent from now on, on all paths.
+    /// See [`ConnectionParameters::dscp`].
+    pub fn set_dscp(&mut self, dscp: Dscp) {
+        self.conn_params = self.conn_params.clone().dscp(dscp);
+        self.paths.set_dscp(dscp);
+    }
+
     /// Get the qlog (if any) for this connection.
     pub const fn qlog_mut(&mut self) -> &mut Qlog {
         &mut self.qlog
@@ -709,7 +754,7 @@ impl Connection {
 
         while self.crypto.has_resumption_token() && self.new_token.has_token() {
             let token = self.make_resumption_token();
-            self.events.client_resumption_token(token);
+            self.release_resumption_token(token);
         }
 
         // If we have a resumption ticket check or set a timer.
@@ -717,7 +762,7 @@ impl Connection {
             let arm = if let Some(expiration_time) = self.release_resumption_token_timer {
                 if expiration_time <= now {
                     let token = self.make_resumption_token();
-                    self.events.client_resumption_token(token);
+                    self.release_resumption_token(token);
                     self.release_resumption_token_timer = None;
 
                     // This means that we release one session ticket every 3 PTOs
@@ -736,6 +781,45 @@ impl Connection {
         }
     }
 
+    fn release_resumption_token(&self, token: ResumptionToken) {
+        if let (Some(store), Some(server_name)) = (&self.session_store, self.crypto.server_name()) {
+            store.borrow_mut().put(server_name, token.clone());
+        }
+        self.events.client_resumption_token(token);
+    }
+
+    /// Use `store` to save resumption tokens for this connection, keyed by the server name.
+    /// If the store already holds a token for the server, resumption is enabled using that
+    /// token, which is then removed from the store, as tokens are single-use.
+    ///
+    /// Tokens continue to be released as `ConnectionEvent::ResumptionToken` events.
+    ///
+    /// # Errors
+    /// When the connection is not a client, or it is not in its initial state, or the stored
+    /// token cannot be used.
+    pub fn set_session_store(
+        &mut self,
+        now: Instant,
+        store: Rc<RefCell<dyn SessionStore>>,
+    ) -> Res<()> {
+        if self.state != State::Init {
+            qerror!("[{self}] set session store in state {:?}", self.state);
+            return Err(Error::ConnectionState);
+        }
+        let server_name = self
+            .crypto
+            .server_name()
+            .ok_or(Error::WrongRole)?
+            .to_owned();
+        let token = store.borrow().get(&server_name);
+        if let Some(token) = token {
+            store.borrow_mut().remove(&server_name);
+            self.enable_resumption(now, token)?;
+        }
+        self.session_store = Some(store);
+        Ok(())
+    }
+
     /// The correct way to obtain a resumption token is to wait for the
     /// `ConnectionEvent::ResumptionToken` event. To emit the event we are waiting for a
     /// resumption token and a `NEW_TOKEN` frame to arrive. Some servers don't send `NEW_TOKEN`
@@ -846,6 +930,19 @@ impl Connection {
         self.address_validation = AddressValidationInfo::Server(Rc::downgrade(validation));
     }
 
+    /// Refuse the connection.  The first packet from the client is answered
+    /// with a `CONNECTION_CLOSE` carrying `CONNECTION_REFUSED`, without
+    /// starting the handshake.
+    pub(crate) fn refuse(&mut self) {
+        assert_eq!(self.role, Role::Server);
+        self.refused = true;
+    }
+
+    /// The remote address of the primary path, if there is one.
+    pub(crate) fn remote_address(&self) -> Option<SocketAddr> {
+        self.paths.primary().map(|p| p.borrow().remote_address())
+    }
+
     /// Send a TLS session ticket AND a `NEW_TOKEN` frame (if possible).
     /// # Errors
     /// When the operation fails, which is usually due to bad inputs or bad connection state.
@@ -902,6 +999,53 @@ impl Connection {
         self.crypto.tls().peer_certificate()
     }
 
+    /// Derive `len` bytes of keying material that is bound to this connection,
+    /// using the TLS exporter interface.
+    ///
+    /// # Errors
+    /// When the handshake is not yet complete or the TLS stack cannot export.
+    pub fn export_keying_material(
+        &self,
+        label: &str,
+        context: Option<&[u8]>,
+        len: usize,
+    ) -> Res<Vec<u8>> {
+        if self.crypto.tls().info().is_none() {
+            return Err(Error::NotConnected);
+        }
+        Ok(self
+            .crypto
+            .tls()
+            .export_keying_material(label, context, len)?)
+    }
+
+    /// Install a hook that is consulted when the peer certificate needs to be verified.
+    /// If the hook returns a status, the connection is authenticated with that status
+    /// and no `ConnectionEvent::AuthenticationNeeded` event is generated.
+    pub fn set_certificate_verifier(&mut self, verifier: Box<dyn CertificateVerifier>) {
+        self.certificate_verifier = Some(verifier);
+    }
+
+    /// Ask the certificate verifier, if any, to authenticate the peer.
+    fn verify_certificate(&mut self) -> Option<AuthenticationStatus> {
+        if self.conn_params.must_staple_enforced()
+            && let Some(cert) = self.crypto.tls().peer_certificate()
+            && cert.must_staple()
+            && !cert.has_stapled_ocsp_response()
+        {
+            qwarn!("[{self}] Must-staple certificate without a stapled OCSP response");
+            return Some(AuthenticationStatus::RequiredTlsFeatureMissing);
+        }
+        let verifier = self.certificate_verifier.as_mut()?;
+        let Some(cert) = self.crypto.tls().peer_certificate() else {
+            return Some(AuthenticationStatus::Unknown);
+        };
+        let server_name = self.crypto.server_name().unwrap_or_default();
+        let status = verifier.verify(server_name, &cert);
+        qdebug!("[{self}] Certificate verifier returned {status:?}");
+        status
+    }
+
     /// Call by application when the peer cert has been verified.
     ///
     /// This panics if there is no active peer.  It's OK to call this
@@ -948,10 +1092,106 @@ impl Connection {
             let p = p.borrow();
             v.rtt = p.rtt().estimate();
             v.rttvar = p.rtt().rttvar();
+            v.bytes_in_flight = p.sender().bytes_in_flight();
+            v.max_send_rate = p.sender().max_send_rate();
         }
         v
     }
 
+    /// Cap the rate at which this connection sends, in bytes per second,
+    /// regardless of the congestion window.  Passing `None` removes the cap.
+    /// This can be used to throttle a connection to a configured bandwidth.
+    pub fn set_max_send_rate(&mut self, bytes_per_sec: Option<u64>, now: Instant) {
+        qdebug!("[{self}] Set maximum send rate to {bytes_per_sec:?}");
+        self.paths.set_max_send_rate(bytes_per_sec, now);
+    }
+
+    /// Switch to a different congestion control algorithm, for example, to move
+    /// from one that is robust during the handshake to one that suits bulk
+    /// transfer.  This replaces any congestion controller set with
+    /// [`ConnectionParameters::congestion_controller`].
+    ///
+    /// The new algorithm starts over from the initial congestion window, but
+    /// packets in flight still count against it and the RTT estimate is kept.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::InvalidInput`] when switching between a controller that is built
+    /// for L4S, such as [`CongestionControl::Prague`], and one that is not, once a
+    /// path exists.  Whether packets are marked for L4S is decided when a path is
+    /// created, and an L4S controller must not respond to classic ECN marks.
+    pub fn set_cc_algorithm(&mut self, cc: CongestionControl, now: Instant) -> Res<()> {
+        let l4s = cc == CongestionControl::Prague;
+        if self
+            .paths
+            .primary()
+            .is_some_and(|p| p.borrow().sender().l4s() != l4s)
+        {
+            qwarn!("[{self}] Unable to switch congestion control to {cc:?}");
+            return Err(Error::InvalidInput);
+        }
+        qdebug!("[{self}] Switch congestion control to {cc:?}");
+        self.conn_params = self.conn_params.clone().replace_congestion_control(cc);
+        self.paths.set_congestion_control(
+            &self.conn_params,
+            self.loss_recovery.cc_outstanding(),
+            now,
+        );
+        Ok(())
+    }
+
+    /// Get a snapshot of the congestion control state of the primary path,
+    /// including what is currently limiting sending.
+    /// Returns `None` if there is no primary path.
+    #[must_use]
+    pub fn congestion_snapshot(&self, now: Instant) -> Option<CongestionSnapshot> {
+        let path = self.paths.primary()?;
+        let path = path.borrow();
+        let sender = path.sender();
+        let rtt = path.rtt();
+        let mtu = path.plpmtu();
+        let next_paced = sender.next_paced(rtt.estimate());
+        let limit = if path.amplification_limit() < mtu {
+            LimitingFactor::Amplification
+        } else if sender.cwnd_avail() < mtu {
+            LimitingFactor::Cwnd
+        } else if next_paced.is_some_and(|t| t > now) {
+            LimitingFactor::Pacing
+        } else if self.streams.send_blocked() {
+            LimitingFactor::FlowControl
+        } else {
+            LimitingFactor::AppLimited
+        };
+        Some(CongestionSnapshot {
+            cwnd: sender.cwnd(),
+            ssthresh: sender.ssthresh(),
+            bytes_in_flight: sender.bytes_in_flight(),
+            phase: sender.phase(),
+            pacing: sender.pacing(),
+            next_paced,
+            min_rtt: rtt.minimum(),
+            smoothed_rtt: rtt.estimate(),
+            limit,
+        })
+    }
+
+    /// Get the congestion control state of the primary path, which can be
+    /// encoded and saved for later.
+    /// Returns `None` if there is no primary path.
+    #[must_use]
+    pub fn cc_state(&self) -> Option<CongestionState> {
+        let path = self.paths.primary()?;
+        let path = path.borrow();
+        let sender = path.sender();
+        Some(CongestionState {
+            cwnd: sender.cwnd(),
+            ssthresh: sender.ssthresh(),
+            bytes_in_flight: sender.bytes_in_flight(),
+            phase: sender.phase(),
+            window_max: sender.window_max(),
+        })
+    }
+
     // This function wraps a call to another function and sets the connection state
     // properly if that call fails.
     fn capture_error<T>(
@@ -1086,15 +1326,12 @@ impl Connection {
     }
 
     /// Process a new input datagram on the connection.
-    pub fn process_input<A: AsRef<[u8]> + AsMut<[u8]>>(&mut self, d: Datagram<A>, now: Instant) {
+    pub fn process_input<A: Payload>(&mut self, d: Datagram<A>, now: Instant) {
         self.process_multiple_input(iter::once(d), now);
     }
 
     /// Process new input datagrams on the connection.
-    pub fn process_multiple_input<
-        A: AsRef<[u8]> + AsMut<[u8]>,
-        I: IntoIterator<Item = Datagram<A>>,
-    >(
+    pub fn process_multiple_input<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -1243,11 +1480,7 @@ impl Connection {
     /// input and single output datagram only.
     #[expect(clippy::missing_panics_doc, reason = "see expect()")]
     #[must_use = "Output of the process function must be handled"]
-    pub fn process<A: AsRef<[u8]> + AsMut<[u8]>>(
-        &mut self,
-        dgram: Option<Datagram<A>>,
-        now: Instant,
-    ) -> Output {
+    pub fn process<A: Payload>(&mut self, dgram: Option<Datagram<A>>, now: Instant) -> Output {
         self.process_multiple(dgram, now, 1.try_into().expect(">0"))
             .try_into()
             .expect("max_datagrams is 1")
@@ -1255,7 +1488,7 @@ impl Connection {
 
     /// Process input and generate output.
     #[must_use = "OutputBatch of the process_multiple function must be handled"]
-    pub fn process_multiple<A: AsRef<[u8]> + AsMut<[u8]>>(
+    pub fn process_multiple<A: Payload>(
         &mut self,
         dgram: Option<Datagram<A>>,
         now: Instant,
@@ -1393,6 +1626,9 @@ impl Connection {
                 self.input(saved.d, saved.t, now);
             }
         }
+        if let Some(status) = self.certificate_verified.take() {
+            self.authenticated(status, now);
+        }
     }
 
     /// In case a datagram arrives that we can only partially process, save any
@@ -1698,12 +1934,7 @@ impl Connection {
 
     /// Take a datagram as input.  This reports an error if the packet was bad.
     /// This takes two times: when the datagram was received, and the current time.
-    fn input(
-        &mut self,
-        d: Datagram<impl AsRef<[u8]> + AsMut<[u8]>>,
-        received: Instant,
-        now: Instant,
-    ) {
+    fn input(&mut self, d: Datagram<impl Payload>, received: Instant, now: Instant) {
         // First determine the path.
         let path = self.paths.find_path(
             d.destination(),
@@ -1720,12 +1951,13 @@ impl Connection {
     fn input_path(
         &mut self,
         path: &PathRef,
-        mut d: Datagram<impl AsRef<[u8]> + AsMut<[u8]>>,
+        mut d: Datagram<impl Payload>,
         now: Instant,
     ) -> Res<()> {
         qtrace!("[{self}] {} input {}", path.borrow(), hex(&d));
         let tos = d.tos();
         let remote = d.source();
+        let d_len = d.len();
         let mut slc = d.as_mut();
         let mut dcid = None;
         let pto = path.borrow().rtt().pto(self.confirmed());
@@ -1758,50 +1990,28 @@ impl Connection {
 
             let packet_len = packet.len();
             match packet.decrypt(self.crypto.states_mut(), now + pto) {
-                Ok(payload) => {
-                    // OK, we have a valid packet.
-                    let pn = payload.pn();
-                    self.idle_timeout.on_packet_received(now);
-                    self.log_packet(
-                        packet::MetaData::new_in(path, tos, packet_len, &payload, self.version),
-                        now,
-                    );
-
-                    #[cfg(feature = "build-fuzzing-corpus")]
-                    if payload.packet_type() == packet::Type::Initial {
-                        let target = if self.role == Role::Client {
-                            "server_initial"
-                        } else {
-                            "client_initial"
-                        };
-                        neqo_common::write_item_to_fuzzing_corpus(target, &payload[..]);
-                    }
-
-                    let space = PacketNumberSpace::from(payload.packet_type());
-                    if let Some(space) = self.acks.get_mut(space) {
-                        if space.is_duplicate(pn) {
-                            qdebug!("Duplicate packet {space}-{pn}");
-                            self.stats.borrow_mut().dups_rx += 1;
-                        } else {
-                            match self.process_packet(path, &payload, now) {
-                                Ok(migrate) => {
-                                    self.postprocess_packet(
-                                        path, tos, remote, &payload, pn, migrate, now,
-                                    );
-                                }
-                                Err(e) => {
-                                    self.ensure_error_path(path, &payload, now);
-                                    return Err(e);
-                                }
-                            }
+                Ok(payload) if remainder.is_empty() => {
+                    // This is the last packet in the datagram.  Freeze the
+                    // datagram if possible, so that frames can keep parts of
+                    // it without copying them.  The packet ends where the
+                    // datagram does.
+                    let start = d_len - packet_len;
+                    let range = payload.payload_range();
+                    let range = start + range.start..start + range.end;
+                    let payload = payload.with_payload(&[], None);
+                    return match d.into_shared() {
+                        Ok(shared) => {
+                            let payload = payload.with_payload(&shared[range], Some(&shared));
+                            self.input_decrypted(path, tos, remote, packet_len, &payload, now)
                         }
-                    } else {
-                        qdebug!(
-                            "[{self}] Received packet {space} for untracked space {}",
-                            payload.pn()
-                        );
-                        return Err(Error::ProtocolViolation);
-                    }
+                        Err(d) => {
+                            let payload = payload.with_payload(&d[range], None);
+                            self.input_decrypted(path, tos, remote, packet_len, &payload, now)
+                        }
+                    };
+                }
+                Ok(payload) => {
+                    self.input_decrypted(path, tos, remote, packet_len, &payload, now)?;
                     dcid = Some(ConnectionId::from(payload.dcid()));
                 }
                 Err(e) => {
@@ -1835,6 +2045,58 @@ impl Connection {
         Ok(())
     }
 
+    /// Process a packet that was decrypted successfully.
+    fn input_decrypted(
+        &mut self,
+        path: &PathRef,
+        tos: Tos,
+        remote: SocketAddr,
+        packet_len: usize,
+        payload: &packet::Decrypted,
+        now: Instant,
+    ) -> Res<()> {
+        let pn = payload.pn();
+        self.idle_timeout.on_packet_received(now);
+        self.log_packet(
+            packet::MetaData::new_in(path, tos, packet_len, payload, self.version),
+            now,
+        );
+
+        #[cfg(feature = "build-fuzzing-corpus")]
+        if payload.packet_type() == packet::Type::Initial {
+            let target = if self.role == Role::Client {
+                "server_initial"
+            } else {
+                "client_initial"
+            };
+            neqo_common::write_item_to_fuzzing_corpus(target, &payload[..]);
+        }
+
+        let space = PacketNumberSpace::from(payload.packet_type());
+        let Some(space) = self.acks.get_mut(space) else {
+            qdebug!(
+                "[{self}] Received packet {space} for untracked space {}",
+                payload.pn()
+            );
+            return Err(Error::ProtocolViolation);
+        };
+        if space.is_duplicate(pn) {
+            qdebug!("Duplicate packet {space}-{pn}");
+            self.stats.borrow_mut().dups_rx += 1;
+            return Ok(());
+        }
+        match self.process_packet(path, payload, now) {
+            Ok(migrate) => {
+                self.postprocess_packet(path, tos, remote, payload, pn, migrate, now);
+                Ok(())
+            }
+            Err(e) => {
+                self.ensure_error_path(path, payload, now);
+                Err(e)
+            }
+        }
+    }
+
     /// Handle receiving a packet for which keys have been discarded.
     fn handle_keys_discarded(&mut self, epoch: Epoch) {
         // Client: receiving undecryptable Initial packets while waiting
@@ -1860,6 +2122,27 @@ impl Connection {
             .then_some(())
             .ok_or(Error::ProtocolViolation)?;
 
+        if self.refused {
+            // Set up the path and connection IDs, so that the `CONNECTION_CLOSE`
+            // can be sent, but don't look at the `ClientHello`.  Unlike other
+            // errors before the handshake, stay in the closing state, so that
+            // more packets from the client don't create another connection.
+            if self.state == State::WaitInitial {
+                self.start_handshake(path, packet, now);
+                let error = CloseReason::Transport(Error::ConnectionRefused);
+                self.state_signaling
+                    .close(Rc::clone(path), error.clone(), FrameType::Padding, "");
+                self.set_state(
+                    State::Closing {
+                        error,
+                        timeout: self.get_closing_period_time(now),
+                    },
+                    now,
+                );
+            }
+            return Ok(false);
+        }
+
         // TODO(ekr@rtfm.com): Have the server blow away the initial
         // crypto state if this fails? Otherwise, we will get a panic
         // on the assert for doesn't exist.
@@ -1885,14 +2168,7 @@ impl Connection {
             ack_eliciting |= f.ack_eliciting();
             probing &= f.path_probing();
             let t = f.get_type();
-            if let Err(e) = self.input_frame(
-                path,
-                packet.version(),
-                packet.packet_type(),
-                f,
-                next_pn,
-                now,
-            ) {
+            if let Err(e) = self.input_frame(path, packet, f, next_pn, now) {
                 self.capture_error(Some(Rc::clone(path)), now, t, Err(e))?;
             }
         }
@@ -2368,9 +2644,11 @@ impl Connection {
     }
 
     // Maybe send a probe.  Return true if the packet was ack-eliciting.
+    #[expect(clippy::too_many_arguments, reason = "Yes, but they're needed.")]
     fn maybe_probe<B: Buffer>(
         &mut self,
         path: &PathRef,
+        space: PacketNumberSpace,
         force_probe: bool,
         builder: &mut packet::Builder<B>,
         ack_end: usize,
@@ -2380,9 +2658,19 @@ impl Connection {
         let untracked = self.received_untracked && !self.state.connected();
         self.received_untracked = false;
 
+        // A peer that supports the ACK frequency extension might delay its
+        // acknowledgment of a probe, so ask for an immediate one instead.
+        let immediate_ack = self.conn_params.immediate_ack_enabled()
+            && space == PacketNumberSpace::ApplicationData
+            && path.borrow().rtt().immediate_ack_allowed();
+
         // Anything written after an ACK already elicits acknowledgment.
         // If we need to probe and nothing has been written, send a PING.
         if builder.len() > ack_end {
+            if force_probe && immediate_ack && builder.remaining() > 0 {
+                builder.encode_frame(FrameType::ImmediateAck, |_| {});
+                self.stats.borrow_mut().frame_tx.immediate_ack += 1;
+            }
             return true;
         }
 
@@ -2407,11 +2695,17 @@ impl Connection {
         }
 
         if probe {
-            // Nothing ack-eliciting and we need to probe; send PING.
+            // Nothing ack-eliciting and we need to probe; send PING,
+            // or IMMEDIATE_ACK if the peer supports it.
             debug_assert_ne!(builder.remaining(), 0);
-            builder.encode_frame(FrameType::Ping, |_| {});
             let stats = &mut self.stats.borrow_mut().frame_tx;
-            stats.ping += 1;
+            if immediate_ack {
+                builder.encode_frame(FrameType::ImmediateAck, |_| {});
+                stats.immediate_ack += 1;
+            } else {
+                builder.encode_frame(FrameType::Ping, |_| {});
+                stats.ping += 1;
+            }
         }
         probe
     }
@@ -2501,7 +2795,8 @@ impl Connection {
 
         // Maybe send a probe now, either to probe for losses or to keep the connection live.
         let force_probe = profile.should_probe(space);
-        ack_eliciting |= self.maybe_probe(path, force_probe, builder, ack_end, &mut tokens, now);
+        ack_eliciting |=
+            self.maybe_probe(path, space, force_probe, builder, ack_end, &mut tokens, now);
         // If this is not the primary path, this should be ack-eliciting.
         debug_assert!(primary || ack_eliciting);
 
@@ -2568,9 +2863,28 @@ impl Connection {
         let mut num_datagrams = 0;
         let mtu = path.borrow().plpmtu();
         let address_family_max_mtu = path.borrow().pmtud().address_family_max_mtu();
+        // Have the pacer release whole batches, and don't build batches that
+        // are larger than it releases at once, if it applies.
+        path.borrow_mut().set_gso_batch(max_datagrams);
+        let max_datagrams = path
+            .borrow()
+            .sender()
+            .burst_size_hint()
+            .map_or(max_datagrams.get(), |hint| min(max_datagrams.get(), hint));
+        // If pacing is offloaded, all datagrams in the batch depart together.
+        let departure_time = || {
+            let path = path.borrow();
+            path.sender().departure_time(path.rtt().estimate(), now)
+        };
+        let txtime = departure_time();
 
         loop {
-            if max_datagrams.get() <= num_datagrams {
+            if max_datagrams <= num_datagrams {
+                break;
+            }
+            if num_datagrams != 0 && txtime.is_some() && departure_time() > txtime {
+                // The next datagram is paced to leave later than the ones
+                // already in the batch.
                 break;
             }
             if path.borrow().pmtud().needs_probe() && num_datagrams != 0 {
@@ -2639,13 +2953,14 @@ impl Connection {
         }
 
         debug_assert!(!send_buffer.is_empty());
-        let batch = path.borrow_mut().datagram_batch(
+        let mut batch = path.borrow_mut().datagram_batch(
             send_buffer,
             packet_tos,
             num_datagrams,
             max_datagram_size.ok_or(Error::Internal)?,
             &mut self.stats.borrow_mut(),
         );
+        batch.set_txtime(txtime);
 
         Ok(SendOptionBatch::Yes(batch))
     }
@@ -2741,8 +3056,10 @@ impl Connection {
                 continue;
             }
 
-            if packet_tos.is_ecn_marked() {
-                tokens.push(recovery::Token::EcnEct0);
+            match Ecn::from(packet_tos) {
+                Ecn::Ect0 => tokens.push(recovery::Token::EcnEct0),
+                Ecn::Ect1 => tokens.push(recovery::Token::EcnEct1),
+                Ecn::NotEct | Ecn::Ce => (),
             }
 
             self.log_packet(
@@ -2785,7 +3102,8 @@ impl Connection {
             );
             if padded {
                 needs_padding = false;
-                self.loss_recovery.on_packet_sent(path, sent, now);
+                self.loss_recovery
+                    .on_packet_sent(path, sent, profile.probing(), now);
             } else if pt == packet::Type::Initial && (self.role == Role::Client || ack_eliciting) {
                 // Packets containing Initial packets might need padding, and we want to
                 // track that padding along with the Initial packet.  So defer tracking.
@@ -2797,7 +3115,8 @@ impl Connection {
                     // an Initial packet.
                     needs_padding = false;
                 }
-                self.loss_recovery.on_packet_sent(path, sent, now);
+                self.loss_recovery
+                    .on_packet_sent(path, sent, profile.probing(), now);
             }
 
             if space == PacketNumberSpace::Handshake {
@@ -2826,6 +3145,10 @@ impl Connection {
 
         if encoder.is_empty() {
             qdebug!("TX blocked, profile={profile:?}");
+            if !profile.ack_only() && closing_frame.is_none() {
+                // There was space for more than ACKs, but nothing to send.
+                path.borrow_mut().on_app_limited();
+            }
             Ok(SendOption::No(profile.paced()))
         } else {
             // Perform additional padding for Initial packets as necessary.
@@ -2833,7 +3156,8 @@ impl Connection {
                 if needs_padding {
                     self.pad_initial(&mut encoder, &mut initial, &profile);
                 }
-                self.loss_recovery.on_packet_sent(path, initial, now);
+                self.loss_recovery
+                    .on_packet_sent(path, initial, profile.probing(), now);
             }
             path.borrow_mut().add_sent(encoder.len());
             Ok(SendOption::Yes)
@@ -2898,7 +3222,8 @@ impl Connection {
             qlog::recovery_parameters_set(
                 &mut self.qlog,
                 path.borrow().plpmtu(),
-                self.conn_params.get_congestion_control(),
+                path.borrow().sender().cwnd(),
+                &self.conn_params,
                 now,
             );
         }
@@ -3179,7 +3504,10 @@ impl Connection {
             HandshakeState::Authenticated(_) | HandshakeState::InProgress => (),
             HandshakeState::AuthenticationPending => {
                 if !was_authentication_pending {
-                    self.events.authentication_needed();
+                    self.certificate_verified = self.verify_certificate();
+                    if self.certificate_verified.is_none() {
+                        self.events.authentication_needed();
+                    }
                 }
             }
             HandshakeState::EchFallbackAuthenticationPending(public_name) => self
@@ -3225,6 +3553,7 @@ impl Connection {
                 .start(now, &mut self.stats.borrow_mut());
         }
         self.paths.start_ecn(&mut self.stats.borrow_mut());
+        self.paths.on_handshake_confirmed();
         Ok(())
     }
 
@@ -3232,21 +3561,24 @@ impl Connection {
     fn input_frame(
         &mut self,
         path: &PathRef,
-        packet_version: Version,
-        packet_type: packet::Type,
+        packet: &packet::Decrypted,
         frame: Frame,
         next_pn: packet::Number,
         now: Instant,
     ) -> Res<()> {
+        let packet_version = packet.version();
+        let packet_type = packet.packet_type();
         if !frame.is_allowed(packet_type) {
             qinfo!("frame not allowed: {frame:?} {packet_type:?}");
             return Err(Error::ProtocolViolation);
         }
         let space = PacketNumberSpace::from(packet_type);
         if frame.is_stream() {
-            return self
-                .streams
-                .input_frame(&frame, &mut self.stats.borrow_mut().frame_rx);
+            return self.streams.input_frame(
+                &frame,
+                packet.shared(),
+                &mut self.stats.borrow_mut().frame_rx,
+            );
         }
         match frame {
             Frame::Padding(length) => {
@@ -3430,6 +3762,10 @@ impl Connection {
                 self.acks
                     .ack_freq(seqno, tolerance - 1, delay, ignore_order);
             }
+            Frame::ImmediateAck => {
+                self.stats.borrow_mut().frame_rx.immediate_ack += 1;
+                self.acks.immediate_ack(space, now);
+            }
             Frame::Datagram { data, .. } => {
                 self.stats.borrow_mut().frame_rx.datagram += 1;
                 self.quic_datagrams
@@ -3470,7 +3806,9 @@ impl Connection {
                             .datagram_outcome(dgram_tracker, OutgoingDatagramOutcome::Lost);
                         self.stats.borrow_mut().datagram_tx.lost += 1;
                     }
-                    recovery::Token::EcnEct0 => self.paths.lost_ecn(&mut self.stats.borrow_mut()),
+                    recovery::Token::EcnEct0 | recovery::Token::EcnEct1 => {
+                        self.paths.lost_ecn(&mut self.stats.borrow_mut());
+                    }
                     // PMTUD probe loss is handled by the PMTUD state machine.
                     recovery::Token::PmtudProbe => (),
                 }
@@ -3540,7 +3878,7 @@ impl Connection {
                     recovery::Token::Datagram(dgram_tracker) => self
                         .events
                         .datagram_outcome(dgram_tracker, OutgoingDatagramOutcome::Acked),
-                    recovery::Token::EcnEct0 => self.paths.acked_ecn(),
+                    recovery::Token::EcnEct0 | recovery::Token::EcnEct1 => self.paths.acked_ecn(),
                     // We don't care about these being ACK'ed
                     recovery::Token::HandshakeDone | recovery::Token::PmtudProbe => (),
                 }
@@ -3600,7 +3938,8 @@ impl Connection {
             qlog::recovery_parameters_set(
                 &mut self.qlog,
                 path.borrow().plpmtu(),
-                self.conn_params.get_congestion_control(),
+                path.borrow().sender().cwnd(),
+                &self.conn_params,
                 now,
             );
         } else {
@@ -3626,8 +3965,12 @@ impl Connection {
         self.set_state(State::Connected, now);
         self.create_resumption_token(now);
         self.saved_datagrams.make_available(Epoch::ApplicationData);
-        self.stats.borrow_mut().resumed =
-            self.crypto.tls().info().ok_or(Error::Internal)?.resumed();
+        let info = self.crypto.tls().info().ok_or(Error::Internal)?;
+        let mut stats = self.stats.borrow_mut();
+        stats.resumed = info.resumed();
+        stats.cipher = Some(info.cipher_suite());
+        stats.key_exchange = Some(info.key_exchange());
+        drop(stats);
         if self.role == Role::Server {
             self.state_signaling.handshake_done();
             self.set_confirmed(now)?;
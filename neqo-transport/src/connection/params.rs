@@ -6,11 +6,18 @@
 
 use std::{cmp::max, time::Duration};
 
-pub use crate::recovery::FAST_PTO_SCALE;
+use neqo_common::Dscp;
+
+pub use crate::recovery::{FAST_PTO_SCALE, TIME_THRESHOLD_SCALE};
 use crate::{
-    CongestionControl, DEFAULT_INITIAL_RTT, Res, SlowStart,
+    CongestionControl, CongestionControllerFactory, DEFAULT_INITIAL_RTT, HyStartConfig,
+    IdleRestart, Res, SlowStart,
+    cc::{CWND_INITIAL_PKTS, PERSISTENT_CONG_THRESH},
     connection::{ConnectionIdManager, Role},
+    pace::Pacer,
+    recovery::{PACKET_THRESHOLD, TIME_THRESHOLD},
     rtt::GRANULARITY,
+    sender::PACING_BURST_SIZE,
     stream_id::StreamType,
     tparams::{
         PreferredAddress, TransportParameter,
@@ -107,7 +114,32 @@ pub enum PreferredAddressConfig {
 pub struct ConnectionParameters {
     versions: version::Config,
     congestion_control: CongestionControl,
+    /// A congestion controller from the application, which overrides `congestion_control`.
+    congestion_controller: Option<CongestionControllerFactory>,
     slow_start: SlowStart,
+    /// The constants for HyStart++, when that is the slow start algorithm.
+    hystart_config: HyStartConfig,
+    /// Whether to use Proportional Rate Reduction during recovery.
+    prr: bool,
+    /// Whether to reduce a congestion window that is not being used.
+    cwv: bool,
+    /// Whether to reduce the congestion window in proportion to the fraction of CE marks.
+    dctcp: bool,
+    /// The initial congestion window, in packets.
+    initial_cwnd: usize,
+    /// The number of PTO periods that a run of lost packets has to span to be
+    /// declared persistent congestion.
+    persistent_congestion_threshold: u32,
+    /// The number of later packets that need to be acknowledged before a
+    /// packet is declared lost.
+    packet_threshold: u64,
+    /// How long after a later packet is acknowledged a packet is declared
+    /// lost, as a multiple of the RTT, scaled by `TIME_THRESHOLD_SCALE`.
+    time_threshold: u8,
+    /// Whether persistent congestion requires an RTT sample.
+    persistent_congestion_rtt_sample: bool,
+    /// What to do with the congestion window after an idle period.
+    idle_restart: IdleRestart,
     /// Initial connection-level flow control limit.
     max_data: u64,
     /// Initial flow control limit for receiving data on bidirectional streams that the peer
@@ -130,6 +162,9 @@ pub struct ConnectionParameters {
     /// acknowledgments every round trip, set the value to `5 * ACK_RATIO_SCALE`.
     /// Values less than `ACK_RATIO_SCALE` are clamped to `ACK_RATIO_SCALE`.
     ack_ratio: u8,
+    /// Whether to send IMMEDIATE_ACK frames to a peer that supports the ACK
+    /// frequency extension.
+    immediate_ack: bool,
     /// The duration of the idle timeout for the connection.
     idle_timeout: Duration,
     preferred_address: PreferredAddressConfig,
@@ -141,6 +176,21 @@ pub struct ConnectionParameters {
     grease: bool,
     disable_migration: bool,
     pacing: bool,
+    /// The number of packets that the pacer lets out in a burst.
+    pacing_burst: usize,
+    /// How much faster than the congestion window the pacer sends, in percent.
+    pacing_gain: usize,
+    /// The pacing gain in slow start, in percent.
+    pacing_gain_slow_start: usize,
+    /// Whether packets are paced before the handshake is confirmed.
+    handshake_pacing: bool,
+    /// How many round trips without sending refill the pacer.
+    pacing_idle_reset: u32,
+    /// How far ahead of their paced departure time packets may be released,
+    /// for the OS to pace them.  Zero disables this.
+    pacing_horizon: Duration,
+    /// The DSCP codepoint to mark outgoing packets with.
+    dscp: Dscp,
     /// Whether the connection performs PLPMTUD.
     pmtud: bool,
     /// Whether PMTUD should take the local interface MTU into account.
@@ -149,6 +199,13 @@ pub struct ConnectionParameters {
     sni_slicing: bool,
     /// Whether to enable mlkem768nistp256-sha256.
     mlkem: bool,
+    /// Whether the hybrid ML-KEM group is advertised ahead of the classical groups.
+    /// If not, it is listed last and no key share is sent for it, so it is only
+    /// negotiated if the server asks for it in a `HelloRetryRequest`.
+    mlkem_preferred: bool,
+    /// Whether to fail authentication of a peer certificate that requires
+    /// a stapled OCSP response ("must-staple") if the peer did not provide one.
+    must_staple: bool,
     /// Whether to randomize the packet number of the first Initial packet.
     randomize_first_pn: bool,
     /// Whether to send the SCONE transport parameter.
@@ -160,7 +217,18 @@ impl Default for ConnectionParameters {
         Self {
             versions: version::Config::default(),
             congestion_control: CongestionControl::Cubic,
+            congestion_controller: None,
             slow_start: SlowStart::Classic,
+            hystart_config: HyStartConfig::default(),
+            prr: false,
+            cwv: false,
+            dctcp: false,
+            initial_cwnd: CWND_INITIAL_PKTS,
+            persistent_congestion_threshold: PERSISTENT_CONG_THRESH,
+            packet_threshold: PACKET_THRESHOLD,
+            time_threshold: TIME_THRESHOLD,
+            persistent_congestion_rtt_sample: true,
+            idle_restart: IdleRestart::Off,
             max_data: INITIAL_LOCAL_MAX_DATA,
             max_stream_data_bidi_remote: u64::try_from(INITIAL_LOCAL_MAX_STREAM_DATA)
                 .expect("usize fits in u64"),
@@ -171,6 +239,7 @@ impl Default for ConnectionParameters {
             max_streams_bidi: LOCAL_STREAM_LIMIT_BIDI,
             max_streams_uni: LOCAL_STREAM_LIMIT_UNI,
             ack_ratio: Self::DEFAULT_ACK_RATIO,
+            immediate_ack: false,
             idle_timeout: Self::DEFAULT_IDLE_TIMEOUT,
             preferred_address: PreferredAddressConfig::Default,
             datagram_size: MAX_DATAGRAM_FRAME_SIZE,
@@ -181,10 +250,19 @@ impl Default for ConnectionParameters {
             grease: true,
             disable_migration: false,
             pacing: true,
+            pacing_burst: PACING_BURST_SIZE,
+            pacing_gain: Pacer::GAIN,
+            pacing_gain_slow_start: Pacer::GAIN,
+            handshake_pacing: true,
+            pacing_idle_reset: 1,
+            pacing_horizon: Duration::ZERO,
+            dscp: Dscp::Cs0,
             pmtud: false,
             pmtud_iface_mtu: true,
             sni_slicing: true,
             mlkem: true,
+            mlkem_preferred: true,
+            must_staple: false,
             randomize_first_pn: true,
             scone: false,
         }
@@ -230,6 +308,27 @@ impl ConnectionParameters {
         self
     }
 
+    #[must_use]
+    pub const fn get_congestion_controller(&self) -> Option<&CongestionControllerFactory> {
+        self.congestion_controller.as_ref()
+    }
+
+    /// Use a congestion controller that the application provides, instead of
+    /// one of the algorithms in [`CongestionControl`].
+    #[must_use]
+    pub fn congestion_controller(mut self, v: CongestionControllerFactory) -> Self {
+        self.congestion_controller = Some(v);
+        self
+    }
+
+    /// Switch to `v`, dropping any congestion controller from the application.
+    #[must_use]
+    pub(crate) fn replace_congestion_control(mut self, v: CongestionControl) -> Self {
+        self.congestion_control = v;
+        self.congestion_controller = None;
+        self
+    }
+
     #[must_use]
     pub const fn get_slow_start(&self) -> SlowStart {
         self.slow_start
@@ -241,6 +340,190 @@ impl ConnectionParameters {
         self
     }
 
+    #[must_use]
+    pub const fn get_hystart_config(&self) -> HyStartConfig {
+        self.hystart_config
+    }
+
+    /// Tune HyStart++, for paths where the constants that RFC 9406 recommends
+    /// don't suit, such as those with a long or variable RTT.  This only applies
+    /// when the slow start algorithm is [`SlowStart::HyStart`].
+    ///
+    /// # Panics
+    ///
+    /// When either of the divisors in `v` is zero.
+    #[must_use]
+    pub const fn hystart_config(mut self, v: HyStartConfig) -> Self {
+        assert!(v.min_rtt_divisor > 0 && v.css_growth_divisor > 0);
+        self.hystart_config = v;
+        self
+    }
+
+    #[must_use]
+    pub const fn prr_enabled(&self) -> bool {
+        self.prr
+    }
+
+    /// Use Proportional Rate Reduction ([RFC 6937]) to spread the reduction of the
+    /// congestion window over the recovery period.  This only applies to `NewReno`,
+    /// `Cubic` and `Westwood`.
+    ///
+    /// [RFC 6937]: https://datatracker.ietf.org/doc/html/rfc6937
+    #[must_use]
+    pub const fn prr(mut self, prr: bool) -> Self {
+        self.prr = prr;
+        self
+    }
+
+    #[must_use]
+    pub const fn cwv_enabled(&self) -> bool {
+        self.cwv
+    }
+
+    /// Use congestion window validation ([RFC 7661]), which reduces the congestion
+    /// window after a long period where the application does not use it.  This only
+    /// applies to `NewReno`, `Cubic` and `Westwood`.
+    ///
+    /// [RFC 7661]: https://datatracker.ietf.org/doc/html/rfc7661
+    #[must_use]
+    pub const fn cwv(mut self, cwv: bool) -> Self {
+        self.cwv = cwv;
+        self
+    }
+
+    #[must_use]
+    pub const fn dctcp_enabled(&self) -> bool {
+        self.dctcp
+    }
+
+    /// Respond to ECN as DCTCP does ([RFC 8257]), reducing the congestion window in
+    /// proportion to the fraction of packets that are marked CE each round, rather
+    /// than by as much as for a loss.  This suits networks that mark early, as L4S
+    /// queues do.  This only applies to `NewReno`, `Cubic` and `Westwood`; `Prague`
+    /// always responds this way.
+    ///
+    /// [RFC 8257]: https://datatracker.ietf.org/doc/html/rfc8257
+    #[must_use]
+    pub const fn dctcp(mut self, dctcp: bool) -> Self {
+        self.dctcp = dctcp;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_initial_cwnd(&self) -> usize {
+        self.initial_cwnd
+    }
+
+    /// Set the initial congestion window, in packets.  The default is 10 packets,
+    /// as recommended in RFC 9002.  This does not apply to
+    /// a congestion controller set with [`Self::congestion_controller`].
+    ///
+    /// # Panics
+    ///
+    /// If `packets` is less than 2, which is the minimum congestion window.
+    #[must_use]
+    pub const fn initial_cwnd(mut self, packets: usize) -> Self {
+        assert!(packets >= 2, "initial congestion window is too small");
+        self.initial_cwnd = packets;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_persistent_congestion_threshold(&self) -> u32 {
+        self.persistent_congestion_threshold
+    }
+
+    /// Set how many PTO periods a run of lost packets has to span for the loss to be
+    /// declared persistent congestion, which collapses the congestion window.  The
+    /// default is 3, as recommended in RFC 9002.  A larger value avoids a collapse
+    /// on paths where losses in long bursts are not caused by congestion.
+    ///
+    /// # Panics
+    ///
+    /// When `v` is 0.
+    #[must_use]
+    pub const fn persistent_congestion_threshold(mut self, v: u32) -> Self {
+        assert!(v > 0, "persistent congestion threshold is zero");
+        self.persistent_congestion_threshold = v;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_packet_threshold(&self) -> u64 {
+        self.packet_threshold
+    }
+
+    /// Set the packet reordering threshold for loss detection: how many packets
+    /// sent after a packet need to be acknowledged before that packet is declared
+    /// lost.  The default is 3, as recommended in RFC 9002.  A larger value avoids
+    /// spurious retransmissions and congestion window reductions on paths that
+    /// reorder packets, at the cost of detecting real losses later.
+    ///
+    /// # Panics
+    ///
+    /// When `packets` is 0.
+    #[must_use]
+    pub const fn packet_threshold(mut self, packets: u64) -> Self {
+        assert!(packets > 0, "packet threshold is zero");
+        self.packet_threshold = packets;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_time_threshold(&self) -> u8 {
+        self.time_threshold
+    }
+
+    /// Set the time threshold for loss detection: how long, as a multiple of the
+    /// RTT, a packet can go unacknowledged after a packet sent later is
+    /// acknowledged before it is declared lost.  The value is scaled by
+    /// `TIME_THRESHOLD_SCALE`, so that the default of 9 is 9/8 of an RTT, as
+    /// recommended in RFC 9002.
+    ///
+    /// # Panics
+    ///
+    /// When `scaled` is less than `TIME_THRESHOLD_SCALE`, which would declare
+    /// packets lost before their acknowledgment could arrive.
+    #[must_use]
+    pub const fn time_threshold(mut self, scaled: u8) -> Self {
+        assert!(
+            scaled >= TIME_THRESHOLD_SCALE,
+            "time threshold is less than one RTT"
+        );
+        self.time_threshold = scaled;
+        self
+    }
+
+    #[must_use]
+    pub const fn persistent_congestion_rtt_sample_required(&self) -> bool {
+        self.persistent_congestion_rtt_sample
+    }
+
+    /// Set whether persistent congestion can only be declared once there is an RTT
+    /// sample, and only for packets sent after it.  This is the default, as RFC 9002
+    /// requires.  Without a sample, the PTO period is based on the initial RTT.
+    #[must_use]
+    pub const fn persistent_congestion_rtt_sample(mut self, required: bool) -> Self {
+        self.persistent_congestion_rtt_sample = required;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_idle_restart(&self) -> IdleRestart {
+        self.idle_restart
+    }
+
+    /// Set what happens to the congestion window when sending resumes after
+    /// nothing was sent for at least a retransmission timeout, while nothing was
+    /// in flight.  A window from before the idle period might no longer suit the
+    /// path, so sending it all at once risks loss.  The default keeps it.  This
+    /// only applies to `NewReno`, `Cubic` and `Westwood`.
+    #[must_use]
+    pub const fn idle_restart(mut self, v: IdleRestart) -> Self {
+        self.idle_restart = v;
+        self
+    }
+
     #[must_use]
     pub const fn get_max_data(&self) -> u64 {
         self.max_data
@@ -333,6 +616,22 @@ impl ConnectionParameters {
         self.ack_ratio
     }
 
+    #[must_use]
+    pub const fn immediate_ack_enabled(&self) -> bool {
+        self.immediate_ack
+    }
+
+    /// Whether to ask for an immediate acknowledgment of probes, using an
+    /// IMMEDIATE_ACK frame instead of a PING.  This only applies when the peer
+    /// supports the ACK frequency extension and so might otherwise delay its
+    /// acknowledgment.  This is off by default, as peers that implement older
+    /// versions of the extension might not understand the frame.
+    #[must_use]
+    pub const fn immediate_ack(mut self, immediate_ack: bool) -> Self {
+        self.immediate_ack = immediate_ack;
+        self
+    }
+
     /// # Panics
     ///
     /// If `timeout` is 2^62 milliseconds or more.
@@ -449,6 +748,141 @@ impl ConnectionParameters {
         self
     }
 
+    #[must_use]
+    pub const fn get_pacing_burst(&self) -> usize {
+        self.pacing_burst
+    }
+
+    /// Set the number of packets that the pacer can send in a burst, after
+    /// it has accumulated enough credit.  The default is 2 packets.  Larger
+    /// bursts need fewer timers, but put more stress on queues in the network.
+    ///
+    /// # Panics
+    ///
+    /// If `packets` is zero.
+    #[must_use]
+    pub const fn pacing_burst(mut self, packets: usize) -> Self {
+        assert!(packets > 0, "pacing burst must be at least one packet");
+        self.pacing_burst = packets;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_pacing_gain(&self) -> usize {
+        self.pacing_gain
+    }
+
+    /// Set the pacing gain, in percent.  The pacer sends at the rate of one
+    /// congestion window per round trip, multiplied by this gain.  RFC 9002 recommends a
+    /// gain that is small, but more than 100%, so that pacing doesn't hold
+    /// back a congestion window that is growing.  The default is 200%.
+    ///
+    /// # Panics
+    ///
+    /// If `percent` is less than 100.
+    #[must_use]
+    pub const fn pacing_gain(mut self, percent: usize) -> Self {
+        assert!(percent >= 100, "pacing gain must be at least 100%");
+        self.pacing_gain = percent;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_pacing_gain_slow_start(&self) -> usize {
+        self.pacing_gain_slow_start
+    }
+
+    /// Set the pacing gain, in percent, that applies during slow start.  Slow
+    /// start doubles the congestion window every round trip, so this is
+    /// usually higher than [`Self::pacing_gain`].  The default is 200%.
+    ///
+    /// # Panics
+    ///
+    /// If `percent` is less than 100.
+    #[must_use]
+    pub const fn pacing_gain_slow_start(mut self, percent: usize) -> Self {
+        assert!(percent >= 100, "pacing gain must be at least 100%");
+        self.pacing_gain_slow_start = percent;
+        self
+    }
+
+    #[must_use]
+    pub const fn handshake_pacing_enabled(&self) -> bool {
+        self.handshake_pacing
+    }
+
+    /// Whether to pace packets before the handshake is confirmed.  If not, the
+    /// handshake flights go out as fast as the congestion window and the
+    /// amplification limit allow, which avoids adding latency to connection
+    /// establishment on paths with a long RTT.  The default is to pace.
+    #[must_use]
+    pub const fn handshake_pacing(mut self, handshake_pacing: bool) -> Self {
+        self.handshake_pacing = handshake_pacing;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_pacing_idle_reset(&self) -> u32 {
+        self.pacing_idle_reset
+    }
+
+    /// Set how long, in round trips, the connection needs to go without
+    /// sending before the pacer is refilled to its full burst.  Time spent
+    /// waiting for the pacer doesn't count, so that a low pacing rate isn't
+    /// mistaken for an idle period.  Any debt that the pacer carries from
+    /// before then is dropped.  The default is 1.
+    ///
+    /// # Panics
+    ///
+    /// If `rtts` is zero.
+    #[must_use]
+    pub const fn pacing_idle_reset(mut self, rtts: u32) -> Self {
+        assert!(
+            rtts > 0,
+            "pacing idle reset must be at least one round trip"
+        );
+        self.pacing_idle_reset = rtts;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_pacing_horizon(&self) -> Duration {
+        self.pacing_horizon
+    }
+
+    /// Offload pacing to the OS.  Packets are released up to `horizon` ahead of
+    /// the time the pacer would send them, with that departure time attached
+    /// to the [`neqo_common::datagram::Batch`] (see
+    /// [`neqo_common::datagram::Batch::txtime`]).  The application then needs
+    /// to pass the departure time on to the OS, e.g. with `SO_TXTIME` on Linux.
+    ///
+    /// This only has an effect if pacing is enabled.  A value of zero, the
+    /// default, keeps pacing entirely in the stack.
+    #[must_use]
+    pub const fn pacing_horizon(mut self, horizon: Duration) -> Self {
+        self.pacing_horizon = horizon;
+        self
+    }
+
+    #[must_use]
+    pub const fn get_dscp(&self) -> Dscp {
+        self.dscp
+    }
+
+    /// Mark outgoing packets with the DSCP codepoint `dscp`, e.g. [`Dscp::Le`]
+    /// for background transfers or [`Dscp::Ef`] for interactive traffic.  The
+    /// ECN bits are managed separately.  The default is [`Dscp::Cs0`].
+    ///
+    /// The codepoint is carried in the TOS of each [`neqo_common::datagram::Batch`];
+    /// the application needs to pass it on to the OS, as `neqo_udp` does.
+    /// `neqo_udp` can only set the DSCP on Linux and Android; elsewhere, it only
+    /// sets the ECN bits and logs a warning.
+    #[must_use]
+    pub const fn dscp(mut self, dscp: Dscp) -> Self {
+        self.dscp = dscp;
+        self
+    }
+
     #[must_use]
     pub const fn pmtud_enabled(&self) -> bool {
         self.pmtud
@@ -493,6 +927,33 @@ impl ConnectionParameters {
         self
     }
 
+    #[must_use]
+    pub const fn is_mlkem_preferred(&self) -> bool {
+        self.mlkem_preferred
+    }
+
+    /// Set whether the hybrid ML-KEM group is preferred over the classical groups.
+    /// This has no effect if ML-KEM is disabled.
+    #[must_use]
+    pub const fn mlkem_preferred(mut self, mlkem_preferred: bool) -> Self {
+        self.mlkem_preferred = mlkem_preferred;
+        self
+    }
+
+    #[must_use]
+    pub const fn must_staple_enforced(&self) -> bool {
+        self.must_staple
+    }
+
+    /// Set whether to enforce must-staple certificates.  When enabled, a peer
+    /// certificate that requires a stapled OCSP response fails authentication
+    /// if no response was stapled, without asking the application.
+    #[must_use]
+    pub const fn must_staple(mut self, must_staple: bool) -> Self {
+        self.must_staple = must_staple;
+        self
+    }
+
     #[must_use]
     pub const fn randomize_first_pn_enabled(&self) -> bool {
         self.randomize_first_pn
@@ -606,4 +1067,13 @@ mod tests {
         let params = params.pmtud_iface_mtu(false);
         assert!(!params.pmtud_iface_mtu_enabled());
     }
+
+    #[test]
+    fn mlkem_preferred_default() {
+        let params = ConnectionParameters::default();
+        assert!(params.mlkem_enabled());
+        assert!(params.is_mlkem_preferred());
+        let params = params.mlkem_preferred(false);
+        assert!(!params.is_mlkem_preferred());
+    }
 }
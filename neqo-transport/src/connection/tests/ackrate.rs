@@ -201,3 +201,39 @@ fn migrate_ack_delay() {
     assert!(af.is_some());
     assert_eq!(client.stats().frame_tx.ack_frequency, ad_before + 1);
 }
+
+/// A PTO probe asks a peer that supports ACK frequency for an immediate
+/// acknowledgment, if that is enabled.
+#[test]
+fn immediate_ack_on_pto() {
+    let mut client = new_client(ConnectionParameters::default().immediate_ack(true));
+    let mut server = default_server();
+    let mut now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    // Lose a packet, then wait for the PTO timer.
+    _ = send_something(&mut client, now);
+    now += client.process_output(now).callback();
+
+    let pings = client.stats().frame_tx.ping;
+    let probe = client.process_output(now).dgram();
+    assert!(probe.is_some());
+    assert_eq!(client.stats().frame_tx.immediate_ack, 1);
+    assert_eq!(client.stats().frame_tx.ping, pings);
+
+    let ack = server.process(probe, now).dgram();
+    assert!(ack.is_some());
+    assert_eq!(server.stats().frame_rx.immediate_ack, 1);
+}
+
+/// Without the option, probes carry a PING as before.
+#[test]
+fn immediate_ack_disabled() {
+    let mut client = default_client();
+    let mut server = default_server();
+    let mut now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    _ = send_something(&mut client, now);
+    now += client.process_output(now).callback();
+    assert!(client.process_output(now).dgram().is_some());
+    assert_eq!(client.stats().frame_tx.immediate_ack, 0);
+}
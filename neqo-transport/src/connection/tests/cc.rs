@@ -4,18 +4,22 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::time::Duration;
+use std::{cell::Cell, num::NonZeroUsize, rc::Rc, time::Duration};
 
-use neqo_common::{Datagram, Ecn, qdebug, qinfo};
+use neqo_common::{Datagram, Ecn, Encoder, qdebug, qinfo};
+use test_fixture::new_neqo_qlog;
 
 use super::{
-    super::Output, CLIENT_HANDSHAKE_1RTT_PACKETS, DEFAULT_RTT, POST_HANDSHAKE_CWND, ack_bytes,
-    assert_full_cwnd, connect_rtt_idle, cwnd, cwnd_avail, cwnd_packets, default_client,
-    default_server, fill_cwnd, induce_persistent_congestion, send_something,
+    super::Output, AT_LEAST_PTO, CLIENT_HANDSHAKE_1RTT_PACKETS, DEFAULT_RTT, POST_HANDSHAKE_CWND,
+    ack_bytes, assert_full_cwnd, connect_rtt_idle, cwnd, cwnd_avail, cwnd_min, cwnd_packets,
+    default_client, default_server, fill_cwnd, induce_persistent_congestion, send_something,
 };
 use crate::{
-    CongestionControl, ConnectionParameters,
+    CongestionControl, CongestionControllerFactory, CongestionPhase, CongestionState,
+    ConnectionParameters, Error, IdleRestart, LimitingFactor,
+    cc::Prague,
     connection::tests::{connect_with_rtt, new_client, new_server, now},
+    pace::Pacer,
     packet,
     recovery::{ACK_ONLY_SIZE_LIMIT, PACKET_THRESHOLD},
     sender::PACING_BURST_SIZE,
@@ -36,6 +40,20 @@ fn cc_slow_start() {
     assert!(cwnd_avail(&client) < ACK_ONLY_SIZE_LIMIT);
 }
 
+#[test]
+/// Verify a configured initial CWND is honored.
+fn cc_slow_start_initial_cwnd() {
+    const PACKETS: usize = 20;
+    let mut client = new_client(ConnectionParameters::default().initial_cwnd(PACKETS));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let stream_id = client.stream_create(StreamType::UniDi).unwrap();
+    let (c_tx_dgrams, _) = fill_cwnd(&mut client, stream_id, now);
+    assert_full_cwnd(&c_tx_dgrams, PACKETS * client.plpmtu(), client.plpmtu());
+    assert!(cwnd_avail(&client) < ACK_ONLY_SIZE_LIMIT);
+}
+
 #[test]
 fn cc_slow_start_pmtud() {
     let mut client = new_client(ConnectionParameters::default().pmtud(true));
@@ -329,6 +347,37 @@ fn cc_slow_start_to_persistent_congestion_some_acks() {
     induce_persistent_congestion(&mut client, &mut server, stream, now);
 }
 
+#[test]
+/// With a larger persistent congestion threshold, the same losses that would
+/// cause persistent congestion only reduce the window.
+fn cc_persistent_congestion_threshold() {
+    let mut client =
+        new_client(ConnectionParameters::default().persistent_congestion_threshold(100));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let stream = client.stream_create(StreamType::BiDi).unwrap();
+    let (c_tx_dgrams, mut now) = fill_cwnd(&mut client, stream, now);
+    now += DEFAULT_RTT / 2;
+    drop(ack_bytes(&mut server, stream, c_tx_dgrams, now));
+
+    // Lose everything for three PTOs, as `induce_persistent_congestion` does.
+    for backoff in [1, 2] {
+        now += AT_LEAST_PTO * backoff;
+        let (c_tx_dgrams, next_now) = fill_cwnd(&mut client, stream, now);
+        now = next_now;
+        assert_eq!(c_tx_dgrams.len(), 2); // Two PTO packets
+    }
+    now += AT_LEAST_PTO * 4;
+    let (c_tx_dgrams, now) = fill_cwnd(&mut client, stream, now);
+    assert_eq!(c_tx_dgrams.len(), 2);
+
+    let s_ack = ack_bytes(&mut server, stream, c_tx_dgrams, now);
+    client.process_input(s_ack, now);
+    assert!(cwnd(&client) > cwnd_min(&client));
+    assert!(cwnd(&client) < POST_HANDSHAKE_CWND);
+}
+
 #[test]
 /// Verify persistent congestion moves to slow start after recovery period
 /// ends.
@@ -371,6 +420,50 @@ fn cc_persistent_congestion_to_slow_start() {
     // ACKing 2 packets should let client send 4.
     let (c_tx_dgrams, _) = fill_cwnd(&mut client, stream, now);
     assert_eq!(c_tx_dgrams.len(), 4);
+
+    let stats = client.stats().cc;
+    assert_eq!(stats.persistent_congestion, 1);
+    assert_eq!(stats.slow_start_exits, 1);
+    assert_eq!(stats.recovery_episodes, 1);
+    assert_eq!(stats.cwnd_min, Some(cwnd_min(&client)));
+    assert!(stats.cwnd_max.unwrap() >= POST_HANDSHAKE_CWND);
+    assert!(stats.ssthresh_updates >= 1);
+    assert!(stats.ssthresh_min.unwrap() <= stats.ssthresh_max.unwrap());
+}
+
+/// Grow the congestion window to twice the initial window, leave the connection
+/// idle for longer than a PTO, then fill the congestion window again.
+fn idle_then_fill_cwnd(restart: IdleRestart) -> (Vec<Datagram>, usize) {
+    let mut client = new_client(ConnectionParameters::default().idle_restart(restart));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    let stream = client.stream_create(StreamType::BiDi).unwrap();
+
+    let (c_tx_dgrams, mut now) = fill_cwnd(&mut client, stream, now);
+    now += DEFAULT_RTT / 2;
+    let s_ack = ack_bytes(&mut server, stream, c_tx_dgrams, now);
+    now += DEFAULT_RTT / 2;
+    client.process_input(s_ack, now);
+    assert!(cwnd(&client) > POST_HANDSHAKE_CWND);
+
+    now += AT_LEAST_PTO;
+    let (c_tx_dgrams, _) = fill_cwnd(&mut client, stream, now);
+    (c_tx_dgrams, client.plpmtu())
+}
+
+#[test]
+/// Verify that the congestion window is kept after an idle period by default.
+fn cc_idle_restart_off() {
+    let (c_tx_dgrams, plpmtu) = idle_then_fill_cwnd(IdleRestart::Off);
+    assert_full_cwnd(&c_tx_dgrams, POST_HANDSHAKE_CWND * 2, plpmtu);
+}
+
+#[test]
+/// Verify that the congestion window goes back to the initial window after an
+/// idle period, if that is configured.
+fn cc_idle_restart_reset() {
+    let (c_tx_dgrams, plpmtu) = idle_then_fill_cwnd(IdleRestart::Reset);
+    assert_full_cwnd(&c_tx_dgrams, POST_HANDSHAKE_CWND, plpmtu);
 }
 
 #[test]
@@ -460,3 +553,260 @@ fn pace() {
     assert_ne!(fin, Duration::new(0, 0));
     assert_ne!(fin, gap);
 }
+
+/// A larger pacing burst lets more packets out before the pacer holds them back.
+#[test]
+fn pace_burst() {
+    const DATA: &[u8] = &[0xcc; 4_096];
+    const BURST: usize = 4;
+    let mut client = new_client(ConnectionParameters::default().pacing_burst(BURST));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let stream = client.stream_create(StreamType::BiDi).unwrap();
+    while client.stream_send(stream, DATA).unwrap() == DATA.len() {}
+
+    // The first packet is not paced, then the burst follows.
+    for _ in 0..=BURST {
+        assert!(client.process_output(now).dgram().is_some());
+    }
+    assert_ne!(client.process_output(now).callback(), Duration::ZERO);
+}
+
+/// The pacing rate is logged to qlog, sampled once per round trip.
+#[test]
+fn pace_qlog() {
+    const DATA: &[u8] = &[0xcc; 4_096];
+    let mut client = default_client();
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    let (log, contents) = new_neqo_qlog();
+    client.set_qlog(log);
+
+    let stream = client.stream_create(StreamType::BiDi).unwrap();
+    while client.stream_send(stream, DATA).unwrap() == DATA.len() {}
+    for _ in 0..=PACING_BURST_SIZE {
+        assert!(client.process_output(now).dgram().is_some());
+    }
+    drop(client);
+
+    let contents = contents.to_string();
+    assert_eq!(contents.matches(r#""pacing_rate":"#).count(), 1);
+}
+
+/// A higher pacing gain in slow start shortens the gap between paced packets.
+#[test]
+fn pace_gain_slow_start() {
+    fn gap(params: ConnectionParameters) -> Duration {
+        const DATA: &[u8] = &[0xcc; 4_096];
+        let mut client = new_client(params);
+        let mut server = default_server();
+        let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+        let stream = client.stream_create(StreamType::BiDi).unwrap();
+        while client.stream_send(stream, DATA).unwrap() == DATA.len() {}
+        for _ in 0..=PACING_BURST_SIZE {
+            assert!(client.process_output(now).dgram().is_some());
+        }
+        client.process_output(now).callback()
+    }
+
+    let default = gap(ConnectionParameters::default());
+    let faster = gap(ConnectionParameters::default().pacing_gain_slow_start(4 * Pacer::GAIN));
+    assert!(faster < default, "{faster:?} < {default:?}");
+    assert_eq!(
+        gap(ConnectionParameters::default().pacing_gain(4 * Pacer::GAIN)),
+        default,
+        "The congestion avoidance gain doesn't apply in slow start"
+    );
+}
+
+#[test]
+fn pace_offload() {
+    const DATA: &[u8] = &[0xcc; 4_096];
+    let mut client = new_client(ConnectionParameters::default().pacing_horizon(DEFAULT_RTT));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let stream = client.stream_create(StreamType::BiDi).unwrap();
+    while client.stream_send(stream, DATA).unwrap() == DATA.len() {}
+
+    // With a generous horizon, the whole congestion window is released at
+    // once, with departure times that increase as the pacer would have sent.
+    let mut txtimes = Vec::new();
+    while let Some(batch) = client
+        .process_multiple_output(now, NonZeroUsize::MIN)
+        .dgram()
+    {
+        txtimes.push(batch.txtime().unwrap());
+    }
+    assert_eq!(
+        txtimes.len(),
+        cwnd_packets(POST_HANDSHAKE_CWND, client.plpmtu())
+    );
+    assert_eq!(txtimes[0], now);
+    assert!(txtimes.is_sorted());
+    assert!(txtimes.last().unwrap() > &now);
+}
+
+#[test]
+fn cc_snapshot() {
+    let mut client = default_client();
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let snapshot = client.congestion_snapshot(now).unwrap();
+    assert_eq!(snapshot.cwnd, cwnd(&client));
+    assert_eq!(snapshot.ssthresh, usize::MAX);
+    assert_eq!(snapshot.phase, CongestionPhase::SlowStart);
+    assert_eq!(snapshot.smoothed_rtt, DEFAULT_RTT);
+    assert_eq!(snapshot.limit, LimitingFactor::AppLimited);
+
+    let stream_id = client.stream_create(StreamType::UniDi).unwrap();
+    let (_, now) = fill_cwnd(&mut client, stream_id, now);
+    let snapshot = client.congestion_snapshot(now).unwrap();
+    assert_eq!(snapshot.bytes_in_flight, client.stats().bytes_in_flight);
+    assert_eq!(snapshot.limit, LimitingFactor::Cwnd);
+}
+
+#[test]
+fn cc_state() {
+    let mut client = default_client();
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let state = client.cc_state().unwrap();
+    assert_eq!(state.cwnd, cwnd(&client));
+    assert_eq!(state.ssthresh, usize::MAX);
+    assert_eq!(state.phase, CongestionPhase::SlowStart);
+    assert_eq!(state.window_max, None);
+
+    // Lose the first packet, so that Cubic reduces the congestion window.
+    let stream_id = client.stream_create(StreamType::UniDi).unwrap();
+    let (mut dgrams, now) = fill_cwnd(&mut client, stream_id, now);
+    dgrams.remove(0);
+    let ack = ack_bytes(&mut server, stream_id, dgrams, now);
+    client.process_input(ack, now);
+
+    let state = client.cc_state().unwrap();
+    assert_eq!(state.cwnd, cwnd(&client));
+    assert_eq!(state.ssthresh, state.cwnd);
+    assert_eq!(state.phase, CongestionPhase::Recovery);
+    assert!(state.window_max.unwrap() > state.cwnd);
+
+    let mut enc = Encoder::default();
+    state.encode(&mut enc);
+    assert_eq!(CongestionState::decode(&mut enc.as_decoder()), Some(state));
+}
+
+/// A cap on the sending rate paces packets, even when pacing is disabled.
+#[test]
+fn max_send_rate() {
+    const DATA: &[u8] = &[0xcc; 4_096];
+    const RATE: u64 = 10_000;
+    let mut client = new_client(ConnectionParameters::default().pacing(false));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    assert_eq!(client.stats().max_send_rate, None);
+
+    client.set_max_send_rate(Some(RATE), now);
+    assert_eq!(client.stats().max_send_rate, Some(RATE));
+
+    let stream = client.stream_create(StreamType::BiDi).unwrap();
+    while client.stream_send(stream, DATA).unwrap() == DATA.len() {}
+
+    // The pacer allows a burst, then holds packets back for as long as
+    // sending one packet takes at the capped rate.
+    for _ in 0..=PACING_BURST_SIZE {
+        assert!(client.process_output(now).dgram().is_some());
+    }
+    let gap = client.process_output(now).callback();
+    let per_packet = Duration::from_secs(1) * u32::try_from(client.plpmtu()).unwrap()
+        / u32::try_from(RATE).unwrap();
+    assert!(gap > per_packet / 2, "gap {gap:?}");
+    assert_eq!(
+        client.congestion_snapshot(now).unwrap().limit,
+        LimitingFactor::Pacing
+    );
+
+    // Removing the cap lets the rest of the congestion window out.
+    client.set_max_send_rate(None, now);
+    assert_eq!(client.stats().max_send_rate, None);
+    assert!(client.process_output(now).dgram().is_some());
+}
+
+/// An application can supply its own congestion controller.
+#[test]
+fn congestion_controller_factory() {
+    let made = Rc::new(Cell::new(0));
+    let made_factory = Rc::clone(&made);
+    let factory = CongestionControllerFactory::new(move |pmtud| {
+        made_factory.set(made_factory.get() + 1);
+        Box::new(Prague::new(pmtud))
+    });
+    let mut client = new_client(ConnectionParameters::default().congestion_controller(factory));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    assert!(made.get() > 0);
+
+    // The supplied controller is used in place of the configured one.
+    let client_pkt = send_something(&mut client, now);
+    assert_eq!(Ecn::from(client_pkt.tos()), Ecn::Ect1);
+}
+
+/// Switching to a different congestion control algorithm keeps what is in flight.
+#[test]
+fn set_cc_algorithm() {
+    let mut client =
+        new_client(ConnectionParameters::default().congestion_control(CongestionControl::NewReno));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+
+    let stream_id = client.stream_create(StreamType::UniDi).unwrap();
+    let (dgrams, now) = fill_cwnd(&mut client, stream_id, now);
+    let in_flight = client.stats().bytes_in_flight;
+    assert!(in_flight > 0);
+
+    client
+        .set_cc_algorithm(CongestionControl::Bbr, now)
+        .unwrap();
+    assert_eq!(
+        client.conn_params.get_congestion_control(),
+        CongestionControl::Bbr
+    );
+    assert_eq!(client.stats().bytes_in_flight, in_flight);
+
+    // The new controller releases what was in flight when it is acknowledged.
+    let ack = ack_bytes(&mut server, stream_id, dgrams, now);
+    client.process_input(ack, now);
+    assert_eq!(client.stats().bytes_in_flight, 0);
+}
+
+/// Switching between L4S and classic congestion control is refused, as packets
+/// would still be marked for the old one.
+#[test]
+fn set_cc_algorithm_l4s() {
+    let mut client =
+        new_client(ConnectionParameters::default().congestion_control(CongestionControl::Prague));
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    assert_eq!(
+        client.set_cc_algorithm(CongestionControl::Cubic, now),
+        Err(Error::InvalidInput)
+    );
+    assert_eq!(
+        client.conn_params.get_congestion_control(),
+        CongestionControl::Prague
+    );
+
+    let mut client = default_client();
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    assert_eq!(
+        client.set_cc_algorithm(CongestionControl::Prague, now),
+        Err(Error::InvalidInput)
+    );
+    client
+        .set_cc_algorithm(CongestionControl::Bbr, now)
+        .unwrap();
+}
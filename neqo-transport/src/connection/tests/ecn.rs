@@ -6,7 +6,7 @@
 
 use std::{ptr::fn_addr_eq, time::Duration};
 
-use neqo_common::{Datagram, Ecn, Tos, event::Provider as _};
+use neqo_common::{Datagram, Dscp, Ecn, Tos, event::Provider as _};
 use strum::IntoEnumIterator as _;
 use test_fixture::{
     DEFAULT_ADDR_V4,
@@ -15,7 +15,7 @@ use test_fixture::{
 };
 
 use crate::{
-    ConnectionEvent, ConnectionId, ConnectionParameters, Output, StreamType,
+    CongestionControl, ConnectionEvent, ConnectionId, ConnectionParameters, Output, StreamType,
     connection::tests::{
         DEFAULT_RTT, assert_path_challenge_min_len, connect_force_idle,
         connect_force_idle_with_modifier, default_client, default_server, handshake_with_modifier,
@@ -264,6 +264,52 @@ fn stats() {
     }
 }
 
+/// A congestion controller for L4S has packets marked ECT(1), which still validates.
+#[test]
+fn l4s() {
+    let now = now();
+    let mut client =
+        new_client(ConnectionParameters::default().congestion_control(CongestionControl::Prague));
+    let mut server = default_server();
+    connect_force_idle(&mut client, &mut server);
+
+    let client_pkt = send_something(&mut client, now);
+    assert_eq!(Ecn::from(client_pkt.tos()), Ecn::Ect1);
+
+    for _ in 0..ecn::TEST_COUNT {
+        let ack = send_and_receive(&mut client, &mut server, now);
+        client.process_input(ack.unwrap(), now);
+    }
+    assert_eq!(
+        client.stats().ecn_path_validation[ecn::ValidationOutcome::Capable],
+        1
+    );
+    let client_pkt = send_something(&mut client, now);
+    assert_eq!(Ecn::from(client_pkt.tos()), Ecn::Ect1);
+    // The server doesn't use L4S.
+    let server_pkt = send_something(&mut server, now);
+    assert_eq!(Ecn::from(server_pkt.tos()), Ecn::Ect0);
+}
+
+#[test]
+fn dscp() {
+    let now = now();
+    let mut client = new_client(ConnectionParameters::default().dscp(Dscp::Le));
+    let mut server = default_server();
+    connect_force_idle(&mut client, &mut server);
+
+    // The DSCP is set alongside the ECN mark.
+    let client_pkt = send_something(&mut client, now);
+    assert_eq!(Dscp::from(client_pkt.tos()), Dscp::Le);
+    assert_ecn_enabled(client_pkt.tos());
+    let server_pkt = send_something(&mut server, now);
+    assert_eq!(Dscp::from(server_pkt.tos()), Dscp::Cs0);
+
+    client.set_dscp(Dscp::Ef);
+    let client_pkt = send_something(&mut client, now);
+    assert_eq!(Dscp::from(client_pkt.tos()), Dscp::Ef);
+}
+
 #[test]
 fn disables_on_loss() {
     let now = now();
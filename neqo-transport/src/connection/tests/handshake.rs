@@ -19,7 +19,13 @@ use std::{
 
 use neqo_common::{Datagram, event::Provider as _, qdebug};
 use neqo_crypto::{
-    AuthenticationStatus, constants::TLS_CHACHA20_POLY1305_SHA256, generate_ech_keys,
+    AuthenticationStatus, CertificateVerifier,
+    agent::CertificateInfo,
+    constants::{
+        TLS_AES_128_GCM_SHA256, TLS_CHACHA20_POLY1305_SHA256, TLS_GRP_EC_SECP256R1,
+        TLS_GRP_EC_X25519,
+    },
+    generate_ech_keys,
 };
 #[cfg(not(feature = "disable-encryption"))]
 use test_fixture::datagram;
@@ -153,6 +159,46 @@ fn handshake_failed_authentication() {
     assert_error(&server, &CloseReason::Transport(Error::Peer(300)));
 }
 
+#[test]
+fn certificate_verifier() {
+    struct Verifier(Rc<RefCell<Vec<String>>>);
+    impl CertificateVerifier for Verifier {
+        fn verify(
+            &mut self,
+            server_name: &str,
+            certificate: &CertificateInfo,
+        ) -> Option<AuthenticationStatus> {
+            assert!(certificate.iter().next().is_some());
+            self.0.borrow_mut().push(server_name.to_owned());
+            Some(AuthenticationStatus::Ok)
+        }
+    }
+
+    let names = Rc::new(RefCell::new(Vec::new()));
+    let mut client = default_client();
+    client.set_certificate_verifier(Box::new(Verifier(Rc::clone(&names))));
+    let mut server = default_server();
+    connect(&mut client, &mut server);
+    assert_eq!(*names.borrow(), [test_fixture::DEFAULT_SERVER_NAME]);
+    let authentication_needed = |e| matches!(e, ConnectionEvent::AuthenticationNeeded);
+    assert!(!client.events().any(authentication_needed));
+}
+
+#[test]
+fn stapled_ocsp_response() {
+    const OCSP_RESPONSE: &[u8] = b"fake ocsp response";
+    let mut client = default_client();
+    let mut server = default_server();
+    server
+        .server_set_stapled_ocsp_responses(test_fixture::DEFAULT_KEYS[0], &[OCSP_RESPONSE])
+        .unwrap();
+    connect(&mut client, &mut server);
+    let cert = client.peer_certificate().unwrap();
+    assert!(cert.has_stapled_ocsp_response());
+    assert_eq!(cert.stapled_ocsp_responses().unwrap(), &[OCSP_RESPONSE]);
+    assert!(!cert.must_staple());
+}
+
 #[test]
 fn no_alpn() {
     let mut client = default_client();
@@ -210,6 +256,58 @@ fn dup_server_flight1() {
     assert_eq!(in_between.dups_rx + 1, after.dups_rx);
 }
 
+#[test]
+fn negotiated_tls_stats() {
+    let mut client = new_client(ConnectionParameters::default().mlkem(false));
+    let mut server = default_server();
+    assert_eq!(client.stats().key_exchange, None);
+    connect(&mut client, &mut server);
+    assert_eq!(client.stats().key_exchange, Some(TLS_GRP_EC_X25519));
+    assert_eq!(server.stats().key_exchange, Some(TLS_GRP_EC_X25519));
+    assert_eq!(client.stats().cipher, Some(TLS_AES_128_GCM_SHA256));
+    assert_eq!(server.stats().cipher, Some(TLS_AES_128_GCM_SHA256));
+}
+
+#[test]
+fn configured_tls_stats() {
+    let mut client = new_client(ConnectionParameters::default().mlkem(false));
+    client.set_ciphers(&[TLS_CHACHA20_POLY1305_SHA256]).unwrap();
+    client.set_groups(&[TLS_GRP_EC_SECP256R1]).unwrap();
+    let mut server = default_server();
+    connect(&mut client, &mut server);
+    assert_eq!(client.stats().cipher, Some(TLS_CHACHA20_POLY1305_SHA256));
+    assert_eq!(client.stats().key_exchange, Some(TLS_GRP_EC_SECP256R1));
+}
+
+#[test]
+fn export_keying_material() {
+    let mut client = new_client(ConnectionParameters::default().mlkem(false));
+    let mut server = default_server();
+    assert_eq!(
+        client.export_keying_material("EXPORTER-test", None, 32),
+        Err(Error::NotConnected)
+    );
+    connect(&mut client, &mut server);
+
+    let c = client
+        .export_keying_material("EXPORTER-test", None, 32)
+        .unwrap();
+    let s = server
+        .export_keying_material("EXPORTER-test", None, 32)
+        .unwrap();
+    assert_eq!(c.len(), 32);
+    assert_eq!(c, s);
+
+    let c_ctx = client
+        .export_keying_material("EXPORTER-test", Some(b"context"), 32)
+        .unwrap();
+    let s_ctx = server
+        .export_keying_material("EXPORTER-test", Some(b"context"), 32)
+        .unwrap();
+    assert_eq!(c_ctx, s_ctx);
+    assert_ne!(c, c_ctx);
+}
+
 // Test that we split crypto data if they cannot fit into one packet.
 // To test this we will use a long server certificate.
 #[test]
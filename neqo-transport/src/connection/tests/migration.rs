@@ -26,9 +26,9 @@ use test_fixture::{
 
 use super::{
     super::{Connection, Output, State, StreamType},
-    CountingConnectionIdGenerator, connect_fail, connect_force_idle, connect_rtt_idle,
-    default_client, default_server, maybe_authenticate, new_client, new_server, send_something,
-    zero_len_cid_client,
+    CountingConnectionIdGenerator, DEFAULT_RTT, connect_fail, connect_force_idle, connect_rtt_idle,
+    cwnd, default_client, default_server, increase_cwnd, maybe_authenticate, new_client,
+    new_server, send_something, zero_len_cid_client,
 };
 use crate::{
     CloseReason, ConnectionId, ConnectionIdDecoder as _, ConnectionIdGenerator, ConnectionIdRef,
@@ -483,6 +483,30 @@ fn migrate_rtt() {
     assert!(rtt < RTT * 2);
 }
 
+/// Returning to a path that was used before doesn't reuse its congestion window,
+/// but a validated path keeps its RTT estimate.
+#[test]
+fn migrate_back_resets_cwnd() {
+    let mut client = default_client();
+    let mut server = default_server();
+    let now = connect_rtt_idle(&mut client, &mut server, DEFAULT_RTT);
+    let cwnd_initial = cwnd(&client);
+
+    let stream = client.stream_create(StreamType::UniDi).unwrap();
+    let now = increase_cwnd(&mut client, &mut server, stream, now);
+    assert!(cwnd(&client) > cwnd_initial);
+    let rtt = client.paths.rtt();
+
+    client
+        .migrate(Some(DEFAULT_ADDR_V4), Some(DEFAULT_ADDR_V4), true, now)
+        .unwrap();
+    client
+        .migrate(Some(DEFAULT_ADDR), Some(DEFAULT_ADDR), true, now)
+        .unwrap();
+    assert_eq!(cwnd(&client), cwnd_initial);
+    assert_eq!(client.paths.rtt(), rtt);
+}
+
 #[test]
 fn migrate_immediate_fail() {
     let mut client = default_client();
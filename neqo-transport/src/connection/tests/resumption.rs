@@ -19,7 +19,8 @@ use super::{
     get_tokens, new_client, resumed_server, send_something,
 };
 use crate::{
-    ConnectionParameters, DEFAULT_INITIAL_RTT, Error, MIN_INITIAL_PACKET_SIZE, State, Version,
+    ConnectionParameters, DEFAULT_INITIAL_RTT, Error, MIN_INITIAL_PACKET_SIZE, MemorySessionStore,
+    SessionStore as _, State, Version,
     addr_valid::{AddressValidation, ValidateAddress},
     frame::FrameType,
 };
@@ -41,6 +42,41 @@ fn resume() {
     assert!(server.tls_info().unwrap().resumed());
 }
 
+#[test]
+fn resume_with_session_store() {
+    let store = Rc::new(RefCell::new(MemorySessionStore::default()));
+    let mut client = default_client();
+    client
+        .set_session_store(now(), Rc::clone(&store) as _)
+        .unwrap();
+    let mut server = default_server();
+    connect(&mut client, &mut server);
+
+    exchange_ticket(&mut client, &mut server, now());
+    assert!(
+        store
+            .borrow()
+            .get(test_fixture::DEFAULT_SERVER_NAME)
+            .is_some()
+    );
+
+    let mut client = default_client();
+    client
+        .set_session_store(now(), Rc::clone(&store) as _)
+        .unwrap();
+    // The token is used up.
+    assert!(
+        store
+            .borrow()
+            .get(test_fixture::DEFAULT_SERVER_NAME)
+            .is_none()
+    );
+    let mut server = resumed_server(&client);
+    connect(&mut client, &mut server);
+    assert!(client.tls_info().unwrap().resumed());
+    assert!(server.tls_info().unwrap().resumed());
+}
+
 #[test]
 fn remember_smoothed_rtt() {
     const RTT1: Duration = Duration::from_millis(130);
@@ -12,7 +12,7 @@ use test_fixture::{assertions, now};
 
 use super::{
     super::Connection, CountingConnectionIdGenerator, Output, connect, default_client,
-    default_server, exchange_ticket, new_server, resumed_server,
+    default_server, exchange_ticket, new_client, new_server, resumed_server,
 };
 use crate::{
     ConnectionParameters, Error, MIN_INITIAL_PACKET_SIZE, StreamType, Version,
@@ -38,6 +38,33 @@ fn zero_rtt_negotiate() {
     assert!(server.tls_info().unwrap().early_data_accepted());
 }
 
+#[test]
+fn zero_rtt_shared_anti_replay() {
+    let mut client = new_client(ConnectionParameters::default().mlkem(false));
+    let mut server = default_server();
+    connect(&mut client, &mut server);
+
+    let token = exchange_ticket(&mut client, &mut server, now());
+    let mut client = new_client(ConnectionParameters::default().mlkem(false));
+    client.enable_resumption(now(), token).unwrap();
+    let stream_id = client.stream_create(StreamType::UniDi).unwrap();
+    client.stream_send(stream_id, &[1, 2, 3]).unwrap();
+    let ci = client.process_output(now()).dgram().unwrap();
+    assertions::assert_coalesced_0rtt(&ci);
+
+    // Two servers that share an anti-replay context only accept the 0-RTT once.
+    let anti_replay = test_fixture::anti_replay();
+    let recvd_stream_evt = |e| matches!(e, ConnectionEvent::NewStream { .. });
+    for accepted in [true, false] {
+        let mut server = resumed_server(&client);
+        server
+            .server_enable_0rtt(&anti_replay, AllowZeroRtt {})
+            .unwrap();
+        server.process_input(ci.clone(), now());
+        assert_eq!(server.events().any(recvd_stream_evt), accepted);
+    }
+}
+
 #[test]
 fn zero_rtt_send_recv() {
     let mut client = default_client();
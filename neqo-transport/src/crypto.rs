@@ -79,26 +79,24 @@ impl Crypto {
             TLS_AES_256_GCM_SHA384,
             TLS_CHACHA20_POLY1305_SHA256,
         ])?;
-        agent.set_groups(if conn_params.mlkem_enabled() {
-            &[
-                TLS_GRP_KEM_MLKEM768X25519,
-                TLS_GRP_EC_X25519,
-                TLS_GRP_EC_SECP256R1,
-                TLS_GRP_EC_SECP384R1,
-                TLS_GRP_EC_SECP521R1,
-            ]
-        } else {
-            &[
-                TLS_GRP_EC_X25519,
-                TLS_GRP_EC_SECP256R1,
-                TLS_GRP_EC_SECP384R1,
-                TLS_GRP_EC_SECP521R1,
-            ]
-        })?;
+        let mlkem_first = conn_params.mlkem_enabled() && conn_params.is_mlkem_preferred();
+        let mlkem_last = conn_params.mlkem_enabled() && !conn_params.is_mlkem_preferred();
+        let groups = [
+            mlkem_first.then_some(TLS_GRP_KEM_MLKEM768X25519),
+            Some(TLS_GRP_EC_X25519),
+            Some(TLS_GRP_EC_SECP256R1),
+            Some(TLS_GRP_EC_SECP384R1),
+            Some(TLS_GRP_EC_SECP521R1),
+            mlkem_last.then_some(TLS_GRP_KEM_MLKEM768X25519),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        agent.set_groups(&groups)?;
         if let Agent::Client(c) = &mut agent {
-            // Configure clients to send additional key shares to reduce the rate of HRRs
-            // when enabling MLKEM.
-            c.send_additional_key_shares(usize::from(conn_params.mlkem_enabled()))?;
+            // Configure clients to send an additional X25519 key share to reduce the rate
+            // of HRRs when preferring MLKEM.
+            c.send_additional_key_shares(usize::from(mlkem_first))?;
 
             // Always enable 0-RTT on the client, but the server needs
             // more configuration passed to server_enable_0rtt.
@@ -167,6 +165,19 @@ impl Crypto {
         }
     }
 
+    pub fn server_set_stapled_ocsp_responses(
+        &self,
+        certificate: &str,
+        ocsp_responses: &[&[u8]],
+    ) -> Res<()> {
+        if let Agent::Server(s) = &self.tls {
+            s.set_stapled_ocsp_responses(certificate, ocsp_responses)?;
+            Ok(())
+        } else {
+            panic!("not a server");
+        }
+    }
+
     pub fn client_enable_ech<A: AsRef<[u8]>>(&mut self, ech_config_list: A) -> Res<()> {
         if let Agent::Client(c) = &mut self.tls {
             c.enable_ech(ech_config_list)?;
@@ -1494,7 +1505,7 @@ impl CryptoStreams {
 
     pub fn inbound_frame(&mut self, space: PacketNumberSpace, offset: u64, data: &[u8]) -> Res<()> {
         let rx = &mut self.get_mut(space).ok_or(Error::Internal)?.rx;
-        rx.inbound_frame(offset, data);
+        rx.inbound_frame(offset, data, None);
         if rx.received() - rx.retired() <= Self::BUFFER_LIMIT {
             Ok(())
         } else {
@@ -0,0 +1,244 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Delivery rate estimation.
+//
+// See <https://datatracker.ietf.org/doc/html/draft-cheng-iccrg-delivery-rate-estimation-02>.
+
+use std::{
+    cmp::max,
+    time::{Duration, Instant},
+};
+
+use crate::recovery::sent;
+
+/// The delivery state of the connection at the time a packet was sent.
+/// This is recorded in each packet that is sent, so that a rate can be
+/// calculated when it is acknowledged.
+#[derive(Debug, Clone, Copy)]
+pub struct SendState {
+    delivered: usize,
+    delivered_time: Instant,
+    first_sent_time: Instant,
+    app_limited: bool,
+}
+
+/// A delivery rate sample, taken when packets are acknowledged.
+#[derive(Debug, Clone, Copy)]
+pub struct RateSample {
+    /// The delivery rate, in bytes per second.  This is `None` if the sample
+    /// covers less than the minimum RTT, which makes it unreliable.
+    pub rate: Option<u64>,
+    /// The total number of bytes delivered, including those in this sample.
+    pub delivered: usize,
+    /// The amount delivered when the most recently sent packet was sent.
+    pub prior_delivered: usize,
+    /// Whether the sender was application limited when the most recently
+    /// sent packet was sent, so that the rate might underestimate the path.
+    pub app_limited: bool,
+    /// The minimum RTT when the sample was taken.
+    pub min_rtt: Duration,
+}
+
+/// Estimates the delivery rate from acknowledgments.
+#[derive(Debug, Default)]
+pub struct DeliveryRate {
+    /// The total number of bytes that have been acknowledged.
+    delivered: usize,
+    /// When `delivered` was last updated.
+    delivered_time: Option<Instant>,
+    /// The send time of the packet that was most recently acknowledged.
+    first_sent_time: Option<Instant>,
+    /// When not zero, the value of `delivered` at which the sender stops
+    /// being application limited.
+    app_limited: usize,
+}
+
+impl DeliveryRate {
+    /// Record the delivery state in a packet that is about to be sent, when
+    /// `bytes_in_flight` are already in flight.
+    pub fn on_packet_sent(
+        &mut self,
+        pkt: &mut sent::Packet,
+        bytes_in_flight: usize,
+        app_limited: bool,
+    ) {
+        if bytes_in_flight == 0 {
+            self.first_sent_time = Some(pkt.time_sent());
+            self.delivered_time = Some(pkt.time_sent());
+        }
+        if app_limited {
+            self.on_app_limited(bytes_in_flight);
+        }
+        pkt.set_delivery_state(SendState {
+            delivered: self.delivered,
+            delivered_time: self.delivered_time.unwrap_or_else(|| pkt.time_sent()),
+            first_sent_time: self.first_sent_time.unwrap_or_else(|| pkt.time_sent()),
+            app_limited: self.app_limited != 0,
+        });
+    }
+
+    /// Note that the sender has nothing to send, even though the congestion window
+    /// has space, while `bytes_in_flight` are in flight.  Rate samples are marked
+    /// as application limited until what is in flight now is delivered.
+    pub fn on_app_limited(&mut self, bytes_in_flight: usize) {
+        self.app_limited = max(self.delivered + bytes_in_flight, 1);
+    }
+
+    /// Take a rate sample from newly acknowledged packets.  Packets that were
+    /// declared lost or sent on another path are not counted.
+    pub fn on_packets_acked(
+        &mut self,
+        acked_pkts: &[sent::Packet],
+        min_rtt: Duration,
+        now: Instant,
+    ) -> Option<RateSample> {
+        let mut prior: Option<(SendState, Instant)> = None;
+        for pkt in acked_pkts.iter().filter(|pkt| pkt.cc_outstanding()) {
+            let Some(state) = pkt.delivery_state() else {
+                continue;
+            };
+            self.delivered += pkt.len();
+            self.delivered_time = Some(now);
+            // Use the most recently sent packet for the sample.
+            if prior.is_none_or(|(p, _)| state.delivered >= p.delivered) {
+                prior = Some((state, pkt.time_sent()));
+                self.first_sent_time = Some(pkt.time_sent());
+            }
+        }
+        if self.app_limited != 0 && self.delivered > self.app_limited {
+            self.app_limited = 0;
+        }
+
+        let (state, time_sent) = prior?;
+        let send_elapsed = time_sent.saturating_duration_since(state.first_sent_time);
+        let ack_elapsed = now.saturating_duration_since(state.delivered_time);
+        let interval = max(send_elapsed, ack_elapsed);
+        let bytes = u128::try_from(self.delivered - state.delivered).expect("usize fits in u128");
+        let rate = (!interval.is_zero() && interval >= min_rtt).then(|| {
+            u64::try_from(bytes * 1_000_000_000 / interval.as_nanos()).unwrap_or(u64::MAX)
+        });
+        Some(RateSample {
+            rate,
+            delivered: self.delivered,
+            prior_delivered: state.delivered,
+            app_limited: state.app_limited,
+            min_rtt,
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use test_fixture::now;
+
+    use super::DeliveryRate;
+    use crate::{
+        packet,
+        recovery::{self, sent},
+    };
+
+    const RTT: Duration = Duration::from_millis(100);
+    const PACKET: usize = 1000;
+
+    fn packet(pn: packet::Number, t: Instant) -> sent::Packet {
+        sent::Packet::new(
+            packet::Type::Short,
+            pn,
+            t,
+            true,
+            recovery::Tokens::new(),
+            PACKET,
+        )
+    }
+
+    /// A packet sent every tenth of a round trip gives a rate of ten packets per
+    /// round trip, once the first round trip is over.
+    #[test]
+    fn rate() {
+        let mut delivery = DeliveryRate::default();
+        let mut pkts = Vec::new();
+        let mut sample = None;
+        for i in 0..30 {
+            let t = now() + RTT / 10 * i;
+            if i >= 10 {
+                let acked = &pkts[usize::try_from(i - 10).unwrap()..][..1];
+                sample = delivery.on_packets_acked(acked, RTT, t);
+            }
+            if i < 20 {
+                let acked = usize::try_from(i.saturating_sub(9)).unwrap();
+                let bytes_in_flight = (pkts.len() - acked) * PACKET;
+                let mut p = packet(u64::from(i), t);
+                delivery.on_packet_sent(&mut p, bytes_in_flight, false);
+                pkts.push(p);
+            }
+        }
+        let sample = sample.unwrap();
+        assert_eq!(sample.delivered, 20 * PACKET);
+        assert_eq!(sample.prior_delivered, 10 * PACKET);
+        assert!(!sample.app_limited);
+        assert_eq!(sample.rate, Some(u64::try_from(PACKET).unwrap() * 100));
+    }
+
+    /// Packets sent while application limited produce samples that say so.
+    #[test]
+    fn app_limited() {
+        let mut delivery = DeliveryRate::default();
+        let mut p = packet(0, now());
+        delivery.on_packet_sent(&mut p, 0, true);
+        let sample = delivery.on_packets_acked(&[p], RTT, now() + RTT).unwrap();
+        assert!(sample.app_limited);
+
+        // Once that is delivered, the sender is no longer limited.
+        let mut p = packet(1, now() + RTT);
+        delivery.on_packet_sent(&mut p, 0, false);
+        let sample = delivery
+            .on_packets_acked(&[p], RTT, now() + RTT * 2)
+            .unwrap();
+        assert!(!sample.app_limited);
+    }
+
+    /// Running out of data to send marks samples for packets that are already
+    /// in flight, and those sent afterwards, as application limited.
+    #[test]
+    fn send_queue_drained() {
+        let mut delivery = DeliveryRate::default();
+        let mut p0 = packet(0, now());
+        delivery.on_packet_sent(&mut p0, 0, false);
+        delivery.on_app_limited(PACKET);
+        let mut p1 = packet(1, now());
+        delivery.on_packet_sent(&mut p1, PACKET, false);
+
+        let sample = delivery.on_packets_acked(&[p0], RTT, now() + RTT).unwrap();
+        assert!(!sample.app_limited);
+        let sample = delivery.on_packets_acked(&[p1], RTT, now() + RTT).unwrap();
+        assert!(sample.app_limited);
+
+        // Everything that was in flight has been delivered.
+        let mut p2 = packet(2, now() + RTT);
+        delivery.on_packet_sent(&mut p2, 0, false);
+        let sample = delivery
+            .on_packets_acked(&[p2], RTT, now() + RTT * 2)
+            .unwrap();
+        assert!(!sample.app_limited);
+    }
+
+    /// Packets without delivery state, or that were lost, are not counted.
+    #[test]
+    fn not_counted() {
+        let mut delivery = DeliveryRate::default();
+        let p = packet(0, now());
+        assert!(delivery.on_packets_acked(&[p], RTT, now() + RTT).is_none());
+
+        let mut p = packet(1, now());
+        delivery.on_packet_sent(&mut p, 0, false);
+        p.declare_lost(now() + RTT, sent::LossTrigger::TimeThreshold);
+        assert!(delivery.on_packets_acked(&[p], RTT, now() + RTT).is_none());
+    }
+}
@@ -167,9 +167,24 @@ pub(crate) struct Info {
 
     /// The ECN counts from the last ACK frame that increased `largest_acked`.
     baseline: Count,
+
+    /// Whether to mark packets with ECT(1) for L4S, rather than ECT(0).
+    l4s: bool,
 }
 
 impl Info {
+    pub(crate) fn new(l4s: bool) -> Self {
+        Self {
+            l4s,
+            ..Self::default()
+        }
+    }
+
+    /// The ECT codepoint that this path uses.
+    const fn ect(&self) -> Ecn {
+        if self.l4s { Ecn::Ect1 } else { Ecn::Ect0 }
+    }
+
     pub(crate) fn start(&mut self, stats: &mut Stats) {
         if !matches!(self.state, ValidationState::NotStarted) {
             return;
@@ -217,22 +232,25 @@ impl Info {
 
     /// Process ECN counts from an ACK frame.
     ///
-    /// Returns whether ECN counts contain new valid ECN CE marks.
+    /// Returns the number of new valid ECN CE marks.
     pub(crate) fn on_packets_acked(
         &mut self,
         acked_packets: &[sent::Packet],
         ack_ecn: Option<&Count>,
         stats: &mut Stats,
-    ) -> bool {
+    ) -> u64 {
         let prev_baseline = self.baseline;
 
         self.validate_ack_ecn_and_update(acked_packets, ack_ecn, stats);
 
-        matches!(self.state, ValidationState::Capable)
-            && (self.baseline - prev_baseline)[Ecn::Ce] > 0
+        if matches!(self.state, ValidationState::Capable) {
+            (self.baseline - prev_baseline)[Ecn::Ce]
+        } else {
+            0
+        }
     }
 
-    /// An [`Ecn::Ect0`] marked packet has been acked.
+    /// An ECT marked packet has been acked.
     pub(crate) const fn acked_ecn(&mut self) {
         if let ValidationState::Testing {
             initial_probes_acked: probes_acked,
@@ -243,7 +261,7 @@ impl Info {
         }
     }
 
-    /// An [`Ecn::Ect0`] marked packet has been declared lost.
+    /// An ECT marked packet has been declared lost.
     pub(crate) fn lost_ecn(&mut self, stats: &mut Stats) {
         if let ValidationState::Testing {
             initial_probes_acked: probes_acked,
@@ -312,20 +330,29 @@ impl Info {
         // > ECN validation also fails if the sum of the increase in ECT(0) and ECN-CE counts is
         // > less than the number of newly acknowledged packets that were originally sent with an
         // > ECT(0) marking.
-        let newly_acked_sent_with_ect0: u64 = acked_packets
+        //
+        // The same applies to ECT(1), when that is used for L4S.
+        let ect = self.ect();
+        let newly_acked_sent_with_ect: u64 = acked_packets
             .iter()
-            .filter(|p| p.ecn_marked_ect0())
+            .filter(|p| {
+                if self.l4s {
+                    p.ecn_marked_ect1()
+                } else {
+                    p.ecn_marked_ect0()
+                }
+            })
             .count()
             .try_into()
             .expect("usize fits into u64");
         let ecn_diff = ack_ecn - self.baseline;
-        let sum_inc = ecn_diff[Ecn::Ect0] + ecn_diff[Ecn::Ce];
-        if sum_inc < newly_acked_sent_with_ect0 {
+        let sum_inc = ecn_diff[ect] + ecn_diff[Ecn::Ce];
+        if sum_inc < newly_acked_sent_with_ect {
             qinfo!(
-                "ECN validation failed, ACK counted {sum_inc} new marks, but {newly_acked_sent_with_ect0} of newly acked packets were sent with ECT(0)"
+                "ECN validation failed, ACK counted {sum_inc} new marks, but {newly_acked_sent_with_ect} of newly acked packets were sent with {ect:?}"
             );
             self.disable_ecn(stats, ValidationError::Bleaching);
-        } else if ecn_diff[Ecn::Ect1] > 0 {
+        } else if !self.l4s && ecn_diff[Ecn::Ect1] > 0 {
             qinfo!("ECN validation failed, ACK counted ECT(1) marks that were never sent");
             self.disable_ecn(stats, ValidationError::ReceivedUnsentECT1);
         } else if self.state != ValidationState::Capable {
@@ -348,7 +375,7 @@ impl Info {
     /// The ECN mark to use for an outgoing UDP datagram.
     pub(crate) const fn ecn_mark(&self) -> Ecn {
         if self.is_marking() {
-            Ecn::Ect0
+            self.ect()
         } else {
             Ecn::NotEct
         }
@@ -53,6 +53,7 @@ pub enum FrameType {
     HandshakeDone = 0x1e,
     // draft-ietf-quic-ack-delay
     AckFrequency = 0xaf,
+    ImmediateAck = 0x1f,
     // draft-ietf-quic-datagram
     Datagram = 0x30,
     DatagramWithLen = 0x31,
@@ -239,6 +240,7 @@ pub enum Frame<'a> {
         /// Ignore reordering when deciding to immediately acknowledge.
         ignore_order: bool,
     },
+    ImmediateAck,
     Datagram {
         data: &'a [u8],
         fill: bool,
@@ -281,6 +283,7 @@ impl<'a> Frame<'a> {
             },
             Self::HandshakeDone => FrameType::HandshakeDone,
             Self::AckFrequency { .. } => FrameType::AckFrequency,
+            Self::ImmediateAck => FrameType::ImmediateAck,
             Self::Datagram { fill, .. } => match fill {
                 false => FrameType::Datagram,
                 true => FrameType::DatagramWithLen,
@@ -668,6 +671,7 @@ impl<'a> Frame<'a> {
                     ignore_order,
                 })
             }
+            FrameType::ImmediateAck => Ok(Self::ImmediateAck),
             FrameType::Datagram | FrameType::DatagramWithLen => {
                 let fill = t == FrameType::Datagram;
                 let data = if fill {
@@ -1048,6 +1052,12 @@ mod tests {
         just_dec(&f, "40af0a0547d001");
     }
 
+    #[test]
+    fn immediate_ack() {
+        let f = Frame::ImmediateAck;
+        just_dec(&f, "1f");
+    }
+
     #[test]
     fn ack_frequency_ignore_error_error() {
         let enc = Encoder::from_hex("40af0a0547d003"); // ignore_order of 3
@@ -1283,5 +1293,6 @@ mod tests {
             .dump(),
             "AckFrequency { seqno: 1, tolerance: 2, delay: 3, ignore_order: false }"
         );
+        assert_eq!(Frame::ImmediateAck.dump(), "ImmediateAck");
     }
 }
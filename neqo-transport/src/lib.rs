@@ -19,6 +19,7 @@ mod cc;
 mod cid;
 mod connection;
 mod crypto;
+mod delivery_rate;
 pub mod ecn;
 mod events;
 mod fc;
@@ -45,6 +46,7 @@ mod saved;
 pub mod send_stream;
 mod sender;
 pub mod server;
+mod session_store;
 mod sni;
 mod stateless_reset;
 mod stats;
@@ -55,10 +57,14 @@ mod tracking;
 pub mod version;
 
 pub use self::{
-    cc::{CongestionControl, CongestionEvent, SlowStart},
+    cc::{
+        CongestionControl, CongestionController, CongestionControllerFactory, CongestionEvent,
+        CongestionPhase, CongestionSnapshot, CongestionState, HyStartConfig, IdleRestart,
+        LimitingFactor, SlowStart,
+    },
     cid::{
         ConnectionId, ConnectionIdDecoder, ConnectionIdGenerator, ConnectionIdRef,
-        EmptyConnectionIdGenerator, RandomConnectionIdGenerator,
+        EmptyConnectionIdGenerator, RandomConnectionIdGenerator, ShardedConnectionIdGenerator,
     },
     connection::{
         Connection, Output, OutputBatch, State, ZeroRttState,
@@ -67,15 +73,18 @@ pub use self::{
             MAX_LOCAL_MAX_STREAM_DATA,
         },
     },
-    events::{ConnectionEvent, ConnectionEvents},
+    delivery_rate::RateSample,
+    events::{ConnectionEvent, ConnectionEvents, OutgoingDatagramOutcome},
     frame::CloseError,
     packet::MIN_INITIAL_PACKET_SIZE,
     pmtud::Pmtud,
     quic_datagrams::DatagramTracking,
-    rtt::DEFAULT_INITIAL_RTT,
+    recovery::sent::Packet as SentPacket,
+    rtt::{DEFAULT_INITIAL_RTT, RttEstimate},
+    session_store::{MemorySessionStore, SessionStore},
     sni::find_sni,
     stateless_reset::Token,
-    stats::{SlowStartExitReason, Stats},
+    stats::{CongestionControlStats, SlowStartExitReason, Stats},
     stream_id::{StreamId, StreamType},
     version::Version,
 };
@@ -9,6 +9,7 @@
 use std::{
     cmp::min,
     fmt::{self, Debug, Display, Formatter},
+    num::NonZeroUsize,
     time::{Duration, Instant},
 };
 
@@ -16,7 +17,10 @@ use neqo_common::qtrace;
 
 use crate::rtt::GRANULARITY;
 
-/// A pacer that uses a leaky bucket.
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// A pacer that uses a token bucket.  Credit accumulates at the pacing rate,
+/// up to the burst size, and each packet spends credit equal to its size.
 pub struct Pacer {
     /// Whether pacing is enabled.
     enabled: bool,
@@ -29,18 +33,22 @@ pub struct Pacer {
     c: isize,
     /// The packet size or minimum capacity for sending, in bytes.
     p: usize,
+    /// The number of packets that are sent together, such as with GSO.
+    batch: usize,
+    /// The number of packets left to release from the current batch.
+    release: usize,
 }
 
 impl Pacer {
-    /// This value determines how much faster the pacer operates than the
-    /// congestion window.
+    /// The default pacing gain, in percent.  This value determines how much
+    /// faster the pacer operates than the congestion window.
     ///
-    /// A value of 1 would cause all packets to be spaced over the entire RTT,
+    /// A value of 100 would cause all packets to be spaced over the entire RTT,
     /// which is a little slow and might act as an additional restriction in
     /// the case the congestion controller increases the congestion window.
     /// This value spaces packets over half the congestion window, which matches
     /// our current congestion controller, which double the window every RTT.
-    const SPEEDUP: usize = 2;
+    pub const GAIN: usize = 200;
 
     /// Create a new `Pacer`.  This takes the current time, the maximum burst size,
     /// and the packet size.
@@ -61,46 +69,108 @@ impl Pacer {
             m,
             c: isize::try_from(m).expect("maximum capacity fits into isize"),
             p,
+            batch: 1,
+            release: 0,
         }
     }
 
+    /// The rate, in bytes per second, that paces a congestion window of
+    /// `cwnd` over the round trip time, `rtt`, sped up by `gain` percent.
+    pub fn rate(rtt: Duration, cwnd: usize, gain: usize) -> u64 {
+        let cwnd = u128::try_from(cwnd.saturating_mul(gain) / 100).expect("usize fits into u128");
+        cwnd.saturating_mul(NANOS_PER_SEC)
+            .checked_div(rtt.as_nanos())
+            .map_or(u64::MAX, |r| u64::try_from(r).unwrap_or(u64::MAX))
+    }
+
     pub const fn mtu(&self) -> usize {
         self.p
     }
 
+    pub const fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn pacing on or off.  A pacer that is off has full credit, so that
+    /// nothing is held back by debt from when it was on.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.refill();
+        }
+    }
+
+    /// Refill to full credit, dropping any debt.  This is for when sending
+    /// resumes after an idle period, which the debt is no longer relevant to.
+    pub fn refill(&mut self) {
+        self.c = isize::try_from(self.m).expect("maximum capacity fits into isize");
+        self.release = 0;
+    }
+
+    /// Change the packet size.  This scales the maximum capacity, so that
+    /// bursts stay the same number of packets.
     pub const fn set_mtu(&mut self, mtu: usize) {
+        if let Some(m) = self.m.saturating_mul(mtu).checked_div(self.p) {
+            self.m = m;
+        }
         self.p = mtu;
     }
 
+    /// Release packets in batches of up to `packets`, such as the number of
+    /// datagrams that are sent together with GSO.  The pacer waits until it
+    /// has credit for a whole batch, then releases all of it at once.
+    /// A batch that is being released continues, up to the new batch size.
+    pub const fn set_batch(&mut self, packets: NonZeroUsize) {
+        self.batch = packets.get();
+        if self.release >= self.batch {
+            self.release = self.batch - 1;
+        }
+    }
+
+    /// The number of packets that the pacer releases together.  This is the
+    /// batch size, limited by the maximum capacity if pacing is enabled.
+    pub fn burst_size_hint(&self) -> usize {
+        if !self.enabled {
+            return self.batch;
+        }
+        self.m
+            .checked_div(self.p)
+            .map_or(self.batch, |n| min(n, self.batch))
+            .max(1)
+    }
+
+    /// The credit, in bytes, needed to release a batch.
+    fn batch_credit(&self) -> isize {
+        isize::try_from(self.p.saturating_mul(self.burst_size_hint())).unwrap_or(isize::MAX)
+    }
+
     /// Determine when the next packet will be available based on the provided
-    /// RTT, provided congestion window and accumulated credit or debt.  This
+    /// rate, in bytes per second, and accumulated credit or debt.  This
     /// doesn't update state.  This returns a time, which could be in the past
     /// (this object doesn't know what the current time is).
-    pub fn next(&self, rtt: Duration, cwnd: usize) -> Instant {
-        let packet = isize::try_from(self.p).expect("packet size fits into isize");
+    pub fn next(&self, rate: u64) -> Instant {
+        let needed = self.batch_credit();
 
-        if self.c >= packet {
-            qtrace!("[{self}] next {cwnd}/{rtt:?} no wait = {:?}", self.t);
+        if self.release > 0 || self.c >= needed {
+            qtrace!("[{self}] next {rate}B/s no wait = {:?}", self.t);
             return self.t;
         }
 
         // This is the inverse of the function in `spend`:
-        // self.t + rtt * (self.p - self.c) / (Self::SPEEDUP * cwnd)
-        let r = rtt.as_nanos();
+        // self.t + (needed - self.c) / rate
         let deficit =
-            u128::try_from(packet - self.c).expect("packet is larger than current credit");
-        let d = r.saturating_mul(deficit);
-        let add = d / u128::try_from(cwnd * Self::SPEEDUP).expect("usize fits into u128");
-        let w = u64::try_from(add).map_or(rtt, Duration::from_nanos);
+            u128::try_from(needed - self.c).expect("needed credit is larger than current credit");
+        let add = deficit.saturating_mul(NANOS_PER_SEC) / u128::from(rate.max(1));
+        let w = Duration::from_nanos(u64::try_from(add).unwrap_or(u64::MAX));
 
         // If the increment is below the timer granularity, send immediately.
         if w < GRANULARITY {
-            qtrace!("[{self}] next {cwnd}/{rtt:?} below granularity ({w:?})");
+            qtrace!("[{self}] next {rate}B/s below granularity ({w:?})");
             return self.t;
         }
 
         let nxt = self.t + w;
-        qtrace!("[{self}] next {cwnd}/{rtt:?} wait {w:?} = {nxt:?}");
+        qtrace!("[{self}] next {rate}B/s wait {w:?} = {nxt:?}");
         nxt
     }
 
@@ -108,33 +178,43 @@ impl Pacer {
     /// future (see [`Pacer::c`]). Users of this API are expected to call
     /// [`Pacer::next`] to determine when to spend.
     ///
-    /// This function takes the current time (`now`), an estimate of the round
-    /// trip time (`rtt`), the estimated congestion window (`cwnd`), and the
-    /// number of bytes that were sent (`count`).
-    pub fn spend(&mut self, now: Instant, rtt: Duration, cwnd: usize, count: usize) {
+    /// This function takes the current time (`now`), the pacing rate in
+    /// bytes per second (`rate`), and the number of bytes that were sent (`count`).
+    pub fn spend(&mut self, now: Instant, rate: u64, count: usize) {
         if !self.enabled {
             self.t = now;
             return;
         }
 
-        qtrace!("[{self}] spend {count} over {cwnd}, {rtt:?}");
+        qtrace!("[{self}] spend {count} at {rate}B/s");
         // Increase the capacity by:
-        //    `(now - self.t) * Self::SPEEDUP * cwnd / rtt`
-        // That is, the elapsed fraction of the RTT times rate that data is added.
+        //    `(now - self.t) * rate`
+        // That is, the elapsed time times the rate that data is added.
         let incr = now
             .saturating_duration_since(self.t)
             .as_nanos()
-            .saturating_mul(u128::try_from(cwnd * Self::SPEEDUP).expect("usize fits into u128"))
-            .checked_div(rtt.as_nanos())
+            .saturating_mul(u128::from(rate))
+            .checked_div(NANOS_PER_SEC)
             .and_then(|i| usize::try_from(i).ok())
             .unwrap_or(self.m);
 
+        let m = isize::try_from(self.m).unwrap_or(isize::MAX);
+        let credit = self
+            .c
+            .saturating_add(isize::try_from(incr).unwrap_or(isize::MAX));
+
+        // A packet sent with credit for a whole batch starts releasing the rest
+        // of the batch.
+        if self.release > 0 {
+            self.release -= 1;
+        } else if min(m, credit) >= self.batch_credit() {
+            self.release = self.burst_size_hint() - 1;
+        }
+
         // Add the capacity up to a limit of `self.m`, then subtract `count`.
         self.c = min(
-            isize::try_from(self.m).unwrap_or(isize::MAX),
-            self.c
-                .saturating_add(isize::try_from(incr).unwrap_or(isize::MAX))
-                .saturating_sub(isize::try_from(count).unwrap_or(isize::MAX)),
+            m,
+            credit.saturating_sub(isize::try_from(count).unwrap_or(isize::MAX)),
         );
         self.t = now;
     }
@@ -155,7 +235,7 @@ impl Debug for Pacer {
 #[cfg(test)]
 #[cfg_attr(coverage_nightly, coverage(off))]
 mod tests {
-    use std::time::Duration;
+    use std::{num::NonZeroUsize, time::Duration};
 
     use test_fixture::now;
 
@@ -169,28 +249,28 @@ mod tests {
     fn even() {
         let n = now();
         let mut p = Pacer::new(true, n, PACKET, PACKET);
-        assert_eq!(p.next(RTT, CWND), n);
-        p.spend(n, RTT, CWND, PACKET);
-        assert_eq!(p.next(RTT, CWND), n + (RTT / 20));
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n);
+        p.spend(n, Pacer::rate(RTT, CWND, Pacer::GAIN), PACKET);
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n + (RTT / 20));
     }
 
     #[test]
     fn backwards_in_time() {
         let n = now();
         let mut p = Pacer::new(true, n + RTT, PACKET, PACKET);
-        assert_eq!(p.next(RTT, CWND), n + RTT);
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n + RTT);
         // Now spend some credit in the past using a time machine.
-        p.spend(n, RTT, CWND, PACKET);
-        assert_eq!(p.next(RTT, CWND), n + (RTT / 20));
+        p.spend(n, Pacer::rate(RTT, CWND, Pacer::GAIN), PACKET);
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n + (RTT / 20));
     }
 
     #[test]
     fn pacing_disabled() {
         let n = now();
         let mut p = Pacer::new(false, n, PACKET, PACKET);
-        assert_eq!(p.next(RTT, CWND), n);
-        p.spend(n, RTT, CWND, PACKET);
-        assert_eq!(p.next(RTT, CWND), n);
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n);
+        p.spend(n, Pacer::rate(RTT, CWND, Pacer::GAIN), PACKET);
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n);
     }
 
     #[test]
@@ -198,10 +278,10 @@ mod tests {
         const SHORT_RTT: Duration = Duration::from_millis(10);
         let n = now();
         let mut p = Pacer::new(true, n, PACKET, PACKET);
-        assert_eq!(p.next(SHORT_RTT, CWND), n);
-        p.spend(n, SHORT_RTT, CWND, PACKET);
+        assert_eq!(p.next(Pacer::rate(SHORT_RTT, CWND, Pacer::GAIN)), n);
+        p.spend(n, Pacer::rate(SHORT_RTT, CWND, Pacer::GAIN), PACKET);
         assert_eq!(
-            p.next(SHORT_RTT, CWND),
+            p.next(Pacer::rate(SHORT_RTT, CWND, Pacer::GAIN)),
             n,
             "Expect packet to be sent immediately, instead of being paced below timer granularity"
         );
@@ -221,13 +301,89 @@ mod tests {
         let start = n;
         let packet_count = 10_000;
         for _ in 0..packet_count {
-            n = p.next(RTT, bdp);
-            p.spend(n, RTT, bdp, PACKET);
+            n = p.next(Pacer::rate(RTT, bdp, Pacer::GAIN));
+            p.spend(n, Pacer::rate(RTT, bdp, Pacer::GAIN), PACKET);
         }
         // We expect _some_ time to have progressed after sending all the packets.
         assert!(n - start > Duration::ZERO);
     }
 
+    /// With batching, the pacer waits for credit for a whole batch, then
+    /// releases the batch without waiting.
+    #[test]
+    fn batch() {
+        const BATCH: usize = 2;
+        let n = now();
+        let rate = Pacer::rate(RTT, CWND, Pacer::GAIN);
+        let mut p = Pacer::new(true, n, PACKET * BATCH, PACKET);
+        p.set_batch(NonZeroUsize::new(BATCH).unwrap());
+        assert_eq!(p.burst_size_hint(), BATCH);
+
+        // The pacer starts with credit for a batch.
+        for _ in 0..BATCH {
+            assert_eq!(p.next(rate), n);
+            p.spend(n, rate, PACKET);
+        }
+
+        // The next batch is released once there is credit for all of it.
+        let t = p.next(rate);
+        assert_eq!(t, n + (RTT / 10));
+        for _ in 0..BATCH {
+            assert_eq!(p.next(rate), t);
+            p.spend(t, rate, PACKET);
+        }
+        assert_eq!(p.next(rate), t + (RTT / 10));
+    }
+
+    /// Changing the batch size doesn't stop the release of a batch.
+    #[test]
+    fn batch_resized() {
+        let n = now();
+        let rate = Pacer::rate(RTT, CWND, Pacer::GAIN);
+        let mut p = Pacer::new(true, n, PACKET * 4, PACKET);
+        p.set_batch(NonZeroUsize::new(4).unwrap());
+        p.spend(n, rate, PACKET);
+        p.spend(n, rate, PACKET);
+
+        // Two more packets are left in this batch; only one of them is
+        // released after the batch shrinks to two.
+        p.set_batch(NonZeroUsize::new(2).unwrap());
+        assert_eq!(p.next(rate), n);
+        p.spend(n, rate, PACKET);
+        assert!(p.next(rate) > n);
+    }
+
+    /// The batch is limited by the maximum capacity.
+    #[test]
+    fn batch_limited_by_capacity() {
+        let mut p = Pacer::new(true, now(), PACKET * 2, PACKET);
+        p.set_batch(NonZeroUsize::new(64).unwrap());
+        assert_eq!(p.burst_size_hint(), 2);
+        p.set_enabled(false);
+        assert_eq!(p.burst_size_hint(), 64);
+    }
+
+    /// Refilling drops any debt.
+    #[test]
+    fn refill() {
+        let n = now();
+        let mut p = Pacer::new(true, n, PACKET, PACKET);
+        p.spend(n, Pacer::rate(RTT, CWND, Pacer::GAIN), CWND);
+        assert!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)) > n);
+        p.refill();
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n);
+    }
+
+    /// The burst size follows changes to the packet size.
+    #[test]
+    fn set_mtu_scales_burst() {
+        let n = now();
+        let mut p = Pacer::new(true, n, PACKET, PACKET);
+        p.set_mtu(2 * PACKET);
+        p.spend(n + RTT, Pacer::rate(RTT, CWND, Pacer::GAIN), 0);
+        assert_eq!(p.next(Pacer::rate(RTT, CWND, Pacer::GAIN)), n + RTT);
+    }
+
     #[test]
     fn pacer_display_and_debug() {
         let mut p = Pacer::new(true, now(), PACKET, PACKET);
@@ -236,6 +392,7 @@ mod tests {
         assert_eq!(p.mtu(), 500);
         p.set_mtu(PACKET);
         assert_eq!(p.to_string(), "Pacer 1000/1000");
+        assert!(format!("{p:?}").ends_with("1000/1000..1000"));
         assert!(format!("{p:?}").starts_with("Pacer@"));
     }
 }
@@ -15,7 +15,7 @@ use std::{
 
 use enum_map::Enum;
 use log::debug;
-use neqo_common::{Buffer, Decoder, Encoder, hex, hex_with_len, qtrace, qwarn};
+use neqo_common::{Buffer, Decoder, Encoder, SharedBytes, hex, hex_with_len, qtrace, qwarn};
 use neqo_crypto::{AeadTrait as _, random};
 use strum::{EnumIter, FromRepr};
 
@@ -978,6 +978,8 @@ impl<'a> Public<'a> {
             dcid: self.dcid,
             scid: self.scid,
             data,
+            offset: header_end,
+            shared: None,
         })
     }
 
@@ -1061,11 +1063,15 @@ pub struct Decrypted<'a> {
     pt: Type,
     pn: Number,
     data: &'a [u8],
+    /// The offset of `data` in the packet.
+    offset: usize,
+    /// The buffer that holds `data`, if the datagram could be frozen.
+    shared: Option<&'a SharedBytes>,
     dcid: ConnectionId,
     scid: Option<ConnectionId>,
 }
 
-impl Decrypted<'_> {
+impl<'a> Decrypted<'a> {
     #[must_use]
     pub const fn version(&self) -> Version {
         self.version
@@ -1096,6 +1102,41 @@ impl Decrypted<'_> {
             .expect("should only be called for long header packets")
             .as_cid_ref()
     }
+
+    /// The range of the payload in the packet.
+    #[must_use]
+    pub const fn payload_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.data.len()
+    }
+
+    /// The buffer that holds the payload, if any.  Frames can keep parts of it
+    /// instead of copying them.
+    #[must_use]
+    pub const fn shared(&self) -> Option<&'a SharedBytes> {
+        self.shared
+    }
+
+    /// Moves the payload to `data`, which is held by `shared`, if set.
+    ///
+    /// Decrypting borrows the datagram mutably, which has to end before the
+    /// datagram can be frozen, see [`neqo_common::datagram::Payload`].
+    #[must_use]
+    pub fn with_payload<'b>(
+        self,
+        data: &'b [u8],
+        shared: Option<&'b SharedBytes>,
+    ) -> Decrypted<'b> {
+        Decrypted {
+            version: self.version,
+            pt: self.pt,
+            pn: self.pn,
+            data,
+            offset: self.offset,
+            shared,
+            dcid: self.dcid,
+            scid: self.scid,
+        }
+    }
 }
 
 impl Deref for Decrypted<'_> {
@@ -7,13 +7,16 @@
 use std::{
     cell::RefCell,
     fmt::{self, Display},
+    iter,
     net::SocketAddr,
     num::NonZeroUsize,
     rc::Rc,
     time::{Duration, Instant},
 };
 
-use neqo_common::{Buffer, Encoder, Tos, datagram, hex, qdebug, qinfo, qlog::Qlog, qtrace, qwarn};
+use neqo_common::{
+    Buffer, Dscp, Encoder, Tos, datagram, hex, qdebug, qinfo, qlog::Qlog, qtrace, qwarn,
+};
 use neqo_crypto::random;
 
 use crate::{
@@ -24,6 +27,7 @@ use crate::{
     frame::{FrameEncoder as _, FrameType},
     packet,
     pmtud::Pmtud,
+    qlog,
     recovery::{self, sent},
     rtt::{RttEstimate, RttSource},
     sender::PacketSender,
@@ -63,6 +67,12 @@ pub struct Paths {
 
     /// Whether PMTUD is enabled for this connection.
     pmtud: bool,
+
+    /// The cap on the sending rate that applies to all paths.
+    max_send_rate: Option<u64>,
+
+    /// Whether the handshake is confirmed, after which all paths are paced.
+    confirmed: bool,
 }
 
 impl Paths {
@@ -75,6 +85,8 @@ impl Paths {
             to_retire: Vec::new(),
             qlog: Qlog::disabled(),
             pmtud,
+            max_send_rate: None,
+            confirmed: false,
         }
     }
 
@@ -94,6 +106,10 @@ impl Paths {
             .unwrap_or_else(|| {
                 let mut p =
                     Path::temporary(local, remote, conn_params, self.qlog.clone(), now, stats);
+                p.set_max_send_rate(self.max_send_rate, now);
+                if self.confirmed {
+                    p.on_handshake_confirmed();
+                }
                 if let Some(primary) = self.primary.as_ref() {
                     p.prime_rtt(primary.borrow().rtt());
                     if let Some(peer_max) = primary.borrow().pmtud().peer_max_udp_payload() {
@@ -104,6 +120,36 @@ impl Paths {
             })
     }
 
+    /// Cap the sending rate on all paths, including any that are created later.
+    pub fn set_max_send_rate(&mut self, rate: Option<u64>, now: Instant) {
+        self.max_send_rate = rate;
+        for p in &self.paths {
+            p.borrow_mut().set_max_send_rate(rate, now);
+        }
+    }
+
+    /// Switch all paths to the congestion controller from `conn_params`.  The
+    /// primary path keeps `in_flight`, the packets that are outstanding on it.
+    /// Other paths have nothing in flight that counts.
+    pub fn set_congestion_control<'a, I>(
+        &self,
+        conn_params: &ConnectionParameters,
+        in_flight: I,
+        now: Instant,
+    ) where
+        I: IntoIterator<Item = &'a sent::Packet>,
+    {
+        for p in self.paths.iter().filter(|p| !p.borrow().is_primary()) {
+            p.borrow_mut()
+                .set_congestion_control(conn_params, iter::empty(), now);
+        }
+        if let Some(primary) = &self.primary {
+            primary
+                .borrow_mut()
+                .set_congestion_control(conn_params, in_flight, now);
+        }
+    }
+
     /// Get a reference to the primary path, if one exists.
     pub fn primary(&self) -> Option<PathRef> {
         self.primary.clone()
@@ -173,6 +219,9 @@ impl Paths {
         qdebug!("[{}] set as primary path", path.borrow());
         let old_path = self.primary.replace(Rc::clone(path)).inspect(|old| {
             old.borrow_mut().set_primary(false, now);
+            if !Rc::ptr_eq(old, path) {
+                path.borrow_mut().take_over(&old.borrow(), now);
+            }
         });
 
         // Swap the primary path into slot 0, so that it is protected from eviction.
@@ -446,6 +495,15 @@ impl Paths {
         }
     }
 
+    /// Start pacing on all paths, including any that are created later, if
+    /// pacing was suspended for the handshake.
+    pub fn on_handshake_confirmed(&mut self) {
+        self.confirmed = true;
+        for p in &self.paths {
+            p.borrow_mut().on_handshake_confirmed();
+        }
+    }
+
     pub fn start_ecn(&self, stats: &mut Stats) {
         if let Some(path) = self.primary() {
             path.borrow_mut().start_ecn(stats);
@@ -465,6 +523,13 @@ impl Paths {
         )
     }
 
+    /// Change the DSCP codepoint for outgoing packets on all paths.
+    pub fn set_dscp(&self, dscp: Dscp) {
+        for p in &self.paths {
+            p.borrow_mut().set_dscp(dscp);
+        }
+    }
+
     pub fn set_qlog(&mut self, qlog: Qlog) {
         for p in &mut self.paths {
             p.borrow_mut().set_qlog(qlog.clone());
@@ -537,6 +602,8 @@ pub struct Path {
     rtt: RttEstimate,
     /// A packet sender for the path, which includes congestion control and a pacer.
     sender: PacketSender,
+    /// The parameters that the packet sender and RTT estimate start out with.
+    conn_params: ConnectionParameters,
 
     /// The number of bytes received on this path.
     /// Note that this value might saturate on a long-lived connection,
@@ -546,6 +613,8 @@ pub struct Path {
     sent_bytes: usize,
     /// The ECN-related state for this path (see RFC9000, Section 13.4 and Appendix A.4)
     ecn_info: ecn::Info,
+    /// The DSCP codepoint for outgoing packets.
+    dscp: Dscp,
     /// For logging of events.
     qlog: Qlog,
 }
@@ -590,6 +659,7 @@ impl Path {
         };
         let mut sender = PacketSender::new(conn_params, Pmtud::new(remote.ip(), iface_mtu), now);
         sender.set_qlog(qlog.clone());
+        let ecn_info = ecn::Info::new(sender.l4s());
         Self {
             local,
             remote,
@@ -601,9 +671,11 @@ impl Path {
             challenge: None,
             rtt: RttEstimate::new(conn_params.get_initial_rtt()),
             sender,
+            conn_params: conn_params.clone(),
             received_bytes: 0,
             sent_bytes: 0,
-            ecn_info: ecn::Info::default(),
+            ecn_info,
+            dscp: conn_params.get_dscp(),
             qlog,
         }
     }
@@ -614,7 +686,28 @@ impl Path {
 
     /// Return the DSCP/ECN marking to use for outgoing packets on this path.
     pub fn tos(&self) -> Tos {
-        self.ecn_info.ecn_mark().into()
+        (self.dscp, self.ecn_info.ecn_mark()).into()
+    }
+
+    /// Change the DSCP codepoint for outgoing packets on this path.
+    pub const fn set_dscp(&mut self, dscp: Dscp) {
+        self.dscp = dscp;
+    }
+
+    /// Switch to the congestion controller from `conn_params`, which also
+    /// applies if the sender is reset later.  See
+    /// [`PacketSender::set_congestion_control`].
+    pub fn set_congestion_control<'a, I>(
+        &mut self,
+        conn_params: &ConnectionParameters,
+        in_flight: I,
+        now: Instant,
+    ) where
+        I: IntoIterator<Item = &'a sent::Packet>,
+    {
+        self.conn_params = conn_params.clone();
+        self.sender
+            .set_congestion_control(conn_params, self.qlog.clone(), in_flight, now);
     }
 
     /// Whether this path is the primary or current path for the connection.
@@ -662,6 +755,26 @@ impl Path {
         }
     }
 
+    /// Carry state over from `old`, the previous primary path, as this path
+    /// becomes primary.
+    ///
+    /// Congestion control always starts over, see RFC 9000, Section 9.4: the
+    /// congestion window of the old path says nothing about this one, and any
+    /// state left from an earlier time that this path was primary is stale.
+    /// The RTT estimate is kept if this path has been validated.  Otherwise, it
+    /// is reset, unless only the peer's port changed, which is most likely a NAT
+    /// rebinding, so the old path's estimate still applies.
+    fn take_over(&mut self, old: &Self, now: Instant) {
+        qdebug!("[{self}] Take over from {old}");
+        self.sender.reset(&self.conn_params, self.qlog.clone(), now);
+        if self.validated.is_none() {
+            self.rtt.reset(self.conn_params.get_initial_rtt());
+            if self.local == old.local && self.remote.ip() == old.remote.ip() {
+                self.rtt.prime_rtt(&old.rtt);
+            }
+        }
+    }
+
     /// Set the current path as valid.  This updates the time that the path was
     /// last validated and cancels any path validation.
     pub fn set_valid(&mut self, now: Instant) {
@@ -994,12 +1107,26 @@ impl Path {
         self.sent_bytes = self.sent_bytes.saturating_add(count);
     }
 
-    /// Record a packet as having been sent on this path.
-    pub fn packet_sent(&mut self, sent: &mut sent::Packet, now: Instant) {
+    /// Record a packet as having been sent on this path.  Probes don't spend
+    /// pacer credit.
+    pub fn packet_sent(&mut self, sent: &mut sent::Packet, probe: bool, now: Instant) {
         if !self.is_primary() {
             sent.clear_primary_path();
         }
-        self.sender.on_packet_sent(sent, self.rtt.estimate(), now);
+        self.sender.on_packet_sent(sent, &self.rtt, probe, now);
+        if let Some(rate) = self.sender.pacing_rate_to_log(self.rtt.estimate(), now) {
+            // qlog logs the pacing rate in bits per second.
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[qlog::Metric::PacingRate(rate.saturating_mul(8))],
+                now,
+            );
+        }
+    }
+
+    /// Record that the application had nothing to send on this path.
+    pub fn on_app_limited(&mut self) {
+        self.sender.on_app_limited();
     }
 
     /// Discard a packet that previously might have been in-flight.
@@ -1037,10 +1164,11 @@ impl Path {
     ) {
         debug_assert!(self.is_primary());
 
-        let ecn_ce_received = self.ecn_info.on_packets_acked(acked_pkts, ack_ecn, stats);
-        if ecn_ce_received {
+        let ce_marks = self.ecn_info.on_packets_acked(acked_pkts, ack_ecn, stats);
+        if ce_marks > 0 {
             let cwnd_reduced = self.sender.on_ecn_ce_received(
                 acked_pkts.first().expect("must be there"),
+                ce_marks,
                 now,
                 &mut stats.cc,
             );
@@ -1107,6 +1235,33 @@ impl Path {
         }
     }
 
+    /// Start pacing, if it was suspended for the handshake.
+    pub const fn on_handshake_confirmed(&mut self) {
+        self.sender.on_handshake_confirmed();
+    }
+
+    /// Have the pacer release packets in batches of up to `packets`.
+    pub const fn set_gso_batch(&mut self, packets: NonZeroUsize) {
+        self.sender.set_gso_batch(packets);
+    }
+
+    /// Cap the sending rate on the path, in bytes per second, or remove the cap.
+    pub fn set_max_send_rate(&mut self, rate: Option<u64>, now: Instant) {
+        if self.sender.max_send_rate() == rate {
+            return;
+        }
+        self.sender.set_max_send_rate(rate);
+        if let Some(rate) = rate {
+            // qlog has nowhere else to put this, so log it as the pacing rate,
+            // which is in bits per second.
+            qlog::metrics_updated(
+                &mut self.qlog,
+                &[qlog::Metric::PacingRate(rate.saturating_mul(8))],
+                now,
+            );
+        }
+    }
+
     /// Update the `QLog` instance.
     pub fn set_qlog(&mut self, qlog: Qlog) {
         self.sender.set_qlog(qlog.clone());
@@ -45,7 +45,7 @@ enum Probe {
     Sent,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Pmtud {
     search_table: &'static [usize],
     header_size: usize,
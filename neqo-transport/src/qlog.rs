@@ -29,8 +29,8 @@ use qlog::events::{
 use smallvec::SmallVec;
 
 use crate::{
-    CloseReason,
-    cc::{CWND_INITIAL_PKTS, CongestionControl, Cubic, PERSISTENT_CONG_THRESH},
+    CloseReason, ConnectionParameters,
+    cc::{CongestionControl, Cubic},
     connection::State,
     frame::{CloseError, Frame},
     packet::{self, metadata::Direction},
@@ -308,13 +308,24 @@ pub fn packets_lost(qlog: &mut Qlog, pkts: &[sent::Packet], now: Instant) {
 pub fn recovery_parameters_set(
     qlog: &mut Qlog,
     plpmtu: usize,
-    cc: CongestionControl,
+    initial_cwnd: usize,
+    conn_params: &ConnectionParameters,
     now: Instant,
 ) {
     qlog.add_event_at(
         || {
-            let loss_reduction_factor = match cc {
-                CongestionControl::NewReno => 0.5,
+            let loss_reduction_factor = match conn_params.get_congestion_control() {
+                // Westwood+ reduces the window to the estimated bandwidth-delay product,
+                // which varies; this is what it does without an estimate.
+                CongestionControl::NewReno | CongestionControl::Westwood => 0.5,
+                // BBR doesn't reduce its rate on loss, but it limits what is
+                // in flight during recovery.
+                CongestionControl::Bbr => 1.0,
+                // BBRv3 reduces its bounds on what is in flight by this much.
+                CongestionControl::Bbr3 => 0.7,
+                // Prague and LEDBAT++ halve the window on loss.  Prague's response to
+                // CE marks is proportional to how many packets are marked.
+                CongestionControl::Prague | CongestionControl::Ledbat => 0.5,
                 CongestionControl::Cubic => {
                     f32::from(u8::try_from(Cubic::BETA_USIZE_DIVIDEND).expect("fits"))
                         / f32::from(u8::try_from(Cubic::BETA_USIZE_DIVISOR).expect("fits"))
@@ -322,21 +333,23 @@ pub fn recovery_parameters_set(
             };
             Some(EventData::RecoveryParametersSet(RecoveryParametersSet {
                 reordering_threshold: Some(
-                    u16::try_from(crate::recovery::PACKET_THRESHOLD).expect("fits"),
+                    u16::try_from(conn_params.get_packet_threshold()).unwrap_or(u16::MAX),
+                ),
+                time_threshold: Some(
+                    f32::from(conn_params.get_time_threshold())
+                        / f32::from(crate::recovery::TIME_THRESHOLD_SCALE),
                 ),
-                time_threshold: Some(9.0 / 8.0),
                 timer_granularity: Some(u16::try_from(GRANULARITY.as_millis()).expect("fits")),
                 initial_rtt: Some(DEFAULT_INITIAL_RTT.as_secs_f32() * 1000.0),
                 max_datagram_size: Some(u32::try_from(plpmtu).expect("MTU fits in u32")),
-                initial_congestion_window: Some(
-                    u64::try_from(CWND_INITIAL_PKTS * plpmtu).expect("fits"),
-                ),
+                initial_congestion_window: Some(u64::try_from(initial_cwnd).expect("fits")),
                 minimum_congestion_window: Some(
                     u32::try_from(2 * plpmtu).expect("MTU fits in u32"),
                 ),
                 loss_reduction_factor: Some(loss_reduction_factor),
                 persistent_congestion_threshold: Some(
-                    u16::try_from(PERSISTENT_CONG_THRESH).expect("fits"),
+                    u16::try_from(conn_params.get_persistent_congestion_threshold())
+                        .unwrap_or(u16::MAX),
                 ),
             }))
         },
@@ -716,7 +729,7 @@ impl From<Frame<'_>> for QuicFrame {
                 trigger_frame_type: Some(frame_type),
             },
             Frame::HandshakeDone => Self::HandshakeDone,
-            Frame::AckFrequency { .. } => Self::Unknown {
+            Frame::AckFrequency { .. } | Frame::ImmediateAck => Self::Unknown {
                 frame_type_value: None,
                 raw_frame_type: frame.get_type().into(),
                 raw: None,
@@ -31,7 +31,15 @@ use crate::{
     tracking::{PacketNumberSpace, PacketNumberSpaceSet},
 };
 
+/// The default number of packets that can be acknowledged out of order
+/// before an earlier packet is declared lost; kPacketThreshold in RFC 9002.
 pub const PACKET_THRESHOLD: u64 = 3;
+/// The default time that a packet can go unacknowledged after a later packet is
+/// acknowledged, as a multiple of the RTT, scaled by `TIME_THRESHOLD_SCALE`;
+/// kTimeThreshold in RFC 9002.
+pub const TIME_THRESHOLD: u8 = 9;
+/// The scale of the time threshold.
+pub const TIME_THRESHOLD_SCALE: u8 = 8;
 /// `ACK_ONLY_SIZE_LIMIT` is the minimum size of the congestion window.
 /// If the congestion window is this small, we will only send ACK frames.
 pub const ACK_ONLY_SIZE_LIMIT: usize = 256;
@@ -110,6 +118,13 @@ impl SendProfile {
         self.paced
     }
 
+    /// Whether packets are sent as probes, either on PTO or on entering
+    /// recovery.  These are not held back by the pacer.
+    #[must_use]
+    pub fn probing(&self) -> bool {
+        !self.probe.is_empty()
+    }
+
     #[must_use]
     pub const fn limit(&self) -> usize {
         self.limit
@@ -304,11 +319,14 @@ impl LossRecoverySpace {
 
     /// Detect lost packets.
     /// `loss_delay` is the time we will wait before declaring something lost.
+    /// `packet_threshold` is how many later packets need to be acknowledged
+    /// before declaring something lost.
     /// `cleanup_delay` is the time we will wait before cleaning up a lost packet.
     pub fn detect_lost_packets(
         &mut self,
         now: Instant,
         loss_delay: Duration,
+        packet_threshold: u64,
         cleanup_delay: Duration,
         lost_packets: &mut Vec<sent::Packet>,
     ) {
@@ -337,9 +355,9 @@ impl LossRecoverySpace {
                     packet.time_sent()
                 );
                 sent::LossTrigger::TimeThreshold
-            } else if largest_acked >= Some(packet.pn() + PACKET_THRESHOLD) {
+            } else if largest_acked >= Some(packet.pn().saturating_add(packet_threshold)) {
                 qtrace!(
-                    "lost={}, is >= {PACKET_THRESHOLD} from largest acked {largest_acked:?}",
+                    "lost={}, is >= {packet_threshold} from largest acked {largest_acked:?}",
                     packet.pn()
                 );
                 sent::LossTrigger::ReorderingThreshold
@@ -485,11 +503,15 @@ pub struct Loss {
     /// The factor by which the PTO period is reduced.
     /// This enables faster probing at a cost in additional lost packets.
     fast_pto: u8,
+    /// The reordering threshold for loss detection, in packets.
+    packet_threshold: u64,
+    /// The time threshold for loss detection, scaled by `TIME_THRESHOLD_SCALE`.
+    time_threshold: u8,
 }
 
 impl Loss {
     #[must_use]
-    pub fn new(stats: StatsCell, fast_pto: u8) -> Self {
+    pub fn new(stats: StatsCell, fast_pto: u8, packet_threshold: u64, time_threshold: u8) -> Self {
         Self {
             confirmed_time: None,
             pto_state: None,
@@ -497,6 +519,8 @@ impl Loss {
             qlog: Qlog::default(),
             stats,
             fast_pto,
+            packet_threshold,
+            time_threshold,
         }
     }
 
@@ -526,14 +550,22 @@ impl Loss {
         dropped
     }
 
-    pub fn on_packet_sent(&mut self, path: &PathRef, mut sent_packet: sent::Packet, now: Instant) {
+    /// Record a packet as sent.  `probe` is set for packets that are sent as
+    /// probes, see [`SendProfile::probing`].
+    pub fn on_packet_sent(
+        &mut self,
+        path: &PathRef,
+        mut sent_packet: sent::Packet,
+        probe: bool,
+        now: Instant,
+    ) {
         let pn_space = PacketNumberSpace::from(sent_packet.packet_type());
         qtrace!("[{self}] packet {pn_space}-{} sent", sent_packet.pn());
         if let Some(pto) = self.pto_state.as_mut() {
             pto.pto_sent(pn_space);
         }
         if let Some(space) = self.spaces.get_mut(pn_space) {
-            path.borrow_mut().packet_sent(&mut sent_packet, now);
+            path.borrow_mut().packet_sent(&mut sent_packet, probe, now);
             space.on_packet_sent(sent_packet);
         } else {
             qinfo!(
@@ -670,9 +702,15 @@ impl Loss {
         let Some(sp) = self.spaces.get_mut(pn_space) else {
             return (Vec::new(), Vec::new());
         };
-        let loss_delay = primary_path.borrow().rtt().loss_delay();
+        let loss_delay = primary_path.borrow().rtt().loss_delay(self.time_threshold);
         let mut lost = Vec::new();
-        sp.detect_lost_packets(now, loss_delay, cleanup_delay, &mut lost);
+        sp.detect_lost_packets(
+            now,
+            loss_delay,
+            self.packet_threshold,
+            cleanup_delay,
+            &mut lost,
+        );
         self.stats.borrow_mut().lost += lost.len();
 
         // Tell the congestion controller about any lost packets.
@@ -737,6 +775,14 @@ impl Loss {
     }
 
     /// This function is called when the connection migrates.
+    /// The packets that count against the congestion window of the primary path.
+    pub fn cc_outstanding(&self) -> impl Iterator<Item = &sent::Packet> {
+        self.spaces
+            .iter()
+            .flat_map(|space| space.sent_packets.iter())
+            .filter(|pkt| pkt.cc_outstanding())
+    }
+
     /// It marks all packets that are outstanding as having being sent on a non-primary path.
     /// This way failure to deliver on the old path doesn't count against the congestion
     /// control state on the new path and the RTT measurements don't apply either.
@@ -793,7 +839,7 @@ impl Loss {
             .iter()
             .filter_map(LossRecoverySpace::loss_recovery_timer_start)
             .min()
-            .map(|val| val + rtt.loss_delay())
+            .map(|val| val + rtt.loss_delay(self.time_threshold))
     }
 
     /// Simple wrapper for the PTO calculation that avoids borrow check rules.
@@ -952,7 +998,7 @@ impl Loss {
         };
         qlog::loss_timer_expired(&mut self.qlog, timer_type, now);
 
-        let loss_delay = primary_path.borrow().rtt().loss_delay();
+        let loss_delay = primary_path.borrow().rtt().loss_delay(self.time_threshold);
         let confirmed = self.confirmed();
 
         let mut lost_packets = Vec::new();
@@ -964,7 +1010,13 @@ impl Loss {
                 confirmed,
                 self.fast_pto,
             );
-            space.detect_lost_packets(now, loss_delay, pto, &mut lost_packets);
+            space.detect_lost_packets(
+                now,
+                loss_delay,
+                self.packet_threshold,
+                pto,
+                &mut lost_packets,
+            );
 
             primary_path.borrow_mut().on_packets_lost(
                 space.largest_acked_sent_time,
@@ -1036,7 +1088,10 @@ mod tests {
     use neqo_common::qlog::Qlog;
     use test_fixture::{DEFAULT_ADDR, now};
 
-    use super::{FAST_PTO_SCALE, LossRecoverySpace, PacketNumberSpace, PtoState, SendProfile};
+    use super::{
+        FAST_PTO_SCALE, LossRecoverySpace, PACKET_THRESHOLD, PacketNumberSpace, PtoState,
+        SendProfile, TIME_THRESHOLD, TIME_THRESHOLD_SCALE,
+    };
     use crate::{
         ConnectionParameters, Token as Srt,
         cid::{ConnectionId, ConnectionIdEntry},
@@ -1078,7 +1133,7 @@ mod tests {
         }
 
         pub fn on_packet_sent(&mut self, sent_packet: sent::Packet, now: Instant) {
-            self.lr.on_packet_sent(&self.path, sent_packet, now);
+            self.lr.on_packet_sent(&self.path, sent_packet, false, now);
         }
 
         pub fn timeout(&mut self, now: Instant) -> Vec<sent::Packet> {
@@ -1120,7 +1175,7 @@ mod tests {
             path.set_primary(true, now());
             path.rtt_mut().set_initial(TEST_RTT);
             Self {
-                lr: recovery::Loss::new(stats, FAST_PTO_SCALE),
+                lr: recovery::Loss::new(stats, FAST_PTO_SCALE, PACKET_THRESHOLD, TIME_THRESHOLD),
                 path: Rc::new(RefCell::new(path)),
             }
         }
@@ -1433,7 +1488,7 @@ mod tests {
         let mut lr = setup_lr(5); // This sends packets 0-4 and acknowledges pn 0.
 
         // Acknowledge just 2-4, which will cause pn 1 to be marked as lost.
-        assert_eq!(super::PACKET_THRESHOLD, 3);
+        assert_eq!(PACKET_THRESHOLD, 3);
         let (_, lost) = lr.on_ack_received(
             PacketNumberSpace::ApplicationData,
             vec![2..=4],
@@ -1444,6 +1499,61 @@ mod tests {
         assert_eq!(lost.len(), 1);
     }
 
+    #[test]
+    fn big_gap_loss_packet_threshold() {
+        let mut lr = setup_lr(5);
+        lr.packet_threshold = PACKET_THRESHOLD + 1;
+
+        // With a larger threshold, acknowledging 2-4 isn't enough to lose pn 1.
+        let (_, lost) = lr.on_ack_received(
+            PacketNumberSpace::ApplicationData,
+            vec![2..=4],
+            None,
+            ACK_DELAY,
+            pn_time(4),
+        );
+        assert!(lost.is_empty());
+    }
+
+    /// A packet threshold that is too large to add to a packet number
+    /// effectively disables packet threshold loss detection.
+    #[test]
+    fn max_loss_packet_threshold() {
+        let mut lr = setup_lr(5);
+        lr.packet_threshold = u64::MAX;
+
+        let (_, lost) = lr.on_ack_received(
+            PacketNumberSpace::ApplicationData,
+            vec![2..=4],
+            None,
+            ACK_DELAY,
+            pn_time(4),
+        );
+        assert!(lost.is_empty());
+    }
+
+    #[test]
+    fn time_loss_detection_threshold() {
+        let mut lr = setup_lr(3);
+        lr.time_threshold = TIME_THRESHOLD_SCALE * 2;
+
+        let pn2_ack_time = pn_time(2) + TEST_RTT;
+        let (_, lost) = lr.on_ack_received(
+            PacketNumberSpace::ApplicationData,
+            vec![2..=2],
+            None,
+            ACK_DELAY,
+            pn2_ack_time,
+        );
+        assert!(lost.is_empty());
+        assert!(lr.timeout(pn2_ack_time).is_empty());
+
+        // The loss timer for pn 1 is set using the larger threshold.
+        let pn1_loss_time = pn_time(1) + TEST_RTT * 2;
+        assert_eq!(lr.next_timeout(), Some(pn1_loss_time));
+        assert_eq!(lr.timeout(pn1_loss_time).len(), 1);
+    }
+
     #[test]
     #[should_panic(expected = "discarding application space")]
     fn drop_app() {
@@ -1530,6 +1640,7 @@ mod tests {
             lr.spaces.get_mut(pn_space).unwrap().detect_lost_packets(
                 pn_time(3),
                 TEST_RTT,
+                PACKET_THRESHOLD,
                 TEST_RTT * 3, // unused
                 &mut lost,
             );
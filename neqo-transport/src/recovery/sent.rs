@@ -13,7 +13,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{packet, recovery};
+use crate::{delivery_rate::SendState, packet, recovery};
 
 /// The reason a packet was declared lost.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,6 +41,8 @@ pub struct Packet {
     loss_info: Option<LossInfo>,
     /// After a PTO, this is true when the packet has been released.
     pto: bool,
+    /// The delivery state when the packet was sent, for delivery rate estimation.
+    delivery: Option<SendState>,
 
     len: usize,
 }
@@ -64,6 +66,7 @@ impl Packet {
             tokens: Rc::new(tokens),
             loss_info: None,
             pto: false,
+            delivery: None,
             len,
         }
     }
@@ -88,6 +91,14 @@ impl Packet {
             .any(|t| matches!(t, recovery::Token::EcnEct0))
     }
 
+    /// Whether the packet was marked ECT(1), for L4S.
+    #[must_use]
+    pub fn ecn_marked_ect1(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|t| matches!(t, recovery::Token::EcnEct1))
+    }
+
     /// Returns `true` if this packet is a PMTUD probe.
     #[must_use]
     pub fn is_pmtud_probe(&self) -> bool {
@@ -143,6 +154,16 @@ impl Packet {
         self.len += padding;
     }
 
+    /// The delivery state recorded when the packet was sent.
+    #[must_use]
+    pub(crate) const fn delivery_state(&self) -> Option<SendState> {
+        self.delivery
+    }
+
+    pub(crate) const fn set_delivery_state(&mut self, state: SendState) {
+        self.delivery = Some(state);
+    }
+
     /// Whether the packet has been declared lost.
     #[must_use]
     pub const fn lost(&self) -> bool {
@@ -232,6 +253,10 @@ impl Packets {
         self.packets.insert(packet.pn, packet);
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Packet> {
+        self.packets.values()
+    }
+
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Packet> {
         self.packets.values_mut()
     }
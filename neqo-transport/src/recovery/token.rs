@@ -67,6 +67,8 @@ pub enum Token {
     Datagram(DatagramTracking),
     /// A packet marked with [`neqo_common::Ecn::Ect0`].
     EcnEct0,
+    /// A packet marked with [`neqo_common::Ecn::Ect1`], for L4S.
+    EcnEct1,
     /// A PMTUD probe packet.
     PmtudProbe,
 }
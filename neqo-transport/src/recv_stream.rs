@@ -13,11 +13,12 @@ use std::{
     collections::BTreeMap,
     fmt::Debug,
     mem,
+    ops::Deref,
     rc::{Rc, Weak},
     time::{Duration, Instant},
 };
 
-use neqo_common::{Buffer, Role, qtrace};
+use neqo_common::{Buffer, Role, SharedBytes, qtrace};
 use smallvec::SmallVec;
 use strum::Display;
 
@@ -115,13 +116,43 @@ impl RecvStreams {
     }
 }
 
+/// A range of data held by [`RxStreamOrderer`].
+#[derive(Debug)]
+enum RxChunk {
+    /// A copy of the data.
+    Owned(Vec<u8>),
+    /// Part of the datagram that carried the data, which stays allocated
+    /// until this is dropped.
+    Shared(SharedBytes),
+}
+
+impl Deref for RxChunk {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(v) => v,
+            Self::Shared(b) => b,
+        }
+    }
+}
+
+/// The number of allocations that [`RxChunk::Shared`] data of one stream can
+/// keep, before data is copied instead.  An allocation can be a receive buffer
+/// that holds many datagrams, which stays allocated as long as any chunk in it
+/// is held, so this limits how much memory a few small chunks can keep.
+const MAX_PINNED_ALLOCATIONS: usize = 4;
+
 /// Holds data not yet read by application. Orders and dedupes data ranges
 /// from incoming STREAM frames.
 #[derive(Debug, Default)]
 pub struct RxStreamOrderer {
-    data_ranges: BTreeMap<u64, Vec<u8>>, // (start_offset, data)
+    data_ranges: BTreeMap<u64, RxChunk>, // (start_offset, data)
     retired: u64,                        // Number of bytes the application has read
     received: u64,                       // The number of bytes stored in `data_ranges`
+    /// The allocations that [`RxChunk::Shared`] data refers to, with the
+    /// number of chunks in each.
+    pinned: SmallVec<[(SharedBytes, usize); MAX_PINNED_ALLOCATIONS]>,
 }
 
 impl RxStreamOrderer {
@@ -133,10 +164,19 @@ impl RxStreamOrderer {
     /// Process an incoming stream frame off the wire. This may result in data
     /// being available to upper layers if frame is not out of order (ooo) or
     /// if the frame fills a gap.
+    ///
+    /// If `shared` is the received datagram that `new_data` is part of, the
+    /// data is kept without copying it, unless it only takes up a small part
+    /// of the datagram, or the stream already keeps too many allocations.
     /// # Panics
     /// Only when `u64` values cannot be converted to `usize`, which only
     /// happens on 32-bit machines that hold far too much data at the same time.
-    pub fn inbound_frame(&mut self, mut new_start: u64, mut new_data: &[u8]) {
+    pub fn inbound_frame(
+        &mut self,
+        mut new_start: u64,
+        mut new_data: &[u8],
+        shared: Option<&SharedBytes>,
+    ) {
         qtrace!("Inbound data offset={new_start} len={}", new_data.len());
 
         // Get entry before where new entry would go, so we can see if we already
@@ -177,7 +217,7 @@ impl RxStreamOrderer {
                 // If it is small enough, extend the previous buffer.
                 // This can't always extend, because otherwise the buffer could end up
                 // growing indefinitely without being released.
-                prev_vec.len() < 4096 && prev_end == new_start
+                matches!(prev_vec, RxChunk::Owned(v) if v.len() < 4096) && prev_end == new_start
             } else {
                 // PPPPPP    ->  PPPPPP
                 //   NNNN
@@ -241,18 +281,65 @@ impl RxStreamOrderer {
             }
 
             for start in to_remove {
-                self.data_ranges.remove(&start);
+                if let Some(chunk) = self.data_ranges.remove(&start) {
+                    self.unpin(&chunk);
+                }
             }
         }
 
         if !to_add.is_empty() {
             self.received += u64::try_from(to_add.len()).expect("usize fits in u64");
-            if extend {
-                if let Some((_, buf)) = self.data_ranges.range_mut(..=new_start).next_back() {
+            // Only keep the datagram if that doesn't hold on to much more memory
+            // than a copy would.
+            if let Some(data) = shared
+                .filter(|d| to_add.len() * 2 >= d.len())
+                .and_then(|d| d.slice_ref(to_add))
+                .filter(|data| self.pin(data))
+            {
+                self.data_ranges.insert(new_start, RxChunk::Shared(data));
+            } else if extend {
+                if let Some((_, RxChunk::Owned(buf))) =
+                    self.data_ranges.range_mut(..=new_start).next_back()
+                {
                     buf.extend_from_slice(to_add);
                 }
             } else {
-                self.data_ranges.insert(new_start, to_add.to_vec());
+                self.data_ranges
+                    .insert(new_start, RxChunk::Owned(to_add.to_vec()));
+            }
+        }
+    }
+
+    /// Account for a chunk that refers to the allocation of `data`, if that
+    /// doesn't take the number of allocations that are kept over the limit.
+    fn pin(&mut self, data: &SharedBytes) -> bool {
+        if let Some((_, count)) = self
+            .pinned
+            .iter_mut()
+            .find(|(alloc, _)| alloc.same_allocation(data))
+        {
+            *count += 1;
+        } else if self.pinned.len() < MAX_PINNED_ALLOCATIONS {
+            self.pinned.push((data.slice(..0), 1));
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Account for a chunk that is no longer held.
+    fn unpin(&mut self, chunk: &RxChunk) {
+        let RxChunk::Shared(data) = chunk else {
+            return;
+        };
+        if let Some(i) = self
+            .pinned
+            .iter()
+            .position(|(alloc, _)| alloc.same_allocation(data))
+        {
+            self.pinned[i].1 -= 1;
+            if self.pinned[i].1 == 0 {
+                self.pinned.swap_remove(i);
             }
         }
     }
@@ -345,11 +432,16 @@ impl RxStreamOrderer {
             if keep {
                 let mut keep = self.data_ranges.split_off(&range_start);
                 mem::swap(&mut self.data_ranges, &mut keep);
+                for chunk in keep.values() {
+                    self.unpin(chunk);
+                }
                 return copied;
             }
         }
 
-        self.data_ranges.clear();
+        for chunk in mem::take(&mut self.data_ranges).values() {
+            self.unpin(chunk);
+        }
         copied
     }
 
@@ -602,7 +694,13 @@ impl RecvStream {
     /// # Panics
     /// Only when `u64` values are so big that they can't fit in a `usize`, which
     /// only happens on a 32-bit machine that has far too much unread data.
-    pub fn inbound_stream_frame(&mut self, fin: bool, offset: u64, data: &[u8]) -> Res<()> {
+    pub fn inbound_stream_frame(
+        &mut self,
+        fin: bool,
+        offset: u64,
+        data: &[u8],
+        shared: Option<&SharedBytes>,
+    ) -> Res<()> {
         // We should post a DataReadable event only once when we change from no-data-ready to
         // data-ready. Therefore remember the state before processing a new frame.
         let already_data_ready = self.data_ready();
@@ -616,7 +714,7 @@ impl RecvStream {
                 fc,
                 session_fc,
             } => {
-                recv_buf.inbound_frame(offset, data);
+                recv_buf.inbound_frame(offset, data, shared);
                 if fin {
                     let all_recv =
                         fc.consumed() == recv_buf.retired() + recv_buf.bytes_ready() as u64;
@@ -643,7 +741,7 @@ impl RecvStream {
                 fc,
                 session_fc,
             } => {
-                recv_buf.inbound_frame(offset, data);
+                recv_buf.inbound_frame(offset, data, shared);
                 if fc.consumed() == recv_buf.retired() + recv_buf.bytes_ready() as u64 {
                     let buf = mem::replace(recv_buf, RxStreamOrderer::new());
                     let fc_copy = mem::take(fc);
@@ -982,9 +1080,9 @@ mod tests {
         time::{Duration, Instant},
     };
 
-    use neqo_common::{Encoder, qtrace};
+    use neqo_common::{Datagram, Encoder, SharedBytes, Tos, qtrace};
 
-    use super::RecvStream;
+    use super::{MAX_PINNED_ALLOCATIONS, RecvStream, RxChunk};
     use crate::{
         ConnectionEvents, Error, INITIAL_LOCAL_MAX_STREAM_DATA, StreamId,
         fc::{ReceiverFlowControl, WINDOW_UPDATE_FRACTION},
@@ -1002,7 +1100,7 @@ mod tests {
         let mut s = RxStreamOrderer::default();
         for r in ranges {
             let data = &ZEROES[..usize::try_from(r.end - r.start).unwrap()];
-            s.inbound_frame(r.start, data);
+            s.inbound_frame(r.start, data, None);
         }
 
         let mut buf = [0xff; 100];
@@ -1134,11 +1232,11 @@ mod tests {
         let mut s = RxStreamOrderer::new();
 
         // Add three chunks.
-        s.inbound_frame(0, &[0; CHUNK_SIZE]);
+        s.inbound_frame(0, &[0; CHUNK_SIZE], None);
         let offset = u64::try_from(CHUNK_SIZE).unwrap();
-        s.inbound_frame(offset, &[0; EXTRA_SIZE]);
+        s.inbound_frame(offset, &[0; EXTRA_SIZE], None);
         let offset = u64::try_from(CHUNK_SIZE + EXTRA_SIZE).unwrap();
-        s.inbound_frame(offset, &[0; EXTRA_SIZE]);
+        s.inbound_frame(offset, &[0; EXTRA_SIZE], None);
 
         // Read, providing only enough space for the first.
         let mut buf = [0; 100];
@@ -1148,12 +1246,106 @@ mod tests {
         assert_eq!(count, EXTRA_SIZE * 2);
     }
 
+    /// Data that takes up most of its datagram is kept without copying it,
+    /// but small parts of a datagram are copied.
+    #[test]
+    fn shared_chunks() {
+        let mut s = RxStreamOrderer::new();
+        let datagram = SharedBytes::from((0..100).collect::<Vec<u8>>());
+
+        let (head, tail) = datagram.split_at(90);
+        s.inbound_frame(0, &head[10..], Some(&datagram));
+        s.inbound_frame(80, tail, Some(&datagram));
+        assert!(matches!(s.data_ranges[&0], RxChunk::Shared(_)));
+        assert!(matches!(s.data_ranges[&80], RxChunk::Owned(_)));
+
+        // Data that isn't part of the datagram is copied, here by extending
+        // the previous copy.
+        s.inbound_frame(90, &[0; 90], Some(&datagram));
+        assert_eq!(s.data_ranges.len(), 2);
+        assert_eq!(s.data_ranges[&80].len(), 100);
+
+        let mut buf = Vec::new();
+        assert_eq!(s.read_to_end(&mut buf), 180);
+        assert_eq!(&buf[..90], &(10..100).collect::<Vec<u8>>()[..]);
+    }
+
+    /// Data is kept in a limited number of allocations, each of which can hold
+    /// many chunks.  Once the limit is reached, data is copied, until chunks
+    /// are read.
+    #[test]
+    fn shared_chunks_limit() {
+        let mut s = RxStreamOrderer::new();
+        let buffers = (0..=MAX_PINNED_ALLOCATIONS)
+            .map(|_| SharedBytes::from(vec![0; 200]))
+            .collect::<Vec<_>>();
+
+        // Leave a gap at the start, so that nothing can be read.
+        let mut offset = 100;
+        for buffer in &buffers {
+            for datagram in [buffer.slice(..100), buffer.slice(100..)] {
+                s.inbound_frame(offset, &datagram, Some(&datagram));
+                offset += 100;
+            }
+        }
+        assert_eq!(s.pinned.len(), MAX_PINNED_ALLOCATIONS);
+        let shared = |s: &RxStreamOrderer| {
+            s.data_ranges
+                .values()
+                .filter(|c| matches!(c, RxChunk::Shared(_)))
+                .count()
+        };
+        assert_eq!(shared(&s), MAX_PINNED_ALLOCATIONS * 2);
+
+        // Once the data is read, more can be kept.
+        s.inbound_frame(0, &[0; 100], None);
+        let mut buf = Vec::new();
+        s.read_to_end(&mut buf);
+        assert!(s.pinned.is_empty());
+        let datagram = buffers[0].slice(..100);
+        s.inbound_frame(offset, &datagram, Some(&datagram));
+        assert_eq!(shared(&s), 1);
+    }
+
+    /// A datagram that was received into a [`neqo_udp::RecvBuf`], which holds
+    /// many datagrams, is kept without copying it.
+    #[test]
+    fn shared_chunks_from_socket() -> Result<(), std::io::Error> {
+        let sender = neqo_udp::Socket::new(std::net::UdpSocket::bind("127.0.0.1:0")?)?;
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        let local = receiver.local_addr()?;
+        // This socket doesn't block, but the datagram arrives quickly.
+        let receiver = neqo_udp::Socket::new(receiver)?;
+
+        let datagram = Datagram::new(
+            "127.0.0.1:0".parse().unwrap(),
+            local,
+            Tos::default(),
+            vec![1; 1000],
+        );
+        sender.send(&datagram.into())?;
+        let mut recv_buf = neqo_udp::RecvBuf::default();
+        let d = loop {
+            match receiver.recv(local, &mut recv_buf) {
+                Ok(mut datagrams) => break datagrams.next().unwrap(),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        };
+        let shared = d.into_shared().unwrap();
+
+        let mut s = RxStreamOrderer::new();
+        s.inbound_frame(0, &shared[10..], Some(&shared));
+        assert!(matches!(s.data_ranges[&0], RxChunk::Shared(_)));
+        Ok(())
+    }
+
     #[test]
     fn recv_overlap_while_reading() {
         let mut s = RxStreamOrderer::new();
 
         // Add a chunk
-        s.inbound_frame(0, &[0; 150]);
+        s.inbound_frame(0, &[0; 150], None);
         assert_eq!(s.data_ranges[&0].len(), 150);
         // Read, providing only enough space for the first 100.
         let mut buf = [0; 100];
@@ -1164,7 +1356,7 @@ mod tests {
         // Add a second frame that overlaps.
         // This shouldn't truncate the first frame, as we're already
         // Reading from it.
-        s.inbound_frame(120, &[0; 60]);
+        s.inbound_frame(120, &[0; 60], None);
         assert_eq!(s.data_ranges[&0].len(), 180);
         // Read second part of first frame and all of the second frame
         let count = s.read(&mut buf[..]);
@@ -1179,9 +1371,9 @@ mod tests {
         let mut s = RxStreamOrderer::new();
 
         // Add three chunks.
-        s.inbound_frame(0, &[0; CHUNK_SIZE]);
+        s.inbound_frame(0, &[0; CHUNK_SIZE], None);
         let offset = u64::try_from(CHUNK_SIZE + EXTRA_SIZE).unwrap();
-        s.inbound_frame(offset, &[0; EXTRA_SIZE]);
+        s.inbound_frame(offset, &[0; EXTRA_SIZE], None);
 
         // Read, providing only enough space for the first chunk.
         let mut buf = [0; 100];
@@ -1190,7 +1382,7 @@ mod tests {
 
         // Now fill the gap and ensure that everything can be read.
         let offset = u64::try_from(CHUNK_SIZE).unwrap();
-        s.inbound_frame(offset, &[0; EXTRA_SIZE]);
+        s.inbound_frame(offset, &[0; EXTRA_SIZE], None);
         let count = s.read(&mut buf[..]);
         assert_eq!(count, EXTRA_SIZE * 2);
     }
@@ -1203,9 +1395,9 @@ mod tests {
         let mut s = RxStreamOrderer::new();
 
         // Add two chunks.
-        s.inbound_frame(0, &[0; CHUNK_SIZE]);
+        s.inbound_frame(0, &[0; CHUNK_SIZE], None);
         let offset = u64::try_from(CHUNK_SIZE).unwrap();
-        s.inbound_frame(offset, &[0; EXTRA_SIZE]);
+        s.inbound_frame(offset, &[0; EXTRA_SIZE], None);
 
         // Read, providing only enough space for some of the first chunk.
         let mut buf = [0; 100];
@@ -1224,9 +1416,9 @@ mod tests {
         let mut s = RxStreamOrderer::new();
 
         // Add two chunks.
-        s.inbound_frame(0, &[0; CHUNK_SIZE]);
+        s.inbound_frame(0, &[0; CHUNK_SIZE], None);
         let offset = u64::try_from(CHUNK_SIZE).unwrap();
-        s.inbound_frame(offset, &[0; EXTRA_SIZE]);
+        s.inbound_frame(offset, &[0; EXTRA_SIZE], None);
 
         let mut buf = [0; 1];
         for _ in 0..CHUNK_SIZE + EXTRA_SIZE {
@@ -1254,7 +1446,7 @@ mod tests {
         );
 
         // test receiving a contig frame and reading it works
-        s.inbound_stream_frame(false, 0, &[1; 10]).unwrap();
+        s.inbound_stream_frame(false, 0, &[1; 10], None).unwrap();
         assert!(s.data_ready());
         check_stats(&s, 10, 0);
 
@@ -1266,7 +1458,7 @@ mod tests {
         check_stats(&s, 10, 10);
 
         // test receiving a noncontig frame
-        s.inbound_stream_frame(false, 12, &[2; 12]).unwrap();
+        s.inbound_stream_frame(false, 12, &[2; 12], None).unwrap();
         assert!(!s.data_ready());
         assert_eq!(s.read(&mut buf).unwrap(), (0, false));
         assert_eq!(s.state.recv_buf().unwrap().retired(), 10);
@@ -1275,7 +1467,7 @@ mod tests {
         check_stats(&s, 22, 10);
 
         // another frame that overlaps the first
-        s.inbound_stream_frame(false, 14, &[3; 8]).unwrap();
+        s.inbound_stream_frame(false, 14, &[3; 8], None).unwrap();
         assert!(!s.data_ready());
         assert_eq!(s.state.recv_buf().unwrap().retired(), 10);
         assert_eq!(s.state.recv_buf().unwrap().buffered(), 12);
@@ -1283,7 +1475,7 @@ mod tests {
         check_stats(&s, 22, 10);
 
         // fill in the gap, but with a FIN
-        s.inbound_stream_frame(true, 10, &[4; 6]).unwrap_err();
+        s.inbound_stream_frame(true, 10, &[4; 6], None).unwrap_err();
         assert!(!s.data_ready());
         assert_eq!(s.read(&mut buf).unwrap(), (0, false));
         assert_eq!(s.state.recv_buf().unwrap().retired(), 10);
@@ -1292,7 +1484,7 @@ mod tests {
         check_stats(&s, 22, 10);
 
         // fill in the gap
-        s.inbound_stream_frame(false, 10, &[5; 10]).unwrap();
+        s.inbound_stream_frame(false, 10, &[5; 10], None).unwrap();
         assert!(s.data_ready());
         assert_eq!(s.state.recv_buf().unwrap().retired(), 10);
         assert_eq!(s.state.recv_buf().unwrap().buffered(), 14);
@@ -1300,7 +1492,7 @@ mod tests {
         check_stats(&s, 24, 10);
 
         // a legit FIN
-        s.inbound_stream_frame(true, 24, &[6; 18]).unwrap();
+        s.inbound_stream_frame(true, 24, &[6; 18], None).unwrap();
         assert_eq!(s.state.recv_buf().unwrap().retired(), 10);
         assert_eq!(s.state.recv_buf().unwrap().buffered(), 32);
         assert!(s.data_ready());
@@ -1324,27 +1516,27 @@ mod tests {
     fn stream_rx_dedupe_tail() {
         let mut s = RxStreamOrderer::new();
 
-        s.inbound_frame(0, &[1; 6]);
+        s.inbound_frame(0, &[1; 6], None);
         check_chunks(&s, &[(0, 6)]);
 
         // New data that overlaps entirely (starting from the head), is ignored.
-        s.inbound_frame(0, &[2; 3]);
+        s.inbound_frame(0, &[2; 3], None);
         check_chunks(&s, &[(0, 6)]);
 
         // New data that overlaps at the tail has any new data appended.
-        s.inbound_frame(2, &[3; 6]);
+        s.inbound_frame(2, &[3; 6], None);
         check_chunks(&s, &[(0, 8)]);
 
         // New data that overlaps entirely (up to the tail), is ignored.
-        s.inbound_frame(4, &[4; 4]);
+        s.inbound_frame(4, &[4; 4], None);
         check_chunks(&s, &[(0, 8)]);
 
         // New data that overlaps, starting from the beginning is appended too.
-        s.inbound_frame(0, &[5; 10]);
+        s.inbound_frame(0, &[5; 10], None);
         check_chunks(&s, &[(0, 10)]);
 
         // New data that is entirely subsumed is ignored.
-        s.inbound_frame(2, &[6; 2]);
+        s.inbound_frame(2, &[6; 2], None);
         check_chunks(&s, &[(0, 10)]);
 
         let mut buf = [0; 16];
@@ -1357,15 +1549,15 @@ mod tests {
     fn stream_rx_dedupe_head() {
         let mut s = RxStreamOrderer::new();
 
-        s.inbound_frame(1, &[6; 6]);
+        s.inbound_frame(1, &[6; 6], None);
         check_chunks(&s, &[(1, 6)]);
 
         // Insertion before an existing chunk causes truncation of the new chunk.
-        s.inbound_frame(0, &[7; 6]);
+        s.inbound_frame(0, &[7; 6], None);
         check_chunks(&s, &[(0, 1), (1, 6)]);
 
         // Perfect overlap with existing slices has no effect.
-        s.inbound_frame(0, &[8; 7]);
+        s.inbound_frame(0, &[8; 7], None);
         check_chunks(&s, &[(0, 1), (1, 6)]);
 
         let mut buf = [0; 16];
@@ -1377,16 +1569,16 @@ mod tests {
     fn stream_rx_dedupe_new_tail() {
         let mut s = RxStreamOrderer::new();
 
-        s.inbound_frame(1, &[6; 6]);
+        s.inbound_frame(1, &[6; 6], None);
         check_chunks(&s, &[(1, 6)]);
 
         // Insertion before an existing chunk causes truncation of the new chunk.
-        s.inbound_frame(0, &[7; 6]);
+        s.inbound_frame(0, &[7; 6], None);
         check_chunks(&s, &[(0, 1), (1, 6)]);
 
         // New data at the end causes the tail to be added to the first chunk,
         // replacing later chunks entirely.
-        s.inbound_frame(0, &[9; 8]);
+        s.inbound_frame(0, &[9; 8], None);
         check_chunks(&s, &[(0, 8)]);
 
         let mut buf = [0; 16];
@@ -1398,15 +1590,15 @@ mod tests {
     fn stream_rx_dedupe_replace() {
         let mut s = RxStreamOrderer::new();
 
-        s.inbound_frame(2, &[6; 6]);
+        s.inbound_frame(2, &[6; 6], None);
         check_chunks(&s, &[(2, 6)]);
 
         // Insertion before an existing chunk causes truncation of the new chunk.
-        s.inbound_frame(1, &[7; 6]);
+        s.inbound_frame(1, &[7; 6], None);
         check_chunks(&s, &[(1, 1), (2, 6)]);
 
         // New data at the start and end replaces all the slices.
-        s.inbound_frame(0, &[9; 10]);
+        s.inbound_frame(0, &[9; 10], None);
         check_chunks(&s, &[(0, 10)]);
 
         let mut buf = [0; 16];
@@ -1419,14 +1611,14 @@ mod tests {
         let mut s = RxStreamOrderer::new();
 
         let mut buf = [0; 18];
-        s.inbound_frame(0, &[1; 10]);
+        s.inbound_frame(0, &[1; 10], None);
 
         // Partially read slices are retained.
         assert_eq!(s.read(&mut buf[..6]), 6);
         check_chunks(&s, &[(0, 10)]);
 
         // Partially read slices are kept and so are added to.
-        s.inbound_frame(3, &buf[..10]);
+        s.inbound_frame(3, &buf[..10], None);
         check_chunks(&s, &[(0, 13)]);
 
         // Wholly read pieces are dropped.
@@ -1434,7 +1626,7 @@ mod tests {
         assert!(s.data_ranges.is_empty());
 
         // New data that overlaps with retired data is trimmed.
-        s.inbound_frame(0, &buf[..]);
+        s.inbound_frame(0, &buf[..], None);
         check_chunks(&s, &[(13, 5)]);
     }
 
@@ -1445,7 +1637,7 @@ mod tests {
 
         assert!(!s.has_frames_to_write());
         let big_buf = vec![0; INITIAL_LOCAL_MAX_STREAM_DATA];
-        s.inbound_stream_frame(false, 0, &big_buf).unwrap();
+        s.inbound_stream_frame(false, 0, &big_buf, None).unwrap();
         assert!(!s.has_frames_to_write());
         assert_eq!(
             s.read(&mut buf).unwrap(),
@@ -1487,8 +1679,8 @@ mod tests {
         let mut s = create_stream(1024 * INITIAL_LOCAL_MAX_STREAM_DATA as u64);
         assert!(!s.has_frames_to_write());
         let big_buf = vec![0; INITIAL_LOCAL_MAX_STREAM_DATA];
-        s.inbound_stream_frame(false, 0, &big_buf).unwrap();
-        s.inbound_stream_frame(false, INITIAL_LOCAL_MAX_STREAM_DATA as u64, &[1; 1])
+        s.inbound_stream_frame(false, 0, &big_buf, None).unwrap();
+        s.inbound_stream_frame(false, INITIAL_LOCAL_MAX_STREAM_DATA as u64, &[1; 1], None)
             .unwrap_err();
     }
 
@@ -1496,7 +1688,7 @@ mod tests {
     fn stream_orderer_bytes_ready() {
         let mut rx_ord = RxStreamOrderer::new();
 
-        rx_ord.inbound_frame(0, &[1; 6]);
+        rx_ord.inbound_frame(0, &[1; 6], None);
         assert_eq!(rx_ord.bytes_ready(), 6);
         assert_eq!(rx_ord.buffered(), 6);
         assert_eq!(rx_ord.retired(), 0);
@@ -1509,19 +1701,19 @@ mod tests {
         assert_eq!(rx_ord.retired(), 2);
 
         // an overlapping frame
-        rx_ord.inbound_frame(5, &[2; 6]);
+        rx_ord.inbound_frame(5, &[2; 6], None);
         assert_eq!(rx_ord.bytes_ready(), 9);
         assert_eq!(rx_ord.buffered(), 9);
         assert_eq!(rx_ord.retired(), 2);
 
         // a noncontig frame
-        rx_ord.inbound_frame(20, &[3; 6]);
+        rx_ord.inbound_frame(20, &[3; 6], None);
         assert_eq!(rx_ord.bytes_ready(), 9);
         assert_eq!(rx_ord.buffered(), 15);
         assert_eq!(rx_ord.retired(), 2);
 
         // an old frame
-        rx_ord.inbound_frame(0, &[4; 2]);
+        rx_ord.inbound_frame(0, &[4; 2], None);
         assert_eq!(rx_ord.bytes_ready(), 9);
         assert_eq!(rx_ord.buffered(), 15);
         assert_eq!(rx_ord.retired(), 2);
@@ -1532,11 +1724,11 @@ mod tests {
         let mut s = create_stream(1024 * INITIAL_LOCAL_MAX_STREAM_DATA as u64);
         let mut buf = vec![0; INITIAL_LOCAL_MAX_STREAM_DATA];
         // Write from buf at first.
-        s.inbound_stream_frame(false, 0, &buf).unwrap();
+        s.inbound_stream_frame(false, 0, &buf, None).unwrap();
         // Then read into it.
         s.read(&mut buf).unwrap();
         assert!(s.has_frames_to_write());
-        s.inbound_stream_frame(true, INITIAL_LOCAL_MAX_STREAM_DATA as u64, &[])
+        s.inbound_stream_frame(true, INITIAL_LOCAL_MAX_STREAM_DATA as u64, &[], None)
             .unwrap();
         assert!(!s.has_frames_to_write());
     }
@@ -1569,7 +1761,7 @@ mod tests {
     fn session_flow_control() {
         let (mut s, session_fc) = create_stream_session_flow_control();
 
-        s.inbound_stream_frame(false, 0, &[0; SESSION_WINDOW])
+        s.inbound_stream_frame(false, 0, &[0; SESSION_WINDOW], None)
             .unwrap();
         assert!(!session_fc.borrow().frame_needed());
         // The buffer is big enough to hold SESSION_WINDOW, this will make sure that we always
@@ -1590,14 +1782,20 @@ mod tests {
         );
 
         // Switch to SizeKnown state
-        s.inbound_stream_frame(true, 2 * u64::try_from(SESSION_WINDOW).unwrap() - 1, &[0])
-            .unwrap();
+        s.inbound_stream_frame(
+            true,
+            2 * u64::try_from(SESSION_WINDOW).unwrap() - 1,
+            &[0],
+            None,
+        )
+        .unwrap();
         assert!(!session_fc.borrow().frame_needed());
         // Receive new data that can be read.
         s.inbound_stream_frame(
             false,
             u64::try_from(SESSION_WINDOW).unwrap(),
             &[0; SESSION_WINDOW / 2 + 1],
+            None,
         )
         .unwrap();
         assert!(!session_fc.borrow().frame_needed());
@@ -1627,7 +1825,7 @@ mod tests {
             ConnectionEvents::default(),
         );
 
-        s.inbound_stream_frame(true, 0, &[0; SESSION_WINDOW])
+        s.inbound_stream_frame(true, 0, &[0; SESSION_WINDOW], None)
             .unwrap();
         assert!(!session_fc.borrow().frame_needed());
         s.read(&mut buf).unwrap();
@@ -1638,7 +1836,7 @@ mod tests {
     fn session_flow_control_reset() {
         let (mut s, session_fc) = create_stream_session_flow_control();
 
-        s.inbound_stream_frame(false, 0, &[0; SESSION_WINDOW / 2])
+        s.inbound_stream_frame(false, 0, &[0; SESSION_WINDOW / 2], None)
             .unwrap();
         assert!(!session_fc.borrow().frame_needed());
 
@@ -1663,7 +1861,8 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 4]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 4], None)
+            .unwrap();
 
         check_fc(&fc.borrow(), SW / 4, 0);
         check_fc(s.fc().unwrap(), SW / 4, 0);
@@ -1683,13 +1882,15 @@ mod tests {
         check_fc(s1.fc().unwrap(), 0, 0);
         check_fc(s2.fc().unwrap(), 0, 0);
 
-        s1.inbound_stream_frame(false, 0, &[0; SW_US / 4]).unwrap();
+        s1.inbound_stream_frame(false, 0, &[0; SW_US / 4], None)
+            .unwrap();
 
         check_fc(&fc.borrow(), SW / 4, 0);
         check_fc(s1.fc().unwrap(), SW / 4, 0);
         check_fc(s2.fc().unwrap(), 0, 0);
 
-        s2.inbound_stream_frame(false, 0, &[0; SW_US / 4]).unwrap();
+        s2.inbound_stream_frame(false, 0, &[0; SW_US / 4], None)
+            .unwrap();
 
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s1.fc().unwrap(), SW / 4, 0);
@@ -1710,8 +1911,10 @@ mod tests {
         check_fc(s1.fc().unwrap(), 0, 0);
         check_fc(s2.fc().unwrap(), 0, 0);
 
-        s1.inbound_stream_frame(false, 0, &[0; SW_US / 4]).unwrap();
-        s2.inbound_stream_frame(false, 0, &[0; SW_US / 4]).unwrap();
+        s1.inbound_stream_frame(false, 0, &[0; SW_US / 4], None)
+            .unwrap();
+        s2.inbound_stream_frame(false, 0, &[0; SW_US / 4], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s1.fc().unwrap(), SW / 4, 0);
         check_fc(s2.fc().unwrap(), SW / 4, 0);
@@ -1735,7 +1938,7 @@ mod tests {
         check_fc(s2.fc().unwrap(), SW / 4, SW / 4);
 
         // Receiving more data on a stream.
-        s1.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4])
+        s1.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW * 3 / 4, SW / 2);
         check_fc(s1.fc().unwrap(), SW / 2, SW / 4);
@@ -1759,14 +1962,16 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 4]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 4], None)
+            .unwrap();
 
         check_fc(&fc.borrow(), SW / 4, 0);
         check_fc(s.fc().unwrap(), SW / 4, 0);
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 8]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 8], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 4, 0);
         check_fc(s.fc().unwrap(), SW / 4, 0);
     }
@@ -1781,13 +1986,14 @@ mod tests {
         let mut s = create_stream_with_fc(Rc::clone(&fc), SW * 3 / 4);
 
         // Receive out of order data.
-        s.inbound_stream_frame(false, SW / 8, &[0; SW_US / 8])
+        s.inbound_stream_frame(false, SW / 8, &[0; SW_US / 8], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 4, 0);
         check_fc(s.fc().unwrap(), SW / 4, 0);
 
         // Filling in the gap will not change fc.
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 8]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 8], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 4, 0);
         check_fc(s.fc().unwrap(), SW / 4, 0);
     }
@@ -1803,7 +2009,7 @@ mod tests {
 
         // Receiving frame past the flow control will cause an error.
         assert_eq!(
-            s.inbound_stream_frame(false, 0, &[0; SW_US * 3 / 4 + 1]),
+            s.inbound_stream_frame(false, 0, &[0; SW_US * 3 / 4 + 1], None),
             Err(Error::FlowControl)
         );
     }
@@ -1834,8 +2040,13 @@ mod tests {
         check_fc(s.fc().unwrap(), 0, 0);
 
         // Receive data up to but not over the fc update trigger point.
-        s.inbound_stream_frame(false, 0, &[0; STREAM_WINDOW_US / WINDOW_UPDATE_FRACTION_US])
-            .unwrap();
+        s.inbound_stream_frame(
+            false,
+            0,
+            &[0; STREAM_WINDOW_US / WINDOW_UPDATE_FRACTION_US],
+            None,
+        )
+        .unwrap();
         let mut buf = [1; CONNECTION_WINDOW_US];
         assert_eq!(
             s.read(&mut buf).unwrap(),
@@ -1857,7 +2068,7 @@ mod tests {
         assert!(!s.fc().unwrap().frame_needed());
 
         // Receive one more byte that will cause a fc update after it is read.
-        s.inbound_stream_frame(false, STREAM_WINDOW / WINDOW_UPDATE_FRACTION, &[0])
+        s.inbound_stream_frame(false, STREAM_WINDOW / WINDOW_UPDATE_FRACTION, &[0], None)
             .unwrap();
         check_fc(
             &fc.borrow(),
@@ -1893,6 +2104,7 @@ mod tests {
             false,
             STREAM_WINDOW / WINDOW_UPDATE_FRACTION,
             &[0; STREAM_WINDOW_US / WINDOW_UPDATE_FRACTION_US],
+            None,
         )
         .unwrap();
         assert_eq!(
@@ -1935,8 +2147,13 @@ mod tests {
         assert_eq!(stats.max_stream_data, 1);
 
         // Receive 1 byte that will cause a session fc update after it is read.
-        s.inbound_stream_frame(false, STREAM_WINDOW * 2 / WINDOW_UPDATE_FRACTION, &[0])
-            .unwrap();
+        s.inbound_stream_frame(
+            false,
+            STREAM_WINDOW * 2 / WINDOW_UPDATE_FRACTION,
+            &[0],
+            None,
+        )
+        .unwrap();
         assert_eq!(s.read(&mut buf).unwrap(), (1, false));
         check_fc(
             &fc.borrow(),
@@ -1980,40 +2197,41 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // Receiving frame past the final size of a stream will return an error.
         assert_eq!(
-            s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4 + 1]),
+            s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4 + 1], None),
             Err(Error::FinalSize)
         );
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // Add new data to the gap will not change fc.
-        s.inbound_stream_frame(false, SW / 8, &[0; SW_US / 8])
+        s.inbound_stream_frame(false, SW / 8, &[0; SW_US / 8], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // Fill the gap
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 8]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 8], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
@@ -2037,26 +2255,27 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(true, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(true, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
         // Receiving frame past the final size of a stream will return an error.
         assert_eq!(
-            s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4 + 1]),
+            s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4 + 1], None),
             Err(Error::FinalSize)
         );
         check_fc(&fc.borrow(), SW / 2, 0);
@@ -2081,7 +2300,8 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(true, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(true, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
@@ -2093,16 +2313,17 @@ mod tests {
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(true, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(true, 0, &[0; SW_US / 2], None)
+            .unwrap();
         // the stream does not have fc any more. We can only check the session fc.
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         assert!(s.fc().is_none());
 
         // Receiving frame past the final size of a stream or the stream's fc limit
         // will NOT return an error.
-        s.inbound_stream_frame(true, 0, &[0; SW_US / 2 + 1])
+        s.inbound_stream_frame(true, 0, &[0; SW_US / 2 + 1], None)
             .unwrap();
-        s.inbound_stream_frame(true, 0, &[0; SW_US * 3 / 4 + 1])
+        s.inbound_stream_frame(true, 0, &[0; SW_US * 3 / 4 + 1], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         assert!(s.fc().is_none());
@@ -2119,7 +2340,7 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
@@ -2131,19 +2352,20 @@ mod tests {
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(true, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(true, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         check_fc(s.fc().unwrap(), SW / 2, SW / 2);
 
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         check_fc(s.fc().unwrap(), SW / 2, SW / 2);
 
         // Receiving frame past the final size of a stream will return an error.
         assert_eq!(
-            s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4 + 1]),
+            s.inbound_stream_frame(true, SW / 4, &[0; SW_US / 4 + 1], None),
             Err(Error::FinalSize)
         );
         check_fc(&fc.borrow(), SW / 2, SW / 2);
@@ -2161,7 +2383,8 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
@@ -2172,36 +2395,39 @@ mod tests {
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         check_fc(s.fc().unwrap(), SW / 2, SW / 2);
 
         // Receiving data past the flow control limit will cause an error.
         assert_eq!(
-            s.inbound_stream_frame(false, 0, &[0; SW_US * 3 / 4 + 1]),
+            s.inbound_stream_frame(false, 0, &[0; SW_US * 3 / 4 + 1], None),
             Err(Error::FlowControl)
         );
 
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         check_fc(s.fc().unwrap(), SW / 2, SW / 2);
 
         // Receiving more data will case the data to be retired.
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 2, &[0; 10]).unwrap();
+        s.inbound_stream_frame(false, SW / 2, &[0; 10], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2 + 10, SW / 2 + 10);
         check_fc(s.fc().unwrap(), SW / 2 + 10, SW / 2 + 10);
 
         // We can still receive the final size.
-        s.inbound_stream_frame(true, SW / 2, &[0; 20]).unwrap();
+        s.inbound_stream_frame(true, SW / 2, &[0; 20], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2 + 20, SW / 2 + 20);
         check_fc(s.fc().unwrap(), SW / 2 + 20, SW / 2 + 20);
 
         // Receiving frame past the final size of a stream will return an error.
         assert_eq!(
-            s.inbound_stream_frame(true, SW / 2, &[0; 21]),
+            s.inbound_stream_frame(true, SW / 2, &[0; 21], None),
             Err(Error::FinalSize)
         );
         check_fc(&fc.borrow(), SW / 2 + 20, SW / 2 + 20);
@@ -2219,7 +2445,8 @@ mod tests {
         check_fc(&fc.borrow(), 0, 0);
         check_fc(s.fc().unwrap(), 0, 0);
 
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, 0);
         check_fc(s.fc().unwrap(), SW / 2, 0);
 
@@ -2233,25 +2460,27 @@ mod tests {
 
         // Receiving duplicate frames (already consumed data) will not cause an error or
         // change fc.
-        s.inbound_stream_frame(false, 0, &[0; SW_US / 2]).unwrap();
+        s.inbound_stream_frame(false, 0, &[0; SW_US / 2], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         check_fc(s.fc().unwrap(), SW / 2, SW / 2);
 
         // Receiving data past the flow control limit will cause an error.
         assert_eq!(
-            s.inbound_stream_frame(false, 0, &[0; SW_US * 3 / 4 + 1]),
+            s.inbound_stream_frame(false, 0, &[0; SW_US * 3 / 4 + 1], None),
             Err(Error::FlowControl)
         );
 
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4])
+        s.inbound_stream_frame(false, SW / 4, &[0; SW_US / 4], None)
             .unwrap();
         check_fc(&fc.borrow(), SW / 2, SW / 2);
         check_fc(s.fc().unwrap(), SW / 2, SW / 2);
 
         // Receiving more data will case the data to be retired.
         // The stream can still receive duplicate data without a fin bit.
-        s.inbound_stream_frame(false, SW / 2, &[0; 10]).unwrap();
+        s.inbound_stream_frame(false, SW / 2, &[0; 10], None)
+            .unwrap();
         check_fc(&fc.borrow(), SW / 2 + 10, SW / 2 + 10);
         check_fc(s.fc().unwrap(), SW / 2 + 10, SW / 2 + 10);
     }
@@ -48,6 +48,7 @@ pub struct RttEstimate {
 }
 
 impl RttEstimate {
+    #[must_use]
     pub fn new(initial_rtt: Duration) -> Self {
         Self {
             first_sample_time: None,
@@ -83,6 +84,14 @@ impl RttEstimate {
         self.ack_delay = other.ack_delay.clone();
     }
 
+    /// Discard all samples, for a path where they no longer apply.
+    /// The peer's acknowledgment delay is kept.
+    pub fn reset(&mut self, initial_rtt: Duration) {
+        let ack_delay = self.ack_delay.clone();
+        *self = Self::new(initial_rtt);
+        self.ack_delay = ack_delay;
+    }
+
     pub const fn set_ack_delay(&mut self, ack_delay: PeerAckDelay) {
         self.ack_delay = ack_delay;
     }
@@ -91,6 +100,14 @@ impl RttEstimate {
         self.ack_delay.update(cwnd, mtu, self.smoothed_rtt);
     }
 
+    /// Whether the peer can be asked to acknowledge immediately
+    /// using an IMMEDIATE_ACK frame.
+    #[must_use]
+    pub const fn immediate_ack_allowed(&self) -> bool {
+        self.ack_delay.is_flexible()
+    }
+
+    #[must_use]
     pub fn is_guesstimate(&self) -> bool {
         self.best_source == RttSource::Guesstimate
     }
@@ -151,10 +168,12 @@ impl RttEstimate {
     }
 
     /// Get the estimated value.
+    #[must_use]
     pub const fn estimate(&self) -> Duration {
         self.smoothed_rtt
     }
 
+    #[must_use]
     pub fn pto(&self, confirmed: bool) -> Duration {
         let mut t = self.estimate() + max(4 * self.rttvar, GRANULARITY);
         if confirmed {
@@ -165,26 +184,35 @@ impl RttEstimate {
 
     /// Calculate the loss delay based on the current estimate and the last
     /// RTT measurement received.
-    pub fn loss_delay(&self) -> Duration {
-        // kTimeThreshold = 9/8
+    /// `time_threshold` is kTimeThreshold, scaled by
+    /// `recovery::TIME_THRESHOLD_SCALE`.
+    #[must_use]
+    pub fn loss_delay(&self, time_threshold: u8) -> Duration {
         // loss_delay = kTimeThreshold * max(latest_rtt, smoothed_rtt)
         // loss_delay = max(loss_delay, kGranularity)
         let rtt = max(self.latest_rtt, self.smoothed_rtt);
-        max(rtt * 9 / 8, GRANULARITY)
+        max(
+            rtt * u32::from(time_threshold) / u32::from(recovery::TIME_THRESHOLD_SCALE),
+            GRANULARITY,
+        )
     }
 
+    #[must_use]
     pub const fn first_sample_time(&self) -> Option<Instant> {
         self.first_sample_time
     }
 
+    #[must_use]
     pub const fn latest_rtt(&self) -> Duration {
         self.latest_rtt
     }
 
+    #[must_use]
     pub const fn rttvar(&self) -> Duration {
         self.rttvar
     }
 
+    #[must_use]
     pub const fn minimum(&self) -> Duration {
         self.min_rtt
     }
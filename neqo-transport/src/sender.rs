@@ -6,16 +6,22 @@
 
 // Congestion control
 
-use std::time::{Duration, Instant};
+use std::{
+    cmp::max,
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
 
 use neqo_common::{qdebug, qlog::Qlog};
 
 use crate::{
     ConnectionParameters, SlowStart, Stats,
     cc::{
-        ClassicCongestionController, ClassicSlowStart, CongestionControl, CongestionController,
-        Cubic, HyStart, NewReno,
+        Bbr, Bbr3, ClassicCongestionController, ClassicSlowStart, CongestionControl,
+        CongestionController, CongestionPhase, Cubic, HyStart, IdleRestart, Ledbat, NewReno,
+        PERSISTENT_CONG_THRESH, Prague, Search, Westwood,
     },
+    delivery_rate::DeliveryRate,
     pace::Pacer,
     pmtud::Pmtud,
     recovery::sent,
@@ -23,60 +29,214 @@ use crate::{
     stats::CongestionControlStats,
 };
 
-/// The number of packets we allow to burst from the pacer.
+/// The number of packets we allow to burst from the pacer by default.
 pub const PACING_BURST_SIZE: usize = 2;
 
 #[derive(Debug)]
 pub struct PacketSender {
     cc: Box<dyn CongestionController>,
+    /// Delivery rate estimation, which the congestion controller receives samples from.
+    delivery: DeliveryRate,
     pacer: Pacer,
+    /// Whether pacing is enabled in the connection parameters.
+    pacing: bool,
+    /// The pacing gain, in percent.
+    pacing_gain: usize,
+    /// The pacing gain in slow start, in percent.
+    pacing_gain_slow_start: usize,
+    /// Whether pacing is suspended until the handshake is confirmed.
+    handshake_burst: bool,
+    /// The number of round trips without sending after which the pacer is refilled.
+    pacing_idle_reset: u32,
+    /// A cap on the sending rate, in bytes per second, set by the application.
+    max_send_rate: Option<u64>,
+    /// How far ahead of the paced departure time packets may be released.
+    pacing_horizon: Duration,
+    /// The number of PTO periods that persistent congestion spans.
+    pc_threshold: u32,
+    /// Whether persistent congestion requires an RTT sample.
+    pc_rtt_sample: bool,
+    /// The state of the congestion controller when statistics were last updated.
+    observed: Observed,
+    /// What to do with the congestion window after an idle period.
+    idle_restart: IdleRestart,
+    /// When a packet that counts toward bytes in flight was last sent.
+    last_sent: Option<Instant>,
+    /// When the pacing rate was last logged, and the rate that was logged.
+    pacing_logged: Option<(Instant, u64)>,
+}
+
+/// The parts of the congestion controller state that statistics track changes to.
+#[derive(Debug, Clone, Copy)]
+struct Observed {
+    phase: CongestionPhase,
+    ssthresh: usize,
+    persistent_congestion: usize,
+}
+
+impl Observed {
+    fn new(cc: &dyn CongestionController, persistent_congestion: usize) -> Self {
+        Self {
+            phase: cc.phase(),
+            ssthresh: cc.ssthresh(),
+            persistent_congestion,
+        }
+    }
 }
 
 impl PacketSender {
     #[must_use]
     pub fn new(conn_params: &ConnectionParameters, pmtud: Pmtud, now: Instant) -> Self {
         let mtu = pmtud.plpmtu();
+        let cc = Self::congestion_controller(conn_params, pmtud);
         Self {
-            cc: match (
-                conn_params.get_congestion_control(),
-                conn_params.get_slow_start(),
-            ) {
-                (CongestionControl::NewReno, SlowStart::Classic) => {
-                    Box::new(ClassicCongestionController::new(
-                        ClassicSlowStart::default(),
-                        NewReno::default(),
-                        pmtud,
-                    ))
-                }
-                (CongestionControl::NewReno, SlowStart::HyStart) => {
-                    Box::new(ClassicCongestionController::new(
-                        HyStart::new(conn_params.pacing_enabled()),
-                        NewReno::default(),
-                        pmtud,
-                    ))
-                }
-                (CongestionControl::Cubic, SlowStart::Classic) => {
-                    Box::new(ClassicCongestionController::new(
-                        ClassicSlowStart::default(),
-                        Cubic::default(),
-                        pmtud,
-                    ))
-                }
-                (CongestionControl::Cubic, SlowStart::HyStart) => {
-                    Box::new(ClassicCongestionController::new(
-                        HyStart::new(conn_params.pacing_enabled()),
-                        Cubic::default(),
-                        pmtud,
-                    ))
-                }
-            },
+            observed: Observed::new(cc.as_ref(), 0),
+            cc,
+            delivery: DeliveryRate::default(),
             pacer: Pacer::new(
                 conn_params.pacing_enabled(),
                 now,
-                mtu * PACING_BURST_SIZE,
+                mtu * conn_params.get_pacing_burst(),
                 mtu,
             ),
+            pacing: conn_params.pacing_enabled(),
+            pacing_gain: conn_params.get_pacing_gain(),
+            pacing_gain_slow_start: conn_params.get_pacing_gain_slow_start(),
+            handshake_burst: !conn_params.handshake_pacing_enabled(),
+            pacing_idle_reset: conn_params.get_pacing_idle_reset(),
+            max_send_rate: None,
+            pacing_horizon: if conn_params.pacing_enabled() {
+                conn_params.get_pacing_horizon()
+            } else {
+                Duration::ZERO
+            },
+            pc_threshold: conn_params.get_persistent_congestion_threshold(),
+            pc_rtt_sample: conn_params.persistent_congestion_rtt_sample_required(),
+            idle_restart: conn_params.get_idle_restart(),
+            last_sent: None,
+            pacing_logged: None,
+        }
+    }
+
+    fn congestion_controller(
+        conn_params: &ConnectionParameters,
+        pmtud: Pmtud,
+    ) -> Box<dyn CongestionController> {
+        if let Some(factory) = conn_params.get_congestion_controller() {
+            return factory.make(pmtud);
+        }
+        let initial_cwnd = conn_params.get_initial_cwnd();
+        let hystart = || {
+            HyStart::new(conn_params.pacing_enabled()).with_config(conn_params.get_hystart_config())
+        };
+        match (
+            conn_params.get_congestion_control(),
+            conn_params.get_slow_start(),
+        ) {
+            (CongestionControl::NewReno, SlowStart::Classic) => Self::classic(
+                conn_params,
+                ClassicSlowStart::default(),
+                NewReno::default(),
+                pmtud,
+            ),
+            (CongestionControl::NewReno, SlowStart::HyStart) => {
+                Self::classic(conn_params, hystart(), NewReno::default(), pmtud)
+            }
+            (CongestionControl::NewReno, SlowStart::Search) => {
+                Self::classic(conn_params, Search::default(), NewReno::default(), pmtud)
+            }
+            (CongestionControl::Cubic, SlowStart::Classic) => Self::classic(
+                conn_params,
+                ClassicSlowStart::default(),
+                Cubic::default(),
+                pmtud,
+            ),
+            (CongestionControl::Cubic, SlowStart::HyStart) => {
+                Self::classic(conn_params, hystart(), Cubic::default(), pmtud)
+            }
+            (CongestionControl::Cubic, SlowStart::Search) => {
+                Self::classic(conn_params, Search::default(), Cubic::default(), pmtud)
+            }
+            (CongestionControl::Westwood, SlowStart::Classic) => Self::classic(
+                conn_params,
+                ClassicSlowStart::default(),
+                Westwood::default(),
+                pmtud,
+            ),
+            (CongestionControl::Westwood, SlowStart::HyStart) => {
+                Self::classic(conn_params, hystart(), Westwood::default(), pmtud)
+            }
+            (CongestionControl::Westwood, SlowStart::Search) => {
+                Self::classic(conn_params, Search::default(), Westwood::default(), pmtud)
+            }
+            // BBR has its own startup, so the slow start setting doesn't apply.
+            (CongestionControl::Bbr, _) => {
+                Box::new(Bbr::new(pmtud).with_initial_cwnd(initial_cwnd))
+            }
+            (CongestionControl::Bbr3, _) => {
+                Box::new(Bbr3::new(pmtud).with_initial_cwnd(initial_cwnd))
+            }
+            (CongestionControl::Prague, _) => {
+                Box::new(Prague::new(pmtud).with_initial_cwnd(initial_cwnd))
+            }
+            // LEDBAT++ exits slow start based on delay.
+            (CongestionControl::Ledbat, _) => {
+                Box::new(Ledbat::new(pmtud).with_initial_cwnd(initial_cwnd))
+            }
+        }
+    }
+
+    /// Build a [`ClassicCongestionController`] from the given slow start and
+    /// window adjustment, with the options from `conn_params` applied.
+    fn classic<S, T>(
+        conn_params: &ConnectionParameters,
+        slow_start: S,
+        congestion_control: T,
+        pmtud: Pmtud,
+    ) -> Box<dyn CongestionController>
+    where
+        ClassicCongestionController<S, T>: CongestionController + 'static,
+    {
+        Box::new(
+            ClassicCongestionController::new(slow_start, congestion_control, pmtud)
+                .with_prr(conn_params.prr_enabled())
+                .with_cwv(conn_params.cwv_enabled())
+                .with_dctcp(conn_params.dctcp_enabled())
+                .with_initial_cwnd(conn_params.get_initial_cwnd()),
+        )
+    }
+
+    /// Start over with fresh congestion control, delivery rate, and pacing state.
+    /// PMTUD state, any cap on the sending rate, and whether the handshake is
+    /// confirmed are kept.
+    pub fn reset(&mut self, conn_params: &ConnectionParameters, qlog: Qlog, now: Instant) {
+        let max_send_rate = self.max_send_rate;
+        let handshake_burst = self.handshake_burst;
+        *self = Self::new(conn_params, self.cc.pmtud().clone(), now);
+        self.handshake_burst &= handshake_burst;
+        self.set_max_send_rate(max_send_rate);
+        self.set_qlog(qlog);
+    }
+
+    /// Switch to the congestion controller from `conn_params`, keeping PMTUD,
+    /// delivery rate, and pacing state.  The new controller starts out with
+    /// `in_flight`, the packets that are still outstanding.
+    pub fn set_congestion_control<'a, I>(
+        &mut self,
+        conn_params: &ConnectionParameters,
+        qlog: Qlog,
+        in_flight: I,
+        now: Instant,
+    ) where
+        I: IntoIterator<Item = &'a sent::Packet>,
+    {
+        let mut cc = Self::congestion_controller(conn_params, self.cc.pmtud().clone());
+        cc.set_qlog(qlog);
+        for pkt in in_flight {
+            cc.on_packet_sent(pkt, now);
         }
+        self.observed = Observed::new(cc.as_ref(), self.observed.persistent_congestion);
+        self.cc = cc;
     }
 
     pub fn set_qlog(&mut self, qlog: Qlog) {
@@ -101,12 +261,155 @@ impl PacketSender {
         self.cc.cwnd_avail()
     }
 
+    #[must_use]
+    pub fn bytes_in_flight(&self) -> usize {
+        self.cc.bytes_in_flight()
+    }
+
+    #[must_use]
+    pub fn ssthresh(&self) -> usize {
+        self.cc.ssthresh()
+    }
+
+    #[must_use]
+    pub fn window_max(&self) -> Option<usize> {
+        self.cc.window_max()
+    }
+
+    #[must_use]
+    pub fn phase(&self) -> CongestionPhase {
+        self.cc.phase()
+    }
+
+    #[must_use]
+    pub const fn pacing(&self) -> bool {
+        self.pacer.enabled()
+    }
+
+    #[must_use]
+    pub const fn max_send_rate(&self) -> Option<u64> {
+        self.max_send_rate
+    }
+
+    /// Start pacing, if it was suspended for the handshake.
+    pub const fn on_handshake_confirmed(&mut self) {
+        self.handshake_burst = false;
+    }
+
+    /// Whether the pacer applies to the next packet.  Pacing only starts once
+    /// there are bytes in flight.
+    fn paced(&self) -> bool {
+        !self.handshake_burst && self.cc.bytes_in_flight() > 0
+    }
+
+    /// Have the pacer release packets in batches of up to `packets`, the
+    /// number of datagrams that can be sent together with GSO.
+    pub const fn set_gso_batch(&mut self, packets: NonZeroUsize) {
+        self.pacer.set_batch(packets);
+    }
+
+    /// The number of packets that the pacer releases together, if the pacer
+    /// applies to the next packet.  A batch of datagrams that is any larger
+    /// would be split by the pacer.
+    #[must_use]
+    pub fn burst_size_hint(&self) -> Option<usize> {
+        self.paced().then(|| self.pacer.burst_size_hint())
+    }
+
+    /// Cap the sending rate at `rate` bytes per second, or remove the cap.
+    /// The cap is enforced by the pacer, which runs while a cap is set even if
+    /// pacing is otherwise disabled.
+    pub fn set_max_send_rate(&mut self, rate: Option<u64>) {
+        self.max_send_rate = rate;
+        self.pacer.set_enabled(self.pacing || rate.is_some());
+    }
+
     #[cfg(test)]
     #[must_use]
     pub fn cwnd_min(&self) -> usize {
         self.cc.cwnd_min()
     }
 
+    /// The rate, in bytes per second, that the pacer sends at.  This is the
+    /// pacing rate of the congestion controller, if it sets one, or a rate
+    /// derived from the congestion window and the pacing gain for the current
+    /// phase.  Any cap on the sending rate applies.
+    fn pacing_rate(&self, rtt: Duration) -> u64 {
+        let rate = self.cc.pacing_rate().unwrap_or_else(|| {
+            let gain = match self.cc.phase() {
+                CongestionPhase::SlowStart | CongestionPhase::ConservativeSlowStart => {
+                    self.pacing_gain_slow_start
+                }
+                CongestionPhase::CongestionAvoidance | CongestionPhase::Recovery => {
+                    self.pacing_gain
+                }
+            };
+            Pacer::rate(rtt, self.cc.cwnd(), gain)
+        });
+        self.max_send_rate.map_or(rate, |cap| rate.min(cap))
+    }
+
+    /// The pacing rate, in bytes per second, if it is due to be logged.  The
+    /// rate is sampled at most once per round trip, and only if it changed.
+    pub fn pacing_rate_to_log(&mut self, rtt: Duration, now: Instant) -> Option<u64> {
+        if !self.pacer.enabled() {
+            return None;
+        }
+        let rate = self.pacing_rate(rtt);
+        if self
+            .pacing_logged
+            .is_some_and(|(t, logged)| logged == rate || now < t + rtt)
+        {
+            return None;
+        }
+        self.pacing_logged = Some((now, rate));
+        Some(rate)
+    }
+
+    /// Count changes to the state of the congestion controller since the last time
+    /// that this was called.
+    fn update_stats(&mut self, cc_stats: &mut CongestionControlStats) {
+        let cwnd = self.cc.cwnd();
+        cc_stats.cwnd_min = Some(cc_stats.cwnd_min.map_or(cwnd, |c| c.min(cwnd)));
+        cc_stats.cwnd_max = Some(cc_stats.cwnd_max.map_or(cwnd, |c| c.max(cwnd)));
+
+        let last = self.observed;
+        let current = Observed::new(self.cc.as_ref(), cc_stats.persistent_congestion);
+        // Persistent congestion passes through recovery and back into slow start
+        // within a single call, so the phase alone doesn't show it.
+        let persistent_congestion = current.persistent_congestion != last.persistent_congestion;
+        let in_slow_start = |p| {
+            matches!(
+                p,
+                CongestionPhase::SlowStart | CongestionPhase::ConservativeSlowStart
+            )
+        };
+        if in_slow_start(last.phase) && (persistent_congestion || !in_slow_start(current.phase)) {
+            cc_stats.slow_start_exits += 1;
+        }
+        if last.phase != CongestionPhase::Recovery
+            && (persistent_congestion || current.phase == CongestionPhase::Recovery)
+        {
+            cc_stats.recovery_episodes += 1;
+        }
+        if current.ssthresh != last.ssthresh {
+            cc_stats.ssthresh_updates += 1;
+            if current.ssthresh != usize::MAX {
+                cc_stats.ssthresh_min = Some(
+                    cc_stats
+                        .ssthresh_min
+                        .map_or(current.ssthresh, |s| s.min(current.ssthresh)),
+                );
+                cc_stats.ssthresh_max = Some(
+                    cc_stats
+                        .ssthresh_max
+                        .map_or(current.ssthresh, |s| s.max(current.ssthresh)),
+                );
+            }
+        }
+        self.observed = current;
+    }
+
     fn maybe_update_pacer_mtu(&mut self) {
         let current_mtu = self.pmtud().plpmtu();
         if current_mtu != self.pacer.mtu() {
@@ -125,8 +428,15 @@ impl PacketSender {
         now: Instant,
         stats: &mut Stats,
     ) {
+        if let Some(sample) = self
+            .delivery
+            .on_packets_acked(acked_pkts, rtt_est.minimum(), now)
+        {
+            self.cc.on_rate_sample(&sample);
+        }
         self.cc
             .on_packets_acked(acked_pkts, rtt_est, now, &mut stats.cc);
+        self.update_stats(&mut stats.cc);
         self.pmtud_mut().on_packets_acked(acked_pkts, now, stats);
         self.maybe_update_pacer_mtu();
     }
@@ -141,6 +451,17 @@ impl PacketSender {
         stats: &mut Stats,
         now: Instant,
     ) -> bool {
+        // Congestion controllers use a period of `PERSISTENT_CONG_THRESH` times the PTO,
+        // so scale the PTO to get the configured period.
+        let pto = pto * self.pc_threshold / PERSISTENT_CONG_THRESH;
+        // Without a requirement for an RTT sample, treat the first lost packet as though
+        // it were sent after one, so that no lost packets are skipped.
+        let first_rtt_sample_time = first_rtt_sample_time.or_else(|| {
+            lost_packets
+                .first()
+                .filter(|_| !self.pc_rtt_sample)
+                .map(sent::Packet::time_sent)
+        });
         let ret = self.cc.on_packets_lost(
             first_rtt_sample_time,
             prev_largest_acked_sent,
@@ -149,6 +470,7 @@ impl PacketSender {
             now,
             &mut stats.cc,
         );
+        self.update_stats(&mut stats.cc);
         // Call below may change the size of MTU probes, so it needs to happen after the CC
         // reaction above, which needs to ignore probes based on their size.
         self.pmtud_mut().on_packets_lost(lost_packets, stats, now);
@@ -160,10 +482,26 @@ impl PacketSender {
     pub fn on_ecn_ce_received(
         &mut self,
         largest_acked_pkt: &sent::Packet,
+        ce_marks: u64,
         now: Instant,
         cc_stats: &mut CongestionControlStats,
     ) -> bool {
-        self.cc.on_ecn_ce_received(largest_acked_pkt, now, cc_stats)
+        let reduced = self
+            .cc
+            .on_ecn_ce_received(largest_acked_pkt, ce_marks, now, cc_stats);
+        self.update_stats(cc_stats);
+        reduced
+    }
+
+    /// Called when there is nothing to send, even though the congestion window has space.
+    pub fn on_app_limited(&mut self) {
+        self.delivery.on_app_limited(self.cc.bytes_in_flight());
+    }
+
+    /// Whether the congestion controller wants packets marked ECT(1), for L4S.
+    #[must_use]
+    pub fn l4s(&self) -> bool {
+        self.cc.l4s()
     }
 
     pub fn discard(&mut self, pkt: &sent::Packet, now: Instant) {
@@ -176,16 +514,85 @@ impl PacketSender {
         self.cc.discard_in_flight(now);
     }
 
-    pub fn on_packet_sent(&mut self, pkt: &sent::Packet, rtt: Duration, now: Instant) {
-        self.pacer
-            .spend(pkt.time_sent(), rtt, self.cc.cwnd(), pkt.len());
+    /// Let the congestion controller restart if nothing has been sent or in flight
+    /// for at least `rto`.
+    fn maybe_restart_after_idle(&mut self, rto: Duration, now: Instant) {
+        if self.idle_restart == IdleRestart::Off || self.cc.bytes_in_flight() > 0 {
+            return;
+        }
+        let Some(last_sent) = self.last_sent else {
+            return;
+        };
+        let periods = now.saturating_duration_since(last_sent).as_nanos() / rto.as_nanos().max(1);
+        if periods > 0 {
+            qdebug!("Idle for {periods} RTOs, restarting");
+            self.cc.on_idle_restart(
+                self.idle_restart,
+                u32::try_from(periods).unwrap_or(u32::MAX),
+                now,
+            );
+        }
+    }
+
+    /// Record a packet as sent.  Probes, which are sent on PTO or on entering
+    /// recovery, are not held back by the pacer, so they don't spend its
+    /// credit either.  Otherwise, a probe could delay the packets that follow.
+    /// The same goes for packets sent before the handshake is confirmed, if
+    /// those are not paced.
+    pub fn on_packet_sent(
+        &mut self,
+        pkt: &mut sent::Packet,
+        rtt_est: &RttEstimate,
+        probe: bool,
+        now: Instant,
+    ) {
+        let rate = self.pacing_rate(rtt_est.estimate());
+        // If pacing is offloaded, the packet leaves later than it is sent, and
+        // it is then that the pacer spends credit on it.
+        let departure = self
+            .departure_time(rtt_est.estimate(), pkt.time_sent())
+            .unwrap_or_else(|| pkt.time_sent());
+        // After an idle period, the pacer starts over with a full burst.  The
+        // idle period only starts once the pacer would have released the next
+        // packet, so time spent waiting on the pacer doesn't count.
+        if self.last_sent.is_some_and(|t| {
+            let idle_since = max(t, self.pacer.next(rate));
+            now.saturating_duration_since(idle_since) > rtt_est.estimate() * self.pacing_idle_reset
+        }) {
+            self.pacer.refill();
+        }
+        if pkt.cc_in_flight() {
+            // Use the PTO in place of the retransmission timeout.
+            self.maybe_restart_after_idle(rtt_est.pto(true), now);
+            self.last_sent = Some(now);
+            self.delivery
+                .on_packet_sent(pkt, self.cc.bytes_in_flight(), self.cc.app_limited());
+        }
+        if !probe && !self.handshake_burst {
+            self.pacer.spend(departure, rate, pkt.len());
+        }
         self.cc.on_packet_sent(pkt, now);
     }
 
+    /// The time at which the next packet can be released.  This is earlier
+    /// than the time the pacer would send it if pacing is offloaded.
     #[must_use]
     pub fn next_paced(&self, rtt: Duration) -> Option<Instant> {
-        // Only pace if there are bytes in flight.
-        (self.cc.bytes_in_flight() > 0).then(|| self.pacer.next(rtt, self.cc.cwnd()))
+        self.paced().then(|| {
+            let t = self.pacer.next(self.pacing_rate(rtt));
+            t.checked_sub(self.pacing_horizon).unwrap_or(t)
+        })
+    }
+
+    /// When pacing is offloaded, the time at which a packet sent `now` should
+    /// depart.
+    #[must_use]
+    pub fn departure_time(&self, rtt: Duration, now: Instant) -> Option<Instant> {
+        if self.pacing_horizon.is_zero() {
+            return None;
+        }
+        let t = self.paced().then(|| self.pacer.next(self.pacing_rate(rtt)));
+        Some(t.map_or(now, |t| t.max(now)))
     }
 
     #[must_use]
@@ -193,3 +600,196 @@ impl PacketSender {
         self.cc.recovery_packet()
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv6Addr},
+        num::NonZeroUsize,
+        time::Duration,
+    };
+
+    use test_fixture::now;
+
+    use super::{PACING_BURST_SIZE, PacketSender};
+    use crate::{ConnectionParameters, pmtud::Pmtud, recovery::sent, rtt::RttEstimate};
+
+    const RTT: Duration = Duration::from_millis(100);
+
+    /// Probes don't spend pacer credit, so they don't delay the packets that follow.
+    #[test]
+    fn probes_not_paced() {
+        let now = now();
+        let mut sender = PacketSender::new(
+            &ConnectionParameters::default(),
+            Pmtud::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            now,
+        );
+        let rtt = RttEstimate::new(RTT);
+        let mtu = sender.pmtud().plpmtu();
+        let mut pn = 0..;
+        let mut send = |sender: &mut PacketSender, probe| {
+            let mut pkt = sent::make_packet(pn.next().unwrap(), now, mtu);
+            sender.on_packet_sent(&mut pkt, &rtt, probe, now);
+        };
+
+        for _ in 0..=PACING_BURST_SIZE {
+            send(&mut sender, true);
+        }
+        assert_eq!(sender.next_paced(RTT), Some(now));
+
+        for _ in 0..=PACING_BURST_SIZE {
+            send(&mut sender, false);
+        }
+        assert!(sender.next_paced(RTT).unwrap() > now);
+    }
+
+    /// Without handshake pacing, packets go out unpaced until the handshake is
+    /// confirmed, and the pacer then starts with full credit.
+    #[test]
+    fn handshake_not_paced() {
+        let now = now();
+        let mut sender = PacketSender::new(
+            &ConnectionParameters::default().handshake_pacing(false),
+            Pmtud::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            now,
+        );
+        let rtt = RttEstimate::new(RTT);
+        let mtu = sender.pmtud().plpmtu();
+        let mut pn = 0..;
+        let mut send = |sender: &mut PacketSender| {
+            let mut pkt = sent::make_packet(pn.next().unwrap(), now, mtu);
+            sender.on_packet_sent(&mut pkt, &rtt, false, now);
+        };
+
+        for _ in 0..=PACING_BURST_SIZE {
+            send(&mut sender);
+        }
+        assert_eq!(sender.next_paced(RTT), None);
+
+        sender.on_handshake_confirmed();
+        assert_eq!(sender.next_paced(RTT), Some(now));
+        for _ in 0..=PACING_BURST_SIZE {
+            send(&mut sender);
+        }
+        assert!(sender.next_paced(RTT).unwrap() > now);
+    }
+
+    /// The pacer only limits the size of GSO batches when it applies.
+    #[test]
+    fn burst_size_hint_when_paced() {
+        let now = now();
+        let mut sender = PacketSender::new(
+            &ConnectionParameters::default().handshake_pacing(false),
+            Pmtud::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            now,
+        );
+        let rtt = RttEstimate::new(RTT);
+        let mtu = sender.pmtud().plpmtu();
+        sender.set_gso_batch(NonZeroUsize::new(10).unwrap());
+        assert_eq!(sender.burst_size_hint(), None);
+
+        let mut pkt = sent::make_packet(0, now, mtu);
+        sender.on_packet_sent(&mut pkt, &rtt, false, now);
+        assert_eq!(sender.burst_size_hint(), None);
+
+        sender.on_handshake_confirmed();
+        assert_eq!(sender.burst_size_hint(), Some(PACING_BURST_SIZE));
+    }
+
+    /// With pacing offloaded, packets are released ahead of their departure
+    /// times, and the pacer spends credit when they depart.
+    #[test]
+    fn pacer_charged_at_departure() {
+        let now = now();
+        let mut sender = PacketSender::new(
+            &ConnectionParameters::default().pacing_horizon(RTT),
+            Pmtud::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            now,
+        );
+        let rtt = RttEstimate::new(RTT);
+        let mtu = sender.pmtud().plpmtu();
+
+        let mut departures = Vec::new();
+        for pn in 0..10 {
+            assert!(sender.next_paced(RTT).is_none_or(|t| t <= now));
+            departures.push(sender.departure_time(RTT, now).unwrap());
+            let mut pkt = sent::make_packet(pn, now, mtu);
+            sender.on_packet_sent(&mut pkt, &rtt, false, now);
+        }
+        assert_eq!(departures[0], now);
+        assert!(departures.is_sorted());
+        let last = *departures.last().unwrap();
+        assert!(last > now);
+
+        // The next packet departs after the last one, even if it is sent once
+        // the packets before it have departed.
+        let later = sender.departure_time(RTT, now).unwrap();
+        assert!(later > last);
+        assert_eq!(sender.departure_time(RTT, last), Some(later));
+    }
+
+    /// Sending after an idle period starts with a full burst, dropping any
+    /// debt the pacer had left.
+    #[test]
+    fn pacer_refilled_after_idle() {
+        let now = now();
+        let mut sender = PacketSender::new(
+            &ConnectionParameters::default(),
+            Pmtud::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            now,
+        );
+        let rtt = RttEstimate::new(RTT);
+        let mtu = sender.pmtud().plpmtu();
+        let mut pn = 0..;
+        let mut send = |sender: &mut PacketSender, now| {
+            let mut pkt = sent::make_packet(pn.next().unwrap(), now, mtu);
+            sender.on_packet_sent(&mut pkt, &rtt, false, now);
+        };
+
+        // Run up debt that takes several round trips to pay off.
+        for _ in 0..100 {
+            send(&mut sender, now);
+        }
+        let next = sender.next_paced(RTT).unwrap();
+        assert!(next > now + RTT * 2);
+
+        // Sending when the pacer allows it doesn't refill it.
+        send(&mut sender, next);
+        assert!(sender.next_paced(RTT).unwrap() > next);
+
+        // Sending more than an RTT after that does.
+        let later = next + RTT * 2;
+        send(&mut sender, later);
+        assert_eq!(sender.next_paced(RTT), Some(later));
+    }
+
+    /// A rate cap that spaces packets more than an RTT apart doesn't count as
+    /// idle, so the pacer isn't refilled before each packet.
+    #[test]
+    fn pacer_not_refilled_by_rate_cap() {
+        let now = now();
+        let mut sender = PacketSender::new(
+            &ConnectionParameters::default(),
+            Pmtud::new(IpAddr::V6(Ipv6Addr::LOCALHOST), None),
+            now,
+        );
+        let rtt = RttEstimate::new(RTT);
+        let mtu = sender.pmtud().plpmtu();
+        // One packet every two round trips.
+        let cap = u64::try_from(mtu).unwrap() * 1000 / u64::try_from(RTT.as_millis() * 2).unwrap();
+        sender.set_max_send_rate(Some(cap));
+
+        let mut t = now;
+        for pn in 0..10 {
+            let mut pkt = sent::make_packet(pn, t, mtu);
+            sender.on_packet_sent(&mut pkt, &rtt, false, t);
+            let next = sender.next_paced(RTT).unwrap();
+            if pn >= u64::try_from(PACING_BURST_SIZE).unwrap() {
+                assert!(next >= t + RTT * 2 - Duration::from_millis(1));
+            }
+            t = t.max(next);
+        }
+    }
+}
@@ -11,6 +11,7 @@ use std::{
     cmp::min,
     collections::VecDeque,
     fmt::{self, Display, Formatter},
+    net::IpAddr,
     num::NonZeroUsize,
     ops::{Deref, DerefMut},
     path::PathBuf,
@@ -19,11 +20,11 @@ use std::{
 };
 
 use neqo_common::{
-    Datagram, Role, Tos, event::Provider as _, hex, qdebug, qerror, qinfo, qlog::Qlog, qtrace,
-    qwarn,
+    Datagram, Role, Tos, datagram::Payload, event::Provider as _, hex, qdebug, qerror, qinfo,
+    qlog::Qlog, qtrace, qwarn,
 };
 use neqo_crypto::{
-    AntiReplay, Cipher, PrivateKey, PublicKey, ZeroRttCheckResult, ZeroRttChecker,
+    AntiReplay, Cipher, Group, PrivateKey, PublicKey, ZeroRttCheckResult, ZeroRttChecker,
     encode_ech_config,
 };
 use rustc_hash::FxHashSet as HashSet;
@@ -79,6 +80,45 @@ impl InitialDetails {
     }
 }
 
+/// Limits on the connections that a [`Server`] admits.
+///
+/// A limit of `None` means no limit.  Connections that are closing do not
+/// count towards any limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionLimits {
+    /// The maximum number of connections.  Further connections are refused.
+    pub connections: Option<usize>,
+    /// The maximum number of connections that have yet to complete their
+    /// handshake.  Further clients are sent a Retry, and refused if they
+    /// have already completed one.
+    pub handshakes: Option<usize>,
+    /// The maximum number of connections from one IP address.  Further
+    /// connections from that address are refused.
+    pub connections_per_ip: Option<usize>,
+}
+
+/// Counts of the connection attempts that a [`Server`] did not admit because
+/// of its [`AdmissionLimits`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionStats {
+    /// Retry packets sent because too many handshakes were in progress.
+    pub retried: usize,
+    /// Connections refused because too many handshakes were in progress.
+    pub refused_handshakes: usize,
+    /// Connections refused because there were too many connections.
+    pub refused_connections: usize,
+    /// Connections refused because there were too many connections from the
+    /// same IP address.
+    pub refused_per_ip: usize,
+}
+
+/// What to do with a new connection, according to the [`AdmissionLimits`].
+enum Admission {
+    Accept,
+    Retry,
+    Refuse,
+}
+
 struct EchConfig {
     config: u8,
     public_name: String,
@@ -107,6 +147,8 @@ pub struct Server {
     protocols: Vec<String>,
     /// The cipher suites that the server supports.
     ciphers: Vec<Cipher>,
+    /// The key exchange groups to enable, in order of preference.
+    groups: Vec<Group>,
     /// Anti-replay configuration for 0-RTT.
     anti_replay: AntiReplay,
     /// A function for determining if 0-RTT can be accepted.
@@ -123,12 +165,18 @@ pub struct Server {
     qlog_dir: Option<PathBuf>,
     /// Encrypted client hello (ECH) configuration.
     ech_config: Option<EchConfig>,
+    /// OCSP responses to staple, by certificate name.
+    ocsp_responses: Vec<(String, Vec<Vec<u8>>)>,
     /// Remaining datagrams of a batch of datagrams provided via
     /// [`Server::process_multiple`]. An earlier datagram in the batch required
     /// an immediate return without further processing of the remaining
     /// datagrams. To be processed on consecutive calls to
     /// [`Server::process_multiple`].
     saved_datagrams: VecDeque<SavedDatagram>,
+    /// Limits on new connections.
+    admission_limits: AdmissionLimits,
+    /// Counts of connections that were not admitted.
+    admission_stats: AdmissionStats,
 }
 
 impl Server {
@@ -158,6 +206,7 @@ impl Server {
             certs: certs.iter().map(|x| String::from(x.as_ref())).collect(),
             protocols: protocols.iter().map(|x| String::from(x.as_ref())).collect(),
             ciphers: Vec::new(),
+            groups: Vec::new(),
             anti_replay,
             zero_rtt_checker: ServerZeroRttChecker::new(zero_rtt_checker),
             cid_generator,
@@ -166,7 +215,10 @@ impl Server {
             address_validation: Rc::new(RefCell::new(validation)),
             qlog_dir: None,
             ech_config: None,
+            ocsp_responses: Vec::new(),
             saved_datagrams: VecDeque::new(),
+            admission_limits: AdmissionLimits::default(),
+            admission_stats: AdmissionStats::default(),
         })
     }
 
@@ -180,12 +232,50 @@ impl Server {
         self.address_validation.borrow_mut().set_validation(v);
     }
 
+    /// Set the key that protects Retry and `NEW_TOKEN` tokens, and the time
+    /// that their expiry is relative to.  Servers that share both accept each
+    /// other's tokens, e.g. when they handle the same address.  Otherwise,
+    /// each server uses a random key.
+    ///
+    /// # Errors
+    /// When the key cannot be imported.
+    pub fn set_token_key(&self, key: &[u8], epoch: Instant) -> Res<()> {
+        self.address_validation
+            .borrow_mut()
+            .set_token_key(key, epoch)
+    }
+
+    /// Set the limits on new connections.
+    pub const fn set_admission_limits(&mut self, limits: &AdmissionLimits) {
+        self.admission_limits = *limits;
+    }
+
+    /// The number of connections that were not admitted because of the
+    /// [`AdmissionLimits`].
+    #[must_use]
+    pub const fn admission_stats(&self) -> &AdmissionStats {
+        &self.admission_stats
+    }
+
     /// Set the cipher suites that should be used.  Set an empty value to use
     /// default values.
     pub fn set_ciphers<A: AsRef<[Cipher]>>(&mut self, ciphers: A) {
         self.ciphers = Vec::from(ciphers.as_ref());
     }
 
+    /// Replace the anti-replay context used for 0-RTT on new connections.
+    /// This can be used to rotate the context, or to share one context
+    /// between several servers.
+    pub fn set_anti_replay(&mut self, anti_replay: AntiReplay) {
+        self.anti_replay = anti_replay;
+    }
+
+    /// Set the key exchange groups that should be used, in order of preference.
+    /// Set an empty value to use default values.
+    pub fn set_groups<A: AsRef<[Group]>>(&mut self, groups: A) {
+        self.groups = Vec::from(groups.as_ref());
+    }
+
     /// # Errors
     /// When the configuration is invalid.
     pub fn enable_ech(
@@ -204,6 +294,13 @@ impl Server {
         self.ech_config.as_ref().map_or(&[], |cfg| &cfg.encoded)
     }
 
+    /// Set the OCSP responses that are stapled to the named certificate.
+    pub fn set_stapled_ocsp_responses(&mut self, certificate: &str, ocsp_responses: Vec<Vec<u8>>) {
+        self.ocsp_responses.retain(|(c, _)| c != certificate);
+        self.ocsp_responses
+            .push((certificate.to_owned(), ocsp_responses));
+    }
+
     /// Writes address validation fuzzing corpus data.
     #[cfg(feature = "build-fuzzing-corpus")]
     fn write_addr_valid_corpus(peer: std::net::SocketAddr, token: &[u8]) {
@@ -228,7 +325,7 @@ impl Server {
     fn handle_initial(
         &mut self,
         initial: InitialDetails,
-        dgram: Datagram<impl AsRef<[u8]> + AsMut<[u8]>>,
+        dgram: Datagram<impl Payload>,
         now: Instant,
     ) -> Output {
         qdebug!("[{self}] Handle initial");
@@ -240,68 +337,137 @@ impl Server {
             .validate(&initial.token, dgram.source(), now);
         match res {
             AddressValidationResult::Invalid => Output::None,
-            AddressValidationResult::Pass => self.accept_connection(initial, dgram, None, now),
+            AddressValidationResult::Pass => self.admit_connection(initial, dgram, None, now),
             AddressValidationResult::ValidRetry(orig_dcid) => {
-                self.accept_connection(initial, dgram, Some(orig_dcid), now)
+                self.admit_connection(initial, dgram, Some(orig_dcid), now)
             }
-            AddressValidationResult::Validate => {
-                qinfo!("[{self}] Send retry for {:?}", initial.dst_cid);
-
-                // > This Destination Connection ID MUST be at least 8 bytes in length.
-                //
-                // <https://www.rfc-editor.org/rfc/rfc9000.html#section-7.2>
-                if initial.dst_cid.len() < 8 {
-                    qerror!(
-                        "[{self}] DCID too short ({} bytes), dropping packet",
-                        initial.dst_cid.len()
-                    );
-                    return Output::None;
-                }
+            AddressValidationResult::Validate => self.send_retry(&initial, &dgram, now),
+        }
+    }
+
+    fn send_retry(
+        &self,
+        initial: &InitialDetails,
+        dgram: &Datagram<impl AsRef<[u8]> + AsMut<[u8]>>,
+        now: Instant,
+    ) -> Output {
+        qinfo!("[{self}] Send retry for {:?}", initial.dst_cid);
+
+        // > This Destination Connection ID MUST be at least 8 bytes in length.
+        //
+        // <https://www.rfc-editor.org/rfc/rfc9000.html#section-7.2>
+        if initial.dst_cid.len() < 8 {
+            qerror!(
+                "[{self}] DCID too short ({} bytes), dropping packet",
+                initial.dst_cid.len()
+            );
+            return Output::None;
+        }
 
-                let res = self.address_validation.borrow().generate_retry_token(
-                    &initial.dst_cid,
+        let res = self.address_validation.borrow().generate_retry_token(
+            &initial.dst_cid,
+            dgram.source(),
+            now,
+        );
+        let Ok(token) = res else {
+            qerror!("[{self}] unable to generate token, dropping packet");
+            return Output::None;
+        };
+        let Some(new_dcid) = self.cid_generator.borrow_mut().generate_cid() else {
+            qerror!("[{self}] no connection ID for retry, dropping packet");
+            return Output::None;
+        };
+        let packet = packet::Builder::retry(
+            initial.version,
+            &initial.src_cid,
+            &new_dcid,
+            &token,
+            &initial.dst_cid,
+        );
+        packet.map_or_else(
+            |_| {
+                qerror!("[{self}] unable to encode retry, dropping packet");
+                Output::None
+            },
+            |p| {
+                qdebug!(
+                    "[{self}] type={:?} path:{} {}->{} {:?} len {}",
+                    packet::Type::Retry,
+                    initial.dst_cid,
+                    dgram.destination(),
                     dgram.source(),
-                    now,
+                    Tos::default(),
+                    p.len(),
                 );
-                let Ok(token) = res else {
-                    qerror!("[{self}] unable to generate token, dropping packet");
-                    return Output::None;
-                };
-                if let Some(new_dcid) = self.cid_generator.borrow_mut().generate_cid() {
-                    let packet = packet::Builder::retry(
-                        initial.version,
-                        &initial.src_cid,
-                        &new_dcid,
-                        &token,
-                        &initial.dst_cid,
-                    );
-                    packet.map_or_else(
-                        |_| {
-                            qerror!("[{self}] unable to encode retry, dropping packet");
-                            Output::None
-                        },
-                        |p| {
-                            qdebug!(
-                                "[{self}] type={:?} path:{} {}->{} {:?} len {}",
-                                packet::Type::Retry,
-                                initial.dst_cid,
-                                dgram.destination(),
-                                dgram.source(),
-                                Tos::default(),
-                                p.len(),
-                            );
-                            Output::Datagram(Datagram::new(
-                                dgram.destination(),
-                                dgram.source(),
-                                Tos::default(),
-                                p,
-                            ))
-                        },
-                    )
-                } else {
-                    qerror!("[{self}] no connection ID for retry, dropping packet");
-                    Output::None
-                }
+                Output::Datagram(Datagram::new(
+                    dgram.destination(),
+                    dgram.source(),
+                    Tos::default(),
+                    p,
+                ))
+            },
+        )
+    }
+
+    /// Decide whether to admit a new connection from `peer`, counting any
+    /// rejection.  `retried` is whether the client has completed a Retry.
+    fn admission(&mut self, peer: IpAddr, retried: bool) -> Admission {
+        let limits = self.admission_limits;
+        if limits == AdmissionLimits::default() {
+            return Admission::Accept;
+        }
+        let (mut connections, mut handshakes, mut from_peer) = (0, 0, 0);
+        for c in &self.connections {
+            let c = c.borrow();
+            if c.state().closed() {
+                continue;
+            }
+            connections += 1;
+            if !c.state().connected() {
+                handshakes += 1;
+            }
+            if c.remote_address().is_some_and(|a| a.ip() == peer) {
+                from_peer += 1;
+            }
+        }
+        let exceeds = |limit: Option<usize>, count| limit.is_some_and(|limit| count >= limit);
+
+        let stats = &mut self.admission_stats;
+        if exceeds(limits.connections, connections) {
+            stats.refused_connections += 1;
+            Admission::Refuse
+        } else if exceeds(limits.connections_per_ip, from_peer) {
+            stats.refused_per_ip += 1;
+            Admission::Refuse
+        } else if exceeds(limits.handshakes, handshakes) {
+            if retried {
+                stats.refused_handshakes += 1;
+                Admission::Refuse
+            } else {
+                stats.retried += 1;
+                Admission::Retry
+            }
+        } else {
+            Admission::Accept
+        }
+    }
+
+    fn admit_connection(
+        &mut self,
+        initial: InitialDetails,
+        dgram: Datagram<impl Payload>,
+        orig_dcid: Option<ConnectionId>,
+        now: Instant,
+    ) -> Output {
+        match self.admission(dgram.source().ip(), orig_dcid.is_some()) {
+            Admission::Accept => self.accept_connection(initial, dgram, orig_dcid, false, now),
+            Admission::Retry => {
+                qinfo!("[{self}] Too many handshakes, sending retry");
+                self.send_retry(&initial, &dgram, now)
+            }
+            Admission::Refuse => {
+                qinfo!("[{self}] Refusing connection from {}", dgram.source());
+                self.accept_connection(initial, dgram, orig_dcid, true, now)
             }
         }
     }
@@ -332,6 +498,12 @@ impl Server {
         orig_dcid: Option<ConnectionId>,
         now: Instant,
     ) {
+        if !self.ciphers.is_empty() && c.set_ciphers(&self.ciphers).is_err() {
+            qwarn!("[{self}] Unable to set ciphers");
+        }
+        if !self.groups.is_empty() && c.set_groups(&self.groups).is_err() {
+            qwarn!("[{self}] Unable to set groups");
+        }
         let zcheck = self.zero_rtt_checker.clone();
         if c.server_enable_0rtt(&self.anti_replay, zcheck).is_err() {
             qwarn!("[{self}] Unable to enable 0-RTT");
@@ -348,13 +520,24 @@ impl Server {
         {
             qwarn!("[{self}] Unable to enable ECH");
         }
+        for (cert, responses) in &self.ocsp_responses {
+            let responses = responses.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            if c.server_set_stapled_ocsp_responses(cert, &responses)
+                .is_err()
+            {
+                qwarn!("[{self}] Unable to staple OCSP responses for {cert}");
+            }
+        }
     }
 
+    /// Create a connection for `initial`.  If `refuse` is set, the connection
+    /// only sends a `CONNECTION_CLOSE`.
     fn accept_connection(
         &mut self,
         initial: InitialDetails,
-        dgram: Datagram<impl AsRef<[u8]> + AsMut<[u8]>>,
+        dgram: Datagram<impl Payload>,
         orig_dcid: Option<ConnectionId>,
+        refuse: bool,
         now: Instant,
     ) -> Output {
         qinfo!(
@@ -376,6 +559,9 @@ impl Server {
         match sconn {
             Ok(mut c) => {
                 self.setup_connection(&mut c, initial, orig_dcid, now);
+                if refuse {
+                    c.refuse();
+                }
                 let out = c.process(Some(dgram), now);
                 self.connections.push(Rc::new(RefCell::new(c)));
                 out
@@ -399,10 +585,7 @@ impl Server {
     }
 
     /// Process new input datagrams on the connection.
-    pub fn process_multiple_input<
-        A: AsRef<[u8]> + AsMut<[u8]>,
-        I: IntoIterator<Item = Datagram<A>>,
-    >(
+    pub fn process_multiple_input<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -428,7 +611,7 @@ impl Server {
     }
 
     // Process a new input datagram on the connection.
-    fn process_input<A: AsRef<[u8]> + AsMut<[u8]>, I: IntoIterator<Item = Datagram<A>>>(
+    fn process_input<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -579,7 +762,7 @@ impl Server {
     /// datagram only.
     #[expect(clippy::missing_panics_doc, reason = "see expect()")]
     #[must_use]
-    pub fn process<A: AsRef<[u8]> + AsMut<[u8]>, I: IntoIterator<Item = Datagram<A>>>(
+    pub fn process<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
@@ -589,7 +772,7 @@ impl Server {
             .expect("max_datagrams is 1")
     }
 
-    pub fn process_multiple<A: AsRef<[u8]> + AsMut<[u8]>, I: IntoIterator<Item = Datagram<A>>>(
+    pub fn process_multiple<A: Payload, I: IntoIterator<Item = Datagram<A>>>(
         &mut self,
         dgrams: I,
         now: Instant,
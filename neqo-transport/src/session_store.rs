@@ -0,0 +1,54 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Storage for resumption tokens that outlives individual connections.
+
+use std::time::Instant;
+
+use neqo_crypto::ResumptionToken;
+use rustc_hash::FxHashMap as HashMap;
+
+/// A store for resumption tokens, keyed by server name.
+///
+/// A token carries everything a client needs to resume a session: the TLS session ticket,
+/// the server's transport parameters, the RTT estimate and any address validation token.
+/// Implementations can persist tokens in external caches so that they survive beyond the
+/// lifetime of a single client process.
+pub trait SessionStore {
+    /// Retrieve a token for `server_name`, if one is available.
+    fn get(&self, server_name: &str) -> Option<ResumptionToken>;
+    /// Save a token for `server_name`, replacing any previous one.
+    fn put(&mut self, server_name: &str, token: ResumptionToken);
+    /// Forget any token stored for `server_name`.
+    fn remove(&mut self, server_name: &str);
+}
+
+/// A simple in-memory [`SessionStore`] that holds the most recent token for each server.
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    tokens: HashMap<String, ResumptionToken>,
+}
+
+impl MemorySessionStore {
+    /// Drop all tokens that have expired by `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.tokens.retain(|_, t| t.expiration_time() > now);
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn get(&self, server_name: &str) -> Option<ResumptionToken> {
+        self.tokens.get(server_name).cloned()
+    }
+
+    fn put(&mut self, server_name: &str, token: ResumptionToken) {
+        self.tokens.insert(server_name.to_owned(), token);
+    }
+
+    fn remove(&mut self, server_name: &str) {
+        self.tokens.remove(server_name);
+    }
+}
@@ -16,6 +16,7 @@ use std::{
 
 use enum_map::EnumMap;
 use neqo_common::{Dscp, Ecn, qdebug};
+use neqo_crypto::{Cipher, Group};
 use strum::IntoEnumIterator as _;
 
 use crate::{cc::CongestionEvent, ecn, packet};
@@ -51,6 +52,7 @@ pub struct FrameStats {
     pub new_token: usize,
 
     pub ack_frequency: usize,
+    pub immediate_ack: usize,
     pub datagram: usize,
 }
 
@@ -90,7 +92,11 @@ impl Debug for FrameStats {
             self.path_challenge,
             self.path_response,
         )?;
-        writeln!(f, "    ack_frequency {}", self.ack_frequency)
+        writeln!(
+            f,
+            "    ack_frequency {} immediate_ack {}",
+            self.ack_frequency, self.immediate_ack
+        )
     }
 }
 
@@ -118,6 +124,7 @@ impl FrameStats {
             + self.handshake_done
             + self.new_token
             + self.ack_frequency
+            + self.immediate_ack
             + self.datagram
     }
 }
@@ -166,6 +173,12 @@ pub struct CongestionControlStats {
     /// Number of CSS (Conservative Slow Start) rounds completed. Only meaningful when HyStart++ is
     /// enabled. Higher values indicate the heuristic spent more time throttling slow start growth.
     pub hystart_css_rounds_finished: usize,
+    /// Number of times HyStart++ returned from CSS (Conservative Slow Start) to slow start
+    /// because the RTT dropped below the CSS baseline, i.e. the delay increase was spurious.
+    pub hystart_css_spurious_exits: usize,
+    /// Number of times HyStart++ exited slow start to congestion avoidance because of a delay
+    /// increase, as opposed to a congestion event.
+    pub hystart_delay_exits: usize,
     /// Cubic's `w_max`: the congestion window (in bytes) just before the most recent
     /// congestion reduction (with fast convergence applied). `None` if no congestion event has
     /// occurred or Cubic is not in use. Recorded as a stat to approximate a connection's ideal
@@ -174,6 +187,23 @@ pub struct CongestionControlStats {
     /// The current congestion window size (in bytes). Updated throughout the connection
     /// lifetime.
     pub cwnd: Option<usize>,
+    /// The smallest congestion window size (in bytes) seen.
+    pub cwnd_min: Option<usize>,
+    /// The largest congestion window size (in bytes) seen.
+    pub cwnd_max: Option<usize>,
+    /// Number of times slow start was exited. This can be more than one, because slow start is
+    /// re-entered after persistent congestion.
+    pub slow_start_exits: usize,
+    /// Number of times recovery was entered.
+    pub recovery_episodes: usize,
+    /// Number of times persistent congestion was declared.
+    pub persistent_congestion: usize,
+    /// Number of times the slow start threshold changed.
+    pub ssthresh_updates: usize,
+    /// The smallest slow start threshold (in bytes) that was set, if any was.
+    pub ssthresh_min: Option<usize>,
+    /// The largest slow start threshold (in bytes) that was set, if any was.
+    pub ssthresh_max: Option<usize>,
 }
 /// ECN counts by QUIC [`packet::Type`].
 #[derive(Default, Clone, PartialEq, Eq)]
@@ -312,6 +342,10 @@ pub struct Stats {
 
     /// Whether the connection was resumed successfully.
     pub resumed: bool,
+    /// The cipher suite negotiated during the handshake, if it has completed.
+    pub cipher: Option<Cipher>,
+    /// The key exchange group negotiated during the handshake, if it has completed.
+    pub key_exchange: Option<Group>,
 
     /// The current, estimated round-trip time on the primary path.
     pub rtt: Duration,
@@ -319,6 +353,11 @@ pub struct Stats {
     pub rttvar: Duration,
     /// Whether the first RTT sample was guessed from a discarded packet.
     pub rtt_init_guess: bool,
+    /// The number of bytes in flight on the primary path.
+    pub bytes_in_flight: usize,
+    /// The cap on the sending rate set with `Connection::set_max_send_rate`,
+    /// in bytes per second.
+    pub max_send_rate: Option<u64>,
 
     /// Count PTOs. Single PTOs, 2 PTOs in a row, 3 PTOs in row, etc. are counted
     /// separately.
@@ -425,6 +464,28 @@ impl Debug for Stats {
             "    final_cwnd {:?} ss_exit_cwnd {:?} ss_exit_reason {:?}",
             self.cc.cwnd, self.cc.slow_start_exit_cwnd, self.cc.slow_start_exit_reason
         )?;
+        writeln!(
+            f,
+            "    cwnd_min {:?} cwnd_max {:?} ss_exits {} recoveries {} persistent {}",
+            self.cc.cwnd_min,
+            self.cc.cwnd_max,
+            self.cc.slow_start_exits,
+            self.cc.recovery_episodes,
+            self.cc.persistent_congestion
+        )?;
+        writeln!(
+            f,
+            "    ssthresh_updates {} ssthresh_min {:?} ssthresh_max {:?}",
+            self.cc.ssthresh_updates, self.cc.ssthresh_min, self.cc.ssthresh_max
+        )?;
+        writeln!(
+            f,
+            "    hystart_css_entries {} css_rounds {} css_spurious_exits {} delay_exits {}",
+            self.cc.hystart_css_entries,
+            self.cc.hystart_css_rounds_finished,
+            self.cc.hystart_css_spurious_exits,
+            self.cc.hystart_delay_exits
+        )?;
         writeln!(
             f,
             "  pmtud: {} sent {} acked {} lost {} iface_mtu {:?} peer_max_udp_payload {} pmtu",
@@ -436,6 +497,11 @@ impl Debug for Stats {
             self.pmtud_pmtu
         )?;
         writeln!(f, "  resumed: {}", self.resumed)?;
+        writeln!(
+            f,
+            "  tls: cipher {:?} key_exchange {:?}",
+            self.cipher, self.key_exchange
+        )?;
         writeln!(f, "  frames rx:")?;
         self.frame_rx.fmt(f)?;
         writeln!(f, "  frames tx:")?;
@@ -488,6 +554,7 @@ fn debug() {
     final_cwnd None ss_exit_cwnd None ss_exit_reason None
   pmtud: 0 sent 0 acked 0 lost 0 iface_mtu None peer_max_udp_payload 0 pmtu
   resumed: false
+  tls: cipher None key_exchange None
   frames rx:
     crypto 0 done 0 token 0 close 0
     ack 0 (max 0) ping 0 padding 0
@@ -496,7 +563,7 @@ fn debug() {
     blocked: stream 0 data 0 stream_data 0
     datagram 0
     ncid 0 rcid 0 pchallenge 0 presponse 0
-    ack_frequency 0
+    ack_frequency 0 immediate_ack 0
   frames tx:
     crypto 0 done 0 token 0 close 0
     ack 0 (max 0) ping 0 padding 0
@@ -505,7 +572,7 @@ fn debug() {
     blocked: stream 0 data 0 stream_data 0
     datagram 0
     ncid 0 rcid 0 pchallenge 0 presponse 0
-    ack_frequency 0
+    ack_frequency 0 immediate_ack 0
   ecn:
     tx:
     acked:
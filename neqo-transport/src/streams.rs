@@ -12,7 +12,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use neqo_common::{Buffer, Role, qtrace, qwarn};
+use neqo_common::{Buffer, Role, SharedBytes, qtrace, qwarn};
 
 use crate::{
     ConnectionEvents, Error, Res,
@@ -112,7 +112,12 @@ impl Streams {
 
     /// # Errors
     /// When the frame is invalid.
-    pub fn input_frame(&mut self, frame: &Frame, stats: &mut FrameStats) -> Res<()> {
+    pub fn input_frame(
+        &mut self,
+        frame: &Frame,
+        shared: Option<&SharedBytes>,
+        stats: &mut FrameStats,
+    ) -> Res<()> {
         match frame {
             Frame::ResetStream {
                 stream_id,
@@ -144,7 +149,7 @@ impl Streams {
             } => {
                 stats.stream += 1;
                 if let (_, Some(rs)) = self.obtain_stream(*stream_id)? {
-                    rs.inbound_stream_frame(*fin, *offset, data)?;
+                    rs.inbound_stream_frame(*fin, *offset, data, shared)?;
                 }
             }
             Frame::MaxData { maximum_data } => {
@@ -483,6 +488,12 @@ impl Streams {
         }
     }
 
+    /// Whether the peer's connection-level flow control prevents sending more stream data.
+    #[must_use]
+    pub fn send_blocked(&self) -> bool {
+        self.sender_fc.borrow().available() == 0
+    }
+
     pub fn handle_data_blocked(&self) {
         self.receiver_fc.borrow_mut().send_flowc_update();
     }
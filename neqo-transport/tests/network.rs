@@ -4,15 +4,21 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{ops::Range, time::Duration};
+use std::{net::SocketAddr, ops::Range, time::Duration};
 
 use neqo_transport::{CloseReason, ConnectionParameters, Error, State};
 use test_fixture::{
-    boxed,
+    DEFAULT_ADDR, boxed,
     sim::{
         Simulator,
-        connection::{Node, ReachState, ReceiveData, SendData},
-        network::{Drop, RandomDelay, TailDrop},
+        connection::{Node, ReachState, ReceiveData, ReceiveDatagrams, SendData, SendDatagrams},
+        flows::Flows,
+        fuzz::Fuzz,
+        network::{
+            Aqm, Corrupt, Delay, Drop, GilbertElliott, Handover, Jitter, Mtu, PacingCheck,
+            RandomDelay, Satellite, TailDrop,
+        },
+        scenario::Scenario,
     },
     simulate,
 };
@@ -160,6 +166,28 @@ simulate!(
     ],
 );
 
+simulate!(
+    transfer_delay_bursty_loss,
+    [
+        Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+        RandomDelay::new(DELAY_RANGE),
+        GilbertElliott::new(5_000, 200_000, 0, 500_000),
+        Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+        RandomDelay::new(DELAY_RANGE),
+        GilbertElliott::new(5_000, 200_000, 0, 500_000),
+    ],
+);
+
+simulate!(
+    transfer_jitter_reorder,
+    [
+        Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+        Jitter::new(DELAY_RANGE).reorder(2, Duration::from_millis(10)),
+        Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+        Jitter::new(DELAY_RANGE).reorder(2, Duration::from_millis(10)),
+    ],
+);
+
 simulate!(
     transfer_taildrop,
     [
@@ -192,6 +220,17 @@ simulate!(
     ],
 );
 
+simulate!(
+    transfer_paced,
+    [
+        Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+        PacingCheck::new(4, Duration::from_millis(1)),
+        TailDrop::dsl_uplink(),
+        Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_downlink(),
+    ],
+);
+
 /// This test is a nasty piece of work.  Delays are anything from 0 to 50ms and 1% of
 /// packets get dropped.
 #[test]
@@ -210,3 +249,213 @@ fn transfer_fixed_seed() {
     sim.seed_str("117f65d90ee5c1a7fb685f3af502c7730ba5d31866b758d98f5e3c2117cf9b86");
     sim.run();
 }
+
+/// The sender's congestion window grows during a transfer over a bottleneck,
+/// and the receiver is delivered at least the amount of data that was sent.
+#[test]
+fn transfer_metrics() {
+    let sim = Simulator::new(
+        "transfer_metrics",
+        boxed![
+            Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+            TailDrop::dsl_uplink(),
+            Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+            TailDrop::dsl_downlink(),
+        ],
+    );
+    let (sim_time, metrics) = sim.setup().run_with_metrics(Duration::from_millis(100));
+    assert_eq!(metrics.series.len(), 2);
+
+    let client = &metrics.node(0).unwrap().samples;
+    assert_eq!(client.len(), sim_time.as_millis() as usize / 100 + 1);
+    let (first, last) = (client.first().unwrap().1, client.last().unwrap().1);
+    assert!(last.cwnd > first.cwnd);
+    assert!(client.iter().any(|(_, s)| s.bytes_in_flight > 0));
+    assert!(client.iter().all(|(_, s)| s.rtt > Duration::ZERO));
+
+    let server = &metrics.node(2).unwrap().samples;
+    assert!(server.last().unwrap().1.delivered > TRANSFER_AMOUNT);
+    assert!(server.is_sorted_by_key(|(_, s)| s.delivered));
+}
+
+/// Two flows that share a bottleneck both make progress.  By the time that the
+/// first one completes, the other has received a fair share of the capacity.
+#[test]
+fn transfer_two_flows() {
+    let sim = Simulator::new(
+        "transfer_two_flows",
+        boxed![
+            Flows::new(boxed![
+                Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+                Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+            ]),
+            TailDrop::dsl_uplink(),
+            Flows::new(boxed![
+                Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+                Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+            ]),
+            TailDrop::dsl_downlink(),
+        ],
+    );
+    let (_, metrics) = sim.setup().run_with_metrics(Duration::from_millis(100));
+    let servers = metrics.flows(2).collect::<Vec<_>>();
+    assert_eq!(servers.len(), 2);
+
+    let done = |i: usize| {
+        servers[i]
+            .samples
+            .iter()
+            .position(|(_, s)| s.delivered >= TRANSFER_AMOUNT)
+            .unwrap()
+    };
+    let (first, other) = if done(0) <= done(1) { (0, 1) } else { (1, 0) };
+    let delivered = servers[other].samples[done(first)].1.delivered;
+    assert!(delivered > TRANSFER_AMOUNT / 3, "{delivered}");
+}
+
+/// Transfers complete over a range of randomized networks.
+#[test]
+fn transfer_fuzz() {
+    const AMOUNT: usize = TRANSFER_AMOUNT / 8;
+    Fuzz::new("transfer_fuzz", 16).run(|up, down| {
+        let mut nodes = boxed![Node::default_client(boxed![SendData::new(AMOUNT)])];
+        nodes.extend(up.nodes());
+        nodes.push(Box::new(Node::default_server(boxed![ReceiveData::new(
+            AMOUNT
+        )])));
+        nodes.extend(down.nodes());
+        nodes
+    });
+}
+
+/// A transfer survives a handover to a path with a longer delay, where the
+/// client address also changes as though a NAT rebound it.
+#[test]
+fn transfer_handover() {
+    const AT: Duration = Duration::from_secs(1);
+    const REBOUND: SocketAddr = SocketAddr::new(DEFAULT_ADDR.ip(), DEFAULT_ADDR.port() + 1);
+    Simulator::new(
+        "transfer_handover",
+        boxed![
+            Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+            Handover::new(AT)
+                .paths(Delay::new(DELAY), Delay::new(DELAY * 3))
+                .rebind_source(REBOUND),
+            TailDrop::dsl_uplink(),
+            Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+            Handover::new(AT)
+                .paths(Delay::new(DELAY), Delay::new(DELAY * 3))
+                .rebind_destination(DEFAULT_ADDR),
+            TailDrop::dsl_downlink(),
+        ],
+    )
+    .run();
+}
+
+simulate!(
+    transfer_geo,
+    [
+        Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_uplink(),
+        Satellite::geo(),
+        Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_downlink(),
+        Satellite::geo(),
+    ]
+);
+
+// A transfer over a satellite in low earth orbit, with handovers that are
+// more frequent than usual, so that the transfer sees several of them.
+simulate!(
+    transfer_leo,
+    [
+        Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_uplink(),
+        Satellite::new(Duration::from_millis(15)).handovers(
+            Duration::from_millis(30),
+            Duration::from_secs(1),
+            Duration::from_millis(50)
+        ),
+        Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_downlink(),
+        Satellite::new(Duration::from_millis(15)).handovers(
+            Duration::from_millis(30),
+            Duration::from_secs(1),
+            Duration::from_millis(50)
+        ),
+    ]
+);
+
+simulate!(
+    transfer_corrupt,
+    [
+        Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_uplink(),
+        Corrupt::percentage(2),
+        Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+        TailDrop::dsl_downlink(),
+        Corrupt::percentage(2).bits(64),
+    ]
+);
+
+// The MTU toward the server drops part way through a transfer, which PMTUD has
+// to detect, while the MTU toward the client stays larger.
+simulate!(
+    transfer_mtu_reduction,
+    [
+        Node::new_client(
+            ConnectionParameters::default().pmtud(true),
+            boxed![ReachState::new(State::Confirmed)],
+            boxed![SendData::new(TRANSFER_AMOUNT)]
+        ),
+        Mtu::new(1500).change(Duration::from_secs(1), 1280),
+        TailDrop::dsl_uplink(),
+        Node::new_server(
+            ConnectionParameters::default().pmtud(true),
+            boxed![ReachState::new(State::Confirmed)],
+            boxed![ReceiveData::new(TRANSFER_AMOUNT)]
+        ),
+        Mtu::new(9000),
+        TailDrop::dsl_downlink(),
+    ]
+);
+
+simulate!(
+    transfer_datagrams,
+    [
+        Node::default_client(boxed![SendDatagrams::new(1_000, 1_000)]),
+        Delay::new(DELAY),
+        Node::default_server(boxed![ReceiveDatagrams::new(1_000)]),
+        Delay::new(DELAY),
+    ]
+);
+
+/// Transfers complete over links managed by each of the AQM algorithms.
+#[test]
+fn transfer_aqm() {
+    let links: [(&str, fn(usize, usize, Duration) -> Aqm); 3] = [
+        ("transfer_codel", Aqm::codel),
+        ("transfer_pie", Aqm::pie),
+        ("transfer_dual_pi2", Aqm::dual_pi2),
+    ];
+    for (name, link) in links {
+        Simulator::new(
+            name,
+            boxed![
+                Node::default_client(boxed![SendData::new(TRANSFER_AMOUNT)]),
+                link(200_000, 32_768, DELAY),
+                Node::default_server(boxed![ReceiveData::new(TRANSFER_AMOUNT)]),
+                link(1_000_000, 65_536, DELAY),
+            ],
+        )
+        .run();
+    }
+}
+
+/// The scenarios that come with `test_fixture` run to completion.
+#[test]
+fn transfer_scenarios() {
+    for name in ["dsl", "lossy-jitter", "bbr"] {
+        Scenario::named(name).run();
+    }
+}
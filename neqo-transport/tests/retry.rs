@@ -220,6 +220,41 @@ fn retry_different_ip() {
     assert_dscp(&client.stats());
 }
 
+/// Servers that share a token key accept each other's Retry tokens.
+#[test]
+fn retry_shared_token_key() {
+    const KEY: [u8; 32] = [0x7b; 32];
+    let mut retry_server = default_server();
+    let mut server = default_server();
+    let mut other_server = default_server();
+    for s in [&retry_server, &server, &other_server] {
+        s.set_validation(ValidateAddress::Always);
+    }
+    for s in [&retry_server, &server] {
+        s.set_token_key(&KEY, now()).unwrap();
+    }
+    let mut client = default_client();
+
+    let dgram = client.process_output(now()).dgram(); // Initial
+    let dgram2 = client.process_output(now()).dgram(); // Initial
+    _ = retry_server.process(dgram, now()).dgram();
+    let retry = retry_server.process(dgram2, now()).dgram().unwrap();
+    assertions::assert_retry(&retry);
+
+    let dgram = client.process(Some(retry), now()).dgram(); // Initial w/token
+    let dgram2 = client.process_output(now()).dgram(); // Initial
+    assert!(dgram.is_some() && dgram2.is_some());
+
+    // A server with a different key drops the Initial with the token.
+    assert!(other_server.process(dgram.clone(), now()).dgram().is_none());
+    assert!(other_server.active_connections().is_empty());
+
+    // One with the same key accepts it.
+    _ = server.process(dgram, now()).dgram();
+    _ = server.process(dgram2, now()).dgram();
+    assert_eq!(server.active_connections().len(), 1);
+}
+
 #[test]
 fn new_token_different_ip() {
     let mut server = default_server();
@@ -17,7 +17,7 @@ use neqo_crypto::{
 use neqo_transport::{
     CloseReason, Connection, ConnectionParameters, Error, MIN_INITIAL_PACKET_SIZE, Output, State,
     StreamType, Version,
-    server::{ConnectionRef, Server, ValidateAddress},
+    server::{AdmissionLimits, AdmissionStats, ConnectionRef, Server, ValidateAddress},
     version,
 };
 use test_fixture::{
@@ -62,6 +62,28 @@ fn single_client() {
     connect(&mut client, &mut server);
 }
 
+#[test]
+fn stapled_ocsp_response() {
+    const OCSP_RESPONSE: &[u8] = b"fake ocsp response";
+    const NEW_OCSP_RESPONSE: &[u8] = b"new ocsp response";
+    let mut server = default_server();
+    server.set_stapled_ocsp_responses(test_fixture::DEFAULT_KEYS[0], vec![OCSP_RESPONSE.to_vec()]);
+    let mut client = default_client();
+    connect(&mut client, &mut server);
+    let cert = client.peer_certificate().unwrap();
+    assert_eq!(cert.stapled_ocsp_responses().unwrap(), &[OCSP_RESPONSE]);
+
+    // A replacement staple is used for subsequent connections.
+    server.set_stapled_ocsp_responses(
+        test_fixture::DEFAULT_KEYS[0],
+        vec![NEW_OCSP_RESPONSE.to_vec()],
+    );
+    let mut client = default_client();
+    connect(&mut client, &mut server);
+    let cert = client.peer_certificate().unwrap();
+    assert_eq!(cert.stapled_ocsp_responses().unwrap(), &[NEW_OCSP_RESPONSE]);
+}
+
 #[test]
 fn connect_single_version_both() {
     fn connect_one_version(version: Version) {
@@ -961,3 +983,112 @@ fn saved_datagrams() {
         .dgram()
         .expect("fourth packet triggers third vn");
 }
+
+/// Send the Initial packets of `client` to `server` and return the client's
+/// state after it processes the response.
+fn initial_response(client: &mut Connection, server: &mut Server) -> State {
+    let out = client.process_output(now()).dgram();
+    let out2 = client.process_output(now()).dgram();
+    let mut response = server.process(out, now()).dgram();
+    if let Some(d) = server.process(out2, now()).dgram() {
+        response.get_or_insert(d);
+    }
+    client.process_input(response.expect("a response"), now());
+    client.state().clone()
+}
+
+fn assert_refused(state: &State) {
+    assert!(
+        matches!(
+            state,
+            State::Draining {
+                error: CloseReason::Transport(Error::Peer(2)),
+                ..
+            }
+        ),
+        "expected CONNECTION_REFUSED, got {state:?}"
+    );
+}
+
+#[test]
+fn admission_connections() {
+    let mut server = default_server();
+    server.set_admission_limits(&AdmissionLimits {
+        connections: Some(1),
+        ..AdmissionLimits::default()
+    });
+    let mut client = default_client();
+    complete_connection(&mut client, &mut server, None);
+
+    let mut refused = default_client();
+    assert_refused(&initial_response(&mut refused, &mut server));
+    assert_eq!(
+        *server.admission_stats(),
+        AdmissionStats {
+            refused_connections: 1,
+            ..AdmissionStats::default()
+        }
+    );
+}
+
+#[test]
+fn admission_connections_per_ip() {
+    let mut server = default_server();
+    server.set_admission_limits(&AdmissionLimits {
+        connections_per_ip: Some(1),
+        ..AdmissionLimits::default()
+    });
+    let mut client = default_client();
+    complete_connection(&mut client, &mut server, None);
+
+    // All test clients use the same address.
+    let mut refused = default_client();
+    assert_refused(&initial_response(&mut refused, &mut server));
+    assert_eq!(server.admission_stats().refused_per_ip, 1);
+}
+
+#[test]
+fn admission_handshakes() {
+    let mut server = default_server();
+    server.set_admission_limits(&AdmissionLimits {
+        handshakes: Some(1),
+        ..AdmissionLimits::default()
+    });
+
+    // This client starts a handshake, but does not complete it.
+    let mut client = default_client();
+    let state = initial_response(&mut client, &mut server);
+    assert!(!state.closed());
+
+    // Another client is sent a Retry, then refused.
+    let mut retried =
+        new_client::<CountingConnectionIdGenerator>(ConnectionParameters::default().mlkem(false));
+    let dgram = retried.process_output(now()).dgram();
+    let retry = server.process(dgram, now()).dgram().expect("a Retry");
+    assertions::assert_retry(&retry);
+    retried.process_input(retry, now());
+    let dgram = retried.process_output(now()).dgram();
+    let response = server.process(dgram, now()).dgram().expect("a response");
+    retried.process_input(response, now());
+    assert_refused(retried.state());
+    assert_eq!(
+        *server.admission_stats(),
+        AdmissionStats {
+            retried: 1,
+            refused_handshakes: 1,
+            ..AdmissionStats::default()
+        }
+    );
+
+    // Once the first client is connected, new clients are admitted.
+    let mut client = default_client();
+    let mut server = default_server();
+    server.set_admission_limits(&AdmissionLimits {
+        handshakes: Some(1),
+        ..AdmissionLimits::default()
+    });
+    complete_connection(&mut client, &mut server, None);
+    let mut admitted = default_client();
+    assert!(!initial_response(&mut admitted, &mut server).closed());
+    assert_eq!(*server.admission_stats(), AdmissionStats::default());
+}
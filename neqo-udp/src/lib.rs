@@ -10,18 +10,24 @@
     reason = "Functions simply delegate to tokio and quinn-udp."
 )]
 
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
 use std::{
-    array,
     io::{self, IoSliceMut},
-    iter,
-    net::SocketAddr,
-    slice::{self, ChunksMut},
+    iter, mem,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
+    slice,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
 use log::{Level, log_enabled};
-use neqo_common::{Datagram, Tos, datagram, qdebug, qtrace};
+use neqo_common::{Datagram, SharedBytesMut, Tos, datagram, qdebug, qtrace, qwarn};
 use quinn_udp::{EcnCodepoint, RecvMeta, Transmit, UdpSocketState};
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod mmsg;
+
 /// Receive buffer size
 ///
 /// Fits a maximum size UDP datagram, or, on platforms with segmentation
@@ -40,36 +46,305 @@ const RECV_BUF_SIZE: usize = u16::MAX as usize;
 /// - Linux/Android: use segmentation offloading via GRO
 /// - Windows: use segmentation offloading via URO (caveat see <https://github.com/quinn-rs/quinn/issues/2041>)
 /// - Apple: no segmentation offloading available, use multiple buffers
+///
+/// Use [`RecvBuf::new`] to override this default.
 #[cfg(not(all(apple, feature = "fast-apple-datapath")))]
-const NUM_BUFS: usize = 1;
+const NUM_BUFS: NonZeroUsize = NonZeroUsize::new(1).expect("1 is non-zero");
 #[cfg(all(apple, feature = "fast-apple-datapath"))]
 // Value approximated based on neqo-bin "Download" benchmark only.
-const NUM_BUFS: usize = 16;
+const NUM_BUFS: NonZeroUsize = NonZeroUsize::new(16).expect("16 is non-zero");
+
+/// The number of buffers that received datagrams can keep allocated, before
+/// [`RecvBuf`] copies datagrams instead.
+///
+/// Received data is handed out without copying it, so that the QUIC stack can
+/// hold on to the contents of `STREAM` frames until the application reads them.
+/// Each buffer stays allocated as long as any datagram in it is referenced.
+/// This limits the memory held that way to 1 MiB.
+const MAX_PINNED_BUFS: usize = 16;
 
 /// A UDP receive buffer.
-pub struct RecvBuf(Vec<Vec<u8>>);
+///
+/// Datagrams are received into a pool of buffers.  A buffer is reused once all
+/// datagrams received into it have been dropped.
+pub struct RecvBuf {
+    bufs: Vec<SharedBytesMut>,
+    metas: Vec<RecvMeta>,
+    /// Buffers that datagrams might still refer to, or that can be reused.
+    spare: Vec<SharedBytesMut>,
+    /// See [`RecvBuf::with_packet_info`].
+    packet_info: bool,
+}
+
+impl RecvBuf {
+    /// Create a receive buffer that reads up to `num_bufs` buffers, each
+    /// holding one or more datagrams, with one syscall.
+    ///
+    /// On Linux, more than one buffer results in the use of `recvmmsg`.
+    #[must_use]
+    pub fn new(num_bufs: NonZeroUsize) -> Self {
+        Self {
+            bufs: iter::repeat_with(|| SharedBytesMut::zeroed(RECV_BUF_SIZE))
+                .take(num_bufs.get())
+                .collect(),
+            metas: vec![RecvMeta::default(); num_bufs.get()],
+            spare: Vec::new(),
+            packet_info: false,
+        }
+    }
+
+    /// Set the destination of received datagrams to the address that the OS
+    /// reports they were sent to, for sockets bound to an unspecified address.
+    ///
+    /// Otherwise, datagrams carry the local address passed to [`recv_inner`].
+    /// On hosts with multiple addresses, replies then come from the address
+    /// that the peer sent to, see [`SendState::with_packet_info`].
+    #[must_use]
+    pub const fn with_packet_info(mut self) -> Self {
+        self.packet_info = true;
+        self
+    }
+
+    /// Make all buffers available for receiving, replacing buffers that
+    /// datagrams still refer to.
+    ///
+    /// Returns whether the received datagrams can refer to the buffers, rather
+    /// than being copied, because not too many buffers are in use already.
+    fn prepare(&mut self) -> bool {
+        for buf in &mut self.bufs {
+            if buf.try_reclaim() {
+                continue;
+            }
+            if let Some(free) = self
+                .spare
+                .iter_mut()
+                .find_map(|b| b.try_reclaim().then_some(b))
+            {
+                mem::swap(buf, free);
+            } else {
+                let pinned = mem::replace(buf, SharedBytesMut::zeroed(RECV_BUF_SIZE));
+                self.spare.push(pinned);
+            }
+        }
+        let pinned = self
+            .spare
+            .iter_mut()
+            .map(SharedBytesMut::try_reclaim)
+            .filter(|reclaimed| !reclaimed)
+            .count();
+        pinned < MAX_PINNED_BUFS
+    }
+}
 
 impl Default for RecvBuf {
     fn default() -> Self {
-        Self(vec![vec![0; RECV_BUF_SIZE]; NUM_BUFS])
+        Self::new(NUM_BUFS)
+    }
+}
+
+/// How a [`datagram::Batch`] was submitted to the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendMode {
+    /// The batch held a single datagram.
+    Single,
+    /// All datagrams of the batch were submitted with one syscall, using
+    /// segmentation offload (e.g. `UDP_SEGMENT` on Linux).
+    Segmented,
+    /// The OS rejected segmentation offload, so each datagram of the batch was
+    /// submitted with its own syscall.
+    Fallback,
+}
+
+/// Statistics on how datagrams were sent on a socket.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// The effective number of segments that can be sent with one syscall.
+    /// A value of 1 means that segmentation offload is not (or no longer) used.
+    pub max_gso_segments: usize,
+    /// Number of batches sent as a single datagram.
+    pub single: usize,
+    /// Number of batches sent using segmentation offload.
+    pub segmented: usize,
+    /// Number of batches that had to be sent one datagram at a time after
+    /// segmentation offload failed.
+    pub fallback: usize,
+}
+
+/// Thread-safe counters from which [`Stats`] are derived.
+#[derive(Debug, Default)]
+pub struct StatsCounters {
+    single: AtomicUsize,
+    segmented: AtomicUsize,
+    fallback: AtomicUsize,
+}
+
+impl StatsCounters {
+    /// Account for a batch that was sent using `mode`.
+    pub fn record(&self, mode: SendMode) {
+        let counter = match mode {
+            SendMode::Single => &self.single,
+            SendMode::Segmented => &self.segmented,
+            SendMode::Fallback => &self.fallback,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Take a snapshot of the counters, for a socket in `state`.
+    pub fn get(&self, state: &UdpSocketState) -> Stats {
+        Stats {
+            max_gso_segments: state.max_gso_segments(),
+            single: self.single.load(Ordering::Relaxed),
+            segmented: self.segmented.load(Ordering::Relaxed),
+            fallback: self.fallback.load(Ordering::Relaxed),
+        }
     }
 }
 
-pub fn send_inner(
+/// Per-socket state for [`send_inner`] and [`send_multiple_inner`], in addition
+/// to the [`UdpSocketState`] of [`quinn_udp`].
+#[derive(Debug)]
+pub struct SendState {
+    /// Whether the socket is an IPv6 socket, which might be dual-stack.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    ipv6: bool,
+    /// Whether sending with `sendmmsg` failed where sending without its extras
+    /// succeeded, so that it is not worth trying again.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mmsg_failed: AtomicBool,
+    /// Whether a warning was logged that the DSCP can't be set.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    dscp_warned: AtomicBool,
+    /// The clock that departure times are set against, once enabled with
+    /// [`enable_txtime`].
+    #[cfg(target_os = "linux")]
+    txtime_clock: OnceLock<TxTimeClock>,
+    /// See [`SendState::with_packet_info`].
+    packet_info: bool,
+}
+
+impl SendState {
+    /// Create the state for sending on `socket`.
+    pub fn new<S: SocketRef>(socket: &S) -> io::Result<Self> {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let _ = socket;
+        Ok(Self {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ipv6: mmsg::is_ipv6(socket)?,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            mmsg_failed: AtomicBool::new(false),
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            dscp_warned: AtomicBool::new(false),
+            #[cfg(target_os = "linux")]
+            txtime_clock: OnceLock::new(),
+            packet_info: false,
+        })
+    }
+
+    /// Send datagrams from their source address, using packet information
+    /// (e.g. `IP_PKTINFO`), for sockets bound to an unspecified address.
+    ///
+    /// Use this with [`RecvBuf::with_packet_info`], so that replies come from
+    /// the address that the peer sent to.  Otherwise, the OS picks the source
+    /// address.
+    #[must_use]
+    pub const fn with_packet_info(mut self) -> Self {
+        self.packet_info = true;
+        self
+    }
+
+    /// Whether to try sending with `sendmmsg`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn use_mmsg(&self) -> bool {
+        !self.mmsg_failed.load(Ordering::Relaxed)
+    }
+
+    /// Stop sending with `sendmmsg`, as it failed with `e` where sending
+    /// without it succeeded.  Departure times and DSCP are lost from now on.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn mmsg_failed(&self, e: &io::Error) {
+        if !self.mmsg_failed.swap(true, Ordering::Relaxed) {
+            qwarn!("Failed to send with departure time or DSCP: {e}; no longer setting them");
+        }
+    }
+
+    /// Warn, once, if `d` has a DSCP, which can't be set on this platform.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn check_dscp(&self, d: &datagram::Batch) {
+        let dscp = neqo_common::Dscp::from(d.tos());
+        if dscp != neqo_common::Dscp::Cs0 && !self.dscp_warned.swap(true, Ordering::Relaxed) {
+            qwarn!("Unable to set DSCP {dscp:?} on this platform; only setting ECN");
+        }
+    }
+}
+
+/// The clock that departure times are set against, see [`enable_txtime`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TxTimeClock {
+    /// `CLOCK_MONOTONIC`, which the `fq` qdisc expects.
+    #[default]
+    Monotonic,
+    /// `CLOCK_TAI`, which the ETF qdisc is usually configured with.
+    Tai,
+}
+
+/// Whether `d` needs something that [`quinn_udp`] cannot provide: a departure
+/// time, or a DSCP codepoint, as [`quinn_udp`] only sets the ECN bits.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn needs_mmsg(send_state: &SendState, d: &datagram::Batch) -> bool {
+    #[cfg(target_os = "linux")]
+    if d.txtime().is_some() && send_state.txtime_clock.get().is_some() {
+        return true;
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = send_state;
+    neqo_common::Dscp::from(d.tos()) != neqo_common::Dscp::Cs0
+}
+
+/// The source address to send `d` from, if packet information is enabled and
+/// it is not the unspecified address that a socket is bound to.
+///
+/// This uses packet information (e.g. `IP_PKTINFO`), which [`quinn_udp`]
+/// supports on Linux, Windows and Apple platforms.
+fn source_ip(send_state: &SendState, d: &datagram::Batch) -> Option<IpAddr> {
+    Some(d.source().ip()).filter(|ip| send_state.packet_info && !ip.is_unspecified())
+}
+
+pub fn send_inner<S: SocketRef>(
     state: &UdpSocketState,
-    socket: quinn_udp::UdpSockRef<'_>,
+    send_state: &SendState,
+    socket: S,
     d: &datagram::Batch,
-) -> io::Result<()> {
+) -> io::Result<SendMode> {
     let transmit = Transmit {
         destination: d.destination(),
         ecn: EcnCodepoint::from_bits(Into::<u8>::into(d.tos())),
         contents: d.data(),
         segment_size: Some(d.datagram_size().get()),
-        src_ip: None,
+        src_ip: source_ip(send_state, d),
     };
 
-    match state.try_send(socket, &transmit) {
-        Ok(()) => {}
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    send_state.check_dscp(d);
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let mut mmsg_err = None;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if needs_mmsg(send_state, d) && send_state.use_mmsg() {
+        match mmsg::send(&socket, send_state, slice::from_ref(d)) {
+            // Nothing was sent, so send without below.
+            Ok(0) => {}
+            Ok(_) if d.num_datagrams() > 1 => return Ok(SendMode::Segmented),
+            Ok(_) => return Ok(SendMode::Single),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Err(e),
+            Err(e) => {
+                qdebug!("Failed to send with departure time or DSCP: {e}; sending without");
+                mmsg_err = Some(e);
+            }
+        }
+    }
+
+    let mode = match state.try_send((&socket).into(), &transmit) {
+        Ok(()) if d.num_datagrams() > 1 => SendMode::Segmented,
+        Ok(()) => SendMode::Single,
         Err(e) if is_emsgsize(&e) => {
             qdebug!(
                 "Failed to send datagram of size {} bytes, in {} segments, each {} bytes, from {} to {}. PMTUD probe? Ignoring error: {e}",
@@ -79,9 +354,23 @@ pub fn send_inner(
                 d.source(),
                 d.destination()
             );
-            return Ok(());
+            return Ok(SendMode::Single);
         }
-        e @ Err(_) => return e,
+        Err(e) if is_segmentation_error(&e) && d.num_datagrams() > 1 => {
+            // quinn-udp has stopped using segmentation offload for subsequent
+            // sends.  Rather than dropping this batch, send it one datagram at
+            // a time.
+            qdebug!("Segmented send failed with {e}; sending datagrams individually");
+            send_individually(state, send_state, &socket, d)?;
+            SendMode::Fallback
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Only the extras of `sendmmsg` can have caused its failure.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(e) = mmsg_err {
+        send_state.mmsg_failed(&e);
     }
 
     qtrace!(
@@ -93,9 +382,183 @@ pub fn send_inner(
         d.destination(),
     );
 
+    Ok(mode)
+}
+
+/// The send and receive buffer sizes of a socket, in bytes, as reported by the
+/// OS.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizes {
+    pub send: usize,
+    pub recv: usize,
+}
+
+/// Grow the send and receive buffers of `socket` to hold at least `bdp` bytes.
+///
+/// This fits a full congestion window on a path with that bandwidth-delay
+/// product.  Otherwise the OS silently drops datagrams on high-BDP paths, such
+/// as satellite links.  Buffers that are already large enough are left as they
+/// are.
+///
+/// Returns the sizes achieved, which can be smaller than requested if the OS
+/// caps them, e.g. at `net.core.rmem_max` and `net.core.wmem_max` on Linux.
+pub fn size_buffers_for_bdp<S: SocketRef>(
+    state: &UdpSocketState,
+    socket: S,
+    bdp: usize,
+) -> io::Result<BufferSizes> {
+    if state.send_buffer_size((&socket).into())? < bdp {
+        state.set_send_buffer_size((&socket).into(), bdp)?;
+    }
+    if state.recv_buffer_size((&socket).into())? < bdp {
+        state.set_recv_buffer_size((&socket).into(), bdp)?;
+    }
+    let sizes = BufferSizes {
+        send: state.send_buffer_size((&socket).into())?,
+        recv: state.recv_buffer_size((&socket).into())?,
+    };
+    if sizes.send < bdp || sizes.recv < bdp {
+        qwarn!(
+            "Socket buffers {sizes:?} are limited by the OS to less than the BDP of {bdp} bytes"
+        );
+    } else {
+        qdebug!("Socket buffers {sizes:?} for a BDP of {bdp} bytes");
+    }
+    Ok(sizes)
+}
+
+/// Enable departure times on `socket`, using `SO_TXTIME` with `clock`.
+///
+/// Once enabled, datagrams with a [`datagram::Batch::txtime`] are held back by
+/// the OS until that time, provided the outgoing interface uses a qdisc that
+/// honors it, such as `fq` for [`TxTimeClock::Monotonic`] or ETF for
+/// [`TxTimeClock::Tai`].  Other qdiscs send such datagrams right away, which
+/// the OS cannot tell the caller about.  Datagrams that are due within a
+/// fraction of a millisecond are sent without a departure time.
+///
+/// Fails where this is not supported, or if it was already enabled with a
+/// different clock; the caller should then leave pacing to the QUIC stack.
+pub fn enable_txtime<S: SocketRef>(
+    send_state: &SendState,
+    socket: &S,
+    clock: TxTimeClock,
+) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(&enabled) = send_state.txtime_clock.get() {
+            return if enabled == clock {
+                Ok(())
+            } else {
+                Err(io::ErrorKind::AlreadyExists.into())
+            };
+        }
+        mmsg::enable_txtime(socket, clock)?;
+        _ = send_state.txtime_clock.set(clock);
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        _ = (send_state, socket, clock);
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// Send multiple [`datagram::Batch`]es, using as few syscalls as possible.
+///
+/// On Linux, this uses `sendmmsg`.  Elsewhere, each batch is sent with
+/// [`send_inner`].  Returns the number of batches that were sent.  This is
+/// less than `batches.len()` when the socket stopped accepting data after some
+/// batches were sent; the remaining batches should be sent once the socket is
+/// writable again.
+pub fn send_multiple_inner<S: SocketRef>(
+    state: &UdpSocketState,
+    send_state: &SendState,
+    socket: S,
+    batches: &[datagram::Batch],
+    counters: &StatsCounters,
+) -> io::Result<usize> {
+    let mut sent = 0;
+    while sent < batches.len() {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if batches.len() - sent > 1 && send_state.use_mmsg() {
+            match mmsg::send(&socket, send_state, &batches[sent..]) {
+                // Let `send_inner` deal with the batch that was not sent.
+                Ok(0) => {}
+                Ok(n) => {
+                    for d in &batches[sent..sent + n] {
+                        counters.record(if d.num_datagrams() > 1 {
+                            SendMode::Segmented
+                        } else {
+                            SendMode::Single
+                        });
+                    }
+                    sent += n;
+                    continue;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return if sent == 0 { Err(e) } else { Ok(sent) };
+                }
+                // Let `send_inner` deal with the failing batch.
+                Err(_) => {}
+            }
+        }
+
+        match send_inner(state, send_state, &socket, &batches[sent]) {
+            Ok(mode) => {
+                counters.record(mode);
+                sent += 1;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock && sent > 0 => return Ok(sent),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(sent)
+}
+
+/// Send each datagram of `d` with its own syscall.
+///
+/// If this fails part way through, e.g. with [`io::ErrorKind::WouldBlock`],
+/// the caller might send the whole batch again.  QUIC tolerates the resulting
+/// duplicate datagrams.
+fn send_individually<S: SocketRef>(
+    state: &UdpSocketState,
+    send_state: &SendState,
+    socket: &S,
+    d: &datagram::Batch,
+) -> io::Result<()> {
+    for contents in d.data().chunks(d.datagram_size().get()) {
+        let transmit = Transmit {
+            destination: d.destination(),
+            ecn: EcnCodepoint::from_bits(Into::<u8>::into(d.tos())),
+            contents,
+            segment_size: None,
+            src_ip: source_ip(send_state, d),
+        };
+        match state.try_send(socket.into(), &transmit) {
+            Err(e) if is_emsgsize(&e) => {
+                qdebug!(
+                    "Failed to send datagram of size {} bytes: {e}",
+                    contents.len()
+                );
+            }
+            res => res?,
+        }
+    }
     Ok(())
 }
 
+/// Whether `e` indicates that the OS (or the network adapter) does not support
+/// segmentation offload.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_segmentation_error(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::EIO | libc::EINVAL))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const fn is_segmentation_error(_e: &io::Error) -> bool {
+    false
+}
+
 #[expect(
     clippy::unnecessary_map_or,
     reason = "Clippy ignores the #[cfg] attribute."
@@ -125,20 +588,25 @@ use std::os::fd::AsFd as SocketRef;
 #[cfg(windows)]
 use std::os::windows::io::AsSocket as SocketRef;
 
-#[expect(clippy::missing_panics_doc, reason = "OK here.")]
 pub fn recv_inner<'a, S: SocketRef>(
     local_address: SocketAddr,
     state: &UdpSocketState,
     socket: S,
     recv_buf: &'a mut RecvBuf,
 ) -> Result<DatagramIter<'a>, io::Error> {
-    let mut metas = [RecvMeta::default(); NUM_BUFS];
-    let mut iovs: [IoSliceMut; NUM_BUFS] = {
-        let mut bufs = recv_buf.0.iter_mut().map(|b| IoSliceMut::new(b));
-        array::from_fn(|_| bufs.next().expect("NUM_BUFS elements"))
-    };
+    let share = recv_buf.prepare();
+    let RecvBuf {
+        bufs,
+        metas,
+        packet_info,
+        ..
+    } = recv_buf;
+    let mut iovs: Vec<IoSliceMut> = bufs
+        .iter_mut()
+        .map(|b| IoSliceMut::new(b.as_mut()))
+        .collect();
 
-    let n = state.recv((&socket).into(), &mut iovs, &mut metas)?;
+    let n = state.recv((&socket).into(), &mut iovs, metas)?;
 
     if log_enabled!(Level::Trace) {
         for meta in metas.iter().take(n) {
@@ -158,37 +626,74 @@ pub fn recv_inner<'a, S: SocketRef>(
 
     Ok(DatagramIter {
         current_buffer: None,
-        remaining_buffers: metas.into_iter().zip(recv_buf.0.iter_mut()).take(n),
+        remaining_buffers: metas.iter().copied().zip(bufs.iter_mut()).take(n),
         local_address,
+        share,
+        packet_info: *packet_info,
     })
 }
 
+type RemainingBuffers<'a> = iter::Take<
+    iter::Zip<iter::Copied<slice::Iter<'a, RecvMeta>>, slice::IterMut<'a, SharedBytesMut>>,
+>;
+
 pub struct DatagramIter<'a> {
     /// The current buffer, containing zero or more datagrams, each sharing the
-    /// same [`RecvMeta`].
-    current_buffer: Option<(RecvMeta, ChunksMut<'a, u8>)>,
+    /// same [`RecvMeta`].  Datagrams are split off the front.
+    current_buffer: Option<(RecvMeta, SharedBytesMut)>,
     /// Remaining buffers, each containing zero or more datagrams, one
     /// [`RecvMeta`] per buffer.
-    remaining_buffers:
-        iter::Take<iter::Zip<array::IntoIter<RecvMeta, NUM_BUFS>, slice::IterMut<'a, Vec<u8>>>>,
+    remaining_buffers: RemainingBuffers<'a>,
     /// The local address of the UDP socket used to receive the datagrams.
     local_address: SocketAddr,
+    /// Whether datagrams refer to the receive buffers, or are copies.
+    share: bool,
+    /// See [`RecvBuf::with_packet_info`].
+    packet_info: bool,
 }
 
-impl<'a> Iterator for DatagramIter<'a> {
-    type Item = Datagram<&'a mut [u8]>;
+impl DatagramIter<'_> {
+    /// The destination of datagrams received with `meta`.
+    fn destination(&self, meta: &RecvMeta) -> SocketAddr {
+        let local = self.local_address;
+        match meta.dst_ip {
+            Some(ip) if self.packet_info && local.ip().is_unspecified() => {
+                // Use the address family of the socket.  Dual-stack sockets
+                // report IPv4 addresses on some platforms.
+                let ip = match (ip, local) {
+                    (IpAddr::V4(v4), SocketAddr::V6(_)) => IpAddr::V6(v4.to_ipv6_mapped()),
+                    (IpAddr::V6(v6), SocketAddr::V4(_)) => {
+                        v6.to_ipv4_mapped().map_or(ip, IpAddr::V4)
+                    }
+                    _ => ip,
+                };
+                SocketAddr::new(ip, local.port())
+            }
+            _ => local,
+        }
+    }
+}
+
+impl Iterator for DatagramIter<'_> {
+    type Item = Datagram<SharedBytesMut>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             // Return the next datagram in the current buffer, if any.
-            if let Some((meta, d)) = self
+            if let Some(&mut (meta, ref mut ds)) = self
                 .current_buffer
                 .as_mut()
-                .and_then(|(meta, ds)| ds.next().map(|d| (meta, d)))
+                .filter(|(_, ds)| !ds.is_empty())
             {
-                return Some(Datagram::from_slice(
+                let d = ds.split_to(meta.stride.min(ds.len()));
+                let d = if self.share {
+                    d
+                } else {
+                    SharedBytesMut::from(d.as_ref().to_vec())
+                };
+                return Some(Datagram::from_shared(
                     meta.addr,
-                    self.local_address,
+                    self.destination(&meta),
                     meta.ecn.map(|n| Tos::from(n as u8)).unwrap_or_default(),
                     d,
                 ));
@@ -215,7 +720,7 @@ impl<'a> Iterator for DatagramIter<'a> {
 
             // Got another buffer. Let's chunk it into datagrams and return the
             // first datagram in the next loop iteration.
-            self.current_buffer = Some((meta, buf[0..meta.len].chunks_mut(meta.stride)));
+            self.current_buffer = Some((meta, buf.split_to(meta.len)));
         }
     }
 }
@@ -223,7 +728,9 @@ impl<'a> Iterator for DatagramIter<'a> {
 /// A wrapper around a UDP socket, sending and receiving [`Datagram`]s.
 pub struct Socket<S> {
     state: UdpSocketState,
+    send_state: SendState,
     inner: S,
+    stats: StatsCounters,
 }
 
 impl<S: SocketRef> Socket<S> {
@@ -231,13 +738,44 @@ impl<S: SocketRef> Socket<S> {
     pub fn new(socket: S) -> Result<Self, io::Error> {
         Ok(Self {
             state: UdpSocketState::new((&socket).into())?,
+            send_state: SendState::new(&socket)?,
             inner: socket,
+            stats: StatsCounters::default(),
         })
     }
 
+    /// Send datagrams from their source address.  See
+    /// [`SendState::with_packet_info`].
+    #[must_use]
+    pub fn with_packet_info(self) -> Self {
+        Self {
+            send_state: self.send_state.with_packet_info(),
+            ..self
+        }
+    }
+
     /// Send a [`datagram::Batch`] on the given [`Socket`].
+    ///
+    /// The ECN bits of the batch's TOS are set on all platforms, the DSCP only
+    /// on Linux and Android.  Elsewhere, a warning is logged the first time a
+    /// batch has a DSCP other than [`neqo_common::Dscp::Cs0`].
     pub fn send(&self, d: &datagram::Batch) -> io::Result<()> {
-        send_inner(&self.state, (&self.inner).into(), d)
+        let mode = send_inner(&self.state, &self.send_state, &self.inner, d)?;
+        self.stats.record(mode);
+        Ok(())
+    }
+
+    /// Send multiple [`datagram::Batch`]es on the given [`Socket`].
+    ///
+    /// See [`send_multiple_inner`].
+    pub fn send_multiple(&self, batches: &[datagram::Batch]) -> io::Result<usize> {
+        send_multiple_inner(
+            &self.state,
+            &self.send_state,
+            &self.inner,
+            batches,
+            &self.stats,
+        )
     }
 
     /// Returns the maximum number of GSO segments supported by this socket.
@@ -245,6 +783,21 @@ impl<S: SocketRef> Socket<S> {
         self.state.max_gso_segments()
     }
 
+    /// Returns statistics on how datagrams were sent on this socket.
+    pub fn stats(&self) -> Stats {
+        self.stats.get(&self.state)
+    }
+
+    /// Enable departure times on this socket.  See [`enable_txtime`].
+    pub fn enable_txtime(&self, clock: TxTimeClock) -> io::Result<()> {
+        enable_txtime(&self.send_state, &self.inner, clock)
+    }
+
+    /// Size the socket buffers for `bdp` bytes.  See [`size_buffers_for_bdp`].
+    pub fn size_buffers_for_bdp(&self, bdp: usize) -> io::Result<BufferSizes> {
+        size_buffers_for_bdp(&self.state, &self.inner, bdp)
+    }
+
     /// Receive a batch of [`Datagram`]s on the given [`Socket`], each
     /// set with the provided local address.
     pub fn recv<'a>(
@@ -271,7 +824,7 @@ mod tests {
         clippy::unwrap_in_result,
         reason = "OK in tests."
     )]
-    use std::{env, num::NonZeroUsize};
+    use std::{env, net::Ipv4Addr};
 
     use neqo_common::{Dscp, Ecn};
 
@@ -302,6 +855,83 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn held_datagrams_are_not_overwritten() -> Result<(), io::Error> {
+        let sender = socket()?;
+        let receiver = socket()?;
+        let receiver_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut recv_buf = RecvBuf::new(NonZeroUsize::MIN);
+
+        let mut held = Vec::new();
+        for i in 0..=MAX_PINNED_BUFS + 1 {
+            let datagram: datagram::Batch = Datagram::new(
+                sender.inner.local_addr()?,
+                receiver.inner.local_addr()?,
+                Tos::default(),
+                vec![u8::try_from(i).unwrap(); 100],
+            )
+            .into();
+            sender.send(&datagram)?;
+            held.extend(receiver.recv(receiver_addr, &mut recv_buf)?);
+        }
+
+        // The receive buffers are not reused while datagrams refer to them,
+        // and datagrams are copied once too many buffers are held.
+        assert_eq!(recv_buf.spare.len(), MAX_PINNED_BUFS);
+        for (i, d) in held.iter().enumerate() {
+            assert_eq!(d.as_ref(), &[u8::try_from(i).unwrap(); 100]);
+        }
+
+        // Once the datagrams are dropped, the buffers are reused.
+        held.clear();
+        assert!(recv_buf.prepare());
+        assert_eq!(recv_buf.spare.len(), MAX_PINNED_BUFS);
+        Ok(())
+    }
+
+    #[test]
+    fn packet_info() -> Result<(), io::Error> {
+        let sender = socket()?;
+        let receiver = Socket::new(std::net::UdpSocket::bind("0.0.0.0:0")?)?.with_packet_info();
+        receiver.inner.set_nonblocking(false)?;
+        let wildcard = receiver.inner.local_addr()?;
+        let datagram: datagram::Batch = Datagram::new(
+            sender.inner.local_addr()?,
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), wildcard.port()),
+            Tos::default(),
+            b"Hello, world!".to_vec(),
+        )
+        .into();
+
+        // Without packet information, datagrams carry the local address.
+        sender.send(&datagram)?;
+        let mut recv_buf = RecvBuf::default();
+        let d = receiver.recv(wildcard, &mut recv_buf)?.next().unwrap();
+        assert_eq!(d.destination(), wildcard);
+
+        sender.send(&datagram)?;
+        let mut recv_buf = RecvBuf::default().with_packet_info();
+        let d = receiver.recv(wildcard, &mut recv_buf)?.next().unwrap();
+        assert_eq!(d.destination(), datagram.destination());
+        assert_eq!(d.source(), datagram.source());
+
+        // Reply from that address, also with a DSCP, which uses `sendmmsg` on
+        // Linux.
+        for tos in [Tos::default(), Tos::from((Dscp::Le, Ecn::Ect0))] {
+            let reply: datagram::Batch =
+                Datagram::new(d.destination(), d.source(), tos, b"Hi!".to_vec()).into();
+            receiver.send(&reply)?;
+            let mut recv_buf = RecvBuf::default();
+            let r = sender
+                .recv(sender.inner.local_addr()?, &mut recv_buf)?
+                .next()
+                .unwrap();
+            assert_eq!(r.source(), d.destination());
+            assert_eq!(r.as_ref(), b"Hi!");
+        }
+        Ok(())
+    }
+
     #[test]
     fn datagram_tos() -> Result<(), io::Error> {
         let sender = socket()?;
@@ -348,6 +978,116 @@ mod tests {
         Ok(())
     }
 
+    /// [`quinn_udp`] only reports the ECN bits on receipt, so read the full
+    /// TOS byte with `IP_RECVTOS` instead.
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn datagram_dscp() -> Result<(), io::Error> {
+        let sender = socket()?;
+        datagram_dscp_from(&sender, sender.inner.local_addr()?)
+    }
+
+    /// A dual-stack socket sends IPv4 datagrams, with the DSCP, from an IPv4
+    /// address.
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn datagram_dscp_dual_stack() -> Result<(), io::Error> {
+        let sender = Socket::new(std::net::UdpSocket::bind("[::]:0")?)?;
+        sender.inner.set_nonblocking(false)?;
+        let port = sender.inner.local_addr()?.port();
+        datagram_dscp_from(&sender, SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn datagram_dscp_from(
+        sender: &Socket<std::net::UdpSocket>,
+        source: SocketAddr,
+    ) -> Result<(), io::Error> {
+        use std::os::fd::AsRawFd as _;
+
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        let enable: libc::c_int = 1;
+        // SAFETY: `enable` is a valid `c_int`.
+        let rv = unsafe {
+            libc::setsockopt(
+                receiver.as_raw_fd(),
+                libc::IPPROTO_IP,
+                libc::IP_RECVTOS,
+                std::ptr::from_ref(&enable).cast(),
+                libc::socklen_t::try_from(size_of::<libc::c_int>()).unwrap(),
+            )
+        };
+        assert_eq!(rv, 0);
+
+        let tos = Tos::from((Dscp::Af41, Ecn::Ect0));
+        let datagram: datagram::Batch = Datagram::new(
+            source,
+            receiver.local_addr()?,
+            tos,
+            b"Hello, world!".to_vec(),
+        )
+        .into();
+        sender.send(&datagram)?;
+
+        let mut buf = [0; 64];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+        let mut ctrl = [0_u64; 8];
+        // SAFETY: All-zero is a valid `msghdr`.
+        let mut hdr: libc::msghdr = unsafe { mem::zeroed() };
+        hdr.msg_iov = &raw mut iov;
+        hdr.msg_iovlen = 1;
+        hdr.msg_control = ctrl.as_mut_ptr().cast();
+        hdr.msg_controllen = size_of_val(&ctrl);
+        // SAFETY: `hdr` refers to valid buffers.
+        let len = unsafe { libc::recvmsg(receiver.as_raw_fd(), &raw mut hdr, 0) };
+        assert_eq!(usize::try_from(len).unwrap(), datagram.data().len());
+
+        let mut received_tos = None;
+        // SAFETY: `recvmsg` filled in the control messages.
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&raw const hdr);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::IPPROTO_IP && (*cmsg).cmsg_type == libc::IP_TOS {
+                    received_tos = Some(*libc::CMSG_DATA(cmsg));
+                }
+                cmsg = libc::CMSG_NXTHDR(&raw const hdr, cmsg);
+            }
+        }
+        assert_eq!(received_tos, Some(u8::from(tos)));
+        Ok(())
+    }
+
+    /// Once `sendmmsg` failed, datagrams are sent without their DSCP.
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn send_after_mmsg_failed() -> Result<(), io::Error> {
+        let sender = socket()?;
+        let receiver = socket()?;
+        let receiver_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        sender
+            .send_state
+            .mmsg_failed(&io::Error::from_raw_os_error(libc::EINVAL));
+        assert!(!sender.send_state.use_mmsg());
+
+        let datagram: datagram::Batch = Datagram::new(
+            sender.inner.local_addr()?,
+            receiver.inner.local_addr()?,
+            Tos::from((Dscp::Af41, Ecn::Ect0)),
+            b"Hello, world!".to_vec(),
+        )
+        .into();
+        sender.send(&datagram)?;
+
+        let mut recv_buf = RecvBuf::default();
+        let d = receiver.recv(receiver_addr, &mut recv_buf)?.next().unwrap();
+        assert_eq!(d.as_ref(), datagram.data());
+        assert_eq!(Ecn::from(d.tos()), Ecn::Ect0);
+        Ok(())
+    }
+
     #[test]
     #[cfg(unix)]
     fn is_emsgsize_true_for_emsgsize() {
@@ -442,6 +1182,181 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn send_stats() -> Result<(), io::Error> {
+        const SEGMENT_SIZE: usize = 128;
+
+        let sender = socket()?;
+        let receiver = socket()?;
+
+        let single: datagram::Batch = Datagram::new(
+            sender.inner.local_addr()?,
+            receiver.inner.local_addr()?,
+            Tos::default(),
+            vec![0xAB; SEGMENT_SIZE],
+        )
+        .into();
+        sender.send(&single)?;
+        assert_eq!(sender.stats().single, 1);
+
+        let max_gso_segments = sender.max_gso_segments();
+        if max_gso_segments > 1 {
+            let batch = datagram::Batch::new(
+                sender.inner.local_addr()?,
+                receiver.inner.local_addr()?,
+                Tos::default(),
+                NonZeroUsize::new(SEGMENT_SIZE).unwrap(),
+                vec![0xAB; SEGMENT_SIZE * 2],
+            );
+            sender.send(&batch)?;
+            let stats = sender.stats();
+            // Segmentation offload might be rejected by the OS, in which case
+            // the datagrams are sent individually.
+            assert_eq!(stats.segmented + stats.fallback, 1);
+        }
+        assert_eq!(sender.stats().max_gso_segments, sender.max_gso_segments());
+        Ok(())
+    }
+
+    #[test]
+    fn send_multiple_recv_multiple() -> Result<(), io::Error> {
+        const NUM_BATCHES: usize = 4;
+
+        let sender = socket()?;
+        let receiver = socket()?;
+        let receiver_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+        let (src, dst) = (sender.inner.local_addr()?, receiver.inner.local_addr()?);
+        let batches: Vec<datagram::Batch> = (0..NUM_BATCHES)
+            .map(|i| {
+                Datagram::new(
+                    src,
+                    dst,
+                    Tos::from((Dscp::Le, Ecn::Ect0)),
+                    vec![u8::try_from(i).unwrap(); 100 + i],
+                )
+                .into()
+            })
+            .collect();
+
+        let mut sent = 0;
+        while sent < NUM_BATCHES {
+            sent += sender.send_multiple(&batches[sent..])?;
+        }
+        assert_eq!(sender.stats().single, NUM_BATCHES);
+
+        let mut datagrams = Vec::new();
+        let mut recv_buf = RecvBuf::new(NonZeroUsize::new(NUM_BATCHES).unwrap());
+        while datagrams.len() < NUM_BATCHES {
+            datagrams.extend(
+                receiver
+                    .recv(receiver_addr, &mut recv_buf)?
+                    .map(|d| (Ecn::from(d.tos()), d.as_ref().to_vec())),
+            );
+        }
+        for (d, (ecn, r)) in batches.iter().zip(&datagrams) {
+            assert_eq!(d.data(), &r[..]);
+            if cfg!(target_os = "linux") {
+                assert_eq!(*ecn, Ecn::Ect0);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn size_buffers_for_bdp() -> Result<(), io::Error> {
+        let socket = socket()?;
+
+        // Buffers that are large enough are left alone.
+        let before = socket.size_buffers_for_bdp(0)?;
+        assert_eq!(socket.size_buffers_for_bdp(1)?, before);
+
+        // Others grow, as far as the OS allows.
+        let bdp = 2 * before.send.max(before.recv);
+        let after = socket.size_buffers_for_bdp(bdp)?;
+        assert!(after.send >= before.send);
+        assert!(after.recv >= before.recv);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore = "SO_TXTIME not available")]
+    fn send_with_txtime() -> Result<(), io::Error> {
+        let sender = socket()?;
+        let receiver = socket()?;
+        let receiver_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        sender.enable_txtime(TxTimeClock::default())?;
+        assert_eq!(
+            sender.enable_txtime(TxTimeClock::Tai).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+
+        // Datagrams that are due in the future, now, or in the past all go.
+        let now = std::time::Instant::now();
+        for txtime in [
+            now + std::time::Duration::from_millis(1),
+            now,
+            now.checked_sub(std::time::Duration::from_millis(1))
+                .unwrap(),
+        ] {
+            let mut datagram: datagram::Batch = Datagram::new(
+                sender.inner.local_addr()?,
+                receiver.inner.local_addr()?,
+                Tos::from((Dscp::Le, Ecn::Ect0)),
+                b"Hello, world!".to_vec(),
+            )
+            .into();
+            datagram.set_txtime(Some(txtime));
+            sender.send(&datagram)?;
+
+            let mut recv_buf = RecvBuf::default();
+            let mut datagrams = receiver.recv(receiver_addr, &mut recv_buf)?;
+            assert_eq!(datagrams.next().unwrap().as_ref(), datagram.data());
+        }
+        Ok(())
+    }
+
+    /// The source address, the GSO segment size and the departure time all fit
+    /// in the control messages of one `sendmmsg` message.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn send_with_all_control_messages() -> Result<(), io::Error> {
+        for (wildcard, local) in [("0.0.0.0:0", "127.0.0.1:0"), ("[::]:0", "[::1]:0")] {
+            let sender = Socket::new(std::net::UdpSocket::bind(wildcard)?)?.with_packet_info();
+            sender.inner.set_nonblocking(false)?;
+            sender.enable_txtime(TxTimeClock::default())?;
+            let receiver = Socket::new(std::net::UdpSocket::bind(local)?)?;
+            receiver.inner.set_nonblocking(false)?;
+            let local: SocketAddr = local.parse().unwrap();
+
+            let mut batch = datagram::Batch::new(
+                SocketAddr::new(local.ip(), sender.inner.local_addr()?.port()),
+                receiver.inner.local_addr()?,
+                Tos::from((Dscp::Le, Ecn::Ect0)),
+                NonZeroUsize::new(100).unwrap(),
+                vec![1; 200],
+            );
+            batch.set_txtime(Some(
+                std::time::Instant::now() + std::time::Duration::from_millis(1),
+            ));
+            assert_eq!(
+                mmsg::send(&sender.inner, &sender.send_state, slice::from_ref(&batch))?,
+                1
+            );
+
+            let mut len = 0;
+            while len < batch.data().len() {
+                let mut recv_buf = RecvBuf::default();
+                for d in receiver.recv(local, &mut recv_buf)? {
+                    assert_eq!(d.source(), batch.source());
+                    len += d.len();
+                }
+            }
+            assert_eq!(len, batch.data().len());
+        }
+        Ok(())
+    }
+
     #[test]
     fn send_ignore_emsgsize() -> Result<(), io::Error> {
         let sender = socket()?;
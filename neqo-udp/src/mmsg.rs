@@ -0,0 +1,389 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sending multiple [`datagram::Batch`]es with one `sendmmsg` syscall.
+//!
+//! [`quinn_udp`] only sends one message per syscall, and only sets the ECN bits
+//! of the TOS byte.
+
+use std::{
+    io, mem,
+    net::{IpAddr, SocketAddr},
+    os::fd::{AsFd, AsRawFd as _},
+    ptr,
+    time::{Duration, Instant},
+};
+
+use neqo_common::datagram;
+
+use crate::SendState;
+#[cfg(target_os = "linux")]
+use crate::TxTimeClock;
+
+/// The space that a control message with a `T` takes up.
+#[allow(
+    clippy::allow_attributes,
+    unused_unsafe,
+    reason = "`CMSG_SPACE` is only `unsafe` in some versions of libc."
+)]
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "Control messages are small."
+)]
+const fn cmsg_space<T>() -> usize {
+    // SAFETY: This only does arithmetic.
+    unsafe { libc::CMSG_SPACE(size_of::<T>() as libc::c_uint) as usize }
+}
+
+/// Space for the control messages of one message, i.e. the TOS byte, the
+/// source address, the GSO segment size and the departure time.
+const CMSG_LEN: usize = cmsg_space::<libc::c_int>()
+    + cmsg_space::<libc::in6_pktinfo>()
+    + cmsg_space::<u16>()
+    + cmsg_space::<u64>();
+
+#[repr(align(8))]
+#[derive(Clone, Copy)]
+struct Cmsgs([u8; CMSG_LEN]);
+
+/// Whether `socket` is an IPv6 socket.
+///
+/// The local address of a datagram does not tell: a dual-stack socket can
+/// send from an IPv4 address.
+pub fn is_ipv6<S: AsFd>(socket: &S) -> io::Result<bool> {
+    // SAFETY: All-zero is a valid `sockaddr_storage`.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = libc::socklen_t::try_from(size_of::<libc::sockaddr_storage>())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    // SAFETY: `storage` is large enough for any address, and `len` says so.
+    let rv = unsafe {
+        libc::getsockname(
+            socket.as_fd().as_raw_fd(),
+            ptr::from_mut(&mut storage).cast(),
+            &raw mut len,
+        )
+    };
+    if rv == 0 {
+        Ok(libc::c_int::from(storage.ss_family) == libc::AF_INET6)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Whether `addr` is an IPv4 address, possibly mapped into IPv6.
+const fn is_ipv4(addr: SocketAddr) -> bool {
+    match addr {
+        SocketAddr::V4(_) => true,
+        SocketAddr::V6(v6) => v6.ip().to_ipv4_mapped().is_some(),
+    }
+}
+
+/// Convert `addr` into a `sockaddr` for a socket of the given family, using an
+/// IPv4-mapped IPv6 address when sending an IPv4 datagram on an IPv6 socket.
+fn sockaddr(addr: SocketAddr, ipv6_socket: bool) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let addr = match addr {
+        SocketAddr::V4(v4) if ipv6_socket => {
+            SocketAddr::new(IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port())
+        }
+        SocketAddr::V6(v6) if !ipv6_socket => v6
+            .ip()
+            .to_ipv4_mapped()
+            .map_or(addr, |v4| SocketAddr::new(IpAddr::V4(v4), v6.port())),
+        addr => addr,
+    };
+    // SAFETY: All-zero is a valid `sockaddr_storage`.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(v4) => {
+            let sin = libc::sockaddr_in {
+                sin_family: libc::sa_family_t::try_from(libc::AF_INET).expect("fits"),
+                sin_port: v4.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(v4.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            // SAFETY: `sockaddr_storage` is large enough and suitably aligned.
+            unsafe {
+                ptr::write(ptr::from_mut(&mut storage).cast(), sin);
+            }
+            size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(v6) => {
+            let sin6 = libc::sockaddr_in6 {
+                sin6_family: libc::sa_family_t::try_from(libc::AF_INET6).expect("fits"),
+                sin6_port: v6.port().to_be(),
+                sin6_flowinfo: v6.flowinfo().to_be(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: v6.ip().octets(),
+                },
+                sin6_scope_id: v6.scope_id(),
+            };
+            // SAFETY: `sockaddr_storage` is large enough and suitably aligned.
+            unsafe {
+                ptr::write(ptr::from_mut(&mut storage).cast(), sin6);
+            }
+            size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (
+        storage,
+        libc::socklen_t::try_from(len).expect("sockaddr fits"),
+    )
+}
+
+#[cfg(target_os = "linux")]
+const fn clockid(clock: TxTimeClock) -> libc::clockid_t {
+    match clock {
+        TxTimeClock::Monotonic => libc::CLOCK_MONOTONIC,
+        TxTimeClock::Tai => libc::CLOCK_TAI,
+    }
+}
+
+/// Enable `SO_TXTIME` on `socket`, so that datagrams can carry a departure
+/// time relative to `clock`.
+///
+/// Reads the option back, as some kernels accept it without honoring it.
+#[cfg(target_os = "linux")]
+pub fn enable_txtime<S: AsFd>(socket: &S, clock: TxTimeClock) -> io::Result<()> {
+    let config = libc::sock_txtime {
+        clockid: clockid(clock),
+        flags: 0,
+    };
+    let mut len = libc::socklen_t::try_from(size_of::<libc::sock_txtime>())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    // SAFETY: `config` is a valid `sock_txtime`.
+    let rv = unsafe {
+        libc::setsockopt(
+            socket.as_fd().as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TXTIME,
+            ptr::from_ref(&config).cast(),
+            len,
+        )
+    };
+    if rv != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut actual = libc::sock_txtime {
+        clockid: -1,
+        flags: 0,
+    };
+    // SAFETY: `actual` is a valid `sock_txtime`, and `len` is its size.
+    let rv = unsafe {
+        libc::getsockopt(
+            socket.as_fd().as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TXTIME,
+            ptr::from_mut(&mut actual).cast(),
+            &raw mut len,
+        )
+    };
+    if rv != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if actual.clockid != config.clockid {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SO_TXTIME is not in effect",
+        ));
+    }
+    Ok(())
+}
+
+/// Datagrams that are due within this time are sent without a departure time.
+/// Otherwise, the ETF qdisc could see them as late by the time they get there,
+/// and drop them.
+#[cfg(target_os = "linux")]
+const TXTIME_SLACK: Duration = Duration::from_micros(100);
+
+/// Maps [`Instant`]s onto the clock that `SO_TXTIME` uses.
+#[cfg(target_os = "linux")]
+struct Clock {
+    instant: Instant,
+    ns: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl Clock {
+    fn now(clock: TxTimeClock) -> Self {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid `timespec`.  Both clocks are always
+        // available.
+        unsafe {
+            libc::clock_gettime(clockid(clock), &raw mut ts);
+        }
+        let secs = u64::try_from(ts.tv_sec).unwrap_or_default();
+        let nanos = u64::try_from(ts.tv_nsec).unwrap_or_default();
+        Self {
+            instant: Instant::now(),
+            ns: secs.saturating_mul(1_000_000_000).saturating_add(nanos),
+        }
+    }
+
+    /// Convert `t` to nanoseconds on the clock, if it is more than
+    /// [`TXTIME_SLACK`] in the future.
+    fn ns(&self, t: Instant) -> Option<u64> {
+        let ahead = t.checked_duration_since(self.instant)?;
+        if ahead <= TXTIME_SLACK {
+            return None;
+        }
+        Some(
+            self.ns
+                .saturating_add(u64::try_from(ahead.as_nanos()).unwrap_or(u64::MAX)),
+        )
+    }
+}
+
+/// Append a control message to `hdr`, returning the next free control message.
+///
+/// Fails, without writing anything, if `cmsg` is null or the message does not
+/// fit in the control buffer of `hdr`.
+///
+/// # Safety
+///
+/// `cmsg` must be null or a control message header within the buffer of `hdr`.
+unsafe fn put_cmsg<T>(
+    hdr: &libc::msghdr,
+    cmsg: *mut libc::cmsghdr,
+    level: libc::c_int,
+    ty: libc::c_int,
+    value: T,
+) -> io::Result<*mut libc::cmsghdr> {
+    if cmsg.is_null() {
+        return Err(io::Error::other("no room for control message"));
+    }
+    let offset = cmsg.addr() - hdr.msg_control.addr();
+    #[allow(
+        clippy::allow_attributes,
+        clippy::useless_conversion,
+        reason = "The type of `msg_controllen` differs between libc implementations."
+    )]
+    let controllen = usize::try_from(hdr.msg_controllen).unwrap_or(usize::MAX);
+    if offset + cmsg_space::<T>() > controllen {
+        return Err(io::Error::other("no room for control message"));
+    }
+    let len = u32::try_from(size_of::<T>()).map_err(io::Error::other)?;
+    unsafe {
+        (*cmsg).cmsg_level = level;
+        (*cmsg).cmsg_type = ty;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(len) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<T>(), value);
+        Ok(libc::CMSG_NXTHDR(hdr, cmsg))
+    }
+}
+
+/// Send `batches` with a single `sendmmsg` syscall.
+///
+/// Returns the number of batches that were sent.  An error refers to the first
+/// batch.
+#[allow(
+    clippy::allow_attributes,
+    trivial_numeric_casts,
+    clippy::cast_possible_truncation,
+    reason = "The types of `msghdr` fields differ between libc implementations."
+)]
+pub fn send<S: AsFd>(
+    socket: &S,
+    send_state: &SendState,
+    batches: &[datagram::Batch],
+) -> io::Result<usize> {
+    let n = batches.len();
+    let mut addrs = Vec::with_capacity(n);
+    let mut iovs = Vec::with_capacity(n);
+    let mut ctrls = vec![Cmsgs([0; CMSG_LEN]); n];
+    for d in batches {
+        addrs.push(sockaddr(d.destination(), send_state.ipv6));
+        iovs.push(libc::iovec {
+            iov_base: d.data().as_ptr().cast_mut().cast(),
+            iov_len: d.data().len(),
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    let clock = send_state.txtime_clock.get().copied().map(Clock::now);
+    // SAFETY: All-zero is a valid `mmsghdr`.
+    let mut hdrs = vec![unsafe { mem::zeroed::<libc::mmsghdr>() }; n];
+    for (i, (hdr, d)) in hdrs.iter_mut().zip(batches).enumerate() {
+        let hdr = &mut hdr.msg_hdr;
+        hdr.msg_name = ptr::from_mut(&mut addrs[i].0).cast();
+        hdr.msg_namelen = addrs[i].1;
+        hdr.msg_iov = ptr::from_mut(&mut iovs[i]);
+        hdr.msg_iovlen = 1;
+        hdr.msg_control = ctrls[i].0.as_mut_ptr().cast();
+        hdr.msg_controllen = CMSG_LEN as _;
+
+        // Unlike `quinn_udp`, set the DSCP as well as the ECN bits.  Like
+        // `quinn_udp`, pick the option by the family of the destination, as
+        // IPv4 datagrams on a dual-stack socket ignore `IPV6_TCLASS`.
+        let tos = libc::c_int::from(u8::from(d.tos()));
+        let mut used = 0;
+        // SAFETY: `cmsg` is always null or within the control buffer.
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(hdr);
+            cmsg = if is_ipv4(d.destination()) {
+                put_cmsg(hdr, cmsg, libc::IPPROTO_IP, libc::IP_TOS, tos)?
+            } else {
+                put_cmsg(hdr, cmsg, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos)?
+            };
+            used += libc::CMSG_SPACE(size_of::<libc::c_int>() as u32);
+            match super::source_ip(send_state, d) {
+                Some(IpAddr::V4(ip)) => {
+                    let pktinfo = libc::in_pktinfo {
+                        ipi_ifindex: 0,
+                        ipi_spec_dst: libc::in_addr {
+                            s_addr: u32::from_ne_bytes(ip.octets()),
+                        },
+                        ipi_addr: libc::in_addr { s_addr: 0 },
+                    };
+                    cmsg = put_cmsg(hdr, cmsg, libc::IPPROTO_IP, libc::IP_PKTINFO, pktinfo)?;
+                    used += libc::CMSG_SPACE(size_of::<libc::in_pktinfo>() as u32);
+                }
+                Some(IpAddr::V6(ip)) => {
+                    let pktinfo = libc::in6_pktinfo {
+                        ipi6_addr: libc::in6_addr {
+                            s6_addr: ip.octets(),
+                        },
+                        ipi6_ifindex: 0,
+                    };
+                    cmsg = put_cmsg(hdr, cmsg, libc::IPPROTO_IPV6, libc::IPV6_PKTINFO, pktinfo)?;
+                    used += libc::CMSG_SPACE(size_of::<libc::in6_pktinfo>() as u32);
+                }
+                None => {}
+            }
+            if d.num_datagrams() > 1 {
+                let segment_size = u16::try_from(d.datagram_size().get())
+                    .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+                cmsg = put_cmsg(hdr, cmsg, libc::SOL_UDP, libc::UDP_SEGMENT, segment_size)?;
+                used += libc::CMSG_SPACE(size_of::<u16>() as u32);
+            }
+            #[cfg(target_os = "linux")]
+            if let Some(txtime) = clock.as_ref().zip(d.txtime()).and_then(|(c, t)| c.ns(t)) {
+                put_cmsg(hdr, cmsg, libc::SOL_SOCKET, libc::SCM_TXTIME, txtime)?;
+                used += libc::CMSG_SPACE(size_of::<u64>() as u32);
+            }
+            _ = cmsg;
+        }
+        hdr.msg_controllen = used as _;
+    }
+
+    let vlen = libc::c_uint::try_from(n).unwrap_or(libc::c_uint::MAX);
+    loop {
+        // SAFETY: All pointers in `hdrs` refer to buffers that outlive the call.
+        let rv = unsafe { libc::sendmmsg(socket.as_fd().as_raw_fd(), hdrs.as_mut_ptr(), vlen, 0) };
+        if let Ok(sent) = usize::try_from(rv) {
+            return Ok(sent);
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
@@ -0,0 +1,711 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{
+    cmp::min,
+    collections::VecDeque,
+    fmt::{self, Debug, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{Datagram, Dscp, Ecn, Tos, qinfo, qtrace};
+use neqo_transport::Output;
+
+use super::{Node, Rng};
+
+/// One second in nanoseconds.
+const ONE_SECOND_NS: u128 = 1_000_000_000;
+/// Probabilities are in parts per billion, so that small changes to the
+/// probability don't get lost.
+const PPB: u64 = 1_000_000_000;
+/// The size of a full datagram, which the algorithms use as a minimum queue.
+const MTU: usize = 1500;
+
+/// The queue delay in microseconds, which is convenient for the PI controllers.
+fn micros(d: Duration) -> i64 {
+    i64::try_from(d.as_micros()).unwrap()
+}
+
+fn is_l4s(d: &Datagram) -> bool {
+    matches!(Ecn::from(d.tos()), Ecn::Ect1 | Ecn::Ce)
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    /// The number of datagrams received.
+    received: usize,
+    /// The number of datagrams marked.
+    marked: usize,
+    /// The number of datagrams dropped by the algorithm.
+    dropped: usize,
+    /// The number of datagrams dropped because the queue was full.
+    overflow: usize,
+    /// The number of datagrams delivered.
+    delivered: usize,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "rx {} mark {} drop {} overflow {} tx {}",
+            self.received, self.marked, self.dropped, self.overflow, self.delivered
+        )
+    }
+}
+
+/// The state of CoDel, from RFC 8289.
+#[derive(Debug, Default)]
+struct CoDel {
+    /// When the queue delay first went above the target, plus one interval.
+    first_above: Option<Instant>,
+    /// Whether the queue is in the dropping state.
+    dropping: bool,
+    /// When to drop next.
+    drop_next: Option<Instant>,
+    /// The number of drops since entering the dropping state.
+    count: u64,
+    /// The value of `count` when last entering the dropping state.
+    last_count: u64,
+}
+
+impl CoDel {
+    const TARGET: Duration = Duration::from_millis(5);
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    /// The time of the next drop: one interval, divided by the square root of
+    /// the number of drops so far.
+    fn control_law(t: Instant, count: u64) -> Instant {
+        // Scale up by a million before taking the square root for precision.
+        let interval = u64::try_from(Self::INTERVAL.as_nanos()).unwrap();
+        t + Duration::from_nanos(interval * 1000 / (count * 1_000_000).isqrt())
+    }
+
+    /// Whether the queue has been above the target for long enough to drop.
+    fn ok_to_drop(&mut self, sojourn: Duration, backlog: usize, now: Instant) -> bool {
+        if sojourn < Self::TARGET || backlog <= MTU {
+            self.first_above = None;
+            false
+        } else if let Some(first_above) = self.first_above {
+            now >= first_above
+        } else {
+            self.first_above = Some(now + Self::INTERVAL);
+            false
+        }
+    }
+}
+
+/// The state of a proportional integral controller, as used by PIE (RFC 8033)
+/// and `DualPI2` (RFC 9332).
+#[derive(Debug, Default)]
+struct Pi {
+    /// The base probability, in parts per billion.
+    p: u64,
+    /// The queue delay at the last update.
+    qdelay_old: Duration,
+    /// When to update the probability next.
+    next_update: Option<Instant>,
+}
+
+impl Pi {
+    /// Move the probability by `delta` parts per billion.
+    fn add(&mut self, delta: i64) {
+        let p = i64::try_from(self.p).unwrap() + delta;
+        self.p = u64::try_from(p.max(0)).unwrap().min(PPB);
+    }
+}
+
+/// The state of PIE, from RFC 8033.
+#[derive(Debug, Default)]
+struct Pie {
+    pi: Pi,
+    /// The time during which bursts are allowed without drops.
+    burst_allowance: Duration,
+}
+
+impl Pie {
+    const TARGET: Duration = Duration::from_millis(15);
+    const T_UPDATE: Duration = Duration::from_millis(15);
+    const MAX_BURST: Duration = Duration::from_millis(150);
+    /// ECN-capable datagrams are marked rather than dropped below this
+    /// probability.
+    const MARK_ECN_THRESHOLD: u64 = PPB / 10;
+
+    fn update(&mut self, qdelay: Duration) {
+        let (target, qd, old) = (
+            micros(Self::TARGET),
+            micros(qdelay),
+            micros(self.pi.qdelay_old),
+        );
+        // alpha = 0.125Hz and beta = 1.25Hz, so that each microsecond of delay
+        // contributes 125 and 1250 parts per billion respectively.
+        let mut delta = 125 * (qd - target) + 1250 * (qd - old);
+        // Move slowly when the probability is small.
+        let scale = [
+            (1_000, 2048),
+            (10_000, 512),
+            (100_000, 128),
+            (1_000_000, 32),
+            (10_000_000, 8),
+            (100_000_000, 2),
+        ]
+        .into_iter()
+        .find_map(|(below, scale)| (self.pi.p < below).then_some(scale))
+        .unwrap_or(1);
+        delta /= scale;
+        // Don't jump too far when the probability is already high.
+        let two_pct = i64::try_from(PPB / 50).unwrap();
+        if (self.pi.p >= PPB / 10 && delta > two_pct) || qdelay > Duration::from_millis(250) {
+            delta = two_pct;
+        }
+        self.pi.add(delta);
+        if qdelay.is_zero() && self.pi.qdelay_old.is_zero() {
+            self.pi.p = self.pi.p * 98 / 100;
+        }
+
+        self.burst_allowance = self.burst_allowance.saturating_sub(Self::T_UPDATE);
+        if self.pi.p == 0 && qdelay < Self::TARGET / 2 && self.pi.qdelay_old < Self::TARGET / 2 {
+            self.burst_allowance = Self::MAX_BURST;
+        }
+        self.pi.qdelay_old = qdelay;
+    }
+
+    /// The probability of dropping a datagram that arrives when `used` bytes
+    /// are queued.
+    fn drop_probability(&self, used: usize) -> u64 {
+        if !self.burst_allowance.is_zero()
+            || (self.pi.qdelay_old < Self::TARGET / 2 && self.pi.p < PPB / 5)
+            || used <= 2 * MTU
+        {
+            0
+        } else {
+            self.pi.p
+        }
+    }
+}
+
+/// The state of `DualPI2`, from RFC 9332.
+#[derive(Debug, Default)]
+struct DualPi2 {
+    pi: Pi,
+}
+
+impl DualPi2 {
+    const TARGET: Duration = Duration::from_millis(15);
+    const T_UPDATE: Duration = Duration::from_millis(16);
+    /// The coupling factor between the classic and L4S queues.
+    const K: u64 = 2;
+    /// L4S datagrams are always marked if they wait longer than this.
+    const L4S_THRESHOLD: Duration = Duration::from_millis(1);
+    /// How much longer classic datagrams wait before they are served first.
+    const TIME_SHIFT: Duration = Duration::from_millis(30);
+
+    fn update(&mut self, qdelay: Duration) {
+        let (target, qd, old) = (
+            micros(Self::TARGET),
+            micros(qdelay),
+            micros(self.pi.qdelay_old),
+        );
+        // alpha = 0.16Hz and beta = 3.2Hz.
+        self.pi.add(160 * (qd - target) + 3200 * (qd - old));
+        self.pi.qdelay_old = qdelay;
+    }
+
+    /// The probability of dropping or marking a classic datagram.
+    const fn classic(&self) -> u64 {
+        self.pi.p * self.pi.p / PPB
+    }
+
+    /// The probability of marking an L4S datagram, based on the classic queue.
+    fn coupled(&self) -> u64 {
+        min(self.pi.p * Self::K, PPB)
+    }
+}
+
+/// The queue management algorithm.
+#[derive(Debug)]
+enum Discipline {
+    CoDel(CoDel),
+    Pie(Pie),
+    DualPi2(DualPi2),
+}
+
+/// A datagram in the queue.
+struct Entry {
+    /// When the datagram arrived.
+    arrived: Instant,
+    d: Datagram,
+}
+
+/// A link with active queue management at the front of it.
+///
+/// Like [`super::network::TailDrop`], datagrams leave the queue at a fixed
+/// rate and then take a fixed time to cross the link, and datagrams that do
+/// not fit in the queue are dropped.  Before that happens, the queue
+/// management algorithm drops datagrams, or marks them with ECN-CE if they are
+/// ECN-capable, to keep the queue short.
+///
+/// The algorithms are:
+///
+/// * `CoDel` (RFC 8289), which drops at dequeue when datagrams wait too long.
+/// * PIE (RFC 8033), which drops at enqueue with a probability from a
+///   proportional integral controller of the queue delay.
+/// * `DualPI2` (RFC 9332), which puts L4S datagrams (ECT(1) or CE) in a
+///   separate queue that is marked early, coupled with a classic queue that is
+///   managed like PIE.
+pub struct Aqm {
+    /// An overhead associated with each entry.  This accounts for
+    /// layer 2, IP, and UDP overheads.
+    overhead: usize,
+    /// The rate at which bytes egress the link, in bytes per second.
+    rate: usize,
+    /// The depth of the queue, in bytes.
+    capacity: usize,
+    /// The time it takes a byte to exit the other end of the link.
+    delay: Duration,
+    discipline: Discipline,
+
+    /// The classic queue, which is the only queue for most algorithms.
+    classic: VecDeque<Entry>,
+    /// The L4S queue, which is only used by `DualPI2`.
+    l4s: VecDeque<Entry>,
+    /// A counter for how many bytes are enqueued.
+    used: usize,
+    /// The time that the next datagram can enter the link.
+    /// Includes any sub-ns remainder (which helps absorb rounding errors).
+    next_deque: Option<(Instant, u32)>,
+    /// The packets that are on the link and when they can be delivered.
+    on_link: VecDeque<(Instant, Datagram)>,
+
+    rng: Option<Rng>,
+    stats: Stats,
+}
+
+impl Aqm {
+    /// # Panics
+    ///
+    /// Panics if rate is zero or over 1Gbps.
+    fn new(rate: usize, capacity: usize, delay: Duration, discipline: Discipline) -> Self {
+        assert!(rate != 0, "zero rate gets you nowhere");
+        assert!(rate <= 1_000_000_000, "rates over 1Gbps are not supported");
+        Self {
+            overhead: 80,
+            rate,
+            capacity,
+            delay,
+            discipline,
+            classic: VecDeque::new(),
+            l4s: VecDeque::new(),
+            used: 0,
+            next_deque: None,
+            on_link: VecDeque::new(),
+            rng: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// A link with the given rate, queue capacity, and link delay, managed with `CoDel`.
+    #[must_use]
+    pub fn codel(rate: usize, capacity: usize, delay: Duration) -> Self {
+        Self::new(rate, capacity, delay, Discipline::CoDel(CoDel::default()))
+    }
+
+    /// A link with the given rate, queue capacity, and link delay, managed with PIE.
+    #[must_use]
+    pub fn pie(rate: usize, capacity: usize, delay: Duration) -> Self {
+        Self::new(
+            rate,
+            capacity,
+            delay,
+            Discipline::Pie(Pie {
+                burst_allowance: Pie::MAX_BURST,
+                ..Pie::default()
+            }),
+        )
+    }
+
+    /// A link with the given rate, queue capacity, and link delay, managed with `DualPI2`.
+    #[must_use]
+    pub fn dual_pi2(rate: usize, capacity: usize, delay: Duration) -> Self {
+        Self::new(
+            rate,
+            capacity,
+            delay,
+            Discipline::DualPi2(DualPi2::default()),
+        )
+    }
+
+    /// How "big" is this datagram, accounting for overheads.
+    fn size(&self, d: &Datagram) -> usize {
+        d.len() + self.overhead
+    }
+
+    /// Whether something with the given probability, in parts per billion, happens.
+    fn happens(&self, probability: u64) -> bool {
+        probability > 0 && self.rng.as_ref().unwrap().borrow_mut().random_from(0..PPB) < probability
+    }
+
+    /// Mark the datagram if it is ECN-capable, otherwise drop it.
+    fn mark_or_drop(&mut self, d: Datagram) -> Option<Datagram> {
+        if Ecn::from(d.tos()).is_ect() {
+            Some(self.mark(d))
+        } else {
+            qtrace!("aqm dropping {} bytes", d.len());
+            self.stats.dropped += 1;
+            None
+        }
+    }
+
+    fn mark(&mut self, d: Datagram) -> Datagram {
+        if Ecn::from(d.tos()) == Ecn::Ce {
+            return d;
+        }
+        qtrace!("aqm marking {} bytes", d.len());
+        self.stats.marked += 1;
+        let tos = Tos::from((Dscp::from(d.tos()), Ecn::Ce));
+        Datagram::new(d.source(), d.destination(), tos, d.to_vec())
+    }
+
+    /// The delay of the datagram at the head of the queue.
+    fn head_delay(queue: &VecDeque<Entry>, now: Instant) -> Duration {
+        queue
+            .front()
+            .map_or(Duration::ZERO, |e| now.saturating_duration_since(e.arrived))
+    }
+
+    /// Run any updates of the drop probability that are due.
+    fn update(&mut self, now: Instant) {
+        let qdelay = Self::head_delay(&self.classic, now).max(Self::head_delay(&self.l4s, now));
+        let (pi, interval) = match &mut self.discipline {
+            Discipline::CoDel(_) => return,
+            Discipline::Pie(pie) => (&mut pie.pi, Pie::T_UPDATE),
+            Discipline::DualPi2(dual) => (&mut dual.pi, DualPi2::T_UPDATE),
+        };
+        let next = *pi.next_update.get_or_insert(now + interval);
+        if now < next {
+            return;
+        }
+        // Only the latest delay is known, so catch up with a single update.
+        pi.next_update = Some(now + interval);
+        match &mut self.discipline {
+            Discipline::CoDel(_) => unreachable!(),
+            Discipline::Pie(pie) => pie.update(qdelay),
+            Discipline::DualPi2(dual) => dual.update(qdelay),
+        }
+    }
+
+    /// Enqueue for sending, unless the algorithm or a full queue drops it.
+    fn enqueue(&mut self, d: Datagram, now: Instant) {
+        self.stats.received += 1;
+        let size = self.size(&d);
+        if self.used + size > self.capacity {
+            qtrace!("aqm overflow, dropping {} bytes", d.len());
+            self.stats.overflow += 1;
+            return;
+        }
+        let mut d = d;
+        if let Discipline::Pie(pie) = &self.discipline {
+            let p = pie.drop_probability(self.used);
+            if self.happens(p) {
+                if p > Pie::MARK_ECN_THRESHOLD {
+                    self.stats.dropped += 1;
+                    return;
+                }
+                let Some(marked) = self.mark_or_drop(d) else {
+                    return;
+                };
+                d = marked;
+            }
+        }
+        self.used += size;
+        let entry = Entry { arrived: now, d };
+        if matches!(self.discipline, Discipline::DualPi2(_)) && is_l4s(&entry.d) {
+            self.l4s.push_back(entry);
+        } else {
+            self.classic.push_back(entry);
+        }
+    }
+
+    /// Take the next datagram from the classic queue, with its delay.
+    fn pop_classic(&mut self, now: Instant) -> Option<(Datagram, Duration)> {
+        let e = self.classic.pop_front()?;
+        self.used -= self.size(&e.d);
+        Some((e.d, now.saturating_duration_since(e.arrived)))
+    }
+
+    /// Dequeue following RFC 8289, except that ECN-capable datagrams are
+    /// marked rather than dropped.
+    fn dequeue_codel(&mut self, now: Instant) -> Option<Datagram> {
+        let Discipline::CoDel(mut codel) =
+            std::mem::replace(&mut self.discipline, Discipline::CoDel(CoDel::default()))
+        else {
+            unreachable!();
+        };
+        let res = self.dequeue_codel_inner(&mut codel, now);
+        self.discipline = Discipline::CoDel(codel);
+        res
+    }
+
+    fn dequeue_codel_inner(&mut self, codel: &mut CoDel, now: Instant) -> Option<Datagram> {
+        let Some((mut d, sojourn)) = self.pop_classic(now) else {
+            codel.first_above = None;
+            codel.dropping = false;
+            return None;
+        };
+        let mut ok = codel.ok_to_drop(sojourn, self.used, now);
+        if codel.dropping {
+            if !ok {
+                codel.dropping = false;
+            }
+            while codel.dropping && codel.drop_next.is_some_and(|t| now >= t) {
+                codel.count += 1;
+                let drop_next = codel.drop_next.unwrap();
+                if Ecn::from(d.tos()).is_ect() {
+                    codel.drop_next = Some(CoDel::control_law(drop_next, codel.count));
+                    return Some(self.mark(d));
+                }
+                _ = self.mark_or_drop(d);
+                let (next, sojourn) = self.pop_classic(now)?;
+                d = next;
+                ok = codel.ok_to_drop(sojourn, self.used, now);
+                if ok {
+                    codel.drop_next = Some(CoDel::control_law(drop_next, codel.count));
+                } else {
+                    codel.dropping = false;
+                }
+            }
+        } else if ok {
+            codel.dropping = true;
+            let delta = codel.count - codel.last_count;
+            codel.count = if delta > 1
+                && codel
+                    .drop_next
+                    .is_some_and(|t| now.saturating_duration_since(t) < CoDel::INTERVAL * 16)
+            {
+                delta
+            } else {
+                1
+            };
+            codel.last_count = codel.count;
+            codel.drop_next = Some(CoDel::control_law(now, codel.count));
+            d = match self.mark_or_drop(d) {
+                Some(d) => return Some(d),
+                None => self.pop_classic(now)?.0,
+            };
+        }
+        Some(d)
+    }
+
+    /// Dequeue following RFC 9332, serving the L4S queue first unless a
+    /// classic datagram has waited much longer.
+    fn dequeue_dual_pi2(&mut self, now: Instant) -> Option<Datagram> {
+        let Discipline::DualPi2(dual) = &self.discipline else {
+            unreachable!();
+        };
+        let (classic, coupled) = (dual.classic(), dual.coupled());
+        loop {
+            let classic_first = match (self.classic.front(), self.l4s.front()) {
+                (None, None) => return None,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (Some(c), Some(l)) => c.arrived + DualPi2::TIME_SHIFT < l.arrived,
+            };
+            if classic_first {
+                let (d, _) = self.pop_classic(now)?;
+                if !self.happens(classic) {
+                    return Some(d);
+                }
+                if let Some(d) = self.mark_or_drop(d) {
+                    return Some(d);
+                }
+            } else {
+                let e = self.l4s.pop_front()?;
+                self.used -= self.size(&e.d);
+                let sojourn = now.saturating_duration_since(e.arrived);
+                if sojourn > DualPi2::L4S_THRESHOLD || self.happens(coupled) {
+                    return Some(self.mark(e.d));
+                }
+                return Some(e.d);
+            }
+        }
+    }
+
+    fn dequeue(&mut self, now: Instant) -> Option<Datagram> {
+        match self.discipline {
+            Discipline::CoDel(_) => self.dequeue_codel(now),
+            Discipline::Pie(_) => self.pop_classic(now).map(|(d, _)| d),
+            Discipline::DualPi2(_) => self.dequeue_dual_pi2(now),
+        }
+    }
+
+    /// Start sending a datagram.
+    /// Send at the given time, with the given sub-nanosecond extra delay.
+    fn send(&mut self, d: Datagram, now: Instant, sub_ns: u32) {
+        let sz = u128::try_from(self.size(&d)).unwrap();
+        // See `TailDrop::send` for how this avoids rounding errors.
+        let t =
+            sz * (ONE_SECOND_NS << 32) / u128::try_from(self.rate).unwrap() + u128::from(sub_ns);
+        let send_ns = u64::try_from(t >> 32).unwrap();
+        let deque_time = now + Duration::from_nanos(send_ns);
+        let sub_ns = u32::try_from(t & u128::from(u32::MAX)).unwrap();
+        self.next_deque = Some((deque_time, sub_ns));
+        self.on_link.push_back((deque_time + self.delay, d));
+    }
+
+    /// While the link is free, start sending the next datagram.
+    fn maybe_send(&mut self, now: Instant) {
+        loop {
+            let (start, sub_ns) = match self.next_deque {
+                Some((t, _)) if now < t => return,
+                Some((t, sub_ns)) => (t, sub_ns),
+                None => (now, 0),
+            };
+            // The link might have been idle since before the next datagram arrived.
+            let arrived = self
+                .classic
+                .front()
+                .into_iter()
+                .chain(self.l4s.front())
+                .map(|e| e.arrived)
+                .min();
+            let (start, sub_ns) = match arrived {
+                Some(arrived) if arrived > start => (arrived, 0),
+                _ => (start, sub_ns),
+            };
+            let Some(d) = self.dequeue(start) else {
+                self.next_deque = None;
+                return;
+            };
+            self.send(d, start, sub_ns);
+        }
+    }
+}
+
+impl Node for Aqm {
+    fn init(&mut self, rng: Rng, _now: Instant) {
+        self.rng = Some(rng);
+    }
+
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        self.update(now);
+        if let Some(dgram) = d {
+            self.enqueue(dgram, now);
+        }
+        self.maybe_send(now);
+
+        if let Some((t, _)) = self.on_link.front()
+            && *t <= now
+        {
+            let (_, d) = self.on_link.pop_front().unwrap();
+            self.stats.delivered += 1;
+            return Output::Datagram(d);
+        }
+        // Only wake for the link if there is something waiting to use it.
+        let next_deque = self
+            .next_deque
+            .filter(|_| !self.classic.is_empty() || !self.l4s.is_empty())
+            .map(|(t, _)| t);
+        let next = self
+            .on_link
+            .front()
+            .map(|(t, _)| *t)
+            .into_iter()
+            .chain(next_deque)
+            .min();
+        next.map_or(Output::None, |t| Output::Callback(t - now))
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: {self:?}: {}", self.stats);
+    }
+}
+
+impl Debug for Aqm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.discipline {
+            Discipline::CoDel(_) => "codel",
+            Discipline::Pie(_) => "pie",
+            Discipline::DualPi2(_) => "dualpi2",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use neqo_common::{Datagram, Ecn};
+    use neqo_transport::Output;
+
+    use super::{CoDel, DualPi2, PPB, Pie};
+    use crate::{
+        DEFAULT_ADDR, now,
+        sim::{Node as _, network::Aqm, rng::Random},
+    };
+
+    #[test]
+    fn codel_control_law() {
+        let t = now();
+        assert_eq!(CoDel::control_law(t, 1), t + CoDel::INTERVAL);
+        assert_eq!(CoDel::control_law(t, 4), t + CoDel::INTERVAL / 2);
+    }
+
+    #[test]
+    fn pie_probability() {
+        let mut pie = Pie::default();
+        for _ in 0..100 {
+            pie.update(Duration::from_millis(50));
+        }
+        assert!(pie.pi.p > 0);
+        let p = pie.pi.p;
+        for _ in 0..100 {
+            pie.update(Duration::ZERO);
+        }
+        assert!(pie.pi.p < p);
+    }
+
+    #[test]
+    fn dual_pi2_coupling() {
+        let mut dual = DualPi2::default();
+        dual.update(Duration::from_millis(40));
+        assert!(dual.pi.p > 0);
+        assert_eq!(dual.coupled(), dual.pi.p * DualPi2::K);
+        assert!(dual.classic() < dual.pi.p);
+        for _ in 0..100 {
+            dual.update(Duration::from_secs(1));
+        }
+        assert_eq!(dual.classic(), PPB);
+    }
+
+    /// A standing queue of ECN-capable datagrams gets marked by `CoDel`.
+    #[test]
+    fn codel_marks() {
+        let mut aqm = Aqm::codel(100_000, 1_000_000, Duration::ZERO);
+        let mut t = now();
+        aqm.init(Rc::new(RefCell::new(Random::default())), t);
+        // Send twice as fast as the link can carry.
+        let mut marked = 0;
+        for _ in 0..200 {
+            let d = Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, Ecn::Ect0.into(), [0; 920]);
+            let mut d = Some(d);
+            let until = t + Duration::from_millis(5);
+            loop {
+                match aqm.process(d.take(), t) {
+                    Output::Datagram(d) => marked += usize::from(Ecn::from(d.tos()) == Ecn::Ce),
+                    Output::Callback(delay) if t + delay < until => t += delay,
+                    _ => break,
+                }
+            }
+            t = until;
+        }
+        assert!(marked > 0);
+        assert_eq!(aqm.stats.dropped, 0);
+    }
+}
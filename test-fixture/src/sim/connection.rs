@@ -15,13 +15,13 @@ use std::{
 use neqo_common::{Datagram, event::Provider as _, qdebug, qinfo, qtrace};
 use neqo_crypto::AuthenticationStatus;
 use neqo_transport::{
-    Connection, ConnectionEvent, ConnectionParameters, EmptyConnectionIdGenerator, Output, State,
-    StreamId, StreamType,
+    Connection, ConnectionEvent, ConnectionParameters, EmptyConnectionIdGenerator,
+    OutgoingDatagramOutcome, Output, State, StreamId, StreamType,
 };
 
 use crate::{
     boxed,
-    sim::{self, GoalStatus, Rng},
+    sim::{self, GoalStatus, Rng, metrics::Sample},
 };
 
 /// A goal for the connection.
@@ -42,6 +42,8 @@ pub struct Node {
     c: Connection,
     setup_goals: Vec<Box<dyn Goal>>,
     goals: Vec<Box<dyn Goal>>,
+    /// The number of bytes in datagrams that were passed to the connection.
+    delivered: usize,
 }
 
 impl Node {
@@ -57,6 +59,7 @@ impl Node {
             c: crate::new_client::<EmptyConnectionIdGenerator>(params.randomize_first_pn(false)),
             setup_goals: setup.into_iter().collect(),
             goals: goals.into_iter().collect(),
+            delivered: 0,
         }
     }
 
@@ -75,6 +78,7 @@ impl Node {
             ),
             setup_goals: setup.into_iter().collect(),
             goals: goals.into_iter().collect(),
+            delivered: 0,
         }
     }
 
@@ -140,6 +144,7 @@ impl sim::Node for Node {
     }
 
     fn process(&mut self, mut d: Option<Datagram>, now: Instant) -> Output {
+        self.delivered += d.as_ref().map_or(0, Datagram::len);
         _ = self.process_goals(|goal, c| goal.process(c, now));
         loop {
             let res = self.c.process(d.take(), now);
@@ -178,6 +183,18 @@ impl sim::Node for Node {
     fn print_summary(&self, test_name: &str) {
         qinfo!("{test_name}: {:?}", self.c.stats());
     }
+
+    fn samples(&self) -> Vec<Sample> {
+        let stats = self.c.stats();
+        vec![Sample {
+            cwnd: stats.cc.cwnd,
+            rtt: stats.rtt,
+            bytes_in_flight: stats.bytes_in_flight,
+            delivered: self.delivered,
+            sent: stats.packets_tx,
+            lost: stats.lost,
+        }]
+    }
 }
 
 impl Debug for Node {
@@ -343,3 +360,134 @@ impl Goal for ReceiveData {
         }
     }
 }
+
+/// A target for a connection that involves sending a number of datagrams of the given size.
+///
+/// Datagrams are not retransmitted, so this is done once every datagram has
+/// been acknowledged or declared lost.  Datagrams are queued no faster than the
+/// connection sends them, so that none are dropped from a full queue.
+#[derive(Debug, Clone)]
+pub struct SendDatagrams {
+    remaining: usize,
+    size: usize,
+    /// The number of datagrams that the connection can queue.
+    queue: usize,
+    /// The number of datagrams that are waiting for an outcome.
+    outstanding: usize,
+    /// The number of datagrams passed to the connection.
+    sent: usize,
+    acked: usize,
+    lost: usize,
+}
+
+impl SendDatagrams {
+    #[must_use]
+    pub const fn new(count: usize, size: usize) -> Self {
+        Self {
+            remaining: count,
+            size,
+            queue: 10,
+            outstanding: 0,
+            sent: 0,
+            acked: 0,
+            lost: 0,
+        }
+    }
+
+    /// Set the number of datagrams that can be queued at once.  This needs to
+    /// be no more than [`ConnectionParameters::outgoing_datagram_queue`],
+    /// which defaults to 10.
+    #[must_use]
+    pub const fn queue(mut self, queue: usize) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    fn send(&mut self, c: &mut Connection) -> GoalStatus {
+        let stats = c.stats();
+        let queued = self
+            .sent
+            .saturating_sub(stats.frame_tx.datagram + stats.datagram_tx.dropped_too_big);
+        let space = self.queue.saturating_sub(queued);
+        let mut status = GoalStatus::Waiting;
+        for _ in 0..min(space, self.remaining) {
+            let id = u64::try_from(self.sent).unwrap();
+            c.send_datagram(vec![0; self.size], Some(id)).unwrap();
+            self.sent += 1;
+            self.remaining -= 1;
+            self.outstanding += 1;
+            status = GoalStatus::Active;
+        }
+        status
+    }
+}
+
+impl Goal for SendDatagrams {
+    fn init(&mut self, c: &mut Connection, _now: Instant) {
+        _ = self.send(c);
+    }
+
+    fn process(&mut self, c: &mut Connection, _now: Instant) -> GoalStatus {
+        self.send(c)
+    }
+
+    fn handle_event(
+        &mut self,
+        _c: &mut Connection,
+        e: &ConnectionEvent,
+        _now: Instant,
+    ) -> GoalStatus {
+        let ConnectionEvent::OutgoingDatagramOutcome { outcome, .. } = e else {
+            return GoalStatus::Waiting;
+        };
+        qtrace!("datagram outcome {outcome:?}");
+        if *outcome == OutgoingDatagramOutcome::Acked {
+            self.acked += 1;
+        } else {
+            self.lost += 1;
+        }
+        self.outstanding -= 1;
+        if self.remaining == 0 && self.outstanding == 0 {
+            qinfo!("sent datagrams: {} acked, {} lost", self.acked, self.lost);
+            GoalStatus::Done
+        } else {
+            GoalStatus::Active
+        }
+    }
+}
+
+/// Receive a prescribed number of datagrams.
+///
+/// As datagrams can be lost, this might never complete on a lossy network.
+/// Either ask for fewer datagrams than are sent, or avoid loss.
+#[derive(Debug, Clone)]
+pub struct ReceiveDatagrams {
+    remaining: usize,
+}
+
+impl ReceiveDatagrams {
+    #[must_use]
+    pub const fn new(count: usize) -> Self {
+        Self { remaining: count }
+    }
+}
+
+impl Goal for ReceiveDatagrams {
+    fn handle_event(
+        &mut self,
+        _c: &mut Connection,
+        e: &ConnectionEvent,
+        _now: Instant,
+    ) -> GoalStatus {
+        if !matches!(e, ConnectionEvent::Datagram(_)) {
+            return GoalStatus::Waiting;
+        }
+        self.remaining -= 1;
+        qtrace!("received datagram, remaining {}", self.remaining);
+        if self.remaining == 0 {
+            GoalStatus::Done
+        } else {
+            GoalStatus::Active
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{
+    fmt::{self, Debug},
+    time::Instant,
+};
+
+use neqo_common::{Datagram, qinfo, qtrace};
+use neqo_transport::Output;
+
+use super::{Node, Rng};
+
+/// A node that corrupts some datagrams by flipping random bits.
+///
+/// Corrupted datagrams should fail authentication and be discarded by the
+/// receiver, but they still have to get past the packet parser.  Flipping many
+/// bits produces something close to garbage.
+///
+/// Only datagrams sent after setup are corrupted, so that connections can be
+/// established.
+pub struct Corrupt {
+    /// The probability that a datagram is corrupted, in percent.
+    pct: u8,
+    /// The number of bits to flip in each corrupted datagram.
+    bits: usize,
+    /// Whether setup is complete.
+    active: bool,
+    rng: Option<Rng>,
+    /// The number of datagrams that were corrupted.
+    corrupted: usize,
+}
+
+impl Corrupt {
+    /// Flip one bit in `pct` percent of datagrams.
+    ///
+    /// # Panics
+    ///
+    /// When `pct` is more than 100.
+    #[must_use]
+    pub fn percentage(pct: u8) -> Self {
+        assert!(pct <= 100);
+        Self {
+            pct,
+            bits: 1,
+            active: false,
+            rng: None,
+            corrupted: 0,
+        }
+    }
+
+    /// Flip `bits` bits in each corrupted datagram.  The same bit might be
+    /// picked more than once.
+    #[must_use]
+    pub const fn bits(mut self, bits: usize) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    fn corrupt(&mut self, d: &mut Datagram) {
+        let mut rng = self.rng.as_ref().unwrap().borrow_mut();
+        if rng.random_from(0..100) >= u64::from(self.pct) {
+            return;
+        }
+        qtrace!("corrupt {}", d.len());
+        self.corrupted += 1;
+        let len = u64::try_from(d.len()).unwrap() * 8;
+        for _ in 0..self.bits {
+            let bit = usize::try_from(rng.random_from(0..len)).unwrap();
+            d[bit / 8] ^= 1 << (bit % 8);
+        }
+    }
+}
+
+impl Node for Corrupt {
+    fn init(&mut self, rng: Rng, _now: Instant) {
+        self.rng = Some(rng);
+    }
+
+    fn process(&mut self, d: Option<Datagram>, _now: Instant) -> Output {
+        d.map_or(Output::None, |mut dgram| {
+            if self.active {
+                self.corrupt(&mut dgram);
+            }
+            Output::Datagram(dgram)
+        })
+    }
+
+    fn prepare(&mut self, _now: Instant) {
+        self.active = true;
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: corrupt: {}", self.corrupted);
+    }
+}
+
+impl Debug for Corrupt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "corrupt-{}", self.pct)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use neqo_common::Datagram;
+    use neqo_transport::Output;
+
+    use crate::{
+        DEFAULT_ADDR, now,
+        sim::{Node as _, network::Corrupt, rng::Random},
+    };
+
+    fn corrupt(mut c: Corrupt) -> Vec<u8> {
+        let t = now();
+        c.init(Rc::new(RefCell::new(Random::default())), t);
+        c.prepare(t);
+        let d = Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, 0.into(), [0; 16]);
+        let Output::Datagram(d) = c.process(Some(d), t) else {
+            panic!("expected a datagram");
+        };
+        d.to_vec()
+    }
+
+    #[test]
+    fn flip_one() {
+        let d = corrupt(Corrupt::percentage(100));
+        let flipped = d.iter().map(|b| b.count_ones()).sum::<u32>();
+        assert_eq!(flipped, 1);
+    }
+
+    #[test]
+    fn none() {
+        assert_eq!(corrupt(Corrupt::percentage(0).bits(8)), [0; 16]);
+    }
+
+    /// Nothing is corrupted before setup completes.
+    #[test]
+    fn inactive() {
+        let mut c = Corrupt::percentage(100);
+        let t = now();
+        c.init(Rc::new(RefCell::new(Random::default())), t);
+        let d = Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, 0.into(), [0; 16]);
+        assert_eq!(c.process(Some(d.clone()), t), Output::Datagram(d));
+    }
+}
@@ -0,0 +1,233 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Multiple flows that share the same simulated network.
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug},
+    net::SocketAddr,
+    rc::Rc,
+    time::Instant,
+};
+
+use neqo_common::{Datagram, qinfo, qtrace};
+use neqo_transport::Output;
+
+use super::{Node, Rng, metrics::Sample};
+use crate::DEFAULT_ADDR;
+
+/// The state of one flow.
+struct Flow {
+    node: Box<dyn Node>,
+    /// When the node next needs to be processed, or `None` if it is idle.
+    wake: Option<Instant>,
+    /// The number of bytes delivered to the node since setup was completed.
+    delivered: usize,
+    /// The time of the last delivery.
+    last: Option<Instant>,
+}
+
+/// A node that holds multiple nodes, usually connections, which share the
+/// rest of the simulated network.
+///
+/// `Flows` are used in pairs, one at each end of a network, where flow `i`
+/// at one end talks to flow `i` at the other.  Flows are distinguished by
+/// their source port, which is reset to the default before datagrams are
+/// delivered, so the port isn't visible to the nodes.
+///
+/// A summary of the throughput of each flow is printed at the end of a run,
+/// along with Jain's fairness index.
+pub struct Flows {
+    nodes: Vec<Flow>,
+    /// The flow that is processed first, which rotates for fairness.
+    next: usize,
+    /// Datagrams that are ready to send.
+    queue: VecDeque<Datagram>,
+    /// When setup completed.
+    start: Option<Instant>,
+}
+
+impl Flows {
+    /// # Panics
+    ///
+    /// When there are no flows, or so many that the source port overflows.
+    #[must_use]
+    pub fn new<I: IntoIterator<Item = Box<dyn Node>>>(nodes: I) -> Self {
+        let nodes = nodes
+            .into_iter()
+            .map(|node| Flow {
+                node,
+                wake: None,
+                delivered: 0,
+                last: None,
+            })
+            .collect::<Vec<_>>();
+        assert!(!nodes.is_empty());
+        let count = u16::try_from(nodes.len()).unwrap();
+        assert!(DEFAULT_ADDR.port().checked_add(count).is_some());
+        Self {
+            nodes,
+            next: 0,
+            queue: VecDeque::new(),
+            start: None,
+        }
+    }
+
+    fn tag(d: &Datagram, flow: usize) -> Datagram {
+        let port = DEFAULT_ADDR.port() + u16::try_from(flow).unwrap();
+        let src = SocketAddr::new(d.source().ip(), port);
+        Datagram::new(src, d.destination(), d.tos(), &d[..])
+    }
+
+    fn untag(d: &Datagram) -> (usize, Datagram) {
+        let flow = usize::from(d.source().port() - DEFAULT_ADDR.port());
+        let src = SocketAddr::new(d.source().ip(), DEFAULT_ADDR.port());
+        (flow, Datagram::new(src, d.destination(), d.tos(), &d[..]))
+    }
+
+    /// Process flow `i`, queueing any datagram that it produces.
+    fn process_flow(&mut self, i: usize, d: Option<Datagram>, now: Instant) {
+        let flow = &mut self.nodes[i];
+        flow.wake = match flow.node.process(d, now) {
+            Output::Datagram(d) => {
+                self.queue.push_back(Self::tag(&d, i));
+                Some(now)
+            }
+            Output::Callback(delay) => Some(now + delay),
+            // The node waits for a datagram, like a server before the client
+            // has sent anything.
+            Output::None => None,
+        };
+    }
+
+    /// The throughput of each flow, in bytes per second, from the end of setup
+    /// to the last delivery.
+    fn throughput(&self) -> Vec<f64> {
+        self.nodes
+            .iter()
+            .map(|f| match (self.start, f.last) {
+                (Some(start), Some(last)) if last > start => {
+                    #[expect(clippy::cast_precision_loss, reason = "This is OK.")]
+                    let bytes = f.delivered as f64;
+                    bytes / (last - start).as_secs_f64()
+                }
+                _ => 0.0,
+            })
+            .collect()
+    }
+
+    /// Jain's fairness index over the throughput of each flow.  This is 1 when
+    /// all flows get the same share, down to `1 / n` when one flow gets
+    /// everything.
+    fn fairness(throughput: &[f64]) -> f64 {
+        let sum = throughput.iter().sum::<f64>();
+        let squares = throughput.iter().map(|x| x * x).sum::<f64>();
+        if squares > 0.0 {
+            #[expect(clippy::cast_precision_loss, reason = "This is OK.")]
+            let n = throughput.len() as f64;
+            sum.powi(2) / (n * squares)
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Node for Flows {
+    fn init(&mut self, rng: Rng, now: Instant) {
+        for f in &mut self.nodes {
+            f.node.init(Rc::clone(&rng), now);
+            f.wake = Some(now);
+        }
+    }
+
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        if let Some(d) = d {
+            let (i, d) = Self::untag(&d);
+            qtrace!("flow {i} <- {}", d.len());
+            let flow = &mut self.nodes[i];
+            if self.start.is_some() {
+                flow.delivered += d.len();
+                flow.last = Some(now);
+            }
+            self.process_flow(i, Some(d), now);
+        }
+
+        // Once earlier datagrams are gone, give every flow that is ready a
+        // chance to send, starting from a different flow each time.
+        if self.queue.is_empty() {
+            let n = self.nodes.len();
+            for i in (self.next..n).chain(0..self.next) {
+                if self.nodes[i].wake.is_some_and(|t| t <= now) {
+                    self.process_flow(i, None, now);
+                }
+            }
+            self.next = (self.next + 1) % n;
+        }
+
+        if let Some(d) = self.queue.pop_front() {
+            Output::Datagram(d)
+        } else if let Some(t) = self.nodes.iter().filter_map(|f| f.wake).min() {
+            Output::Callback(t - now)
+        } else {
+            Output::None
+        }
+    }
+
+    fn prepare(&mut self, now: Instant) {
+        for f in &mut self.nodes {
+            f.node.prepare(now);
+            f.wake = Some(now);
+        }
+        self.start = Some(now);
+    }
+
+    fn done(&self) -> bool {
+        self.queue.is_empty() && self.nodes.iter().all(|f| f.node.done())
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        let throughput = self.throughput();
+        for (i, (f, rate)) in self.nodes.iter().zip(&throughput).enumerate() {
+            qinfo!(
+                "{test_name}: flow {i}: {} bytes, {:.3} Mbit/s",
+                f.delivered,
+                rate * 8.0 / 1_000_000.0
+            );
+            f.node.print_summary(test_name);
+        }
+        qinfo!(
+            "{test_name}: fairness index {:.3}",
+            Self::fairness(&throughput)
+        );
+    }
+
+    fn samples(&self) -> Vec<Sample> {
+        self.nodes
+            .iter()
+            .map(|f| f.node.samples().first().copied().unwrap_or_default())
+            .collect()
+    }
+}
+
+impl Debug for Flows {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "flows-{}", self.nodes.len())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::Flows;
+
+    #[test]
+    fn fairness() {
+        assert!((Flows::fairness(&[5.0, 5.0]) - 1.0).abs() < f64::EPSILON);
+        assert!((Flows::fairness(&[10.0, 0.0]) - 0.5).abs() < f64::EPSILON);
+        assert!((Flows::fairness(&[0.0, 0.0]) - 1.0).abs() < f64::EPSILON);
+    }
+}
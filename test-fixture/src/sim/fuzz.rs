@@ -0,0 +1,260 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Running a scenario over many randomized networks.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    time::Duration,
+};
+
+use neqo_common::{Encoder, hex, qinfo};
+
+use super::{
+    Node, Simulator,
+    network::{GilbertElliott, Jitter},
+    rng::Random,
+};
+
+/// The characteristics of one direction of a randomized network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    /// The smallest one-way delay.
+    pub delay: Duration,
+    /// The largest additional delay for each datagram.
+    pub jitter: Duration,
+    /// The percentage of datagrams that are reordered.
+    pub reorder: u8,
+    /// The percentage of datagrams that are dropped.
+    pub loss: u8,
+    /// The average number of datagrams in each loss burst.
+    pub burst: u64,
+}
+
+impl Network {
+    const MAX_DELAY_MS: u64 = 200;
+    const MAX_REORDER: u8 = 5;
+    const MAX_LOSS: u8 = 5;
+    const MAX_BURST: u64 = 4;
+
+    fn random(rng: &mut Random) -> Self {
+        let delay = rng.random_from(1..Self::MAX_DELAY_MS);
+        let pct = |rng: &mut Random, max: u8| {
+            u8::try_from(rng.random_from(0..u64::from(max) + 1)).unwrap()
+        };
+        Self {
+            delay: Duration::from_millis(delay),
+            jitter: Duration::from_millis(rng.random_from(0..delay / 2 + 1)),
+            reorder: pct(rng, Self::MAX_REORDER),
+            loss: pct(rng, Self::MAX_LOSS),
+            burst: rng.random_from(1..Self::MAX_BURST + 1),
+        }
+    }
+
+    /// The nodes for this direction of the network.
+    ///
+    /// Loss comes in bursts from a [`GilbertElliott`] model, which drops half of
+    /// the datagrams in the bad state.  Dropping all of them would risk losing
+    /// everything that is sent until a connection times out.
+    #[must_use]
+    pub fn nodes(&self) -> Vec<Box<dyn Node>> {
+        const PPM: u64 = 1_000_000;
+        let bad = 2 * u64::from(self.loss);
+        let r = PPM / self.burst;
+        let p = r * bad / (100 - bad);
+        let jitter =
+            Jitter::new(self.delay..self.delay + self.jitter).reorder(self.reorder, self.delay / 2);
+        vec![
+            Box::new(jitter),
+            Box::new(GilbertElliott::new(p, r, 0, PPM / 2)),
+        ]
+    }
+
+    /// Simpler networks, which are tried in turn when shrinking a failure.
+    fn shrink(&self) -> Vec<Self> {
+        let mut simpler = Vec::new();
+        let mut add = |f: &dyn Fn(&mut Self)| {
+            let mut n = self.clone();
+            f(&mut n);
+            if n != *self {
+                simpler.push(n);
+            }
+        };
+        add(&|n| n.loss = 0);
+        add(&|n| n.reorder = 0);
+        add(&|n| n.jitter = Duration::ZERO);
+        add(&|n| n.burst = 1);
+        add(&|n| n.loss /= 2);
+        add(&|n| n.reorder /= 2);
+        add(&|n| n.jitter /= 2);
+        add(&|n| n.delay = (n.delay / 2).max(Duration::from_millis(1)));
+        simpler
+    }
+}
+
+/// Runs a scenario over many randomized networks, each with its own seed.
+///
+/// When a run fails, the network is shrunk to the simplest one that still
+/// fails with the same seed, and the seed and networks are reported.  Setting
+/// the `SIMULATION_SEED` environment variable runs only that seed.
+pub struct Fuzz {
+    name: String,
+    runs: usize,
+}
+
+impl Fuzz {
+    #[must_use]
+    pub fn new<A: AsRef<str>>(name: A, runs: usize) -> Self {
+        Self {
+            name: String::from(name.as_ref()),
+            runs,
+        }
+    }
+
+    /// Run `scenario` for each seed.  The function receives a network for
+    /// each direction, and returns the nodes for the simulation.
+    ///
+    /// # Panics
+    ///
+    /// When the scenario fails for any seed.
+    pub fn run<F>(&self, scenario: F)
+    where
+        F: Fn(&Network, &Network) -> Vec<Box<dyn Node>>,
+    {
+        crate::fixture_init();
+        let seeds = std::env::var("SIMULATION_SEED").map_or_else(
+            |_| {
+                let mut rng = Random::default();
+                (0..self.runs)
+                    .map(|_| {
+                        let mut enc = Encoder::default();
+                        for _ in 0..4 {
+                            enc.encode_uint(8, rng.random());
+                        }
+                        <[u8; 32]>::try_from(enc.as_ref()).unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |seed| vec![<[u8; 32]>::try_from(Encoder::from_hex(seed).as_ref()).unwrap()],
+        );
+
+        for seed in seeds {
+            let mut rng = Random::new(&seed);
+            let nets = (Network::random(&mut rng), Network::random(&mut rng));
+            if self.attempt(&seed, &nets, &scenario) {
+                continue;
+            }
+            let smallest = self.shrink(&seed, nets.clone(), &scenario);
+            panic!(
+                "{}: failed with seed {}\n  networks: {nets:?}\n  smallest failing networks: {smallest:?}",
+                self.name,
+                hex(seed)
+            );
+        }
+    }
+
+    /// Run once, returning `true` if the run succeeded.
+    fn attempt<F>(&self, seed: &[u8; 32], nets: &(Network, Network), scenario: &F) -> bool
+    where
+        F: Fn(&Network, &Network) -> Vec<Box<dyn Node>>,
+    {
+        qinfo!("{}: {nets:?}", self.name);
+        catch_unwind(AssertUnwindSafe(|| {
+            let mut sim = Simulator::new(&self.name, scenario(&nets.0, &nets.1));
+            sim.seed_str(hex(seed));
+            sim.run();
+        }))
+        .is_ok()
+    }
+
+    /// Find the simplest networks that still fail with `seed`.
+    fn shrink<F>(
+        &self,
+        seed: &[u8; 32],
+        mut nets: (Network, Network),
+        scenario: &F,
+    ) -> (Network, Network)
+    where
+        F: Fn(&Network, &Network) -> Vec<Box<dyn Node>>,
+    {
+        'outer: loop {
+            let candidates = nets
+                .0
+                .shrink()
+                .into_iter()
+                .map(|n| (n, nets.1.clone()))
+                .chain(nets.1.shrink().into_iter().map(|n| (nets.0.clone(), n)))
+                .collect::<Vec<_>>();
+            for c in candidates {
+                if !self.attempt(seed, &c, scenario) {
+                    nets = c;
+                    continue 'outer;
+                }
+            }
+            return nets;
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::{
+        cell::Cell,
+        panic::{AssertUnwindSafe, catch_unwind},
+    };
+
+    use super::{Fuzz, Network};
+    use crate::{
+        boxed,
+        sim::{
+            connection::{Node, ReceiveData, SendData},
+            rng::Random,
+        },
+    };
+
+    #[test]
+    fn shrink_to_nothing() {
+        let mut n = Network::random(&mut Random::new(&[3; 32]));
+        while let Some(s) = n.shrink().into_iter().next() {
+            n = s;
+        }
+        assert_eq!(n.loss, 0);
+        assert_eq!(n.reorder, 0);
+        assert!(n.jitter.is_zero());
+        assert_eq!(n.burst, 1);
+        assert_eq!(n.delay.as_millis(), 1);
+    }
+
+    /// A failure that depends on loss is shrunk to one percent of loss in one
+    /// direction, with everything else removed.
+    #[test]
+    fn shrink_failure() {
+        let runs = Cell::new(0);
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            Fuzz::new("shrink_failure", 20).run(|up, down| {
+                runs.set(runs.get() + 1);
+                assert!(up.loss == 0 && down.loss == 0);
+                let mut nodes = boxed![Node::default_client(boxed![SendData::new(1_000)])];
+                nodes.extend(up.nodes());
+                nodes.push(Box::new(Node::default_server(boxed![ReceiveData::new(
+                    1_000
+                )])));
+                nodes.extend(down.nodes());
+                nodes
+            });
+        }));
+        let msg = *res.unwrap_err().downcast::<String>().unwrap();
+        assert!(msg.contains("failed with seed"), "{msg}");
+        let (_, smallest) = msg.split_once("smallest failing networks: ").unwrap();
+        assert_eq!(smallest.matches("loss: 1,").count(), 1, "{msg}");
+        assert_eq!(smallest.matches("loss: 0,").count(), 1, "{msg}");
+        assert_eq!(smallest.matches("jitter: 0ns").count(), 2, "{msg}");
+        assert!(runs.get() > 1);
+    }
+}
@@ -0,0 +1,226 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{
+    fmt::{self, Debug, Display},
+    time::Instant,
+};
+
+use neqo_common::{Datagram, qinfo, qtrace};
+use neqo_transport::Output;
+
+use super::{Node, Rng};
+
+/// The probabilities of [`GilbertElliott`] are in parts per million.
+const PPM: u64 = 1_000_000;
+
+#[derive(Debug, Default)]
+struct Stats {
+    /// The number of datagrams passed on.
+    delivered: usize,
+    /// The number of datagrams dropped.
+    dropped: usize,
+    /// The number of times that a datagram was dropped after one was delivered.
+    bursts: usize,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dropped {} of {} in {} bursts",
+            self.dropped,
+            self.delivered + self.dropped,
+            self.bursts
+        )
+    }
+}
+
+/// A dropper that follows a two-state Gilbert-Elliott model, which produces
+/// bursts of loss.
+///
+/// The link is either good or bad, and each state has its own probability of
+/// dropping a datagram.  Before each datagram, the link changes to the other
+/// state with a probability that depends on the current state.  The link starts
+/// out good.
+///
+/// All probabilities are in parts per million.
+pub struct GilbertElliott {
+    /// The probability of changing from the good to the bad state.
+    p: u64,
+    /// The probability of changing from the bad to the good state.
+    r: u64,
+    /// The probability of dropping a datagram in the good state.
+    loss_good: u64,
+    /// The probability of dropping a datagram in the bad state.
+    loss_bad: u64,
+    bad: bool,
+    /// Whether the last datagram was dropped.
+    last_dropped: bool,
+    rng: Option<Rng>,
+    stats: Stats,
+}
+
+impl GilbertElliott {
+    /// Make a new dropper that changes from good to bad with probability `p`
+    /// and back with probability `r`, and drops datagrams with probability
+    /// `loss_good` and `loss_bad` in the respective state.
+    ///
+    /// # Panics
+    ///
+    /// When any probability is more than one million.
+    #[must_use]
+    pub const fn new(p: u64, r: u64, loss_good: u64, loss_bad: u64) -> Self {
+        assert!(p <= PPM && r <= PPM && loss_good <= PPM && loss_bad <= PPM);
+        Self {
+            p,
+            r,
+            loss_good,
+            loss_bad,
+            bad: false,
+            last_dropped: false,
+            rng: None,
+            stats: Stats {
+                delivered: 0,
+                dropped: 0,
+                bursts: 0,
+            },
+        }
+    }
+
+    /// Drop `pct` percent of datagrams, in bursts of `burst` datagrams on
+    /// average.
+    ///
+    /// This is the simple Gilbert model, which drops all datagrams in the bad
+    /// state and none in the good state.
+    ///
+    /// # Panics
+    ///
+    /// When `pct` is 100 or more, or `burst` is zero.
+    #[must_use]
+    pub const fn bursts(pct: u8, burst: u64) -> Self {
+        assert!(pct < 100 && burst > 0);
+        // The mean time in the bad state is `1 / r`.  The share of time in the
+        // bad state is `p / (p + r)`, which is the loss rate.
+        let r = PPM / burst;
+        let pct = pct as u64;
+        let p = r * pct / (100 - pct);
+        Self::new(p, r, 0, PPM)
+    }
+
+    fn happens(&self, probability: u64) -> bool {
+        let mut rng = self.rng.as_ref().unwrap().borrow_mut();
+        rng.random_from(0..PPM) < probability
+    }
+
+    /// Determine whether or not to drop a datagram.
+    ///
+    /// # Panics
+    ///
+    /// When this is invoked after test configuration has been torn down,
+    /// such that the RNG is no longer available.
+    #[must_use]
+    pub fn drop(&mut self) -> bool {
+        let change = if self.bad { self.r } else { self.p };
+        if self.happens(change) {
+            self.bad = !self.bad;
+        }
+        let drop = self.happens(if self.bad {
+            self.loss_bad
+        } else {
+            self.loss_good
+        });
+
+        if drop {
+            self.stats.dropped += 1;
+            self.stats.bursts += usize::from(!self.last_dropped);
+        } else {
+            self.stats.delivered += 1;
+        }
+        self.last_dropped = drop;
+        drop
+    }
+}
+
+impl Node for GilbertElliott {
+    fn init(&mut self, rng: Rng, _now: Instant) {
+        self.rng = Some(rng);
+    }
+
+    // Pass any datagram provided directly out, but drop some of them.
+    fn process(&mut self, d: Option<Datagram>, _now: Instant) -> Output {
+        d.map_or(Output::None, |dgram| {
+            if self.drop() {
+                qtrace!(
+                    "drop {} in {} state",
+                    dgram.len(),
+                    if self.bad { "bad" } else { "good" }
+                );
+                Output::None
+            } else {
+                Output::Datagram(dgram)
+            }
+        })
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: gilbert-elliott: {stats}", stats = self.stats);
+    }
+}
+
+impl Debug for GilbertElliott {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("gilbert-elliott")
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        now,
+        sim::{Node as _, network::GilbertElliott, rng::Random},
+    };
+
+    fn run(mut ge: GilbertElliott, trials: usize) -> GilbertElliott {
+        ge.init(Rc::new(RefCell::new(Random::new(&[7; 32]))), now());
+        for _ in 0..trials {
+            _ = ge.drop();
+        }
+        ge
+    }
+
+    /// The Gilbert model drops the expected share of datagrams, in bursts of
+    /// the expected length.
+    #[test]
+    fn bursts() {
+        let ge = run(GilbertElliott::bursts(5, 4), 100_000);
+        assert!((4_500..=5_500).contains(&ge.stats.dropped));
+        let burst = ge.stats.dropped / ge.stats.bursts;
+        assert!((3..=5).contains(&burst));
+    }
+
+    /// Without changing state, the loss is random.
+    #[test]
+    fn random() {
+        let ge = run(GilbertElliott::new(0, 0, 100_000, 0), 10_000);
+        assert!((900..=1_100).contains(&ge.stats.dropped));
+        assert!(!ge.bad);
+    }
+
+    /// The same seed gives the same pattern of loss.
+    #[test]
+    fn deterministic() {
+        let a = run(GilbertElliott::bursts(10, 3), 1_000);
+        let b = run(GilbertElliott::bursts(10, 3), 1_000);
+        assert_eq!(a.stats.dropped, b.stats.dropped);
+        assert_eq!(a.stats.bursts, b.stats.bursts);
+    }
+}
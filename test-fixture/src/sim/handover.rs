@@ -0,0 +1,255 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fmt::{self, Debug},
+    net::SocketAddr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use neqo_common::{Datagram, qinfo};
+use neqo_transport::Output;
+
+use super::{Node, Rng};
+
+/// Addresses that are rewritten after the handover.
+#[derive(Debug, Default, Clone, Copy)]
+struct Rebind {
+    source: Option<SocketAddr>,
+    destination: Option<SocketAddr>,
+}
+
+impl Rebind {
+    fn apply(self, d: Datagram) -> Datagram {
+        if self.source.is_none() && self.destination.is_none() {
+            return d;
+        }
+        Datagram::new(
+            self.source.unwrap_or_else(|| d.source()),
+            self.destination.unwrap_or_else(|| d.destination()),
+            d.tos(),
+            &d[..],
+        )
+    }
+}
+
+/// A node that changes the path part way through a simulation.
+///
+/// Until the handover, datagrams are passed to the first of two paths; after
+/// it, new datagrams take the second path.  Datagrams that are still on the
+/// first path are delivered as normal, so the paths can be used to change the
+/// delay or rate of a link.  The handover can also rewrite addresses, which
+/// looks like a NAT rebinding to the endpoints.  As the client and server in
+/// simulations often share an address, this needs a node in each direction:
+/// one that rewrites the source address of datagrams toward the server and one
+/// that restores the destination address of datagrams toward the client.
+///
+/// The handover happens a fixed time after setup completes, so the handshake
+/// always uses the first path.
+pub struct Handover {
+    /// The time after setup at which the handover happens.
+    at: Duration,
+    /// The paths before and after the handover, if they are changed.
+    paths: Option<[Box<dyn Node>; 2]>,
+    rebind: Rebind,
+    /// When setup completed.
+    start: Option<Instant>,
+    /// When the handover happened.
+    switched: Option<Instant>,
+    /// The number of datagrams that took each path.
+    datagrams: [usize; 2],
+}
+
+impl Handover {
+    /// Hand over `at` this time after setup completes.  Without paths or
+    /// rebinding, this passes datagrams through unchanged.
+    #[must_use]
+    pub fn new(at: Duration) -> Self {
+        Self {
+            at,
+            paths: None,
+            rebind: Rebind::default(),
+            start: None,
+            switched: None,
+            datagrams: [0; 2],
+        }
+    }
+
+    /// Send datagrams over `before` until the handover and `after` from then on.
+    #[must_use]
+    pub fn paths<B: Node + 'static, A: Node + 'static>(mut self, before: B, after: A) -> Self {
+        self.paths = Some([Box::new(before), Box::new(after)]);
+        self
+    }
+
+    /// After the handover, set the source address of all datagrams to `addr`.
+    #[must_use]
+    pub const fn rebind_source(mut self, addr: SocketAddr) -> Self {
+        self.rebind.source = Some(addr);
+        self
+    }
+
+    /// After the handover, set the destination address of all datagrams to `addr`.
+    #[must_use]
+    pub const fn rebind_destination(mut self, addr: SocketAddr) -> Self {
+        self.rebind.destination = Some(addr);
+        self
+    }
+
+    fn maybe_switch(&mut self, now: Instant) {
+        if self.switched.is_none() && self.start.is_some_and(|start| now >= start + self.at) {
+            qinfo!("handover after {:?}", self.at);
+            self.switched = Some(now);
+        }
+    }
+}
+
+impl Node for Handover {
+    fn init(&mut self, rng: Rng, now: Instant) {
+        for p in self.paths.iter_mut().flatten() {
+            p.init(Rc::clone(&rng), now);
+        }
+    }
+
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        self.maybe_switch(now);
+        let current = usize::from(self.switched.is_some());
+        if d.is_some() {
+            self.datagrams[current] += 1;
+        }
+        // Addresses are only rewritten after the handover.
+        let rebind = self.switched.map(|_| self.rebind);
+        let output = |d: Datagram| Output::Datagram(rebind.map_or(d, |r| r.apply(d)));
+        let Some(paths) = &mut self.paths else {
+            return d.map_or(Output::None, output);
+        };
+
+        // The current path takes any new datagram, then the old path drains.
+        let mut d = d;
+        let mut wait: Option<Duration> = None;
+        for i in [current, 1 - current] {
+            match paths[i].process(d.take(), now) {
+                Output::Datagram(d) => return output(d),
+                Output::Callback(t) => wait = Some(wait.map_or(t, |w| w.min(t))),
+                Output::None => (),
+            }
+        }
+        wait.map_or(Output::None, Output::Callback)
+    }
+
+    fn prepare(&mut self, now: Instant) {
+        self.start = Some(now);
+        for p in self.paths.iter_mut().flatten() {
+            p.prepare(now);
+        }
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!(
+            "{test_name}: handover: at {:?}, {} datagrams before, {} after",
+            self.switched.zip(self.start).map(|(s, t)| s - t),
+            self.datagrams[0],
+            self.datagrams[1]
+        );
+        for p in self.paths.iter().flatten() {
+            p.print_summary(test_name);
+        }
+    }
+}
+
+impl Debug for Handover {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "handover-{:?}", self.at)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{
+        cell::RefCell,
+        net::SocketAddr,
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use neqo_common::Datagram;
+    use neqo_transport::Output;
+
+    use crate::{
+        DEFAULT_ADDR, now,
+        sim::{
+            Node as _,
+            network::{Delay, Handover},
+            rng::Random,
+        },
+    };
+
+    const AT: Duration = Duration::from_millis(100);
+    const REBOUND: SocketAddr = SocketAddr::new(DEFAULT_ADDR.ip(), DEFAULT_ADDR.port() + 1);
+
+    fn start(mut handover: Handover) -> (Handover, Instant) {
+        let t = now();
+        handover.init(Rc::new(RefCell::new(Random::default())), t);
+        handover.prepare(t);
+        (handover, t)
+    }
+
+    fn dgram(i: u8) -> Datagram {
+        Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, 0.into(), [i])
+    }
+
+    #[test]
+    fn rebind() {
+        let (mut handover, t) = start(
+            Handover::new(AT)
+                .rebind_source(REBOUND)
+                .rebind_destination(DEFAULT_ADDR),
+        );
+        let Output::Datagram(d) = handover.process(Some(dgram(0)), t) else {
+            panic!("expected a datagram");
+        };
+        assert_eq!(d.source(), DEFAULT_ADDR);
+        let Output::Datagram(d) = handover.process(Some(dgram(1)), t + AT) else {
+            panic!("expected a datagram");
+        };
+        assert_eq!(d.source(), REBOUND);
+        assert_eq!(d.destination(), DEFAULT_ADDR);
+        assert_eq!(&d[..], [1]);
+    }
+
+    /// Datagrams on the old path are still delivered after the handover, even
+    /// when the new path is faster.
+    #[test]
+    fn change_path() {
+        let slow = Duration::from_millis(50);
+        let fast = Duration::from_millis(10);
+        let (mut handover, t) = start(Handover::new(AT).paths(Delay::new(slow), Delay::new(fast)));
+        let sent = t + AT - Duration::from_millis(1);
+        assert_eq!(
+            handover.process(Some(dgram(0)), sent),
+            Output::Callback(slow)
+        );
+        assert_eq!(
+            handover.process(Some(dgram(1)), t + AT),
+            Output::Callback(fast)
+        );
+        let Output::Datagram(d) = handover.process(None, t + AT + fast) else {
+            panic!("expected a datagram");
+        };
+        assert_eq!(&d[..], [1]);
+        assert_eq!(
+            handover.process(None, t + AT + fast),
+            Output::Callback(sent + slow - (t + AT + fast))
+        );
+        let Output::Datagram(d) = handover.process(None, sent + slow) else {
+            panic!("expected a datagram");
+        };
+        assert_eq!(&d[..], [0]);
+        assert_eq!(handover.process(None, sent + slow), Output::None);
+    }
+}
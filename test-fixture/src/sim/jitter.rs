@@ -0,0 +1,177 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+    ops::Range,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use neqo_common::{Datagram, qinfo, qtrace};
+use neqo_transport::Output;
+
+use super::{Node, Rng, delay::RandomDelayIter};
+
+/// A delay that varies for each datagram, which can also reorder datagrams.
+///
+/// Each datagram is delayed by a random amount within the given range.  Unlike
+/// [`super::network::RandomDelay`], datagrams leave in the order in which they
+/// arrived, unless they are picked for reordering.  A datagram that is picked
+/// is held back for longer, so that the datagrams that follow overtake it.
+pub struct Jitter {
+    random: RandomDelayIter,
+    rng: Option<Rng>,
+    /// The probability that a datagram is reordered, in percent.
+    reorder: u8,
+    /// How much longer a reordered datagram is held.
+    hold: Duration,
+    /// The time at which the last datagram that was not reordered leaves.
+    last: Option<Instant>,
+    queue: BTreeMap<Instant, Datagram>,
+    /// The number of datagrams that were reordered.
+    reordered: usize,
+}
+
+impl Jitter {
+    /// Delay each datagram by an amount within `bounds`, without reordering.
+    #[must_use]
+    pub fn new(bounds: Range<Duration>) -> Self {
+        Self {
+            random: RandomDelayIter::new(bounds),
+            rng: None,
+            reorder: 0,
+            hold: Duration::ZERO,
+            last: None,
+            queue: BTreeMap::default(),
+            reordered: 0,
+        }
+    }
+
+    /// Hold back `pct` percent of datagrams for an extra `hold`, so that they
+    /// arrive after datagrams that were sent later.
+    ///
+    /// # Panics
+    ///
+    /// When `pct` is more than 100.
+    #[must_use]
+    pub fn reorder(mut self, pct: u8, hold: Duration) -> Self {
+        assert!(pct <= 100);
+        self.reorder = pct;
+        self.hold = hold;
+        self
+    }
+
+    fn reordered(&self) -> bool {
+        let mut rng = self.rng.as_ref().unwrap().borrow_mut();
+        rng.random_from(0..100) < u64::from(self.reorder)
+    }
+
+    fn insert(&mut self, d: Datagram, now: Instant) {
+        let mut t = now + self.random.next();
+        if self.reordered() {
+            qtrace!("reorder {}", d.len());
+            self.reordered += 1;
+            t += self.hold;
+        } else {
+            // Keep the order by never leaving before the previous datagram.
+            t = self.last.map_or(t, |last| t.max(last));
+            self.last = Some(t);
+        }
+        while self.queue.contains_key(&t) {
+            t += Duration::from_nanos(1);
+        }
+        self.queue.insert(t, d);
+    }
+}
+
+impl Node for Jitter {
+    fn init(&mut self, rng: Rng, _now: Instant) {
+        self.rng = Some(Rc::clone(&rng));
+        self.random.set_rng(rng);
+    }
+
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        if let Some(dgram) = d {
+            self.insert(dgram, now);
+        }
+        if let Some((&k, _)) = self.queue.range(..=now).next() {
+            Output::Datagram(self.queue.remove(&k).unwrap())
+        } else if let Some(&t) = self.queue.keys().next() {
+            Output::Callback(t - now)
+        } else {
+            Output::None
+        }
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: jitter: reordered {}", self.reordered);
+    }
+}
+
+impl Debug for Jitter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("jitter")
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use neqo_common::Datagram;
+    use neqo_transport::Output;
+
+    use crate::{
+        DEFAULT_ADDR, now,
+        sim::{Node as _, network::Jitter, rng::Random},
+    };
+
+    /// Send `count` datagrams, at most one each millisecond, and return the order in
+    /// which they come out.
+    fn order(mut jitter: Jitter, count: u8) -> Vec<u8> {
+        let mut t = now();
+        jitter.init(Rc::new(RefCell::new(Random::new(&[7; 32]))), t);
+        let mut out = Vec::new();
+        let mut input =
+            (0..count).map(|i| Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, 0.into(), [i]));
+        let mut d = input.next();
+        loop {
+            match jitter.process(d.take(), t) {
+                Output::Datagram(d) => out.push(d[0]),
+                Output::Callback(delay) => {
+                    d = input.next();
+                    t += if d.is_some() {
+                        delay.min(Duration::from_millis(1))
+                    } else {
+                        delay
+                    };
+                }
+                Output::None => return out,
+            }
+        }
+    }
+
+    #[test]
+    fn in_order() {
+        let out = order(Jitter::new(Duration::ZERO..Duration::from_millis(20)), 100);
+        assert_eq!(out, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reorder() {
+        let jitter = Jitter::new(Duration::from_millis(5)..Duration::from_millis(10))
+            .reorder(10, Duration::from_millis(20));
+        let out = order(jitter, 100);
+        assert_eq!(out.len(), 100);
+        let late = out.iter().zip(&out[1..]).filter(|(a, b)| a > b).count();
+        assert!((3..20).contains(&late), "{late} out of order");
+    }
+}
@@ -0,0 +1,303 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Metrics that are sampled from simulator nodes at a fixed interval.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use super::NodeHolder;
+
+/// The state of a node at a point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// The congestion window, if one has been established.
+    pub cwnd: Option<usize>,
+    /// The estimated round-trip time.
+    pub rtt: Duration,
+    /// The number of bytes in flight.
+    pub bytes_in_flight: usize,
+    /// The total number of bytes that have been delivered to the node.
+    pub delivered: usize,
+    /// The total number of packets that the node has sent.
+    pub sent: usize,
+    /// The total number of packets that the node has declared lost.
+    pub lost: usize,
+}
+
+/// The samples taken from a single node.
+#[derive(Debug, Clone)]
+pub struct Series {
+    /// The position of the node in the simulator.
+    pub node: usize,
+    /// The position of the sample among those that the node reports, which
+    /// identifies a flow for nodes that carry more than one.
+    pub flow: usize,
+    /// A description of the node.
+    pub name: String,
+    /// The samples, each with the simulated time since the start of the run.
+    pub samples: Vec<(Duration, Sample)>,
+}
+
+/// The samples taken from all nodes that report them during a run.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// One series for each node that produced samples, in the order of the
+    /// nodes.
+    pub series: Vec<Series>,
+}
+
+impl Metrics {
+    pub(super) fn record<F>(
+        &mut self,
+        (node, flow): (usize, usize),
+        name: F,
+        t: Duration,
+        sample: &Sample,
+    ) where
+        F: FnOnce() -> String,
+    {
+        let i = match self
+            .series
+            .binary_search_by_key(&(node, flow), |s| (s.node, s.flow))
+        {
+            Ok(i) => i,
+            Err(i) => {
+                self.series.insert(
+                    i,
+                    Series {
+                        node,
+                        flow,
+                        name: name(),
+                        samples: Vec::new(),
+                    },
+                );
+                i
+            }
+        };
+        self.series[i].samples.push((t, *sample));
+    }
+
+    /// The series for the node at position `node` in the simulator.  For
+    /// nodes that carry multiple flows, this is the first flow.
+    #[must_use]
+    pub fn node(&self, node: usize) -> Option<&Series> {
+        self.flows(node).next()
+    }
+
+    /// The series for each flow of the node at position `node`.
+    pub fn flows(&self, node: usize) -> impl Iterator<Item = &Series> {
+        self.series.iter().filter(move |s| s.node == node)
+    }
+
+    /// Write the samples as CSV, with one row for each sample.
+    ///
+    /// # Errors
+    ///
+    /// When writing fails.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(
+            w,
+            "node,flow,name,time_us,cwnd,rtt_us,bytes_in_flight,delivered,sent,lost"
+        )?;
+        for series in &self.series {
+            for (t, s) in &series.samples {
+                writeln!(
+                    w,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    series.node,
+                    series.flow,
+                    series.name,
+                    t.as_micros(),
+                    s.cwnd.map(|c| c.to_string()).unwrap_or_default(),
+                    s.rtt.as_micros(),
+                    s.bytes_in_flight,
+                    s.delivered,
+                    s.sent,
+                    s.lost
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the samples as JSON, with an object for each node.
+    ///
+    /// # Errors
+    ///
+    /// When writing fails.
+    pub fn write_json<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "[")?;
+        for (i, series) in self.series.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(
+                w,
+                "{sep}\n  {{\"node\":{},\"flow\":{},\"name\":",
+                series.node, series.flow
+            )?;
+            write_json_string(&mut w, &series.name)?;
+            write!(w, ",\"samples\":[")?;
+            for (j, (t, s)) in series.samples.iter().enumerate() {
+                let sep = if j == 0 { "" } else { "," };
+                write!(
+                    w,
+                    "{sep}\n    {{\"time_us\":{},\"cwnd\":{},\"rtt_us\":{},\"bytes_in_flight\":{},\"delivered\":{},\"sent\":{},\"lost\":{}}}",
+                    t.as_micros(),
+                    s.cwnd
+                        .map_or_else(|| String::from("null"), |c| c.to_string()),
+                    s.rtt.as_micros(),
+                    s.bytes_in_flight,
+                    s.delivered,
+                    s.sent,
+                    s.lost
+                )?;
+            }
+            write!(w, "\n  ]}}")?;
+        }
+        writeln!(w, "\n]")
+    }
+}
+
+/// Write `s` as a quoted JSON string.
+fn write_json_string<W: Write>(mut w: W, s: &str) -> io::Result<()> {
+    write!(w, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(w, "\\\"")?,
+            '\\' => write!(w, "\\\\")?,
+            '\n' => write!(w, "\\n")?,
+            '\r' => write!(w, "\\r")?,
+            '\t' => write!(w, "\\t")?,
+            c if c.is_control() => write!(w, "\\u{:04x}", u32::from(c))?,
+            c => write!(w, "{c}")?,
+        }
+    }
+    write!(w, "\"")
+}
+
+/// Takes samples from nodes at a fixed interval.
+pub(super) struct Sampler {
+    interval: Duration,
+    start: Instant,
+    next: Instant,
+    metrics: Metrics,
+}
+
+impl Sampler {
+    pub(super) fn new(interval: Duration, now: Instant) -> Self {
+        assert!(
+            !interval.is_zero(),
+            "the sampling interval must not be zero"
+        );
+        Self {
+            interval,
+            start: now,
+            next: now,
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Take samples for each interval that starts before `until`.  Nodes
+    /// don't change state between events, so their current state applies to
+    /// all of those.
+    pub(super) fn sample(&mut self, nodes: &[NodeHolder], until: Instant) {
+        while self.next < until {
+            let t = self.next - self.start;
+            for (i, n) in nodes.iter().enumerate() {
+                for (flow, sample) in n.samples().iter().enumerate() {
+                    self.metrics
+                        .record((i, flow), || format!("{:?}", n.node), t, sample);
+                }
+            }
+            self.next += self.interval;
+        }
+    }
+
+    /// Take the remaining samples, up to and including `end`.
+    pub(super) fn finish(mut self, nodes: &[NodeHolder], end: Instant) -> Metrics {
+        self.sample(nodes, end + Duration::from_nanos(1));
+        self.metrics
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Metrics, Sample, write_json_string};
+
+    fn metrics() -> Metrics {
+        let mut m = Metrics::default();
+        let s = Sample {
+            cwnd: Some(12_000),
+            rtt: Duration::from_millis(10),
+            bytes_in_flight: 3_000,
+            delivered: 1_200,
+            sent: 20,
+            lost: 1,
+        };
+        m.record((2, 0), || String::from("b"), Duration::from_millis(5), &s);
+        m.record(
+            (0, 0),
+            || String::from("a"),
+            Duration::from_millis(5),
+            &Sample::default(),
+        );
+        m.record((2, 0), || unreachable!(), Duration::from_millis(10), &s);
+        m.record((2, 1), || String::from("b"), Duration::from_millis(10), &s);
+        m
+    }
+
+    #[test]
+    fn series() {
+        let m = metrics();
+        assert_eq!(m.series.len(), 3);
+        assert_eq!(m.node(0).unwrap().name, "a");
+        assert_eq!(m.node(2).unwrap().samples.len(), 2);
+        assert_eq!(m.flows(2).count(), 2);
+        assert!(m.node(1).is_none());
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        metrics().write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "node,flow,name,time_us,cwnd,rtt_us,bytes_in_flight,delivered,sent,lost\n\
+             0,0,a,5000,,0,0,0,0,0\n\
+             2,0,b,5000,12000,10000,3000,1200,20,1\n\
+             2,0,b,10000,12000,10000,3000,1200,20,1\n\
+             2,1,b,10000,12000,10000,3000,1200,20,1\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut m = metrics();
+        m.series.truncate(1);
+        let mut out = Vec::new();
+        m.write_json(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[\n  {\"node\":0,\"flow\":0,\"name\":\"a\",\"samples\":[\n    \
+             {\"time_us\":5000,\"cwnd\":null,\"rtt_us\":0,\"bytes_in_flight\":0,\"delivered\":0,\"sent\":0,\"lost\":0}\n  ]}\n]\n"
+        );
+    }
+
+    #[test]
+    fn json_string() {
+        let mut out = Vec::new();
+        write_json_string(&mut out, "a\"b\\c\nd\u{1}é").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\"a\\\"b\\\\c\\nd\\u0001é\""
+        );
+    }
+}
@@ -6,13 +6,24 @@
 
 #![expect(clippy::unwrap_used, reason = "This is test code.")]
 
+mod aqm;
 /// Tests with simulated network components.
 pub mod connection;
+mod corrupt;
 mod delay;
 mod drop;
+pub mod flows;
+pub mod fuzz;
+mod gilbert_elliott;
+mod handover;
 pub mod http3_connection;
+mod jitter;
+pub mod metrics;
 mod mtu;
+mod pacing;
 pub mod rng;
+mod satellite;
+pub mod scenario;
 mod taildrop;
 
 use std::{
@@ -20,6 +31,7 @@ use std::{
     cmp::min,
     fmt::Debug,
     fs::{File, create_dir_all},
+    io::BufWriter,
     ops::{Deref, DerefMut},
     path::PathBuf,
     rc::Rc,
@@ -27,6 +39,7 @@ use std::{
 };
 
 use NodeState::{Active, Idle, Waiting};
+use metrics::{Metrics, Sample, Sampler};
 use neqo_common::{Datagram, Encoder, qdebug, qerror, qinfo, qtrace};
 use neqo_transport::Output;
 use rng::Random;
@@ -35,9 +48,16 @@ use crate::now;
 
 pub mod network {
     pub use super::{
+        aqm::Aqm,
+        corrupt::Corrupt,
         delay::{Delay, RandomDelay},
         drop::Drop,
+        gilbert_elliott::GilbertElliott,
+        handover::Handover,
+        jitter::Jitter,
         mtu::Mtu,
+        pacing::PacingCheck,
+        satellite::Satellite,
         taildrop::TailDrop,
     };
 }
@@ -94,6 +114,12 @@ pub trait Node: Debug {
     }
     /// Print out a summary of the state of the node.
     fn print_summary(&self, _test_name: &str) {}
+    /// Report the current state of the node, for nodes that have something
+    /// to report.  Nodes that carry multiple flows report one sample for each.
+    /// See [`ReadySimulator::run_with_metrics`].
+    fn samples(&self) -> Vec<Sample> {
+        Vec::new()
+    }
 }
 
 /// The state of a single node.  Nodes will be activated if they are `Active`
@@ -153,6 +179,7 @@ pub struct Simulator {
     name: String,
     nodes: Vec<NodeHolder>,
     rng: Rng,
+    sampler: Option<Sampler>,
 }
 
 impl Simulator {
@@ -173,6 +200,7 @@ impl Simulator {
             name,
             nodes,
             rng: Rc::default(),
+            sampler: None,
         };
         // Seed from the `SIMULATION_SEED` environment variable, if set.
         if let Ok(seed) = std::env::var("SIMULATION_SEED") {
@@ -184,8 +212,7 @@ impl Simulator {
             if create_dir_all(&dir).is_err() {
                 qerror!("Failed to create directory {dir}");
             } else {
-                let seed_str = sim.rng.borrow().seed_str();
-                let path = PathBuf::from(format!("{dir}/{}-{seed_str}", sim.name));
+                let path = PathBuf::from(format!("{dir}/{}-{}", sim.name, sim.seed()));
                 if File::create(&path).is_err() {
                     qerror!("Failed to write seed to {}", path.to_string_lossy());
                 }
@@ -202,6 +229,14 @@ impl Simulator {
         self.rng = Rc::new(RefCell::new(Random::new(&seed)));
     }
 
+    /// The seed of the random number generator, as a hex string that can be
+    /// passed to [`Simulator::seed_str`] or the `SIMULATION_SEED` environment
+    /// variable to repeat a run.
+    #[must_use]
+    pub fn seed(&self) -> String {
+        self.rng.borrow().seed_str()
+    }
+
     fn next_time(&self, now: Instant) -> Instant {
         let mut next = None;
         for n in &self.nodes {
@@ -251,6 +286,9 @@ impl Simulator {
             if dgram.is_none() {
                 let next = self.next_time(now);
                 if next > now {
+                    if let Some(sampler) = &mut self.sampler {
+                        sampler.sample(&self.nodes, next);
+                    }
                     qdebug!(
                         "[{}] advancing time by {:?} to {:?}",
                         self.name,
@@ -304,6 +342,27 @@ impl Simulator {
             n.print_summary(&self.name);
         }
     }
+
+    fn dump_metrics(&self, dir: &str, metrics: &Metrics) {
+        if create_dir_all(dir).is_err() {
+            qerror!("Failed to create directory {dir}");
+            return;
+        }
+        let path = PathBuf::from(format!("{dir}/{}.csv", self.name));
+        if File::create(&path)
+            .and_then(|f| metrics.write_csv(BufWriter::new(f)))
+            .is_err()
+        {
+            qerror!("Failed to write metrics to {}", path.to_string_lossy());
+        }
+        let path = path.with_extension("json");
+        if File::create(&path)
+            .and_then(|f| metrics.write_json(BufWriter::new(f)))
+            .is_err()
+        {
+            qerror!("Failed to write metrics to {}", path.to_string_lossy());
+        }
+    }
 }
 
 pub struct ReadySimulator {
@@ -318,6 +377,34 @@ impl ReadySimulator {
         reason = "run duration only needed in some tests"
     )]
     pub fn run(mut self) -> Duration {
+        self.run_inner()
+    }
+
+    /// Runs the simulation, taking a sample from each node that provides
+    /// them every `interval` of simulated time.
+    ///
+    /// If the `DUMP_SIMULATION_METRICS` environment variable is set, the
+    /// samples are also written to CSV and JSON files in that directory.
+    ///
+    /// # Panics
+    /// When `interval` is zero, or when sanity checks fail.
+    #[must_use]
+    pub fn run_with_metrics(mut self, interval: Duration) -> (Duration, Metrics) {
+        self.sim.sampler = Some(Sampler::new(interval, self.now));
+        let sim_time = self.run_inner();
+        let metrics = self
+            .sim
+            .sampler
+            .take()
+            .unwrap()
+            .finish(&self.sim.nodes, self.now + sim_time);
+        if let Ok(dir) = std::env::var("DUMP_SIMULATION_METRICS") {
+            self.sim.dump_metrics(&dir, &metrics);
+        }
+        (sim_time, metrics)
+    }
+
+    fn run_inner(&mut self) -> Duration {
         let real_start = Instant::now();
         let end = self.sim.process_loop(self.start, self.now);
         let sim_time = end - self.now;
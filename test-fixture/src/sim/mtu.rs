@@ -4,17 +4,30 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{net::IpAddr, time::Instant};
+use std::{
+    fmt::{self, Debug},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
 
-use neqo_common::Datagram;
+use neqo_common::{Datagram, qinfo, qtrace};
 use neqo_transport::Output;
 
 use super::{Node, Rng};
 
 /// Drops all datagrams larger than the configured MTU.
-#[derive(Debug)]
+///
+/// This applies in one direction only, so using a different MTU in each
+/// direction only needs a different node in each.  The MTU can also change
+/// part way through, which looks like a route change to the endpoints.
 pub struct Mtu {
     mtu: usize,
+    /// The time after setup when the MTU changes, and the new MTU.
+    change: Option<(Duration, usize)>,
+    /// When setup completed.
+    start: Option<Instant>,
+    /// The number of datagrams dropped.
+    dropped: usize,
 }
 
 impl Mtu {
@@ -23,22 +36,96 @@ impl Mtu {
     /// Limit includes IP and UDP header size.
     #[must_use]
     pub const fn new(mtu: usize) -> Self {
-        Self { mtu }
+        Self {
+            mtu,
+            change: None,
+            start: None,
+            dropped: 0,
+        }
+    }
+
+    /// Change the MTU to `mtu` at `after` this time once setup completes.
+    #[must_use]
+    pub const fn change(mut self, after: Duration, mtu: usize) -> Self {
+        self.change = Some((after, mtu));
+        self
+    }
+
+    fn update(&mut self, now: Instant) {
+        if let (Some((after, mtu)), Some(start)) = (self.change, self.start)
+            && now >= start + after
+        {
+            qinfo!("MTU changes from {} to {mtu}", self.mtu);
+            self.mtu = mtu;
+            self.change = None;
+        }
     }
 }
 
 impl Node for Mtu {
     fn init(&mut self, _rng: Rng, _now: Instant) {}
 
-    fn process(&mut self, d: Option<Datagram>, _now: Instant) -> Output {
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        self.update(now);
         d.filter(|dgram| {
             let header = match dgram.destination().ip() {
                 IpAddr::V4(_) => 20 + 8,
                 IpAddr::V6(_) => 40 + 8,
             };
 
-            header + dgram.len() <= self.mtu
+            let fits = header + dgram.len() <= self.mtu;
+            if !fits {
+                qtrace!("MTU {} drops {}", self.mtu, dgram.len());
+                self.dropped += 1;
+            }
+            fits
         })
         .into()
     }
+
+    fn prepare(&mut self, now: Instant) {
+        self.start = Some(now);
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: mtu {}: dropped {}", self.mtu, self.dropped);
+    }
+}
+
+impl Debug for Mtu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mtu-{}", self.mtu)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use neqo_common::Datagram;
+    use neqo_transport::Output;
+
+    use crate::{
+        DEFAULT_ADDR, now,
+        sim::{Node as _, network::Mtu, rng::Random},
+    };
+
+    /// IPv6 and UDP headers.
+    const HEADER: usize = 48;
+
+    #[test]
+    fn reduce() {
+        let after = Duration::from_secs(1);
+        let mut mtu = Mtu::new(1500).change(after, 1280);
+        let t = now();
+        mtu.init(Rc::new(RefCell::new(Random::default())), t);
+        mtu.prepare(t);
+        let d = Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, 0.into(), vec![0; 1500 - HEADER]);
+        assert_eq!(mtu.process(Some(d.clone()), t), Output::Datagram(d.clone()));
+        assert_eq!(mtu.process(Some(d), t + after), Output::None);
+        assert_eq!(mtu.dropped, 1);
+        let d = Datagram::new(DEFAULT_ADDR, DEFAULT_ADDR, 0.into(), vec![0; 1280 - HEADER]);
+        assert_eq!(mtu.process(Some(d.clone()), t + after), Output::Datagram(d));
+    }
 }
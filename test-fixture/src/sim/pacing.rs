@@ -0,0 +1,158 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{
+    fmt::{self, Debug, Display},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{Datagram, qinfo};
+use neqo_transport::Output;
+
+use super::Node;
+
+#[derive(Debug, Default)]
+struct Stats {
+    /// The number of datagrams that were checked.
+    datagrams: usize,
+    /// The number of bursts.
+    bursts: usize,
+    /// The largest burst.
+    max_burst: usize,
+    /// The smallest gap between bursts.
+    min_gap: Option<Duration>,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} datagrams in {} bursts, max burst {}, min gap {:?}",
+            self.datagrams, self.bursts, self.max_burst, self.min_gap
+        )
+    }
+}
+
+/// A node that checks that datagrams are paced.
+///
+/// This passes datagrams through unchanged, so it should be placed directly
+/// after the node that sends them.  Datagrams that are sent less than a
+/// minimum gap apart form a burst.  This panics if a burst contains more
+/// than the allowed number of datagrams.
+///
+/// Only datagrams sent after setup are checked, as the handshake is not
+/// paced.
+pub struct PacingCheck {
+    /// The largest allowed burst, in datagrams.
+    max_burst: usize,
+    /// The minimum time between bursts.
+    gap: Duration,
+    /// Whether setup is complete.
+    active: bool,
+    /// When the last datagram was sent.
+    last: Option<Instant>,
+    /// The size of the current burst.
+    burst: usize,
+    stats: Stats,
+}
+
+impl PacingCheck {
+    /// Check that no more than `max_burst` datagrams are sent with less than
+    /// `gap` between them.
+    #[must_use]
+    pub fn new(max_burst: usize, gap: Duration) -> Self {
+        Self {
+            max_burst,
+            gap,
+            active: false,
+            last: None,
+            burst: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    fn check(&mut self, now: Instant) {
+        let gap = self.last.map(|last| now - last);
+        self.last = Some(now);
+        self.stats.datagrams += 1;
+        if let Some(gap) = gap.filter(|&gap| gap < self.gap) {
+            self.burst += 1;
+            assert!(
+                self.burst <= self.max_burst,
+                "burst of {} datagrams exceeds {}, with {gap:?} since the last",
+                self.burst,
+                self.max_burst
+            );
+        } else {
+            if let Some(gap) = gap {
+                self.stats.min_gap = Some(self.stats.min_gap.map_or(gap, |m| m.min(gap)));
+            }
+            self.stats.bursts += 1;
+            self.burst = 1;
+        }
+        self.stats.max_burst = self.stats.max_burst.max(self.burst);
+    }
+}
+
+impl Node for PacingCheck {
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        d.map_or(Output::None, |dgram| {
+            if self.active {
+                self.check(now);
+            }
+            Output::Datagram(dgram)
+        })
+    }
+
+    fn prepare(&mut self, _now: Instant) {
+        self.active = true;
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: pacing: {}", self.stats);
+    }
+}
+
+impl Debug for PacingCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pacing-{}-{:?}", self.max_burst, self.gap)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::time::Duration;
+
+    use crate::{now, sim::network::PacingCheck};
+
+    const GAP: Duration = Duration::from_millis(1);
+
+    fn check(sends: &[Duration]) -> PacingCheck {
+        let mut p = PacingCheck::new(2, GAP);
+        let t = now();
+        for &s in sends {
+            p.check(t + s);
+        }
+        p
+    }
+
+    #[test]
+    fn paced() {
+        let ms = Duration::from_millis;
+        let p = check(&[ms(0), ms(0), ms(2), ms(3), ms(3), ms(10)]);
+        assert_eq!(p.stats.bursts, 4);
+        assert_eq!(p.stats.max_burst, 2);
+        assert_eq!(p.stats.min_gap, Some(ms(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "burst of 3 datagrams exceeds 2")]
+    fn burst() {
+        let us = Duration::from_micros;
+        _ = check(&[us(0), us(500), us(999)]);
+    }
+}
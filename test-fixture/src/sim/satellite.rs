@@ -0,0 +1,193 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug},
+    time::{Duration, Instant},
+};
+
+use neqo_common::{Datagram, qinfo, qtrace};
+use neqo_transport::Output;
+
+use super::{Node, Rng};
+
+/// The handovers between satellites of a low earth orbit constellation.
+#[derive(Debug, Clone, Copy)]
+struct Handovers {
+    /// The delay just before each handover.
+    max_delay: Duration,
+    /// The time between handovers.
+    period: Duration,
+    /// How long the link is down at each handover.
+    gap: Duration,
+}
+
+/// One direction of a satellite link.
+///
+/// For a geostationary satellite, the delay is long, but constant.  Satellites
+/// in low earth orbit are much closer, but the delay changes as they move.
+/// This models that with a delay that grows steadily until the link is handed
+/// over to the next satellite, when the delay drops back to the minimum.  The
+/// link is down for a short time at each handover, dropping all datagrams.
+///
+/// Datagrams are delivered in order, even when the delay drops.  This does not
+/// limit the rate of the link; add a [`super::network::TailDrop`] for that.
+pub struct Satellite {
+    /// The smallest delay.
+    delay: Duration,
+    handovers: Option<Handovers>,
+    /// When the link started.
+    start: Option<Instant>,
+    /// The datagrams on the link and when they can be delivered.
+    queue: VecDeque<(Instant, Datagram)>,
+    /// The number of datagrams dropped during handovers.
+    dropped: usize,
+}
+
+impl Satellite {
+    /// A link with a constant `delay`.
+    #[must_use]
+    pub const fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            handovers: None,
+            start: None,
+            queue: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Add handovers every `period`, during which the delay grows to `max_delay`.
+    /// The link is down for `gap` at the start of each handover.  There is no
+    /// handover at the start, so that connections can be established.
+    ///
+    /// # Panics
+    ///
+    /// When `max_delay` is less than the minimum delay, or `gap` is not less than `period`.
+    #[must_use]
+    pub fn handovers(mut self, max_delay: Duration, period: Duration, gap: Duration) -> Self {
+        assert!(max_delay >= self.delay);
+        assert!(gap < period);
+        self.handovers = Some(Handovers {
+            max_delay,
+            period,
+            gap,
+        });
+        self
+    }
+
+    /// A geostationary satellite, with a one-way delay of 280ms.
+    #[must_use]
+    pub const fn geo() -> Self {
+        Self::new(Duration::from_millis(280))
+    }
+
+    /// A satellite in low earth orbit, with a one-way delay between 15ms and
+    /// 30ms, handovers every 15 seconds, and a 50ms gap at each handover.
+    #[must_use]
+    pub fn leo() -> Self {
+        Self::new(Duration::from_millis(15)).handovers(
+            Duration::from_millis(30),
+            Duration::from_secs(15),
+            Duration::from_millis(50),
+        )
+    }
+
+    /// The delay for a datagram sent at `now`, or `None` if the link is down.
+    fn delay_at(&self, now: Instant) -> Option<Duration> {
+        let Some(h) = self.handovers else {
+            return Some(self.delay);
+        };
+        let elapsed = (now - self.start.unwrap()).as_nanos();
+        let period = h.period.as_nanos();
+        let phase = elapsed % period;
+        if elapsed >= period && phase < h.gap.as_nanos() {
+            return None;
+        }
+        let extra = (h.max_delay - self.delay).as_nanos() * phase / period;
+        Some(self.delay + Duration::from_nanos(u64::try_from(extra).unwrap()))
+    }
+
+    fn insert(&mut self, d: Datagram, now: Instant) {
+        let Some(delay) = self.delay_at(now) else {
+            qtrace!("satellite handover, drop {}", d.len());
+            self.dropped += 1;
+            return;
+        };
+        // Keep the order by never leaving before the previous datagram.
+        let t = self
+            .queue
+            .back()
+            .map_or(now + delay, |&(last, _)| last.max(now + delay));
+        self.queue.push_back((t, d));
+    }
+}
+
+impl Node for Satellite {
+    fn init(&mut self, _rng: Rng, now: Instant) {
+        self.start = Some(now);
+    }
+
+    fn process(&mut self, d: Option<Datagram>, now: Instant) -> Output {
+        if let Some(dgram) = d {
+            self.insert(dgram, now);
+        }
+        match self.queue.front() {
+            Some(&(t, _)) if t <= now => Output::Datagram(self.queue.pop_front().unwrap().1),
+            Some(&(t, _)) => Output::Callback(t - now),
+            None => Output::None,
+        }
+    }
+
+    fn print_summary(&self, test_name: &str) {
+        qinfo!("{test_name}: satellite: dropped {}", self.dropped);
+    }
+}
+
+impl Debug for Satellite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "satellite-{:?}", self.delay)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    use crate::{
+        now,
+        sim::{Node as _, network::Satellite, rng::Random},
+    };
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn geo() {
+        let mut sat = Satellite::geo();
+        let t = now();
+        sat.init(Rc::new(RefCell::new(Random::default())), t);
+        assert_eq!(sat.delay_at(t), Some(MS * 280));
+        assert_eq!(sat.delay_at(t + MS * 100_000), Some(MS * 280));
+    }
+
+    #[test]
+    fn leo() {
+        let mut sat = Satellite::new(MS * 10).handovers(MS * 20, MS * 1000, MS * 50);
+        let t = now();
+        sat.init(Rc::new(RefCell::new(Random::default())), t);
+        // No gap at the start.
+        assert_eq!(sat.delay_at(t), Some(MS * 10));
+        assert_eq!(sat.delay_at(t + MS * 500), Some(MS * 15));
+        assert_eq!(sat.delay_at(t + MS * 1000), None);
+        assert_eq!(sat.delay_at(t + MS * 1049), None);
+        assert_eq!(sat.delay_at(t + MS * 1050), Some(MS * 10 + MS / 2));
+        assert_eq!(sat.delay_at(t + MS * 1900), Some(MS * 19));
+    }
+}
@@ -0,0 +1,325 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Simulations that are described in TOML files.
+//!
+//! A scenario lists the nodes of a simulation in order, each with a `type`
+//! and the values that configure it.  For example:
+//!
+//! ```toml
+//! name = "dsl"
+//!
+//! [[nodes]]
+//! type = "client"
+//! goals = [{ send = 1048576 }]
+//! params = { congestion_control = "cubic", pacing = true }
+//!
+//! [[nodes]]
+//! type = "tail_drop"
+//! rate = 200000
+//! capacity = 8192
+//! delay_ms = 50
+//!
+//! [[nodes]]
+//! type = "server"
+//! goals = [{ receive = 1048576 }]
+//!
+//! [[nodes]]
+//! type = "tail_drop"
+//! rate = 1000000
+//! capacity = 32768
+//! delay_ms = 50
+//! ```
+//!
+//! Connections complete the handshake before their goals start.
+
+#![expect(clippy::unwrap_used, reason = "This is test code.")]
+
+use std::{fs, path::Path, time::Duration};
+
+use neqo_transport::{ConnectionParameters, State};
+use serde_derive::Deserialize;
+
+use super::{
+    Node, Simulator,
+    connection::{self, Goal, ReachState, ReceiveData, ReceiveDatagrams, SendData, SendDatagrams},
+    network::{Delay, Drop, GilbertElliott, Jitter, Mtu, RandomDelay, TailDrop},
+};
+use crate::boxed;
+
+/// The directory that holds the scenarios that come with this crate.
+const SCENARIO_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios");
+
+/// Connection parameters that differ from the defaults for simulations.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Params {
+    congestion_control: Option<String>,
+    slow_start: Option<String>,
+    pacing: Option<bool>,
+    pmtud: Option<bool>,
+    ack_ratio: Option<u8>,
+}
+
+impl Params {
+    fn connection_parameters(&self) -> ConnectionParameters {
+        // Simulator logic does not work with multi-packet MLKEM crypto flights.
+        let mut params = ConnectionParameters::default().pmtud(true).mlkem(false);
+        if let Some(cc) = &self.congestion_control {
+            params = params.congestion_control(cc.parse().unwrap());
+        }
+        if let Some(ss) = &self.slow_start {
+            params = params.slow_start(ss.parse().unwrap());
+        }
+        if let Some(pacing) = self.pacing {
+            params = params.pacing(pacing);
+        }
+        if let Some(pmtud) = self.pmtud {
+            params = params.pmtud(pmtud);
+        }
+        if let Some(ack_ratio) = self.ack_ratio {
+            params = params.ack_ratio(ack_ratio);
+        }
+        params
+    }
+}
+
+/// A goal for a connection.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GoalSpec {
+    /// Send this many bytes on a stream.
+    Send(usize),
+    /// Receive this many bytes.
+    Receive(usize),
+    /// Send datagrams.
+    SendDatagrams { count: usize, size: usize },
+    /// Receive this many datagrams.
+    ReceiveDatagrams(usize),
+}
+
+impl GoalSpec {
+    fn goal(&self) -> Box<dyn Goal> {
+        match *self {
+            Self::Send(amount) => Box::new(SendData::new(amount)),
+            Self::Receive(amount) => Box::new(ReceiveData::new(amount)),
+            Self::SendDatagrams { count, size } => Box::new(SendDatagrams::new(count, size)),
+            Self::ReceiveDatagrams(count) => Box::new(ReceiveDatagrams::new(count)),
+        }
+    }
+}
+
+/// One node in the simulation.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum NodeSpec {
+    Client {
+        #[serde(default)]
+        params: Params,
+        goals: Vec<GoalSpec>,
+    },
+    Server {
+        #[serde(default)]
+        params: Params,
+        goals: Vec<GoalSpec>,
+    },
+    Delay {
+        delay_ms: u64,
+    },
+    RandomDelay {
+        min_ms: u64,
+        max_ms: u64,
+    },
+    Jitter {
+        min_ms: u64,
+        max_ms: u64,
+        #[serde(default)]
+        reorder: u8,
+        #[serde(default)]
+        hold_ms: u64,
+    },
+    Drop {
+        percent: u8,
+    },
+    GilbertElliott {
+        percent: u8,
+        burst: u64,
+    },
+    TailDrop {
+        rate: usize,
+        capacity: usize,
+        delay_ms: u64,
+        #[serde(default)]
+        ecn: bool,
+    },
+    Mtu {
+        mtu: usize,
+    },
+}
+
+impl NodeSpec {
+    fn node(&self) -> Box<dyn Node> {
+        let ms = Duration::from_millis;
+        let goals = |goals: &[GoalSpec]| goals.iter().map(GoalSpec::goal).collect::<Vec<_>>();
+        match self {
+            Self::Client { params, goals: g } => Box::new(connection::Node::new_client(
+                params.connection_parameters(),
+                boxed![ReachState::new(State::Confirmed)],
+                goals(g),
+            )),
+            Self::Server { params, goals: g } => Box::new(connection::Node::new_server(
+                params.connection_parameters(),
+                boxed![ReachState::new(State::Confirmed)],
+                goals(g),
+            )),
+            Self::Delay { delay_ms } => Box::new(Delay::new(ms(*delay_ms))),
+            Self::RandomDelay { min_ms, max_ms } => {
+                Box::new(RandomDelay::new(ms(*min_ms)..ms(*max_ms)))
+            }
+            Self::Jitter {
+                min_ms,
+                max_ms,
+                reorder,
+                hold_ms,
+            } => Box::new(Jitter::new(ms(*min_ms)..ms(*max_ms)).reorder(*reorder, ms(*hold_ms))),
+            Self::Drop { percent } => Box::new(Drop::percentage(*percent)),
+            Self::GilbertElliott { percent, burst } => {
+                Box::new(GilbertElliott::bursts(*percent, *burst))
+            }
+            Self::TailDrop {
+                rate,
+                capacity,
+                delay_ms,
+                ecn,
+            } => Box::new(TailDrop::new(*rate, *capacity, *ecn, ms(*delay_ms))),
+            Self::Mtu { mtu } => Box::new(Mtu::new(*mtu)),
+        }
+    }
+}
+
+/// A simulation, as described in a TOML file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    name: String,
+    /// The seed, as a hex string.
+    seed: Option<String>,
+    nodes: Vec<NodeSpec>,
+}
+
+impl Scenario {
+    /// Parse a scenario from TOML.
+    ///
+    /// # Panics
+    ///
+    /// When the scenario is not valid.
+    #[must_use]
+    pub fn parse(s: &str) -> Self {
+        toml::from_str(s).unwrap()
+    }
+
+    /// Load a scenario from a file.
+    ///
+    /// # Panics
+    ///
+    /// When the file can't be read or the scenario is not valid.
+    #[must_use]
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        Self::parse(&fs::read_to_string(path).unwrap())
+    }
+
+    /// Load one of the scenarios that come with this crate, by name.
+    ///
+    /// # Panics
+    ///
+    /// When there is no such scenario or it is not valid.
+    #[must_use]
+    pub fn named(name: &str) -> Self {
+        Self::load(format!("{SCENARIO_DIR}/{name}.toml"))
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Build a simulator for the scenario.
+    ///
+    /// # Panics
+    ///
+    /// When a connection parameter has an invalid value.
+    #[must_use]
+    pub fn simulator(&self) -> Simulator {
+        let mut sim = Simulator::new(&self.name, self.nodes.iter().map(NodeSpec::node));
+        if let Some(seed) = &self.seed {
+            sim.seed_str(seed);
+        }
+        sim
+    }
+
+    /// Run the scenario.
+    ///
+    /// # Panics
+    ///
+    /// When the scenario is not valid or the simulation fails.
+    pub fn run(&self) {
+        self.simulator().run();
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod test {
+    use super::{NodeSpec, Scenario};
+
+    #[test]
+    fn parse() {
+        let s = Scenario::parse(
+            r#"
+            name = "parse"
+            [[nodes]]
+            type = "client"
+            goals = [{ send = 1000 }, { send_datagrams = { count = 2, size = 100 } }]
+            params = { congestion_control = "reno", slow_start = "hystart" }
+            [[nodes]]
+            type = "jitter"
+            min_ms = 10
+            max_ms = 20
+            [[nodes]]
+            type = "server"
+            goals = [{ receive = 1000 }, { receive_datagrams = 2 }]
+            [[nodes]]
+            type = "delay"
+            delay_ms = 10
+            "#,
+        );
+        assert_eq!(s.name(), "parse");
+        assert_eq!(s.nodes.len(), 4);
+        assert!(matches!(&s.nodes[0], NodeSpec::Client { goals, .. } if goals.len() == 2));
+        _ = s.simulator();
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown field")]
+    fn unknown_field() {
+        _ = Scenario::parse(
+            r#"
+            name = "unknown"
+            [[nodes]]
+            type = "delay"
+            delay = 10
+            "#,
+        );
+    }
+
+    /// All of the scenarios that come with the crate can be loaded.
+    #[test]
+    fn bundled() {
+        for entry in std::fs::read_dir(super::SCENARIO_DIR).unwrap() {
+            _ = Scenario::load(entry.unwrap().path()).simulator();
+        }
+    }
+}